@@ -0,0 +1,93 @@
+//! The `syn` + `quote` implementations of the derives in `virtue_bench::derives`, which generate the `reference/<name>.rs` files.
+//!
+//! Like most derives that are built on `syn`, they add the trait as a bound to every type parameter and use `Generics::split_for_impl`.
+
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+use syn::{parse2, parse_quote, Data, DeriveInput, Fields, GenericParam};
+
+/// The `syn` + `quote` implementation of the derive of the case with the given name
+pub fn derive(case: &str) -> fn(TokenStream) -> TokenStream {
+    match case {
+        "clone_point" | "clone_giant" => derive_clone,
+        "debug_shape" => derive_debug,
+        _ => panic!("No syn derive for case {}", case),
+    }
+}
+
+/// Add `bound` to every type parameter of `input`
+fn add_bound(input: &mut DeriveInput, bound: syn::TypeParamBound) {
+    for param in &mut input.generics.params {
+        if let GenericParam::Type(param) = param {
+            param.bounds.push(bound.clone());
+        }
+    }
+}
+
+/// `#[derive(Clone)]` for structs with named fields
+pub fn derive_clone(input: TokenStream) -> TokenStream {
+    let mut input: DeriveInput = parse2(input).unwrap();
+    add_bound(&mut input, parse_quote!(::core::clone::Clone));
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => panic!("Only structs are supported"),
+    };
+    let fields = fields.iter().map(|field| {
+        let name = &field.ident;
+        quote!(#name: ::core::clone::Clone::clone(&self.#name),)
+    });
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    quote! {
+        impl #impl_generics ::core::clone::Clone for #name #ty_generics #where_clause {
+            fn clone(&self) -> Self {
+                Self { #(#fields)* }
+            }
+        }
+    }
+}
+
+/// `#[derive(Debug)]` for enums
+pub fn derive_debug(input: TokenStream) -> TokenStream {
+    let mut input: DeriveInput = parse2(input).unwrap();
+    add_bound(&mut input, parse_quote!(::core::fmt::Debug));
+    let variants = match &input.data {
+        Data::Enum(data) => &data.variants,
+        _ => panic!("Only enums are supported"),
+    };
+    let arms = variants.iter().map(|variant| {
+        let name = &variant.ident;
+        let label = name.to_string();
+        match &variant.fields {
+            Fields::Named(fields) => {
+                let names: Vec<_> = fields.named.iter().map(|field| &field.ident).collect();
+                let labels = names.iter().map(|name| name.as_ref().unwrap().to_string());
+                quote! {
+                    Self::#name { #(#names),* } => f.debug_struct(#label) #(.field(#labels, #names))* .finish(),
+                }
+            }
+            Fields::Unnamed(fields) => {
+                let names: Vec<_> = (0..fields.unnamed.len())
+                    .map(|index| Ident::new(&format!("field_{}", index), Span::call_site()))
+                    .collect();
+                quote! {
+                    Self::#name(#(#names),*) => f.debug_tuple(#label) #(.field(#names))* .finish(),
+                }
+            }
+            Fields::Unit => quote! {
+                Self::#name => f.write_str(#label),
+            },
+        }
+    });
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    quote! {
+        impl #impl_generics ::core::fmt::Debug for #name #ty_generics #where_clause {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use proc_macro2::TokenStream;
+use virtue_bench::derives::{cases, token_count};
+
+fn derive(c: &mut Criterion) {
+    let mut group = c.benchmark_group("derive");
+    for case in cases() {
+        let syn_derive = common::derive(case.name);
+        let output = (case.derive)(case.input.clone()).unwrap();
+        let reference: TokenStream = case.reference.parse().unwrap();
+        println!(
+            "{:<20} virtue: {:>6} tokens, syn + quote: {:>6} tokens",
+            case.name,
+            token_count(output),
+            token_count(reference)
+        );
+        group.bench_function(format!("{}/virtue", case.name), |b| {
+            b.iter(|| (case.derive)(case.input.clone()).unwrap())
+        });
+        group.bench_function(format!("{}/syn", case.name), |b| {
+            b.iter(|| syn_derive(case.input.clone()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, derive);
+criterion_main!(benches);
@@ -0,0 +1,81 @@
+//! Measures the memory that is allocated while parsing and generating code, with a global allocator that counts allocations.
+//!
+//! Run with `cargo bench -p virtue_bench --bench memory`. This prints the amount of allocations and allocated bytes for every input, and the amount of attributes that virtue keeps in memory for it. The `derive/` rows also include a derive that generates a setter for every field, so the allocations of the builders can be compared with those of the parser.
+
+use proc_macro2::TokenStream;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use virtue::parse::{StructBody, Visibility};
+use virtue::prelude::*;
+use virtue_bench::{giant_enum, giant_struct, giant_tuple_struct};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn measure(name: &str, input: TokenStream, derive: impl FnOnce(&mut Generator, &Body)) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    let (mut generator, _, body) = Parse::new(input).unwrap().into_generator();
+    derive(&mut generator, &body);
+    drop(generator.finish().unwrap());
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes;
+    println!(
+        "{:<20} {:>8} allocations {:>10} bytes {:>6} attributes",
+        name,
+        allocations,
+        bytes,
+        body.attribute_count()
+    );
+}
+
+/// Generates `#[inline] pub fn set_<field>(&mut self, value: <type>) -> &mut Self` for every field.
+fn setters(generator: &mut Generator, body: &Body) {
+    let fields = match body {
+        Body::Struct(StructBody {
+            fields: Some(Fields::Struct(fields)),
+            ..
+        }) => fields,
+        _ => unreachable!(),
+    };
+    let mut r#impl = generator.r#impl();
+    for (ident, field) in fields {
+        r#impl
+            .generate_fn(format!("set_{}", ident))
+            .with_visibility(Visibility::Pub)
+            .with_attr("inline")
+            .with_self_arg(FnSelfArg::MutSelf)
+            .with_arg("value", field)
+            .with_return_type("&mut Self")
+            .body(|b| {
+                b.push_parsed(format!("self.{} = value; self", ident))?;
+                Ok(())
+            })
+            .unwrap();
+    }
+}
+
+fn main() {
+    let parse_only = |_: &mut Generator, _: &Body| {};
+    measure("struct/500", giant_struct(500), parse_only);
+    measure("tuple_struct/500", giant_tuple_struct(500), parse_only);
+    measure("enum/500", giant_enum(500), parse_only);
+    measure("derive/struct/500", giant_struct(500), setters);
+}
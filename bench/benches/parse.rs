@@ -0,0 +1,106 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use proc_macro2::TokenStream;
+use std::iter::Peekable;
+use virtue::parse::utils::{consume_punct_if, for_each_split_on_punct, read_tokens_until_punct};
+use virtue::prelude::*;
+use virtue_bench::{giant_enum, giant_struct, giant_tuple_struct};
+
+const SIZES: &[usize] = &[10, 100, 1000];
+
+fn bench_inputs(c: &mut Criterion, name: &str, input: fn(usize) -> TokenStream) {
+    let mut group = c.benchmark_group(name);
+    for &size in SIZES {
+        let input = input(size);
+        group.bench_with_input(BenchmarkId::new("parse", size), &input, |b, input| {
+            b.iter(|| Parse::new(input.clone()).unwrap())
+        });
+        group.bench_with_input(
+            BenchmarkId::new("parse_and_generate", size),
+            &input,
+            |b, input| {
+                b.iter(|| {
+                    let (mut generator, _, _) = Parse::new(input.clone()).unwrap().into_generator();
+                    generator
+                        .impl_for("Clone")
+                        .generate_fn("clone")
+                        .with_self_arg(FnSelfArg::RefSelf)
+                        .with_return_type("Self")
+                        .body(|body| {
+                            body.push_parsed("unimplemented!()")?;
+                            Ok(())
+                        })
+                        .unwrap();
+                    generator.finish().unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+/// The tokens between the brackets of the struct in `input`.
+fn fields_of(input: TokenStream) -> Vec<TokenTree> {
+    input
+        .into_iter()
+        .filter_map(|token| match token {
+            TokenTree::Group(group) if group.delimiter() != Delimiter::None => Some(group),
+            _ => None,
+        })
+        .last()
+        .expect("No fields found")
+        .stream()
+        .into_iter()
+        .collect()
+}
+
+/// How the fields were split before the token buffer: reading every field into a new `Vec` from a `Peekable` iterator.
+fn split_fields_baseline(input: &mut Peekable<impl Iterator<Item = TokenTree>>) -> usize {
+    let mut fields = 0;
+    while input.peek().is_some() {
+        let field = read_tokens_until_punct(input, &[',']).unwrap();
+        consume_punct_if(input, ',');
+        fields += field.len();
+    }
+    fields
+}
+
+/// How the fields are split by [`Parse`] now: over the buffered tokens, with every field moved out of the buffer.
+fn split_fields_buffer(tokens: Vec<TokenTree>) -> usize {
+    let mut fields = 0;
+    for_each_split_on_punct(tokens, ',', |field| {
+        fields += field.count();
+        Ok(())
+    })
+    .unwrap();
+    fields
+}
+
+/// Compares the field splitting of the current parser with the baseline implementation it replaced.
+fn bench_split_fields(c: &mut Criterion, name: &str, input: fn(usize) -> TokenStream) {
+    let mut group = c.benchmark_group(format!("split_fields/{}", name));
+    for &size in SIZES {
+        let tokens = fields_of(input(size));
+        assert_eq!(
+            split_fields_baseline(&mut tokens.clone().into_iter().peekable()),
+            split_fields_buffer(tokens.clone())
+        );
+        group.bench_with_input(BenchmarkId::new("baseline", size), &tokens, |b, tokens| {
+            b.iter(|| split_fields_baseline(&mut tokens.clone().into_iter().peekable()))
+        });
+        group.bench_with_input(BenchmarkId::new("buffer", size), &tokens, |b, tokens| {
+            b.iter(|| split_fields_buffer(tokens.clone()))
+        });
+    }
+    group.finish();
+}
+
+fn parse(c: &mut Criterion) {
+    bench_inputs(c, "struct", giant_struct);
+    bench_inputs(c, "tuple_struct", giant_tuple_struct);
+    bench_inputs(c, "enum", giant_enum);
+    bench_split_fields(c, "struct", giant_struct);
+    bench_split_fields(c, "tuple_struct", giant_tuple_struct);
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
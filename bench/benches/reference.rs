@@ -0,0 +1,32 @@
+//! Regenerates the `reference/<name>.rs` files with the `syn` + `quote` derives of `common`, and formats them with rustfmt.
+//!
+//! Run with `cargo bench -p virtue_bench --bench reference` after changing the input of a case or one of the derives.
+
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+use virtue_bench::derives::cases;
+
+fn main() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("reference");
+    for case in cases() {
+        let output = common::derive(case.name)(case.input);
+        let path = dir.join(format!("{}.rs", case.name));
+        std::fs::write(&path, output.to_string()).unwrap();
+        let status = Command::new("rustfmt")
+            .args(["--edition", "2021"])
+            .arg(&path)
+            .status()
+            .unwrap();
+        assert!(status.success(), "rustfmt failed on {}", path.display());
+        let code = std::fs::read_to_string(&path).unwrap();
+        let header = format!(
+            "// The output of the syn + quote implementation of the `{}` case in `virtue_bench::derives`.\n\
+             // Generated by `cargo bench -p virtue_bench --bench reference`. Regenerate it when the input of the case changes.\n\n",
+            case.name
+        );
+        std::fs::write(&path, header + &code).unwrap();
+        println!("{}", path.display());
+    }
+}
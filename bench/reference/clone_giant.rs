@@ -0,0 +1,112 @@
+// The output of the syn + quote implementation of the `clone_giant` case in `virtue_bench::derives`.
+// Generated by `cargo bench -p virtue_bench --bench reference`. Regenerate it when the input of the case changes.
+
+impl<'a, T: Clone + ::core::clone::Clone> ::core::clone::Clone for Giant<'a, T>
+where
+    T: Send,
+{
+    fn clone(&self) -> Self {
+        Self {
+            field_0: ::core::clone::Clone::clone(&self.field_0),
+            field_1: ::core::clone::Clone::clone(&self.field_1),
+            field_2: ::core::clone::Clone::clone(&self.field_2),
+            field_3: ::core::clone::Clone::clone(&self.field_3),
+            field_4: ::core::clone::Clone::clone(&self.field_4),
+            field_5: ::core::clone::Clone::clone(&self.field_5),
+            field_6: ::core::clone::Clone::clone(&self.field_6),
+            field_7: ::core::clone::Clone::clone(&self.field_7),
+            field_8: ::core::clone::Clone::clone(&self.field_8),
+            field_9: ::core::clone::Clone::clone(&self.field_9),
+            field_10: ::core::clone::Clone::clone(&self.field_10),
+            field_11: ::core::clone::Clone::clone(&self.field_11),
+            field_12: ::core::clone::Clone::clone(&self.field_12),
+            field_13: ::core::clone::Clone::clone(&self.field_13),
+            field_14: ::core::clone::Clone::clone(&self.field_14),
+            field_15: ::core::clone::Clone::clone(&self.field_15),
+            field_16: ::core::clone::Clone::clone(&self.field_16),
+            field_17: ::core::clone::Clone::clone(&self.field_17),
+            field_18: ::core::clone::Clone::clone(&self.field_18),
+            field_19: ::core::clone::Clone::clone(&self.field_19),
+            field_20: ::core::clone::Clone::clone(&self.field_20),
+            field_21: ::core::clone::Clone::clone(&self.field_21),
+            field_22: ::core::clone::Clone::clone(&self.field_22),
+            field_23: ::core::clone::Clone::clone(&self.field_23),
+            field_24: ::core::clone::Clone::clone(&self.field_24),
+            field_25: ::core::clone::Clone::clone(&self.field_25),
+            field_26: ::core::clone::Clone::clone(&self.field_26),
+            field_27: ::core::clone::Clone::clone(&self.field_27),
+            field_28: ::core::clone::Clone::clone(&self.field_28),
+            field_29: ::core::clone::Clone::clone(&self.field_29),
+            field_30: ::core::clone::Clone::clone(&self.field_30),
+            field_31: ::core::clone::Clone::clone(&self.field_31),
+            field_32: ::core::clone::Clone::clone(&self.field_32),
+            field_33: ::core::clone::Clone::clone(&self.field_33),
+            field_34: ::core::clone::Clone::clone(&self.field_34),
+            field_35: ::core::clone::Clone::clone(&self.field_35),
+            field_36: ::core::clone::Clone::clone(&self.field_36),
+            field_37: ::core::clone::Clone::clone(&self.field_37),
+            field_38: ::core::clone::Clone::clone(&self.field_38),
+            field_39: ::core::clone::Clone::clone(&self.field_39),
+            field_40: ::core::clone::Clone::clone(&self.field_40),
+            field_41: ::core::clone::Clone::clone(&self.field_41),
+            field_42: ::core::clone::Clone::clone(&self.field_42),
+            field_43: ::core::clone::Clone::clone(&self.field_43),
+            field_44: ::core::clone::Clone::clone(&self.field_44),
+            field_45: ::core::clone::Clone::clone(&self.field_45),
+            field_46: ::core::clone::Clone::clone(&self.field_46),
+            field_47: ::core::clone::Clone::clone(&self.field_47),
+            field_48: ::core::clone::Clone::clone(&self.field_48),
+            field_49: ::core::clone::Clone::clone(&self.field_49),
+            field_50: ::core::clone::Clone::clone(&self.field_50),
+            field_51: ::core::clone::Clone::clone(&self.field_51),
+            field_52: ::core::clone::Clone::clone(&self.field_52),
+            field_53: ::core::clone::Clone::clone(&self.field_53),
+            field_54: ::core::clone::Clone::clone(&self.field_54),
+            field_55: ::core::clone::Clone::clone(&self.field_55),
+            field_56: ::core::clone::Clone::clone(&self.field_56),
+            field_57: ::core::clone::Clone::clone(&self.field_57),
+            field_58: ::core::clone::Clone::clone(&self.field_58),
+            field_59: ::core::clone::Clone::clone(&self.field_59),
+            field_60: ::core::clone::Clone::clone(&self.field_60),
+            field_61: ::core::clone::Clone::clone(&self.field_61),
+            field_62: ::core::clone::Clone::clone(&self.field_62),
+            field_63: ::core::clone::Clone::clone(&self.field_63),
+            field_64: ::core::clone::Clone::clone(&self.field_64),
+            field_65: ::core::clone::Clone::clone(&self.field_65),
+            field_66: ::core::clone::Clone::clone(&self.field_66),
+            field_67: ::core::clone::Clone::clone(&self.field_67),
+            field_68: ::core::clone::Clone::clone(&self.field_68),
+            field_69: ::core::clone::Clone::clone(&self.field_69),
+            field_70: ::core::clone::Clone::clone(&self.field_70),
+            field_71: ::core::clone::Clone::clone(&self.field_71),
+            field_72: ::core::clone::Clone::clone(&self.field_72),
+            field_73: ::core::clone::Clone::clone(&self.field_73),
+            field_74: ::core::clone::Clone::clone(&self.field_74),
+            field_75: ::core::clone::Clone::clone(&self.field_75),
+            field_76: ::core::clone::Clone::clone(&self.field_76),
+            field_77: ::core::clone::Clone::clone(&self.field_77),
+            field_78: ::core::clone::Clone::clone(&self.field_78),
+            field_79: ::core::clone::Clone::clone(&self.field_79),
+            field_80: ::core::clone::Clone::clone(&self.field_80),
+            field_81: ::core::clone::Clone::clone(&self.field_81),
+            field_82: ::core::clone::Clone::clone(&self.field_82),
+            field_83: ::core::clone::Clone::clone(&self.field_83),
+            field_84: ::core::clone::Clone::clone(&self.field_84),
+            field_85: ::core::clone::Clone::clone(&self.field_85),
+            field_86: ::core::clone::Clone::clone(&self.field_86),
+            field_87: ::core::clone::Clone::clone(&self.field_87),
+            field_88: ::core::clone::Clone::clone(&self.field_88),
+            field_89: ::core::clone::Clone::clone(&self.field_89),
+            field_90: ::core::clone::Clone::clone(&self.field_90),
+            field_91: ::core::clone::Clone::clone(&self.field_91),
+            field_92: ::core::clone::Clone::clone(&self.field_92),
+            field_93: ::core::clone::Clone::clone(&self.field_93),
+            field_94: ::core::clone::Clone::clone(&self.field_94),
+            field_95: ::core::clone::Clone::clone(&self.field_95),
+            field_96: ::core::clone::Clone::clone(&self.field_96),
+            field_97: ::core::clone::Clone::clone(&self.field_97),
+            field_98: ::core::clone::Clone::clone(&self.field_98),
+            field_99: ::core::clone::Clone::clone(&self.field_99),
+        }
+    }
+}
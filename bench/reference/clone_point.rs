@@ -0,0 +1,12 @@
+// The output of the syn + quote implementation of the `clone_point` case in `virtue_bench::derives`.
+// Generated by `cargo bench -p virtue_bench --bench reference`. Regenerate it when the input of the case changes.
+
+impl<T: ::core::clone::Clone> ::core::clone::Clone for Point<T> {
+    fn clone(&self) -> Self {
+        Self {
+            x: ::core::clone::Clone::clone(&self.x),
+            y: ::core::clone::Clone::clone(&self.y),
+            label: ::core::clone::Clone::clone(&self.label),
+        }
+    }
+}
@@ -0,0 +1,17 @@
+// The output of the syn + quote implementation of the `debug_shape` case in `virtue_bench::derives`.
+// Generated by `cargo bench -p virtue_bench --bench reference`. Regenerate it when the input of the case changes.
+
+impl<T: Copy + ::core::fmt::Debug> ::core::fmt::Debug for Shape<T>
+where
+    T: Default,
+{
+    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+        match self {
+            Self::Circle { radius } => f.debug_struct("Circle").field("radius", radius).finish(),
+            Self::Rect(field_0, field_1) => {
+                f.debug_tuple("Rect").field(field_0).field(field_1).finish()
+            }
+            Self::Empty => f.write_str("Empty"),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Representative derives that are implemented with virtue, and the output of the same derives implemented with `syn` and `quote`.
+//!
+//! The `syn` + `quote` derives are in `benches/common`, as `syn` is only a dev-dependency. Their output is checked in as `reference/<name>.rs` and regenerated with `cargo bench -p virtue_bench --bench reference`.
+//!
+//! `cargo bench -p virtue_bench --bench derive` measures the expansion time of both implementations and prints the token counts of both outputs. The `size` test fails if a virtue derive generates more than a few percent more tokens than its reference.
+
+use crate::giant_struct;
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use virtue::prelude::*;
+
+/// A derive that is implemented with virtue, with the `syn` + `quote` output of the same derive.
+pub struct DeriveCase {
+    /// The name of the case, which is also the name of its reference file
+    pub name: &'static str,
+    /// The item that is derived
+    pub input: TokenStream,
+    /// The virtue implementation of the derive
+    pub derive: fn(TokenStream) -> Result<TokenStream>,
+    /// The output of the `syn` + `quote` implementation of the derive
+    pub reference: &'static str,
+}
+
+/// All derive cases
+pub fn cases() -> Vec<DeriveCase> {
+    vec![
+        DeriveCase {
+            name: "clone_point",
+            input: "pub struct Point<T> { pub x: T, pub y: T, label: &'static str }"
+                .parse()
+                .unwrap(),
+            derive: derive_clone,
+            reference: include_str!("../reference/clone_point.rs"),
+        },
+        DeriveCase {
+            name: "debug_shape",
+            input: "pub enum Shape<T: Copy> where T: Default { Circle { radius: T }, Rect(T, T), Empty }"
+                .parse()
+                .unwrap(),
+            derive: derive_debug,
+            reference: include_str!("../reference/debug_shape.rs"),
+        },
+        DeriveCase {
+            name: "clone_giant",
+            input: giant_struct(100),
+            derive: derive_clone,
+            reference: include_str!("../reference/clone_giant.rs"),
+        },
+    ]
+}
+
+/// The amount of tokens in `stream`. The delimiters of a group count as one token.
+pub fn token_count(stream: TokenStream) -> usize {
+    stream
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Group(group) if group.delimiter() == Delimiter::None => {
+                token_count(group.stream())
+            }
+            TokenTree::Group(group) => 1 + token_count(group.stream()),
+            _ => 1,
+        })
+        .sum()
+}
+
+/// `#[derive(Clone)]` for structs with named fields
+pub fn derive_clone(input: TokenStream) -> Result<TokenStream> {
+    let (mut generator, _, body) = Parse::new(input)?.into_generator();
+    let fields = match body {
+        Body::Struct(body) => body.fields,
+        Body::Enum(_) => return Err(Error::custom("Only structs are supported")),
+    };
+    generator
+        .impl_for("::core::clone::Clone")
+        .modify_generic_constraints(|generics, constraints| {
+            for generic in generics.iter_generics() {
+                constraints.push_constraint(generic, "::core::clone::Clone")?;
+            }
+            Ok(())
+        })?
+        .generate_fn("clone")
+        .with_self_arg(FnSelfArg::RefSelf)
+        .with_return_type("Self")
+        .body(|b| {
+            b.ident_str("Self");
+            b.group(Delimiter::Brace, |b| {
+                for name in fields.iter().flat_map(Fields::names) {
+                    b.push_parsed(format!(
+                        "{0}: ::core::clone::Clone::clone(&self.{0}),",
+                        name
+                    ))?;
+                }
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+    generator.finish()
+}
+
+/// `#[derive(Debug)]`, with [`Generator::generate_debug_impl`]
+pub fn derive_debug(input: TokenStream) -> Result<TokenStream> {
+    let (mut generator, _, body) = Parse::new(input)?.into_generator();
+    generator.generate_debug_impl(&body)?;
+    generator.finish()
+}
@@ -0,0 +1,59 @@
+//! Inputs for the virtue benchmarks. Run the benchmarks with `cargo bench -p virtue_bench`.
+//!
+//! To compare a change against the current state, save a baseline before making the change and compare against it afterwards:
+//!
+//! ```text
+//! cargo bench -p virtue_bench -- --save-baseline before
+//! # make your changes
+//! cargo bench -p virtue_bench -- --baseline before
+//! ```
+//!
+//! The `split_fields` groups of `cargo bench -p virtue_bench --bench parse` time how the parser splits the fields of a struct against the baseline implementation from before the token buffer, so the two can be compared in a single run.
+//!
+//! The memory that is allocated while parsing is printed by `cargo bench -p virtue_bench --bench memory`, and the derives of [`derives`] are compared with the same derives implemented with `syn` and `quote` by `cargo bench -p virtue_bench --bench derive`.
+
+pub mod derives;
+
+use proc_macro2::TokenStream;
+use std::fmt::Write;
+
+/// A struct with `fields` named fields, similar to what code generators like bindgen or prost output.
+pub fn giant_struct(fields: usize) -> TokenStream {
+    let mut code = String::from("#[derive(Debug)] pub struct Giant<'a, T: Clone> where T: Send {");
+    for i in 0..fields {
+        write!(
+            code,
+            "#[doc = \"field {i}\"] pub field_{i}: Option<std::collections::HashMap<&'a str, Vec<(T, fn(u8) -> u{size})>>>,",
+            i = i,
+            size = 8 << (i % 4)
+        )
+        .unwrap();
+    }
+    code.push('}');
+    code.parse().unwrap()
+}
+
+/// A tuple struct with `fields` fields.
+pub fn giant_tuple_struct(fields: usize) -> TokenStream {
+    let mut code = String::from("pub struct Giant(");
+    for i in 0..fields {
+        write!(code, "pub [u{}; 4],", 8 << (i % 4)).unwrap();
+    }
+    code.push_str(");");
+    code.parse().unwrap()
+}
+
+/// An enum with `variants` variants, alternating between unit, tuple and struct variants.
+pub fn giant_enum(variants: usize) -> TokenStream {
+    let mut code = String::from("pub enum Giant<T> {");
+    for i in 0..variants {
+        match i % 3 {
+            0 => write!(code, "Unit{},", i),
+            1 => write!(code, "Tuple{}(T, Box<dyn Fn(T) -> T>),", i),
+            _ => write!(code, "Struct{} {{ a: T, b: Vec<Vec<u8>> }},", i),
+        }
+        .unwrap();
+    }
+    code.push('}');
+    code.parse().unwrap()
+}
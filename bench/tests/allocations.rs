@@ -0,0 +1,96 @@
+//! Checks that the accessors that borrow from the parsed input do not allocate, with a global allocator that counts the allocations of the current thread.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use virtue::prelude::*;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (result, ALLOCATIONS.with(Cell::get) - before)
+}
+
+#[test]
+fn generic_constraints_and_names_are_borrowed() {
+    let input: TokenStream =
+        "struct Foo<'a: 'b, 'b, T: Clone + Send, U: Iterator<Item = T>, const N: usize> { t: &'a [T; N], u: &'b U }"
+            .parse()
+            .unwrap();
+    let generics = match Parse::new(input).unwrap() {
+        Parse::Struct {
+            generics: Some(generics),
+            ..
+        } => generics,
+        _ => unreachable!(),
+    };
+
+    let (constraint_tokens, allocations) = count_allocations(|| {
+        generics
+            .iter()
+            .map(|generic| generic.constraints().len())
+            .sum::<usize>()
+    });
+    assert_eq!(constraint_tokens, 12);
+    assert_eq!(allocations, 0);
+
+    let (names, allocations) = count_allocations(|| {
+        generics
+            .iter_generics()
+            .filter(|generic| generic.name_ref() == "T")
+            .count()
+    });
+    assert_eq!(names, 1);
+    assert_eq!(allocations, 0);
+
+    // cloning the names, like `SimpleGeneric::name` does, allocates for every name
+    let (names, allocations) = count_allocations(|| {
+        generics
+            .iter_generics()
+            .map(|generic| generic.name())
+            .collect::<Vec<Ident>>()
+    });
+    assert_eq!(names.len(), 2);
+    assert!(allocations > 1, "{} allocations", allocations);
+}
+
+#[test]
+fn field_names_borrow_the_attributes() {
+    let input: TokenStream = "struct Foo { #[a] #[b] a: u8, #[c] b: u16, c: u32 }"
+        .parse()
+        .unwrap();
+    let fields = match Parse::new(input).unwrap() {
+        Parse::Struct { body, .. } => body.fields.unwrap(),
+        _ => unreachable!(),
+    };
+
+    // only the `Vec` of names is allocated, the attributes are borrowed from the fields
+    let (attributes, allocations) = count_allocations(|| {
+        fields
+            .names()
+            .iter()
+            .map(|name| name.attributes().len())
+            .sum::<usize>()
+    });
+    assert_eq!(attributes, 3);
+    assert_eq!(allocations, 1);
+}
@@ -0,0 +1,20 @@
+use proc_macro2::TokenStream;
+use virtue_bench::derives::{cases, token_count};
+
+/// How many percent more tokens a virtue derive may generate than its `syn` + `quote` reference. Virtue puts the bounds of the impl in a where clause, which costs a few tokens.
+const MAX_OVERHEAD_PERCENT: usize = 5;
+
+#[test]
+fn derives_are_not_larger_than_syn() {
+    for case in cases() {
+        let output = token_count((case.derive)(case.input.clone()).unwrap());
+        let reference = token_count(case.reference.parse::<TokenStream>().unwrap());
+        assert!(
+            output * 100 <= reference * (100 + MAX_OVERHEAD_PERCENT),
+            "{}: virtue generates {} tokens, syn + quote {}",
+            case.name,
+            output,
+            reference
+        );
+    }
+}
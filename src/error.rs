@@ -5,13 +5,26 @@ use crate::{
 use std::fmt;
 
 /// Errors that can occur while parsing or generator your derive macro.
+///
+/// This enum is `#[non_exhaustive]` so that new errors can be added without a breaking change.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
-    /// The data type at `Span` is unknown. This will be called when [`Parse::new`] is called on anything that is not a `struct` or `enum`.
+    /// The data type at `Span` is unknown. This will be called when [`Parse::new`] is called on anything that is not a `struct` or `enum`, and not one of the items listed in [`ItemKind`].
     ///
     /// [`Parse::new`]: enum.Parse.html#method.new
     UnknownDataType(Span),
 
+    /// The item at `span` is a valid Rust item, but not one that virtue can parse. This will be returned when [`Parse::new`] is called on e.g. a `union` or a `trait`.
+    ///
+    /// [`Parse::new`]: enum.Parse.html#method.new
+    UnsupportedItem {
+        /// The kind of item that was found
+        kind: ItemKind,
+        /// The span of the item keyword
+        span: Span,
+    },
+
     /// The rust syntax is invalid. This can be returned while parsing the Enum or Struct.
     ///
     /// This error is assumed to not appear as rustc will do syntax checking before virtue gets access to the [`TokenStream`].
@@ -43,6 +56,62 @@ pub enum Error {
         /// Optionally the position that the error occurred at
         span: Option<Span>,
     },
+
+    /// An error with an additional help message, e.g. `did you mean \`skip\`?`. See [`Error::custom_with_help`] and [`Error::with_help`].
+    ///
+    /// The help message is shown as a second error at the same location, similar to how rustc shows a `help:` note below an error.
+    WithHelp {
+        /// The actual error
+        error: Box<Error>,
+        /// The help message, without a `help: ` prefix
+        help: String,
+    },
+}
+
+/// The kind of an item that virtue does not support, see [`Error::UnsupportedItem`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ItemKind {
+    /// A `union`
+    Union,
+    /// A `trait`
+    Trait,
+    /// A `fn`
+    Fn,
+    /// A `type` alias
+    Type,
+    /// An `impl` block
+    Impl,
+}
+
+impl ItemKind {
+    /// The keyword of this item, e.g. `"union"`.
+    pub fn keyword(self) -> &'static str {
+        match self {
+            ItemKind::Union => "union",
+            ItemKind::Trait => "trait",
+            ItemKind::Fn => "fn",
+            ItemKind::Type => "type",
+            ItemKind::Impl => "impl",
+        }
+    }
+
+    pub(crate) fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "union" => Some(ItemKind::Union),
+            "trait" => Some(ItemKind::Trait),
+            "fn" => Some(ItemKind::Fn),
+            "type" => Some(ItemKind::Type),
+            "impl" => Some(ItemKind::Impl),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ItemKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(self.keyword())
+    }
 }
 
 impl From<PushParseError> for Error {
@@ -54,6 +123,18 @@ impl From<PushParseError> for Error {
     }
 }
 
+/// Convert a [`LexError`], e.g. from calling `TokenStream::from_str` directly. The code that failed to parse is not known, so [`PushParseError::code`] will be empty.
+///
+/// [`PushParseError::code`]: generate/struct.PushParseError.html#structfield.code
+impl From<LexError> for Error {
+    fn from(error: LexError) -> Self {
+        Self::from(PushParseError {
+            error,
+            code: String::new(),
+        })
+    }
+}
+
 impl Error {
     /// Throw a custom error
     pub fn custom(s: impl Into<String>) -> Self {
@@ -87,6 +168,25 @@ impl Error {
         }
     }
 
+    /// Throw a custom error at a given location, with a help message that is shown below the error.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// let error = Error::custom_with_help("unknown key `serde`", "did you mean `skip`?", Span::call_site());
+    /// assert_eq!(error.to_string(), "unknown key `serde`\nhelp: did you mean `skip`?");
+    /// ```
+    pub fn custom_with_help(s: impl Into<String>, help: impl Into<String>, span: Span) -> Self {
+        Self::custom_at(s, span).with_help(help)
+    }
+
+    /// Add a help message to this error, which is shown below the error.
+    pub fn with_help(self, help: impl Into<String>) -> Self {
+        Self::WithHelp {
+            error: Box::new(self),
+            help: help.into(),
+        }
+    }
+
     pub(crate) fn wrong_token<T>(token: Option<&TokenTree>, expected: &str) -> Result<T> {
         Err(Self::InvalidRustSyntax {
             span: token.map(|t| t.span()).unwrap_or_else(Span::call_site),
@@ -96,48 +196,173 @@ impl Error {
 
     /// Return a new error that is located at the given span
     pub fn with_span(mut self, new_span: Span) -> Self {
-        match &mut self {
+        self.set_span(new_span);
+        self
+    }
+
+    fn set_span(&mut self, new_span: Span) {
+        match self {
             Error::UnknownDataType(span) => *span = new_span,
+            Error::UnsupportedItem { span, .. } => *span = new_span,
             Error::InvalidRustSyntax { span, .. } => *span = new_span,
             Error::ExpectedIdent(span) => *span = new_span,
             Error::PushParse { span, .. } => {
                 *span = Some(new_span);
             }
             Error::Custom { span, .. } => *span = Some(new_span),
+            Error::WithHelp { error, .. } => error.set_span(new_span),
         }
+    }
 
-        self
+    /// The location of this error, if it has one. This is the span that [`into_token_stream`] shows the error at, unless it is `None`.
+    ///
+    /// [`into_token_stream`]: #method.into_token_stream
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::UnknownDataType(span)
+            | Self::UnsupportedItem { span, .. }
+            | Self::ExpectedIdent(span)
+            | Self::InvalidRustSyntax { span, .. } => Some(*span),
+            Self::Custom { span, .. } | Self::PushParse { span, .. } => *span,
+            Self::WithHelp { error, .. } => error.span(),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "span-locations"))]
+impl Error {
+    /// The start and end of [`span`]. Empty spans like `Span::call_site()` have no location.
+    ///
+    /// [`span`]: #method.span
+    fn location(&self) -> Option<(proc_macro2::LineColumn, proc_macro2::LineColumn)> {
+        let span = self.span()?;
+        let (start, end) = (span.start(), span.end());
+        if start == end {
+            None
+        } else {
+            Some((start, end))
+        }
+    }
+
+    /// Render this error like rustc does, with the line of `source` that the error points at. `source` has to be the code that the tokens of the error were parsed from, e.g. the input of a test.
+    ///
+    /// This is only available with the `span-locations` feature, as the location of a span is not available inside of a proc macro.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// let source = "struct Foo {\n    a: u8,\n}";
+    /// let (_, _, body) = Parse::new(source.parse().unwrap())?.into_generator();
+    /// # let fields = match body { Body::Struct(body) => body.fields.unwrap(), _ => unreachable!() };
+    /// let (ident, _) = fields.get(0).unwrap();
+    /// let error = Error::custom_at("unknown field", ident.unwrap().span());
+    /// assert_eq!(error.to_string(), "unknown field (at 2:5)");
+    /// assert_eq!(
+    ///     error.render_with_source(source),
+    ///     "error: unknown field\n --> 2:5\n  |\n2 |     a: u8,\n  |     ^"
+    /// );
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn render_with_source(&self, source: &str) -> String {
+        if let Self::WithHelp { error, help } = self {
+            return format!("{}\n  = help: {}", error.render_with_source(source), help);
+        }
+        let mut result = format!("error: {}", Message(self));
+        let (start, end) = match self.location() {
+            Some(location) => location,
+            None => return result,
+        };
+        let line = match source.lines().nth(start.line.saturating_sub(1)) {
+            Some(line) => line,
+            None => return result,
+        };
+        let gutter = " ".repeat(start.line.to_string().len());
+        let width = if end.line == start.line {
+            end.column.saturating_sub(start.column).max(1)
+        } else {
+            line.chars().count().saturating_sub(start.column).max(1)
+        };
+        result += &format!(
+            "\n{gutter}--> {line_number}:{column}\n{gutter} |\n{line_number} | {line}\n{gutter} | {padding}{carets}",
+            gutter = gutter,
+            line_number = start.line,
+            column = start.column + 1,
+            line = line,
+            padding = " ".repeat(start.column),
+            carets = "^".repeat(width),
+        );
+        result
     }
 }
 
 // helper functions for the unit tests
 #[cfg(test)]
 impl Error {
-    pub fn is_unknown_data_type(&self) -> bool {
+    pub(crate) fn is_unknown_data_type(&self) -> bool {
         matches!(self, Error::UnknownDataType(_))
     }
 
-    pub fn is_invalid_rust_syntax(&self) -> bool {
+    pub(crate) fn unsupported_item_kind(&self) -> Option<ItemKind> {
+        match self {
+            Error::UnsupportedItem { kind, .. } => Some(*kind),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn is_invalid_rust_syntax(&self) -> bool {
         matches!(self, Error::InvalidRustSyntax { .. })
     }
 }
 
+/// Shows the location of the error after the message, e.g. `unknown field (at 2:5)`, when the `span-locations` feature is enabled. The column starts at 1, like in the messages of rustc.
 impl fmt::Display for Error {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Self::UnknownDataType(_) => {
+        if let Self::WithHelp { error, help } = self {
+            return write!(fmt, "{}\nhelp: {}", error, help);
+        }
+        Message(self).fmt(fmt)?;
+        #[cfg(any(test, feature = "span-locations"))]
+        if let Some((start, _)) = self.location() {
+            write!(fmt, " (at {}:{})", start.line, start.column + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// The message of an error without its location, for [`compile_error`] which shows the error at its location already
+struct Message<'a>(&'a Error);
+
+impl fmt::Display for Message<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Error::UnknownDataType(_) => {
                 write!(fmt, "Unknown data type, only enum and struct are supported")
             }
-            Self::InvalidRustSyntax { expected, .. } => {
+            Error::UnsupportedItem { kind, .. } => write!(
+                fmt,
+                "`{}` is not supported, only enum and struct are supported",
+                kind
+            ),
+            Error::InvalidRustSyntax { expected, .. } => {
                 write!(fmt, "Invalid rust syntax, expected {}", expected)
             }
-            Self::ExpectedIdent(_) => write!(fmt, "Expected ident"),
-            Self::PushParse { error, .. } => write!(
+            Error::ExpectedIdent(_) => write!(fmt, "Expected ident"),
+            Error::PushParse { error, .. } => write!(
                 fmt,
                 "Invalid code passed to `StreamBuilder::push_parsed`: {:?}",
                 error
             ),
-            Self::Custom { error, .. } => write!(fmt, "{}", error),
+            Error::Custom { error, .. } => write!(fmt, "{}", error),
+            Error::WithHelp { error, help } => write!(fmt, "{}\nhelp: {}", Message(error), help),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::PushParse { error, .. } => Some(error),
+            Self::WithHelp { error, .. } => error.source(),
+            _ => None,
         }
     }
 }
@@ -145,28 +370,132 @@ impl fmt::Display for Error {
 impl Error {
     /// Turn this error into a [`TokenStream`] so it shows up as a [`compile_error`] for the user.
     pub fn into_token_stream(self) -> TokenStream {
-        let maybe_span = match &self {
-            Self::UnknownDataType(span)
-            | Self::ExpectedIdent(span)
-            | Self::InvalidRustSyntax { span, .. } => Some(*span),
-            Self::Custom { span, .. } | Self::PushParse { span, .. } => *span,
-        };
-        self.throw_with_span(maybe_span.unwrap_or_else(Span::call_site))
+        let mut stream = TokenStream::new();
+        self.append_to_token_stream(&mut stream);
+        stream
+    }
+
+    /// Add this error as a [`compile_error`] to the end of `stream`, without creating an intermediate [`TokenStream`].
+    ///
+    /// This is useful for attribute macros, which should still return the original item when they fail so the user does not get additional errors about a missing item:
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// let mut item = TokenStream::from_str("struct Foo;").unwrap();
+    /// let error = Error::custom("Foo is not supported");
+    /// error.append_to_token_stream(&mut item);
+    /// assert_eq!(item.to_string(), "struct Foo ; compile_error ! { \"Foo is not supported\" }");
+    /// ```
+    pub fn append_to_token_stream(self, stream: &mut TokenStream) {
+        let span = self.span().unwrap_or_else(Span::call_site);
+        self.throw_with_span_into(span, stream);
     }
 
     /// Turn this error into a [`TokenStream`] so it shows up as a [`compile_error`] for the user. The error will be shown at the given `span`.
     pub fn throw_with_span(self, span: Span) -> TokenStream {
-        // compile_error!($message)
-        let mut builder = StreamBuilder::new();
-        builder.ident_str("compile_error");
-        builder.punct('!');
-        builder
-            .group(Delimiter::Brace, |b| {
-                b.lit_str(self.to_string());
-                Ok(())
-            })
-            .unwrap();
-        builder.set_span_on_all_tokens(span);
-        builder.stream
+        let mut stream = TokenStream::new();
+        self.throw_with_span_into(span, &mut stream);
+        stream
     }
+
+    fn throw_with_span_into(self, span: Span, stream: &mut TokenStream) {
+        match self {
+            // compile_error!($message) compile_error!("help: $help")
+            Self::WithHelp { error, help } => {
+                error.throw_with_span_into(span, stream);
+                compile_error(format!("help: {}", help), span, stream);
+            }
+            // compile_error!($message)
+            error => compile_error(Message(&error).to_string(), span, stream),
+        }
+    }
+}
+
+fn compile_error(message: String, span: Span, stream: &mut TokenStream) {
+    let mut builder = StreamBuilder::new();
+    builder
+        .macro_call("compile_error", Delimiter::Brace, |b| {
+            b.lit_str(message);
+            Ok(())
+        })
+        .expect("`compile_error` is a valid path");
+    builder.set_span_on_all_tokens(span);
+    stream.extend(builder.stream);
+}
+
+#[test]
+fn test_error_source() {
+    use std::error::Error as _;
+    use std::str::FromStr;
+
+    let lex_error = TokenStream::from_str("\"unterminated").unwrap_err();
+    let error = Error::from(lex_error).with_help("close the string");
+    let source = error.source().expect("PushParse has a source");
+    assert_eq!(source.to_string(), "Could not parse code");
+    assert!(source.source().is_some());
+
+    let boxed: Box<dyn std::error::Error> = Box::new(Error::custom("foo"));
+    assert_eq!(boxed.to_string(), "foo");
+    assert!(boxed.source().is_none());
+}
+
+#[test]
+fn test_with_help() {
+    let error = Error::custom_with_help(
+        "unknown key `serde`",
+        "did you mean `skip`?",
+        Span::call_site(),
+    );
+    assert_eq!(
+        error.into_token_stream().to_string(),
+        "compile_error ! { \"unknown key `serde`\" } compile_error ! { \"help: did you mean `skip`?\" }"
+    );
+
+    // the span of the inner error is used
+    let error = Error::custom("foo")
+        .with_help("bar")
+        .with_span(Span::call_site());
+    assert!(matches!(
+        error,
+        Error::WithHelp { error, .. } if matches!(*error, Error::Custom { span: Some(_), .. })
+    ));
+}
+
+#[test]
+fn test_error_location() {
+    use crate::token_stream;
+
+    let tokens: Vec<TokenTree> = token_stream("struct Foo {\n    é: u8, b: bool\n}").collect();
+    let group = match &tokens[2] {
+        TokenTree::Group(group) => group.stream().into_iter().collect::<Vec<_>>(),
+        _ => unreachable!(),
+    };
+    let source = "struct Foo {\n    é: u8, b: bool\n}";
+    let error = Error::custom_at("unknown type", group[6].span());
+    assert_eq!(error.to_string(), "unknown type (at 2:15)");
+    assert_eq!(
+        error.render_with_source(source),
+        "error: unknown type\n --> 2:15\n  |\n2 |     é: u8, b: bool\n  |               ^^^^"
+    );
+    // the message of `compile_error!` does not contain the location
+    assert_eq!(
+        error.into_token_stream().to_string(),
+        "compile_error ! { \"unknown type\" }"
+    );
+
+    let error = Error::custom_at("multiline", tokens[2].span()).with_help("remove it");
+    assert_eq!(error.to_string(), "multiline (at 1:12)\nhelp: remove it");
+    assert_eq!(
+        error.render_with_source(source),
+        "error: multiline\n --> 1:12\n  |\n1 | struct Foo {\n  |            ^\n  = help: remove it"
+    );
+
+    let error = Error::custom("no location");
+    assert!(error.span().is_none());
+    assert_eq!(error.render_with_source(source), "error: no location");
+    assert_eq!(
+        Error::custom_at("call site", Span::call_site()).to_string(),
+        "call site"
+    );
 }
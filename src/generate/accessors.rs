@@ -0,0 +1,246 @@
+use super::unraw;
+use super::{FnSelfArg, Generator, StreamBuilder};
+use crate::parse::{Attribute, Fields, StructBody, UnnamedField};
+use crate::prelude::{Delimiter, Ident, TokenStream};
+use crate::{Error, Result};
+
+/// The methods that [`Generator::generate_accessors`] generates.
+///
+/// [`Generator::generate_accessors`]: struct.Generator.html#method.generate_accessors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccessorConfig<'a> {
+    /// Generate a `fn new(a: A, b: B) -> Self`. Skipped fields are set to `Default::default()`, and so are fields with a `#[cfg(...)]` or `#[cfg_attr(..., cfg(...))]`, with that cfg on their initializer, so the constructor is valid whether the cfg is enabled or not.
+    pub constructor: bool,
+    /// Generate a `fn <prefix><field>(&self) -> &<type>` for every field.
+    pub getters: bool,
+    /// Generate a `fn set_<field>(&mut self, value: <type>) -> &mut Self` for every field.
+    pub setters: bool,
+    /// The prefix of the getters, e.g. `"get_"` for `get_a`. With an empty prefix the getters have the same name as the fields.
+    pub prefix: &'a str,
+}
+
+impl Default for AccessorConfig<'_> {
+    /// Only getters without a prefix
+    fn default() -> Self {
+        Self {
+            constructor: false,
+            getters: true,
+            setters: false,
+            prefix: "",
+        }
+    }
+}
+
+impl Generator {
+    /// Generate an `impl <target_name>` with a constructor, getters and setters for every field of a struct with named fields. See [`AccessorConfig`] for the methods that can be generated.
+    ///
+    /// The methods have the same visibility as the struct, and the `#[cfg(...)]` of the field they access, see [`Attribute::cfg_of`].
+    ///
+    /// [`Attribute::cfg_of`]: ../parse/struct.Attribute.html#method.cfg_of
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// use virtue::generate::AccessorConfig;
+    ///
+    /// let input = TokenStream::from_str("pub struct Foo { a: u32 }").unwrap();
+    /// let (mut generator, _, body) = Parse::new(input)?.into_generator();
+    /// # let body = match body { Body::Struct(body) => body, _ => unreachable!() };
+    /// generator.generate_accessors(
+    ///     &body,
+    ///     AccessorConfig { constructor: false, getters: true, setters: true, prefix: "get_" },
+    /// )?;
+    /// // impl Foo {
+    /// //     #[doc = "Returns a reference to `a`"]
+    /// //     pub fn get_a(&self) -> &u32 { &self.a }
+    /// //     #[doc = "Sets `a` and returns `self`, so calls can be chained"]
+    /// //     pub fn set_a(&mut self, a: u32) -> &mut Self { self.a = a; self }
+    /// // }
+    /// # generator.assert_eq("impl Foo { \
+    /// #     # [doc = \"Returns a reference to `a`\"] pub fn get_a (& self) ->& u32 { & self . a } \
+    /// #     # [doc = \"Sets `a` and returns `self`, so calls can be chained\"] \
+    /// #     pub fn set_a (& mut self , a : u32) ->& mut Self { self . a = a ; self } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn generate_accessors(&mut self, body: &StructBody, config: AccessorConfig) -> Result {
+        self.generate_accessors_with(body, config, |_, _| false)
+    }
+
+    /// Generate accessors like [`generate_accessors`], but skip every field for which `skip_field` returns `true`, e.g. because it has a `#[accessors(skip)]` attribute.
+    ///
+    /// Skipped fields get no getter and setter, and are initialized with `Default::default()` by the constructor.
+    ///
+    /// [`generate_accessors`]: #method.generate_accessors
+    pub fn generate_accessors_with(
+        &mut self,
+        body: &StructBody,
+        config: AccessorConfig,
+        skip_field: impl Fn(&Ident, &UnnamedField) -> bool,
+    ) -> Result {
+        let fields: Vec<(&Ident, &UnnamedField, bool)> = match &body.fields {
+            Some(Fields::Struct(fields)) => fields
+                .iter()
+                .map(|(ident, field)| (ident, field, skip_field(ident, field)))
+                .collect(),
+            Some(Fields::Tuple(_)) => {
+                return Err(Error::custom_at(
+                    "Accessors can only be generated for structs with named fields",
+                    self.target_name().span(),
+                ))
+            }
+            None => Vec::new(),
+        };
+        let visibility = self.target_visibility().clone();
+        // the `#[cfg(...)]` of every field, including the ones of a `#[cfg_attr(..., cfg(...))]`
+        let cfgs: Vec<Option<TokenStream>> = fields
+            .iter()
+            .map(|(_, field, _)| Attribute::cfg_of(&field.attributes))
+            .collect();
+        let mut r#impl = self.r#impl();
+
+        if config.constructor {
+            let mut builder = r#impl
+                .generate_fn("new")
+                .with_visibility(visibility.clone())
+                .with_attr("doc = \"Creates a new instance from the given fields\"")
+                .with_return_type("Self");
+            for ((ident, field, skip), cfg) in fields.iter().zip(&cfgs) {
+                if !skip && cfg.is_none() {
+                    builder = builder.with_arg(ident.to_string(), *field);
+                }
+            }
+            builder.body(|b| {
+                b.ident_str("Self").group(Delimiter::Brace, |b| {
+                    for ((ident, _, skip), cfg) in fields.iter().zip(&cfgs) {
+                        if let Some(cfg) = cfg {
+                            b.cfg_attribute(cfg.clone());
+                        }
+                        b.ident((*ident).clone());
+                        if *skip || cfg.is_some() {
+                            b.punct(':')
+                                .push_parsed("::core::default::Default::default()")?;
+                        }
+                        b.punct(',');
+                    }
+                    Ok(())
+                })?;
+                Ok(())
+            })?;
+        }
+
+        for ((ident, field, _), cfg) in fields.iter().zip(&cfgs).filter(|((_, _, skip), _)| !skip) {
+            let name = unraw(ident);
+            let mut cfg_attr = None;
+            if let Some(cfg) = cfg {
+                let mut attr = StreamBuilder::new();
+                attr.ident_str("cfg").group(Delimiter::Parenthesis, |b| {
+                    b.extend(cfg.clone());
+                    Ok(())
+                })?;
+                cfg_attr = Some(attr);
+            }
+            if config.getters {
+                let getter = if config.prefix.is_empty() {
+                    ident.to_string()
+                } else {
+                    format!("{}{}", config.prefix, name)
+                };
+                let mut return_type = StreamBuilder::new();
+                return_type.punct('&').extend(field.r#type.iter().cloned());
+                let mut getter = r#impl
+                    .generate_fn(getter)
+                    .with_visibility(visibility.clone());
+                if let Some(attr) = &cfg_attr {
+                    getter = getter.with_attr(attr.clone());
+                }
+                getter
+                    .with_attr(format!("doc = \"Returns a reference to `{}`\"", name))
+                    .with_self_arg(FnSelfArg::RefSelf)
+                    .with_self_arg_trailing_comma(false)
+                    .with_return_type(return_type)
+                    .body(|b| {
+                        b.punct('&')
+                            .ident_str("self")
+                            .punct('.')
+                            .ident((*ident).clone());
+                        Ok(())
+                    })?;
+            }
+            if config.setters {
+                let mut setter = r#impl
+                    .generate_fn(format!("set_{}", name))
+                    .with_visibility(visibility.clone());
+                if let Some(attr) = &cfg_attr {
+                    setter = setter.with_attr(attr.clone());
+                }
+                setter
+                    .with_attr(format!(
+                        "doc = \"Sets `{}` and returns `self`, so calls can be chained\"",
+                        name
+                    ))
+                    .with_self_arg(FnSelfArg::MutSelf)
+                    .with_arg(ident.to_string(), *field)
+                    .with_return_type("&mut Self")
+                    .body(|b| {
+                        b.ident_str("self")
+                            .punct('.')
+                            .ident((*ident).clone())
+                            .punct('=')
+                            .ident((*ident).clone())
+                            .punct(';')
+                            .ident_str("self");
+                        Ok(())
+                    })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_generate_accessors() {
+    use crate::parse::{Body, Parse};
+    use crate::token_stream;
+
+    let parse = |input: &str| {
+        let (generator, _, body) =
+            Parse::new(token_stream(input).collect::<proc_macro2::TokenStream>())
+                .unwrap()
+                .into_generator();
+        match body {
+            Body::Struct(body) => (generator, body),
+            Body::Enum(_) => unreachable!(),
+        }
+    };
+
+    let (mut generator, body) =
+        parse("pub(crate) struct Foo<T> { r#type: T, #[cfg(test)] b: u8, #[skip] c: Vec<T>, #[cfg_attr(unix, cfg(feature = \"d\"))] d: u8 }");
+    generator
+        .generate_accessors_with(
+            &body,
+            AccessorConfig {
+                constructor: true,
+                ..AccessorConfig::default()
+            },
+            |_, field| !field.attributes.is_empty() && field.attributes[0].path_is("skip"),
+        )
+        .unwrap();
+    generator.assert_eq(
+        "impl < T > Foo < T > { \
+         # [doc = \"Creates a new instance from the given fields\"] \
+         pub (crate) fn new (r#type : T) ->Self { \
+         Self { r#type , # [cfg (test)] b : :: core :: default :: Default :: default () , \
+         c : :: core :: default :: Default :: default () , \
+         # [cfg (any (not (unix) , all (feature = \"d\")))] d : :: core :: default :: Default :: default () , } } \
+         # [doc = \"Returns a reference to `type`\"] pub (crate) fn r#type (& self) ->& T { & self . r#type } \
+         # [cfg (test)] # [doc = \"Returns a reference to `b`\"] pub (crate) fn b (& self) ->& u8 { & self . b } \
+         # [cfg (any (not (unix) , all (feature = \"d\")))] # [doc = \"Returns a reference to `d`\"] \
+         pub (crate) fn d (& self) ->& u8 { & self . d } }",
+    );
+
+    let (mut generator, body) = parse("struct Foo(u8);");
+    assert!(generator
+        .generate_accessors(&body, AccessorConfig::default())
+        .is_err());
+    generator.assert_eq("");
+}
@@ -0,0 +1,117 @@
+use super::{GenAttr, StreamBuilder};
+use crate::parse::Attribute;
+use crate::prelude::{Delimiter, TokenStream};
+use crate::Result;
+use std::borrow::Cow;
+use std::fmt;
+
+/// The contents of an attribute that is passed to a builder, e.g. to [`FnBuilder::with_attr`]. This is everything between the `#[` and `]`, e.g. `inline` or `serde(skip)`.
+///
+/// This can be created from a string, which will be parsed, or from tokens that were already built, e.g. a [`GenAttr`], a [`StreamBuilder`] or a parsed [`Attribute`] that should be emitted again.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// use virtue::generate::GenAttr;
+///
+/// # let mut generator = Generator::with_name("Foo");
+/// generator
+///     .generate_struct("Bar")
+///     .with_attr("derive(Debug)")?
+///     .with_attr(GenAttr::new("serde")?.arg("transparent")?)?;
+/// # generator.assert_eq("# [derive (Debug)] # [serde (transparent)] struct Bar { }");
+/// # Ok::<_, virtue::Error>(())
+/// ```
+///
+/// [`FnBuilder::with_attr`]: struct.FnBuilder.html#method.with_attr
+/// [`GenAttr`]: struct.GenAttr.html
+/// [`Attribute`]: ../parse/struct.Attribute.html
+pub struct AttrTokens(AttrTokensInner);
+
+enum AttrTokensInner {
+    Code(String),
+    Tokens(TokenStream),
+}
+
+impl AttrTokens {
+    /// Add the contents of the attribute to `builder`, parsing them first if they were created from a string
+    pub(crate) fn append_to(self, builder: &mut StreamBuilder) -> Result {
+        match self.0 {
+            AttrTokensInner::Code(code) => {
+                builder.push_parsed(code)?;
+            }
+            AttrTokensInner::Tokens(tokens) => {
+                builder.extend(tokens);
+            }
+        }
+        Ok(())
+    }
+
+    /// Build `#[<contents>]`, or `#![<contents>]` if `is_inner` is set
+    pub(crate) fn into_attribute(self, is_inner: bool) -> Result<StreamBuilder> {
+        let mut builder = StreamBuilder::new();
+        builder.punct('#');
+        if is_inner {
+            builder.punct('!');
+        }
+        builder.group(Delimiter::Bracket, |builder| self.append_to(builder))?;
+        Ok(builder)
+    }
+}
+
+impl fmt::Display for AttrTokens {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match &self.0 {
+            AttrTokensInner::Code(code) => code.fmt(fmt),
+            AttrTokensInner::Tokens(tokens) => tokens.fmt(fmt),
+        }
+    }
+}
+
+impl From<String> for AttrTokens {
+    fn from(code: String) -> Self {
+        Self(AttrTokensInner::Code(code))
+    }
+}
+
+impl<'a> From<&'a String> for AttrTokens {
+    fn from(code: &'a String) -> Self {
+        Self(AttrTokensInner::Code(code.clone()))
+    }
+}
+
+impl<'a> From<&'a str> for AttrTokens {
+    fn from(code: &'a str) -> Self {
+        Self(AttrTokensInner::Code(code.to_owned()))
+    }
+}
+
+impl<'a> From<Cow<'a, str>> for AttrTokens {
+    fn from(code: Cow<'a, str>) -> Self {
+        Self(AttrTokensInner::Code(code.into_owned()))
+    }
+}
+
+impl From<TokenStream> for AttrTokens {
+    fn from(tokens: TokenStream) -> Self {
+        Self(AttrTokensInner::Tokens(tokens))
+    }
+}
+
+impl From<StreamBuilder> for AttrTokens {
+    fn from(builder: StreamBuilder) -> Self {
+        builder.stream.into()
+    }
+}
+
+impl<'a> From<GenAttr<'a>> for AttrTokens {
+    fn from(attribute: GenAttr<'a>) -> Self {
+        attribute.into_contents().into()
+    }
+}
+
+impl<'a> From<&'a Attribute> for AttrTokens {
+    /// The contents of the attribute. Note that an inner attribute like `#![allow(unused)]` is emitted as whatever the builder emits, usually an outer attribute.
+    fn from(attribute: &'a Attribute) -> Self {
+        attribute.tokens.stream().into()
+    }
+}
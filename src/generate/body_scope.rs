@@ -0,0 +1,180 @@
+use super::{
+    generate_item::FnParent, FnBuilder, GenEnum, GenStruct, Impl, ImplFor, Parent, StreamBuilder,
+    StringOrIdent,
+};
+use crate::parse::{GenericConstraints, Generics};
+use crate::prelude::{Delimiter, Ident, Span};
+use crate::Result;
+
+/// Builder for items that are local to a function body or block, e.g. a `struct Local;` with an `impl Trait for Local` inside of a helper function. Created with [`StreamBuilder::body_scope`].
+///
+/// The items are added to the body when their builder is dropped, so they are generated in the same order as top-level items.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// # let mut generator = Generator::with_name("Foo");
+/// generator
+///     .generate_fn("helper")
+///     .with_return_type("impl Default")
+///     .body(|b| {
+///         let mut scope = b.body_scope();
+///         scope.generate_struct("Local").make_zst();
+///         scope
+///             .impl_trait_for_type("Default", "Local")
+///             .generate_fn("default")
+///             .with_return_type("Self")
+///             .body(|b| {
+///                 b.ident_str("Local");
+///                 Ok(())
+///             })?;
+///         b.ident_str("Local");
+///         Ok(())
+///     })?;
+///
+/// // fn helper() -> impl Default {
+/// //     struct Local;
+/// //     impl Default for Local {
+/// //         fn default() -> Self { Local }
+/// //     }
+/// //     Local
+/// // }
+/// # generator.assert_eq("fn helper () ->impl Default { \
+/// #     struct Local ; \
+/// #     impl Default for Local { fn default () ->Self { Local } } \
+/// #     Local }");
+/// # Ok::<_, virtue::Error>(())
+/// ```
+///
+/// Items in a function body can not use the generics of the surrounding impl, so the impls of a body scope never inherit the generics of the target type.
+///
+/// [`StreamBuilder::body_scope`]: struct.StreamBuilder.html#method.body_scope
+pub struct BodyScope<'a> {
+    builder: &'a mut StreamBuilder,
+    // Items in a body have no enclosing type, so this is only used to satisfy [`Parent::name`].
+    name: Ident,
+}
+
+impl<'a> BodyScope<'a> {
+    pub(super) fn new(builder: &'a mut StreamBuilder) -> Self {
+        Self {
+            builder,
+            name: Ident::new("_", Span::call_site()),
+        }
+    }
+
+    /// Generate a local struct with the given name. See [`GenStruct`] for more info.
+    pub fn generate_struct(&mut self, name: impl Into<String>) -> GenStruct<'_, Self> {
+        GenStruct::new(self, name)
+    }
+
+    /// Generate a local enum with the given name. See [`GenEnum`] for more info.
+    pub fn generate_enum(&mut self, name: impl Into<String>) -> GenEnum<'_, Self> {
+        GenEnum::new(self, name)
+    }
+
+    /// Generate a local function with the given name. See [`FnBuilder`] for more info.
+    pub fn generate_fn(&mut self, name: impl Into<String>) -> FnBuilder<'_, Self> {
+        FnBuilder::new(self, name)
+    }
+
+    /// Generate an `impl <name>` implementation. See [`Impl`] for more information.
+    pub fn r#impl(&mut self, name: impl Into<String>) -> Impl<'_, Self> {
+        Impl::new(self, name)
+    }
+
+    /// Generate an `impl <name>` implementation. See [`Impl`] for more information.
+    ///
+    /// Alias for [`impl`] which doesn't need a `r#` prefix.
+    ///
+    /// [`impl`]: #method.impl
+    pub fn generate_impl(&mut self, name: impl Into<String>) -> Impl<'_, Self> {
+        Impl::new(self, name)
+    }
+
+    /// Generate an `impl <type_name>` block. See [`ImplFor`] for more information.
+    pub fn impl_for_type(&mut self, type_name: impl Into<StringOrIdent>) -> ImplFor<'_, Self> {
+        ImplFor::new(self, type_name.into(), None)
+    }
+
+    /// Generate an `impl <trait_name> for <type_name>` block. See [`ImplFor`] for more information.
+    pub fn impl_trait_for_type(
+        &mut self,
+        trait_name: impl Into<StringOrIdent>,
+        type_name: impl Into<StringOrIdent>,
+    ) -> ImplFor<'_, Self> {
+        ImplFor::new(self, type_name.into(), Some(trait_name.into()))
+    }
+}
+
+impl Parent for BodyScope<'_> {
+    fn append(&mut self, builder: StreamBuilder) {
+        self.builder.append(builder);
+    }
+
+    fn name(&self) -> &Ident {
+        &self.name
+    }
+
+    fn generics(&self) -> Option<&Generics> {
+        None
+    }
+
+    fn generic_constraints(&self) -> Option<&GenericConstraints> {
+        None
+    }
+}
+
+impl FnParent for BodyScope<'_> {
+    fn append(&mut self, fn_definition: StreamBuilder, fn_body: StreamBuilder) -> Result {
+        let mut builder = fn_definition;
+        builder.group(Delimiter::Brace, |body| {
+            *body = fn_body;
+            Ok(())
+        })?;
+        self.builder.append(builder);
+        Ok(())
+    }
+}
+
+impl StreamBuilder {
+    /// Generate items that are local to this function body or block. See [`BodyScope`] for more information.
+    pub fn body_scope(&mut self) -> BodyScope<'_> {
+        BodyScope::new(self)
+    }
+}
+
+#[test]
+fn test_body_scope() {
+    use crate::generate::Generator;
+
+    let mut generator = Generator::with_name("Foo").with_lifetime("a");
+    generator
+        .impl_for("Trait")
+        .generate_fn("foo")
+        .body(|b| {
+            let mut scope = b.body_scope();
+            scope
+                .generate_enum("Local")
+                .add_value("A")
+                .add_field("a", "u8");
+            scope
+                .generate_impl("Local")
+                .generate_fn("new")
+                .with_return_type("Self")
+                .body(|b| {
+                    b.push_parsed("Local::A { a: 0 }")?;
+                    Ok(())
+                })?;
+            scope.generate_fn("bar").body(|_| Ok(()))?;
+            b.push_parsed("bar()")?;
+            Ok(())
+        })
+        .unwrap();
+    generator.assert_eq(
+        "impl < 'a > Trait for Foo < 'a > { fn foo () { \
+         enum Local { A { a : u8 , } , } \
+         impl Local { fn new () ->Self { Local :: A { a : 0 } } } \
+         fn bar () { } \
+         bar () } }",
+    );
+}
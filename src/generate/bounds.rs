@@ -0,0 +1,217 @@
+use super::{Generator, Parent};
+use crate::parse::{
+    uses_ident_outside_phantom_data, Body, Fields, GenericConstraints, Generics, UnnamedField,
+};
+use crate::prelude::TokenTree;
+use crate::Result;
+
+/// How a single field contributes to the bounds computed by [`Generator::infer_bounds_with`].
+///
+/// [`Generator::infer_bounds_with`]: struct.Generator.html#method.infer_bounds_with
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FieldBound {
+    /// Add `G: Trait` for every generic `G` that is used in the type of this field, except for generics that are only used in `PhantomData<G>`.
+    Infer,
+    /// This field adds no bounds, e.g. because it is skipped by the derive.
+    Skip,
+    /// Add the given predicates instead of inferring them, e.g. from a `#[prefix(bound = "T: Trait")]` on the field. See [`utils::parse_bound_attribute`].
+    ///
+    /// [`utils::parse_bound_attribute`]: ../utils/fn.parse_bound_attribute.html
+    Custom(String),
+}
+
+/// The where-predicates computed by [`Generator::infer_bounds`]. Add them to an impl with [`apply`]:
+///
+/// ```ignore
+/// let bounds = generator.infer_bounds("Encode", &body, &skip_filter);
+/// generator
+///     .impl_for("Encode")
+///     .modify_generic_constraints(|_, constraints| bounds.apply(constraints))?;
+/// ```
+///
+/// [`Generator::infer_bounds`]: struct.Generator.html#method.infer_bounds
+/// [`apply`]: #method.apply
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InferredBounds {
+    predicates: Vec<String>,
+}
+
+impl InferredBounds {
+    /// `G: trait_name` for every generic in `used_generics`, followed by the `custom` predicates
+    pub(crate) fn new(used_generics: &[String], trait_name: &str, custom: &[String]) -> Self {
+        let mut predicates: Vec<String> = used_generics
+            .iter()
+            .map(|generic| format!("{} : {}", generic, trait_name))
+            .collect();
+        predicates.extend(
+            custom
+                .iter()
+                .filter(|bound| !bound.trim().is_empty())
+                .cloned(),
+        );
+        Self { predicates }
+    }
+
+    /// The predicates that will be added by [`apply`], e.g. `["T : Encode"]`.
+    ///
+    /// [`apply`]: #method.apply
+    pub fn predicates(&self) -> &[String] {
+        &self.predicates
+    }
+
+    /// Replace all inferred predicates with `bound`, if it is `Some`. An empty `bound` removes all predicates.
+    ///
+    /// This is intended for a container attribute like `#[prefix(bound = "T: Trait")]`, see [`utils::parse_bound_attribute`].
+    ///
+    /// [`utils::parse_bound_attribute`]: ../utils/fn.parse_bound_attribute.html
+    pub fn with_override(mut self, bound: Option<String>) -> Self {
+        if let Some(bound) = bound {
+            self.predicates.clear();
+            if !bound.trim().is_empty() {
+                self.predicates.push(bound);
+            }
+        }
+        self
+    }
+
+    /// Add the predicates to `constraints`. Predicates that are already present are not added again.
+    pub fn apply(&self, constraints: &mut GenericConstraints) -> Result {
+        for predicate in &self.predicates {
+            constraints.push_parsed_constraint(predicate)?;
+        }
+        Ok(())
+    }
+}
+
+impl Generator {
+    /// Compute the bounds that a derive of `trait_name` usually needs: `G: trait_name` for every generic `G` that is used in a field that is not skipped by `skip_field`.
+    ///
+    /// Generics that are only used in `PhantomData<G>` and generics that are not used in any field do not get a bound.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = TokenStream::from_str(
+    ///     "struct Foo<A, B, C> { a: Vec<A>, #[skip] b: B, c: PhantomData<C> }"
+    /// ).unwrap();
+    /// let (mut generator, _, body) = Parse::new(input)?.into_generator();
+    /// let bounds = generator.infer_bounds("Encode", &body, |field| !field.attributes.is_empty());
+    /// generator
+    ///     .impl_for("Encode")
+    ///     .modify_generic_constraints(|_, constraints| bounds.apply(constraints))?;
+    /// # generator.assert_eq("impl < A , B , C > Encode for Foo < A , B , C > where A : Encode { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// See [`infer_bounds_with`] to override the bounds of a single field.
+    ///
+    /// [`infer_bounds_with`]: #method.infer_bounds_with
+    pub fn infer_bounds(
+        &self,
+        trait_name: impl AsRef<str>,
+        body: &Body,
+        skip_field: impl Fn(&UnnamedField) -> bool,
+    ) -> InferredBounds {
+        self.infer_bounds_with(trait_name, body, |field| {
+            Ok(if skip_field(field) {
+                FieldBound::Skip
+            } else {
+                FieldBound::Infer
+            })
+        })
+        .expect("infer_bounds can not fail")
+    }
+
+    /// Compute the bounds for `trait_name` like [`infer_bounds`], but let `field_bound` decide how every field contributes to them.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// use virtue::generate::FieldBound;
+    /// use virtue::utils::parse_bound_attribute;
+    ///
+    /// let input = TokenStream::from_str(
+    ///     "struct Foo<A, B> { a: A, #[encode(bound = \"Box<B>: Encode\")] b: Box<B> }"
+    /// ).unwrap();
+    /// let (mut generator, _, body) = Parse::new(input)?.into_generator();
+    /// let bounds = generator.infer_bounds_with("Encode", &body, |field| {
+    ///     Ok(match parse_bound_attribute(&field.attributes, "encode")? {
+    ///         Some(bound) => FieldBound::Custom(bound),
+    ///         None => FieldBound::Infer,
+    ///     })
+    /// })?;
+    /// assert_eq!(bounds.predicates(), ["A : Encode", "Box<B>: Encode"]);
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`infer_bounds`]: #method.infer_bounds
+    pub fn infer_bounds_with(
+        &self,
+        trait_name: impl AsRef<str>,
+        body: &Body,
+        field_bound: impl Fn(&UnnamedField) -> Result<FieldBound>,
+    ) -> Result<InferredBounds> {
+        let mut inferred_types = Vec::new();
+        let mut custom = Vec::new();
+        let all_fields: Vec<&Fields> = match body {
+            Body::Struct(body) => body.fields.iter().collect(),
+            Body::Enum(body) => body
+                .variants
+                .iter()
+                .filter_map(|variant| variant.fields.as_ref())
+                .collect(),
+        };
+        for (_, field) in all_fields.into_iter().flat_map(Fields::iter) {
+            match field_bound(field)? {
+                FieldBound::Infer => inferred_types.push(field.r#type.as_slice()),
+                FieldBound::Skip => {}
+                FieldBound::Custom(bound) => custom.push(bound),
+            }
+        }
+
+        let used_generics = used_generics(self.generics(), &inferred_types);
+        Ok(InferredBounds::new(
+            &used_generics,
+            trait_name.as_ref(),
+            &custom,
+        ))
+    }
+}
+
+/// The names of the simple generics that are used outside of `PhantomData<..>` in any of the given `types`
+pub(crate) fn used_generics(generics: Option<&Generics>, types: &[&[TokenTree]]) -> Vec<String> {
+    let mut result = Vec::new();
+    if let Some(generics) = generics {
+        for generic in generics.iter_generics() {
+            let ident = generic.ident.to_string();
+            if types
+                .iter()
+                .any(|ty| uses_ident_outside_phantom_data(ty, &ident))
+            {
+                result.push(ident);
+            }
+        }
+    }
+    result
+}
+
+#[test]
+fn test_infer_bounds() {
+    use crate::parse::Parse;
+    use crate::token_stream;
+
+    let input = token_stream(
+        "enum Foo<'a, A, B, C, const N: usize> { X(&'a A, [B; N]), Y { c: PhantomData<C>, b: B } }",
+    );
+    let (generator, _, body) = Parse::new(input.collect::<proc_macro2::TokenStream>())
+        .unwrap()
+        .into_generator();
+    let bounds = generator.infer_bounds("Clone", &body, |_| false);
+    assert_eq!(bounds.predicates(), ["A : Clone", "B : Clone"]);
+
+    let bounds = bounds.clone().with_override(Some("A: Copy".to_string()));
+    assert_eq!(bounds.predicates(), ["A: Copy"]);
+    let bounds = bounds.with_override(Some(String::new()));
+    assert!(bounds.predicates().is_empty());
+}
@@ -0,0 +1,78 @@
+use super::StreamBuilder;
+use crate::prelude::{Delimiter, Span, TokenStream};
+
+/// A warning that was emitted with [`Generator::warn`].
+///
+/// [`Generator::warn`]: struct.Generator.html#method.warn
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Warning {
+    /// The message of the warning
+    pub message: String,
+    /// The location that the warning points at
+    pub span: Span,
+}
+
+impl Warning {
+    pub(crate) fn new(message: String, span: Span) -> Self {
+        Self { message, span }
+    }
+
+    /// Stable rust has no way for a proc macro to emit a warning, so this generates the use of a deprecated constant instead:
+    ///
+    /// ```ignore
+    /// const _: () = {
+    ///     #[deprecated(note = "<message>")]
+    ///     const WARNING: () = ();
+    ///     WARNING
+    /// };
+    /// ```
+    ///
+    /// All tokens get the span of the warning, so rustc shows `use of deprecated constant: <message>` at that location.
+    #[cfg_attr(
+        all(feature = "nightly", not(any(test, feature = "proc-macro2"))),
+        allow(dead_code)
+    )]
+    pub(crate) fn to_stream(&self) -> StreamBuilder {
+        let mut builder = StreamBuilder::new();
+        builder
+            .push_parsed("const _: () =")
+            .and_then(|b| {
+                b.group(Delimiter::Brace, |b| {
+                    b.punct('#').group(Delimiter::Bracket, |b| {
+                        b.ident_str("deprecated")
+                            .group(Delimiter::Parenthesis, |b| {
+                                b.ident_str("note").punct('=').lit_str(&self.message);
+                                Ok(())
+                            })?;
+                        Ok(())
+                    })?;
+                    b.push_parsed("const WARNING: () = (); WARNING")?;
+                    Ok(())
+                })
+            })
+            .expect("Could not build warning")
+            .punct(';');
+        builder.set_span_on_all_tokens(self.span);
+        builder
+    }
+
+    /// Emit this warning. With the `nightly` feature this is a real warning, emitted with [`proc_macro::Diagnostic`], otherwise the tokens of [`to_stream`] are added to `stream`.
+    ///
+    /// [`proc_macro::Diagnostic`]: https://doc.rust-lang.org/proc_macro/struct.Diagnostic.html
+    /// [`to_stream`]: #method.to_stream
+    pub(crate) fn emit(&self, stream: &mut TokenStream) {
+        #[cfg(all(feature = "nightly", not(any(test, feature = "proc-macro2"))))]
+        {
+            let _ = stream;
+            crate::prelude::Diagnostic::spanned(
+                self.span,
+                crate::prelude::Level::Warning,
+                self.message.as_str(),
+            )
+            .emit();
+        }
+        #[cfg(not(all(feature = "nightly", not(any(test, feature = "proc-macro2")))))]
+        stream.extend(self.to_stream().stream);
+    }
+}
@@ -0,0 +1,195 @@
+use super::bounds::used_generics;
+use super::{FieldBound, Generator, InferredBounds, Parent};
+use crate::parse::{Body, Fields, UnnamedField};
+use crate::Result;
+
+type AnalyzeField<'a, A> = Box<dyn Fn(&UnnamedField) -> Result<A> + 'a>;
+type FieldBoundFn<'a, A> = Box<dyn Fn(&UnnamedField, &A) -> FieldBound + 'a>;
+type Member<'a, A> = Box<dyn FnOnce(&mut Generator, &FamilyAnalysis<'_, A>) -> Result + 'a>;
+
+/// A group of traits that are derived together, e.g. `Encode`, `Decode` and `BorrowDecode`. See [`Generator::impl_family`].
+///
+/// Every field of the input is analyzed once with the `analyze_field` callback, e.g. to parse its attributes. The result is shared by the callbacks of all traits, together with the generics that need a bound.
+///
+/// [`Generator::impl_family`]: struct.Generator.html#method.impl_family
+pub struct TraitFamily<'a, A> {
+    analyze_field: AnalyzeField<'a, A>,
+    field_bound: FieldBoundFn<'a, A>,
+    members: Vec<Member<'a, A>>,
+}
+
+impl<'a, A> TraitFamily<'a, A> {
+    /// Create a new family. `analyze_field` is called once for every field of the input.
+    pub fn new(analyze_field: impl Fn(&UnnamedField) -> Result<A> + 'a) -> Self {
+        Self {
+            analyze_field: Box::new(analyze_field),
+            field_bound: Box::new(|_, _| FieldBound::Infer),
+            members: Vec::new(),
+        }
+    }
+
+    /// Decide how every field contributes to [`FamilyAnalysis::bounds`], based on the field and its analysis. By default every field is [`FieldBound::Infer`].
+    ///
+    /// [`FamilyAnalysis::bounds`]: struct.FamilyAnalysis.html#method.bounds
+    /// [`FieldBound::Infer`]: enum.FieldBound.html#variant.Infer
+    #[must_use]
+    pub fn with_field_bound(
+        mut self,
+        field_bound: impl Fn(&UnnamedField, &A) -> FieldBound + 'a,
+    ) -> Self {
+        self.field_bound = Box::new(field_bound);
+        self
+    }
+
+    /// Add a trait to this family. `generate` is called by [`Generator::impl_family`] in the order that the traits were added, and should generate the impl of the trait.
+    ///
+    /// [`Generator::impl_family`]: struct.Generator.html#method.impl_family
+    #[must_use]
+    pub fn with_trait(
+        mut self,
+        generate: impl FnOnce(&mut Generator, &FamilyAnalysis<'_, A>) -> Result + 'a,
+    ) -> Self {
+        self.members.push(Box::new(generate));
+        self
+    }
+}
+
+/// The shared analysis of a [`TraitFamily`], which is passed to the callback of every trait.
+pub struct FamilyAnalysis<'b, A> {
+    body: &'b Body,
+    fields: Vec<Vec<A>>,
+    used_generics: Vec<String>,
+    custom_bounds: Vec<String>,
+}
+
+impl<'b, A> FamilyAnalysis<'b, A> {
+    /// The body of the input
+    pub fn body(&self) -> &'b Body {
+        self.body
+    }
+
+    /// The analysis of every field of the struct, in the order of the fields. This is empty for enums and structs without fields.
+    pub fn struct_fields(&self) -> &[A] {
+        match self.body {
+            Body::Struct(_) => self.fields.first().map_or(&[], Vec::as_slice),
+            Body::Enum(_) => &[],
+        }
+    }
+
+    /// The analysis of every field of the enum variant at `variant_index`, in the order of the fields. This is empty for structs, variants without fields and indices that are out of range.
+    pub fn variant_fields(&self, variant_index: usize) -> &[A] {
+        match self.body {
+            Body::Struct(_) => &[],
+            Body::Enum(_) => self.fields.get(variant_index).map_or(&[], Vec::as_slice),
+        }
+    }
+
+    /// The bounds for `trait_name`, see [`Generator::infer_bounds`]. The generics that need a bound are computed once for the whole family.
+    ///
+    /// [`Generator::infer_bounds`]: struct.Generator.html#method.infer_bounds
+    pub fn bounds(&self, trait_name: impl AsRef<str>) -> InferredBounds {
+        InferredBounds::new(
+            &self.used_generics,
+            trait_name.as_ref(),
+            &self.custom_bounds,
+        )
+    }
+}
+
+impl Generator {
+    /// Generate the impls of all traits in `family`. The fields of `body` are analyzed once, and the analysis is passed to the callback of every trait.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// use virtue::generate::{FieldBound, TraitFamily};
+    ///
+    /// let input = TokenStream::from_str("struct Foo<A, B> { a: A, #[skip] b: B }").unwrap();
+    /// let (mut generator, _, body) = Parse::new(input)?.into_generator();
+    ///
+    /// // `true` if the field is skipped
+    /// let family = TraitFamily::new(|field| Ok(!field.attributes.is_empty()))
+    ///     .with_field_bound(|_, skip| if *skip { FieldBound::Skip } else { FieldBound::Infer })
+    ///     .with_trait(|generator, analysis| {
+    ///         let bounds = analysis.bounds("Encode");
+    ///         generator
+    ///             .impl_for("Encode")
+    ///             .modify_generic_constraints(|_, constraints| bounds.apply(constraints))?;
+    ///         Ok(())
+    ///     })
+    ///     .with_trait(|generator, analysis| {
+    ///         assert_eq!(analysis.struct_fields(), [false, true]);
+    ///         let bounds = analysis.bounds("Decode");
+    ///         generator
+    ///             .impl_for("Decode")
+    ///             .modify_generic_constraints(|_, constraints| bounds.apply(constraints))?;
+    ///         Ok(())
+    ///     });
+    /// generator.impl_family(family, &body)?;
+    /// # generator.assert_eq("impl < A , B > Encode for Foo < A , B > where A : Encode { } \
+    /// #     impl < A , B > Decode for Foo < A , B > where A : Decode { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn impl_family<A>(&mut self, family: TraitFamily<'_, A>, body: &Body) -> Result {
+        let all_fields: Vec<Option<&Fields>> = match body {
+            Body::Struct(body) => vec![body.fields.as_ref()],
+            Body::Enum(body) => body
+                .variants
+                .iter()
+                .map(|variant| variant.fields.as_ref())
+                .collect(),
+        };
+
+        let mut fields = Vec::with_capacity(all_fields.len());
+        let mut inferred_types = Vec::new();
+        let mut custom_bounds = Vec::new();
+        for variant_fields in all_fields {
+            let mut analyzed = Vec::new();
+            for (_, field) in variant_fields.into_iter().flat_map(Fields::iter) {
+                let analysis = (family.analyze_field)(field)?;
+                match (family.field_bound)(field, &analysis) {
+                    FieldBound::Infer => inferred_types.push(field.r#type.as_slice()),
+                    FieldBound::Skip => {}
+                    FieldBound::Custom(bound) => custom_bounds.push(bound),
+                }
+                analyzed.push(analysis);
+            }
+            fields.push(analyzed);
+        }
+
+        let analysis = FamilyAnalysis {
+            body,
+            fields,
+            used_generics: used_generics(self.generics(), &inferred_types),
+            custom_bounds,
+        };
+        for member in family.members {
+            member(self, &analysis)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_impl_family_enum() {
+    use crate::parse::Parse;
+    use crate::token_stream;
+
+    let input = token_stream("enum Foo<A, B> { X(A, u8), Y, Z { b: PhantomData<B> } }");
+    let (mut generator, _, body) = Parse::new(input.collect::<proc_macro2::TokenStream>())
+        .unwrap()
+        .into_generator();
+    let family = TraitFamily::new(|field| Ok(field.type_string()))
+        .with_trait(|_, analysis| {
+            assert!(analysis.struct_fields().is_empty());
+            assert_eq!(analysis.variant_fields(0), ["A", "u8"]);
+            assert!(analysis.variant_fields(1).is_empty());
+            assert_eq!(analysis.variant_fields(2), ["PhantomData<B>"]);
+            assert!(analysis.variant_fields(3).is_empty());
+            assert_eq!(analysis.bounds("Encode").predicates(), ["A : Encode"]);
+            Ok(())
+        })
+        .with_trait(|_, _| Err(crate::Error::custom("second trait")));
+    let error = generator.impl_family(family, &body).unwrap_err();
+    assert_eq!(error.to_string(), "second trait");
+}
@@ -0,0 +1,269 @@
+use super::StreamBuilder;
+use crate::parse::{Attribute, Fields, IdentOrIndex, UnnamedField};
+use crate::prelude::{Delimiter, Ident, Span, TokenStream, TokenTree};
+
+/// Everything needed to generate code for a single field, passed to the callback of [`StreamBuilder::for_each_field`].
+///
+/// This combines the name, type, attributes and span of the field with the name it is bound to when the fields are destructured, and the [`StreamBuilder`] that the code for this field should be written to.
+///
+/// [`StreamBuilder::for_each_field`]: struct.StreamBuilder.html#method.for_each_field
+pub struct FieldContext<'a> {
+    name: IdentOrIndex<'a>,
+    field: &'a UnnamedField,
+    binding: Ident,
+    builder: &'a mut StreamBuilder,
+}
+
+impl<'a> FieldContext<'a> {
+    /// The prefix of the [`binding`] of tuple fields, e.g. `field_0`.
+    ///
+    /// [`binding`]: #method.binding
+    pub const BINDING_PREFIX: &'static str = "field_";
+
+    /// The name of the field. This is either the ident of a named field, or the index of a tuple field.
+    pub fn name(&self) -> &IdentOrIndex<'a> {
+        &self.name
+    }
+
+    /// The type of the field.
+    pub fn ty(&self) -> &'a [TokenTree] {
+        &self.field.r#type
+    }
+
+    /// The attributes of the field.
+    pub fn attributes(&self) -> &'a [Attribute] {
+        &self.field.attributes
+    }
+
+    /// The parsed field, including its visibility.
+    pub fn field(&self) -> &'a UnnamedField {
+        self.field
+    }
+
+    /// The span of the field. This is the span of the field name, or the span of the type for tuple fields.
+    pub fn span(&self) -> Span {
+        match self.name {
+            IdentOrIndex::Ident { ident, .. } => ident.span(),
+            IdentOrIndex::Index { span, .. } => span,
+        }
+    }
+
+    /// The name this field is bound to when the fields are destructured. This is the field name for named fields (`Self { a, b }`), and `field_<index>` for tuple fields (`Self(field_0, field_1)`).
+    pub fn binding(&self) -> &Ident {
+        &self.binding
+    }
+
+    /// The [`StreamBuilder`] to write the code for this field to.
+    pub fn builder(&mut self) -> &mut StreamBuilder {
+        self.builder
+    }
+
+    /// The combined `#[cfg(...)]` predicate of this field, see [`Attribute::cfg_of`].
+    ///
+    /// [`Attribute::cfg_of`]: ../parse/struct.Attribute.html#method.cfg_of
+    pub fn cfg(&self) -> Option<TokenStream> {
+        Attribute::cfg_of(self.attributes())
+    }
+
+    /// Write the code of `inner` to the [`builder`], so that it only exists if the field exists.
+    ///
+    /// If the field has a `#[cfg(...)]`, the code is wrapped in `#[cfg(...)] { ... }`. Note that because of the block, variables declared in `inner` are not visible to code after it. Without a `#[cfg(...)]` the code is written as-is.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// # let input = TokenStream::from_str("struct Foo { a: u32, #[cfg(feature = \"b\")] b: String }").unwrap();
+    /// # let (mut generator, _, body) = Parse::new(input).unwrap().into_generator();
+    /// # let fields = match &body {
+    /// #     Body::Struct(body) => body.fields.as_ref().unwrap(),
+    /// #     _ => unreachable!(),
+    /// # };
+    /// generator
+    ///     .r#impl()
+    ///     .generate_fn("print")
+    ///     .with_self_arg(FnSelfArg::RefSelf)
+    ///     .body(|b| {
+    ///         b.for_each_field(fields, |mut field| {
+    ///             let name = field.name().to_string();
+    ///             field.with_cfg(|b| {
+    ///                 b.push_parsed(format!("println!(\"{{:?}}\", self.{});", name))?;
+    ///                 Ok(())
+    ///             })
+    ///         })
+    ///     })?;
+    /// # generator.assert_eq("impl Foo { fn print (& self ,) { \
+    /// #     println ! (\"{:?}\" , self . a) ; \
+    /// #     # [cfg (feature = \"b\")] { println ! (\"{:?}\" , self . b) ; } } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`builder`]: #method.builder
+    pub fn with_cfg<F>(&mut self, inner: F) -> crate::Result
+    where
+        F: FnOnce(&mut StreamBuilder) -> crate::Result,
+    {
+        match self.cfg() {
+            Some(predicate) => {
+                self.builder.cfg_attribute(predicate);
+                self.builder.group(Delimiter::Brace, inner)?;
+                Ok(())
+            }
+            None => inner(self.builder),
+        }
+    }
+}
+
+impl StreamBuilder {
+    /// Add a `#[cfg(predicate)]` attribute
+    pub(crate) fn cfg_attribute(&mut self, predicate: TokenStream) -> &mut Self {
+        self.punct('#')
+            .group(Delimiter::Bracket, |b| {
+                b.ident_str("cfg").group(Delimiter::Parenthesis, |b| {
+                    b.extend(predicate);
+                    Ok(())
+                })?;
+                Ok(())
+            })
+            .expect("Could not build cfg attribute")
+    }
+
+    /// Call `cb` for every field in `fields`, with a [`FieldContext`] that writes to this builder.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// # let input = TokenStream::from_str("struct Foo { a: u32, b: String }").unwrap();
+    /// # let (mut generator, _, body) = Parse::new(input).unwrap().into_generator();
+    /// # let fields = match &body {
+    /// #     Body::Struct(body) => body.fields.as_ref().unwrap(),
+    /// #     _ => unreachable!(),
+    /// # };
+    /// generator
+    ///     .r#impl()
+    ///     .generate_fn("print")
+    ///     .with_self_arg(FnSelfArg::RefSelf)
+    ///     .body(|b| {
+    ///         b.push_parsed("let Self { a, b } = self;")?;
+    ///         b.for_each_field(fields, |mut field| {
+    ///             let name = field.name().to_string();
+    ///             let binding = field.binding().clone();
+    ///             field
+    ///                 .builder()
+    ///                 .push_parsed(format!("println!(\"{{}}\", {:?});", name))?
+    ///                 .ident_str("drop")
+    ///                 .group(Delimiter::Parenthesis, |b| {
+    ///                     b.ident(binding);
+    ///                     Ok(())
+    ///                 })?
+    ///                 .punct(';');
+    ///             Ok(())
+    ///         })
+    ///     })?;
+    /// # generator.assert_eq("impl Foo { fn print (& self ,) { \
+    /// #     let Self { a , b } = self ; \
+    /// #     println ! (\"{}\" , \"a\") ; drop (a) ; \
+    /// #     println ! (\"{}\" , \"b\") ; drop (b) ; } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn for_each_field<F>(&mut self, fields: &Fields, mut cb: F) -> crate::Result
+    where
+        F: FnMut(FieldContext<'_>) -> crate::Result,
+    {
+        let unnamed_fields: Vec<&UnnamedField> = match fields {
+            Fields::Tuple(fields) => fields.iter().collect(),
+            Fields::Struct(fields) => fields.iter().map(|(_, field)| field).collect(),
+        };
+        let bindings = fields.bindings(FieldContext::BINDING_PREFIX)?;
+        for ((name, field), binding) in fields.names().into_iter().zip(unnamed_fields).zip(bindings)
+        {
+            cb(FieldContext {
+                name,
+                field,
+                binding,
+                builder: self,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Call `per_field` for every field like [`for_each_field`], or `trivial` if there are no fields. This is the case for unit structs like `struct Foo;`, and for `struct Foo {}`, `struct Foo()` and variants without fields.
+    ///
+    /// Many derives have a simpler body for types without fields, e.g. an `Encode` that writes nothing:
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// # let input = TokenStream::from_str("struct Foo {}").unwrap();
+    /// # let (mut generator, _, body) = Parse::new(input).unwrap().into_generator();
+    /// # let body = match body { Body::Struct(body) => body, _ => unreachable!() };
+    /// generator
+    ///     .impl_for("Encode")
+    ///     .generate_fn("encode")
+    ///     .with_self_arg(FnSelfArg::RefSelf)
+    ///     .with_arg("out", "&mut Vec<u8>")
+    ///     .body(|b| {
+    ///         b.body_for_fields_or(
+    ///             body.fields.as_ref(),
+    ///             |b| {
+    ///                 b.push_parsed("let _ = out;")?;
+    ///                 Ok(())
+    ///             },
+    ///             |mut field| {
+    ///                 let name = field.name().to_string();
+    ///                 field.builder().push_parsed(format!("self.{}.encode(out);", name))?;
+    ///                 Ok(())
+    ///             },
+    ///         )
+    ///     })?;
+    /// # generator.assert_eq("impl Encode for Foo { fn encode (& self , out : & mut Vec < u8 >) { let _ = out ; } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`for_each_field`]: #method.for_each_field
+    pub fn body_for_fields_or<T, F>(
+        &mut self,
+        fields: Option<&Fields>,
+        trivial: T,
+        per_field: F,
+    ) -> crate::Result
+    where
+        T: FnOnce(&mut StreamBuilder) -> crate::Result,
+        F: FnMut(FieldContext<'_>) -> crate::Result,
+    {
+        match fields {
+            Some(fields) if !fields.is_empty() => self.for_each_field(fields, per_field),
+            _ => trivial(self),
+        }
+    }
+}
+
+#[test]
+fn test_for_each_field_tuple() {
+    use crate::parse::StructBody;
+    use crate::token_stream;
+
+    let body = StructBody::take(&mut token_stream("(#[a] u8, pub String)")).unwrap();
+    let mut builder = StreamBuilder::new();
+    let mut seen = Vec::new();
+    builder
+        .for_each_field(body.fields.as_ref().unwrap(), |mut field| {
+            seen.push((
+                field.name().to_string(),
+                field.binding().to_string(),
+                field.ty().iter().map(|t| t.to_string()).collect::<String>(),
+                field.attributes().len(),
+            ));
+            let binding = field.binding().clone();
+            field.builder().ident(binding).punct(';');
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(
+        seen,
+        [
+            ("0".to_owned(), "field_0".to_owned(), "u8".to_owned(), 1),
+            ("1".to_owned(), "field_1".to_owned(), "String".to_owned(), 0),
+        ]
+    );
+    assert_eq!(builder.stream.to_string(), "field_0 ; field_1 ;");
+}
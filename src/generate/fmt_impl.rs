@@ -0,0 +1,443 @@
+use super::{FnSelfArg, Generator, StreamBuilder};
+use crate::parse::{Attribute, Body, Fields, IdentOrIndex};
+use crate::prelude::{Delimiter, Ident, TokenStream};
+use crate::{Error, Result};
+
+/// The way [`Generator::generate_display_impl`] formats a struct or enum.
+///
+/// [`Generator::generate_display_impl`]: struct.Generator.html#method.generate_display_impl
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DisplayStrategy {
+    /// Write the name of the struct or the enum variant, e.g. `Foo`.
+    Name,
+    /// Forward to the `Debug` implementation of the type.
+    Debug,
+    /// Forward to the `Display` implementation of the only field. Every struct or enum variant must have exactly one field.
+    Transparent,
+}
+
+impl Generator {
+    /// Generate an `impl Debug for <target_name>`, similar to `#[derive(Debug)]`.
+    ///
+    /// Structs are written with `f.debug_struct(..)` or `f.debug_tuple(..)`, enums match on `self` and do the same for each variant. Every generic type will be bound by `Debug`. The match arms of variants and the bindings and `.field(..)` calls of named fields with a `#[cfg(...)]` get the same `#[cfg(...)]`. A `#[cfg(...)]` on an unnamed field returns an error, because the fields after it would change position.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// # let input = TokenStream::from_str("struct Foo { a: u32, b: String }").unwrap();
+    /// # let (mut generator, _, body) = Parse::new(input).unwrap().into_generator();
+    /// generator.generate_debug_impl(&body)?;
+    /// // impl ::core::fmt::Debug for Foo {
+    /// //     fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+    /// //         match self {
+    /// //             Self { a: __self_a, b: __self_b } => f
+    /// //                 .debug_struct("Foo")
+    /// //                 .field("a", __self_a)
+    /// //                 .field("b", __self_b)
+    /// //                 .finish(),
+    /// //         }
+    /// //     }
+    /// // }
+    /// # generator.assert_eq("impl :: core :: fmt :: Debug for Foo { \
+    /// #     fn fmt (& self , f : & mut :: core :: fmt :: Formatter <'_ >) ->:: core :: fmt :: Result { \
+    /// #         match self { Self { a : __self_a , b : __self_b , } =>f . debug_struct (\"Foo\") \
+    /// #         . field (\"a\" , __self_a) . field (\"b\" , __self_b) . finish () , } } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn generate_debug_impl(&mut self, body: &Body) -> Result {
+        self.generate_debug_impl_with(body, |_| false)
+    }
+
+    /// Generate an `impl Debug for <target_name>` like [`generate_debug_impl`], but skip every field for which `skip_field` returns `true`.
+    ///
+    /// This is usually used to skip fields with a certain attribute. Structs and struct variants with skipped fields will end with `.finish_non_exhaustive()`.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// # let input = TokenStream::from_str("struct Foo { a: u32, #[debug(skip)] b: String }").unwrap();
+    /// # let (mut generator, _, body) = Parse::new(input).unwrap().into_generator();
+    /// generator.generate_debug_impl_with(&body, |field| {
+    ///     field.attributes().iter().any(|attr| attr.tokens.stream().to_string() == "debug (skip)")
+    /// })?;
+    /// // impl ::core::fmt::Debug for Foo {
+    /// //     fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+    /// //         match self {
+    /// //             Self { a: __self_a, b: _ } => f
+    /// //                 .debug_struct("Foo")
+    /// //                 .field("a", __self_a)
+    /// //                 .finish_non_exhaustive(),
+    /// //         }
+    /// //     }
+    /// // }
+    /// # generator.assert_eq("impl :: core :: fmt :: Debug for Foo { \
+    /// #     fn fmt (& self , f : & mut :: core :: fmt :: Formatter <'_ >) ->:: core :: fmt :: Result { \
+    /// #         match self { Self { a : __self_a , b : _ , } =>f . debug_struct (\"Foo\") \
+    /// #         . field (\"a\" , __self_a) . finish_non_exhaustive () , } } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`generate_debug_impl`]: #method.generate_debug_impl
+    pub fn generate_debug_impl_with(
+        &mut self,
+        body: &Body,
+        skip_field: impl Fn(&IdentOrIndex) -> bool,
+    ) -> Result {
+        let arms = match_arms(&self.target_name(), body)?;
+        let mut impl_for = self.impl_for("::core::fmt::Debug");
+        impl_for.modify_generic_constraints(|generics, constraints| {
+            for generic in generics.iter_generics() {
+                constraints.push_constraint(generic, "::core::fmt::Debug")?;
+            }
+            Ok(())
+        })?;
+        generate_fmt_fn(&mut impl_for, &arms, &skip_field, |arm, builder| {
+            let fields = match &arm.fields {
+                Some(fields) => fields,
+                None => {
+                    builder.ident_str("f").punct('.').ident_str("write_str");
+                    builder.group(Delimiter::Parenthesis, |b| {
+                        b.lit_str(&arm.name);
+                        Ok(())
+                    })?;
+                    return Ok(());
+                }
+            };
+            let is_struct = matches!(fields, Fields::Struct(_));
+            let debug_builder = |b: &mut StreamBuilder| -> Result {
+                b.ident_str("f")
+                    .punct('.')
+                    .ident_str(if is_struct {
+                        "debug_struct"
+                    } else {
+                        "debug_tuple"
+                    })
+                    .group(Delimiter::Parenthesis, |b| {
+                        b.lit_str(&arm.name);
+                        Ok(())
+                    })?;
+                Ok(())
+            };
+            let field_call = |b: &mut StreamBuilder, field: &IdentOrIndex| -> Result {
+                b.punct('.')
+                    .ident_str("field")
+                    .group(Delimiter::Parenthesis, |b| {
+                        if is_struct {
+                            b.lit_str(field_name(field)).punct(',');
+                        }
+                        b.push(binding(field));
+                        Ok(())
+                    })?;
+                Ok(())
+            };
+            let has_skipped_fields = arm.names.iter().any(&skip_field);
+            let finish = if is_struct && has_skipped_fields {
+                "finish_non_exhaustive"
+            } else {
+                "finish"
+            };
+            let fields: Vec<_> = arm
+                .names
+                .iter()
+                .filter(|field| !skip_field(field))
+                .map(|field| (field, Attribute::cfg_of(field.attributes())))
+                .collect();
+
+            if fields.iter().all(|(_, cfg)| cfg.is_none()) {
+                debug_builder(builder)?;
+                for (field, _) in &fields {
+                    field_call(builder, field)?;
+                }
+                builder
+                    .punct('.')
+                    .ident_str(finish)
+                    .group(Delimiter::Parenthesis, |_| Ok(()))?;
+                return Ok(());
+            }
+
+            // A method chain can not be gated per call, so every `.field(..)` becomes its own statement
+            builder.group(Delimiter::Brace, |b| {
+                b.ident_str("let")
+                    .ident_str("mut")
+                    .ident_str("__builder")
+                    .punct('=');
+                debug_builder(b)?;
+                b.punct(';');
+                for (field, cfg) in &fields {
+                    if let Some(cfg) = cfg {
+                        b.cfg_attribute(cfg.clone());
+                    }
+                    b.ident_str("__builder");
+                    field_call(b, field)?;
+                    b.punct(';');
+                }
+                b.ident_str("__builder")
+                    .punct('.')
+                    .ident_str(finish)
+                    .group(Delimiter::Parenthesis, |_| Ok(()))?;
+                Ok(())
+            })?;
+            Ok(())
+        })
+    }
+
+    /// Generate an `impl Display for <target_name>`. See [`DisplayStrategy`] for the available formats.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use virtue::generate::DisplayStrategy;
+    /// # use std::str::FromStr;
+    /// # let input = TokenStream::from_str("enum Foo { A(u32), B(String) }").unwrap();
+    /// # let (mut generator, _, body) = Parse::new(input).unwrap().into_generator();
+    /// generator.generate_display_impl(&body, DisplayStrategy::Transparent)?;
+    /// // impl ::core::fmt::Display for Foo {
+    /// //     fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+    /// //         match self {
+    /// //             Self::A(__self_0) => ::core::fmt::Display::fmt(__self_0, f),
+    /// //             Self::B(__self_0) => ::core::fmt::Display::fmt(__self_0, f),
+    /// //         }
+    /// //     }
+    /// // }
+    /// # generator.assert_eq("impl :: core :: fmt :: Display for Foo { \
+    /// #     fn fmt (& self , f : & mut :: core :: fmt :: Formatter <'_ >) ->:: core :: fmt :: Result { \
+    /// #         match self { \
+    /// #             Self :: A (__self_0 ,) =>:: core :: fmt :: Display :: fmt (__self_0 , f) , \
+    /// #             Self :: B (__self_0 ,) =>:: core :: fmt :: Display :: fmt (__self_0 , f) , } } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn generate_display_impl(&mut self, body: &Body, strategy: DisplayStrategy) -> Result {
+        let arms = match_arms(&self.target_name(), body)?;
+        if strategy == DisplayStrategy::Transparent {
+            for arm in &arms {
+                let field_count = arm.names.len();
+                if field_count != 1 {
+                    return Err(Error::custom_at(
+                        format!(
+                            "Display can only be forwarded to a single field, but {} has {} fields",
+                            arm.name, field_count
+                        ),
+                        arm.ident.span(),
+                    ));
+                }
+            }
+        }
+
+        let mut impl_for = self.impl_for("::core::fmt::Display");
+        impl_for.modify_generic_constraints(|generics, constraints| {
+            match strategy {
+                DisplayStrategy::Name => {}
+                DisplayStrategy::Debug => {
+                    constraints.push_parsed_constraint("Self: ::core::fmt::Debug")?;
+                }
+                DisplayStrategy::Transparent => {
+                    for generic in generics.iter_generics() {
+                        constraints.push_constraint(generic, "::core::fmt::Display")?;
+                    }
+                }
+            }
+            Ok(())
+        })?;
+        if strategy == DisplayStrategy::Debug {
+            return impl_for
+                .generate_fn("fmt")
+                .with_self_arg(FnSelfArg::RefSelf)
+                .with_self_arg_trailing_comma(false)
+                .with_arg("f", "&mut ::core::fmt::Formatter<'_>")
+                .with_return_type("::core::fmt::Result")
+                .body(|b| {
+                    b.push_parsed("::core::fmt::Debug::fmt(self, f)")?;
+                    Ok(())
+                });
+        }
+        generate_fmt_fn(&mut impl_for, &arms, &|_| false, |arm, builder| {
+            match strategy {
+                DisplayStrategy::Transparent => {
+                    let field = arm.names.first().expect("Checked above");
+                    builder.push_parsed("::core::fmt::Display::fmt")?.group(
+                        Delimiter::Parenthesis,
+                        |b| {
+                            b.push(binding(field)).punct(',').ident_str("f");
+                            Ok(())
+                        },
+                    )?;
+                }
+                _ => {
+                    builder.ident_str("f").punct('.').ident_str("write_str");
+                    builder.group(Delimiter::Parenthesis, |b| {
+                        b.lit_str(&arm.name);
+                        Ok(())
+                    })?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+/// A single arm of the `match self { .. }` in a generated `fmt` function.
+struct MatchArm<'a> {
+    /// `Self` for structs, `Self::Variant` for enums
+    path: String,
+    /// The name of the struct or variant, without a `r#` prefix
+    name: String,
+    ident: Ident,
+    fields: Option<&'a Fields>,
+    /// The names of `fields`
+    names: Vec<IdentOrIndex<'a>>,
+    /// The `#[cfg(...)]` predicate of the variant, which the arm needs as well
+    cfg: Option<TokenStream>,
+}
+
+fn match_arms<'a>(target_name: &Ident, body: &'a Body) -> Result<Vec<MatchArm<'a>>> {
+    let arms = match body {
+        Body::Struct(body) => vec![MatchArm {
+            path: String::from("Self"),
+            name: unraw(target_name),
+            ident: target_name.clone(),
+            fields: body.fields.as_ref(),
+            names: body.fields.as_ref().map_or_else(Vec::new, Fields::names),
+            cfg: None,
+        }],
+        Body::Enum(body) => body
+            .variants
+            .iter()
+            .map(|variant| MatchArm {
+                path: format!("Self::{}", variant.name),
+                name: unraw(&variant.name),
+                ident: variant.name.clone(),
+                fields: variant.fields.as_ref(),
+                names: variant.fields.as_ref().map_or_else(Vec::new, Fields::names),
+                cfg: Attribute::cfg_of(&variant.attributes),
+            })
+            .collect(),
+    };
+    for arm in &arms {
+        for field in &arm.names {
+            if let IdentOrIndex::Index { span, .. } = field {
+                if Attribute::cfg_of(field.attributes()).is_some() {
+                    return Err(Error::custom_at(
+                        "A #[cfg(...)] on an unnamed field is not supported, because the fields after it would change position",
+                        *span,
+                    ));
+                }
+            }
+        }
+    }
+    Ok(arms)
+}
+
+/// Generate `fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { match self { .. } }`, with `arm_body` writing the expression of every arm.
+///
+/// Fields for which `skip_field` returns `true` are matched with `_`. Named fields with a `#[cfg(...)]` are only matched with that same `#[cfg(...)]`.
+fn generate_fmt_fn<P: super::Parent>(
+    impl_for: &mut super::ImplFor<'_, P>,
+    arms: &[MatchArm],
+    skip_field: &dyn Fn(&IdentOrIndex) -> bool,
+    arm_body: impl Fn(&MatchArm, &mut StreamBuilder) -> Result,
+) -> Result {
+    impl_for
+        .generate_fn("fmt")
+        .with_self_arg(FnSelfArg::RefSelf)
+        .with_self_arg_trailing_comma(false)
+        .with_arg("f", "&mut ::core::fmt::Formatter<'_>")
+        .with_return_type("::core::fmt::Result")
+        .body(|b| {
+            if arms.is_empty() {
+                b.match_uninhabited(&FnSelfArg::RefSelf)?;
+                return Ok(());
+            }
+            b.ident_str("match").ident_str("self");
+            b.group(Delimiter::Brace, |b| {
+                for arm in arms {
+                    if let Some(cfg) = &arm.cfg {
+                        b.cfg_attribute(cfg.clone());
+                    }
+                    b.push_parsed(&arm.path)?;
+                    if let Some(fields) = arm.fields {
+                        b.group(fields.delimiter(), |b| {
+                            for field in &arm.names {
+                                if let IdentOrIndex::Ident { ident, attributes } = field {
+                                    if let Some(cfg) = Attribute::cfg_of(attributes) {
+                                        b.cfg_attribute(cfg);
+                                    }
+                                    b.ident((*ident).clone()).punct(':');
+                                }
+                                if skip_field(field) {
+                                    b.ident_str("_");
+                                } else {
+                                    b.push(binding(field));
+                                }
+                                b.punct(',');
+                            }
+                            Ok(())
+                        })?;
+                    }
+                    b.puncts("=>");
+                    arm_body(arm, b)?;
+                    b.punct(',');
+                }
+                Ok(())
+            })?;
+            Ok(())
+        })
+}
+
+fn binding(field: &IdentOrIndex) -> Ident {
+    match field {
+        IdentOrIndex::Ident { ident, .. } => {
+            Ident::new(&format!("__self_{}", unraw(ident)), ident.span())
+        }
+        IdentOrIndex::Index { index, span, .. } => Ident::new(&format!("__self_{}", index), *span),
+    }
+}
+
+fn field_name(field: &IdentOrIndex) -> String {
+    match field {
+        IdentOrIndex::Ident { ident, .. } => unraw(ident),
+        IdentOrIndex::Index { index, .. } => index.to_string(),
+    }
+}
+
+/// The name of `ident` without the `r#` of a raw identifier
+pub(crate) fn unraw(ident: &Ident) -> String {
+    let name = ident.to_string();
+    match name.strip_prefix("r#") {
+        Some(name) => name.to_owned(),
+        None => name,
+    }
+}
+
+#[test]
+fn test_generate_debug_impl_field_cfg() {
+    use crate::parse::Parse;
+    use crate::token_stream;
+
+    let parse = |input: &str| {
+        let (generator, _, body) =
+            Parse::new(token_stream(input).collect::<proc_macro2::TokenStream>())
+                .unwrap()
+                .into_generator();
+        (generator, body)
+    };
+
+    let (mut generator, body) = parse(
+        "struct Foo { a: u8, #[cfg(test)] b: u8, #[cfg_attr(unix, cfg(feature = \"c\"))] c: u8 }",
+    );
+    generator.generate_debug_impl(&body).unwrap();
+    generator.assert_eq(
+        "impl :: core :: fmt :: Debug for Foo { \
+         fn fmt (& self , f : & mut :: core :: fmt :: Formatter <'_ >) ->:: core :: fmt :: Result { \
+         match self { Self { a : __self_a , # [cfg (test)] b : __self_b , \
+         # [cfg (any (not (unix) , all (feature = \"c\")))] c : __self_c , } =>{ \
+         let mut __builder = f . debug_struct (\"Foo\") ; \
+         __builder . field (\"a\" , __self_a) ; \
+         # [cfg (test)] __builder . field (\"b\" , __self_b) ; \
+         # [cfg (any (not (unix) , all (feature = \"c\")))] __builder . field (\"c\" , __self_c) ; \
+         __builder . finish () } , } } }",
+    );
+
+    let (mut generator, body) = parse("enum Foo { A(u8, #[cfg(test)] u8) }");
+    assert!(generator.generate_debug_impl(&body).is_err());
+    generator.assert_eq("");
+}
@@ -0,0 +1,360 @@
+use super::{ExprTokens, StreamBuilder};
+use crate::parse::{Attribute, AttributeLocation};
+use crate::prelude::{Delimiter, Group, Punct, Result, Spacing};
+
+/// A builder for an attribute, e.g. `#[cfg(feature = "std")]`, `#[doc = "..."]` or `#[serde(rename = "id", skip_serializing_if = "Option::is_none")]`, with a path, nested arguments and key-value pairs.
+///
+/// An attribute that is created with e.g. [`ImplFor::attr`] is added to the item when this builder is dropped, unless one of its methods returned an error.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// # let mut generator = Generator::with_name("Foo");
+/// let mut impl_for = generator.impl_for("Bar");
+/// impl_for.attr("cfg")?.args("feature = \"std\"")?;
+/// impl_for.attr("doc")?.value("\"Implements Bar\"")?;
+/// impl_for.inner_attr("allow")?.args("unused")?;
+/// # drop(impl_for);
+///
+/// // will output:
+/// // #[cfg(feature = "std")]
+/// // #[doc = "Implements Bar"]
+/// // impl Bar for Foo {
+/// //     #![allow(unused)]
+/// // }
+/// # generator.assert_eq("# [cfg (feature = \"std\")] # [doc = \"Implements Bar\"] impl Bar for Foo { # ! [allow (unused)] }");
+/// # Ok::<_, virtue::Error>(())
+/// ```
+///
+/// An attribute that is created with [`GenAttr::new`] can be passed to every `with_attr` method, e.g. [`GenStruct::with_attr`] and [`FnBuilder::with_attr`], or turned into a `#[...]` [`StreamBuilder`] with [`into_stream`] or an [`Attribute`] with [`into_attribute`].
+///
+/// ```
+/// # use virtue::prelude::*;
+/// use virtue::generate::GenAttr;
+///
+/// let attr = GenAttr::new("serde")?
+///     .key_str("rename", "id")?
+///     .arg("default")?
+///     .nested(GenAttr::new("bound")?.key_str("serialize", "T: Serialize")?);
+/// # let mut generator = Generator::with_name("Foo");
+/// generator.generate_struct("Bar").add_field("a", "u8").with_attr(attr)?;
+///
+/// // #[serde(rename = "id", default, bound(serialize = "T: Serialize"))]
+/// // struct Bar { a: u8 }
+/// # generator.assert_eq("# [serde (rename = \"id\" , default , bound (serialize = \"T: Serialize\"))] struct Bar { a : u8 , }");
+/// # Ok::<_, virtue::Error>(())
+/// ```
+///
+/// [`ImplFor::attr`]: struct.ImplFor.html#method.attr
+/// [`GenAttr::new`]: #method.new
+/// [`GenStruct::with_attr`]: struct.GenStruct.html#method.with_attr
+/// [`FnBuilder::with_attr`]: struct.FnBuilder.html#method.with_attr
+/// [`into_stream`]: #method.into_stream
+/// [`Attribute`]: ../parse/struct.Attribute.html
+/// [`into_attribute`]: #method.into_attribute
+pub struct GenAttr<'a> {
+    // `None` for an attribute created with `GenAttr::new`, which is not emitted on drop
+    target: Option<&'a mut Vec<StreamBuilder>>,
+    is_inner: bool,
+    path: StreamBuilder,
+    args: AttrArgs,
+    // set when one of the methods failed, so the incomplete attribute is not emitted
+    poisoned: bool,
+}
+
+enum AttrArgs {
+    None,
+    List(Vec<StreamBuilder>),
+    Value(StreamBuilder),
+}
+
+impl GenAttr<'static> {
+    /// Create a new attribute with the given path, e.g. `serde` or `doc`, that is not added to an item yet. See the type documentation for how to use it.
+    pub fn new(path: impl AsRef<str>) -> Result<Self> {
+        let mut builder = StreamBuilder::new();
+        builder.push_parsed(path)?;
+        Ok(Self {
+            target: None,
+            is_inner: false,
+            path: builder,
+            args: AttrArgs::None,
+            poisoned: false,
+        })
+    }
+}
+
+impl<'a> GenAttr<'a> {
+    pub(crate) fn attached(
+        target: &'a mut Vec<StreamBuilder>,
+        is_inner: bool,
+        path: impl AsRef<str>,
+    ) -> Result<Self> {
+        let mut attr = GenAttr::new(path)?;
+        attr.target = Some(target);
+        attr.is_inner = is_inner;
+        Ok(attr)
+    }
+
+    /// Build into a new [`StreamBuilder`], and mark this attribute as poisoned if `build` fails
+    fn build(&mut self, build: impl FnOnce(&mut StreamBuilder) -> Result) -> Result<StreamBuilder> {
+        let mut builder = StreamBuilder::new();
+        match build(&mut builder) {
+            Ok(()) => Ok(builder),
+            Err(e) => {
+                self.poisoned = true;
+                Err(e)
+            }
+        }
+    }
+
+    fn push_arg(mut self, arg: StreamBuilder) -> Self {
+        match &mut self.args {
+            AttrArgs::List(args) => args.push(arg),
+            args => *args = AttrArgs::List(vec![arg]),
+        }
+        self
+    }
+
+    /// Set the arguments of this attribute, e.g. `feature = "std"` for `#[cfg(feature = "std")]`.
+    ///
+    /// This replaces all arguments and a value that was set with [`value`]. If `args` can not be parsed, an error is returned and the attribute is not emitted.
+    ///
+    /// [`value`]: #method.value
+    pub fn args(mut self, args: impl AsRef<str>) -> Result<Self> {
+        let args = self.build(|builder| {
+            builder.push_parsed(args)?;
+            Ok(())
+        })?;
+        self.args = AttrArgs::List(vec![args]);
+        Ok(self)
+    }
+
+    /// Add an argument, e.g. `skip` for `#[serde(skip)]`. The argument is parsed, so it can also be more complex, like `feature = "std"`.
+    ///
+    /// This replaces a value that was set with [`value`]. If `arg` can not be parsed, an error is returned and the attribute is not emitted.
+    ///
+    /// [`value`]: #method.value
+    pub fn arg(mut self, arg: impl AsRef<str>) -> Result<Self> {
+        let arg = self.build(|builder| {
+            builder.push_parsed(arg)?;
+            Ok(())
+        })?;
+        Ok(self.push_arg(arg))
+    }
+
+    /// Add a `key = value` argument, e.g. `with = my_module` for `#[my_derive(with = my_module)]`. The value is an [`ExprTokens`], so it can be a string that is parsed or tokens that were already parsed.
+    ///
+    /// [`ExprTokens`]: struct.ExprTokens.html
+    pub fn key_value(mut self, key: impl AsRef<str>, value: impl Into<ExprTokens>) -> Result<Self> {
+        let arg = self.build(|builder| {
+            builder.push_parsed(key)?.punct('=');
+            value.into().append_to(builder)
+        })?;
+        Ok(self.push_arg(arg))
+    }
+
+    /// Add a `key = "value"` argument with a string literal, e.g. `rename = "id"` for `#[serde(rename = "id")]`
+    pub fn key_str(mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Result<Self> {
+        let arg = self.build(|builder| {
+            builder.push_parsed(key)?.punct('=').lit_str(value.as_ref());
+            Ok(())
+        })?;
+        Ok(self.push_arg(arg))
+    }
+
+    /// Add a nested attribute as argument, e.g. `bound(serialize = "T: Serialize")` or `any(unix, windows)`
+    #[must_use]
+    pub fn nested(self, attribute: GenAttr<'_>) -> Self {
+        self.push_arg(attribute.into_contents())
+    }
+
+    /// Set the value of this attribute, e.g. `"Some docs"` for `#[doc = "Some docs"]`. The value is an [`ExprTokens`], see [`key_value`].
+    ///
+    /// This replaces all arguments. If `value` can not be parsed, an error is returned and the attribute is not emitted.
+    ///
+    /// [`ExprTokens`]: struct.ExprTokens.html
+    /// [`key_value`]: #method.key_value
+    pub fn value(mut self, value: impl Into<ExprTokens>) -> Result<Self> {
+        let value = self.build(|builder| value.into().append_to(builder))?;
+        self.args = AttrArgs::Value(value);
+        Ok(self)
+    }
+
+    /// Set the value of this attribute to a string literal, e.g. `value_str(" Some docs")` for `#[doc = " Some docs"]`
+    ///
+    /// This replaces all arguments.
+    #[must_use]
+    pub fn value_str(mut self, value: impl AsRef<str>) -> Self {
+        let mut builder = StreamBuilder::new();
+        builder.lit_str(value.as_ref());
+        self.args = AttrArgs::Value(builder);
+        self
+    }
+
+    /// The contents of the attribute, without the surrounding `#[...]`. The attribute is no longer added to its item.
+    pub(crate) fn into_contents(mut self) -> StreamBuilder {
+        self.target = None;
+        let mut builder = std::mem::take(&mut self.path);
+        match std::mem::replace(&mut self.args, AttrArgs::None) {
+            AttrArgs::None => {}
+            AttrArgs::List(args) => {
+                builder
+                    .group(Delimiter::Parenthesis, |builder| {
+                        for (idx, arg) in args.into_iter().enumerate() {
+                            if idx != 0 {
+                                builder.punct(',');
+                            }
+                            builder.append(arg);
+                        }
+                        Ok(())
+                    })
+                    .expect("Could not build attribute arguments");
+            }
+            AttrArgs::Value(value) => {
+                builder.punct('=').append(value);
+            }
+        }
+        builder
+    }
+
+    /// The attribute `#[...]`, or `#![...]` for an inner attribute, e.g. to add it to a [`StreamBuilder`] manually. An attribute that was created with e.g. [`ImplFor::attr`] is no longer added to its item.
+    ///
+    /// [`ImplFor::attr`]: struct.ImplFor.html#method.attr
+    pub fn into_stream(self) -> StreamBuilder {
+        let is_inner = self.is_inner;
+        let contents = self.into_contents();
+        let mut builder = StreamBuilder::new();
+        builder.punct('#');
+        if is_inner {
+            builder.punct('!');
+        }
+        builder
+            .group(Delimiter::Bracket, |builder| {
+                builder.append(contents);
+                Ok(())
+            })
+            .expect("Could not build attribute");
+        builder
+    }
+
+    /// An outer [`Attribute`] at the given location, e.g. to modify the attributes of a parsed field before emitting them again
+    ///
+    /// [`Attribute`]: ../parse/struct.Attribute.html
+    pub fn into_attribute(self, location: AttributeLocation) -> Attribute {
+        Attribute {
+            location,
+            punct: Punct::new('#', Spacing::Alone),
+            is_inner: false,
+            tokens: Group::new(Delimiter::Bracket, self.into_contents().stream),
+        }
+    }
+}
+
+impl Drop for GenAttr<'_> {
+    fn drop(&mut self) {
+        let target = match self.target.take() {
+            Some(target) if !self.poisoned => target,
+            _ => return,
+        };
+        let attr = GenAttr {
+            target: None,
+            is_inner: self.is_inner,
+            path: std::mem::take(&mut self.path),
+            args: std::mem::replace(&mut self.args, AttrArgs::None),
+            poisoned: false,
+        };
+        target.push(attr.into_stream());
+    }
+}
+
+#[test]
+fn test_failed_attr_is_not_emitted() {
+    let mut generator = crate::generate::Generator::with_name("Foo");
+    let mut impl_for = generator.impl_for("Bar");
+    assert!(impl_for.attr("cfg").unwrap().args("feature = (").is_err());
+    assert!(impl_for.attr("doc").unwrap().value("\"unclosed").is_err());
+    assert!(impl_for.inner_attr("allow").unwrap().args("]").is_err());
+    assert!(impl_for.attr("serde").unwrap().arg("(").is_err());
+    assert!(impl_for
+        .attr("serde")
+        .unwrap()
+        .arg("default")
+        .unwrap()
+        .key_str("(", "id")
+        .is_err());
+    drop(impl_for);
+    generator.assert_eq("impl Bar for Foo { }");
+}
+
+#[test]
+fn test_gen_attr() {
+    let attr = GenAttr::new("cfg")
+        .unwrap()
+        .nested(
+            GenAttr::new("any")
+                .unwrap()
+                .arg("unix")
+                .unwrap()
+                .key_str("target_os", "wasi")
+                .unwrap(),
+        )
+        .into_attribute(AttributeLocation::Field);
+    assert_eq!(
+        attr.to_token_stream().to_string(),
+        "# [cfg (any (unix , target_os = \"wasi\"))]"
+    );
+    assert!(attr.as_cfg().is_some());
+    assert!(attr.path_is("cfg"));
+
+    // Parsed attributes can be emitted again with every `with_attr`
+    let mut generator = crate::generate::Generator::with_name("Foo");
+    generator
+        .r#impl()
+        .generate_const("A", "u8")
+        .with_attr(&attr)
+        .with_value(|b| {
+            b.push_parsed("1")?;
+            Ok(())
+        })
+        .unwrap();
+    generator
+        .assert_eq("impl Foo { # [cfg (any (unix , target_os = \"wasi\"))] const A : u8 = 1 ; }");
+
+    let attr = GenAttr::new("my_derive")
+        .unwrap()
+        .key_value("with", "Vec<u8>")
+        .unwrap()
+        .value("5")
+        .unwrap();
+    assert_eq!(attr.into_stream().stream.to_string(), "# [my_derive = 5]");
+
+    assert!(GenAttr::new("serde").unwrap().arg("(").is_err());
+    assert_eq!(
+        GenAttr::new("inline")
+            .unwrap()
+            .into_stream()
+            .stream
+            .to_string(),
+        "# [inline]"
+    );
+
+    // arguments can be added to an attribute on an item one by one
+    let mut generator = crate::generate::Generator::with_name("Foo");
+    let mut impl_for = generator.impl_for("Bar");
+    impl_for
+        .attr("cfg")
+        .unwrap()
+        .nested(GenAttr::new("any").unwrap().arg("unix").unwrap())
+        .arg("test")
+        .unwrap();
+    drop(impl_for);
+    generator.assert_eq("# [cfg (any (unix) , test)] impl Bar for Foo { }");
+
+    // borrowed strings can be passed to every `with_attr`
+    let derive = String::from("derive(Debug)");
+    let mut generator = crate::generate::Generator::with_name("Foo");
+    generator
+        .generate_struct("Bar")
+        .with_attr(derive.as_str())
+        .unwrap();
+    generator.assert_eq("# [derive (Debug)] struct Bar { }");
+}
@@ -1,7 +1,10 @@
-use super::{Impl, ImplFor, Parent, StreamBuilder, StringOrIdent};
+use super::stream_builder::ident_from_str;
+use super::{
+    AttrTokens, ExprTokens, Impl, ImplFor, Parent, StreamBuilder, StringOrIdent, TypeTokens,
+};
 use crate::parse::Visibility;
-use crate::prelude::{Delimiter, Ident, Span};
-use crate::Result;
+use crate::prelude::{Delimiter, Ident, Literal, TokenStream, TokenTree};
+use crate::{Error, Result};
 
 /// Builder to generate an `enum <Name> { <value> { ... }, ... }`
 ///
@@ -42,6 +45,8 @@ pub struct GenEnum<'a, P: Parent> {
     parent: &'a mut P,
     name: Ident,
     visibility: Visibility,
+    repr: Option<StreamBuilder>,
+    attrs: Vec<StreamBuilder>,
     values: Vec<EnumValue>,
     additional: Vec<StreamBuilder>,
 }
@@ -50,8 +55,10 @@ impl<'a, P: Parent> GenEnum<'a, P> {
     pub(crate) fn new(parent: &'a mut P, name: impl Into<String>) -> Self {
         Self {
             parent,
-            name: Ident::new(name.into().as_str(), Span::call_site()),
+            name: ident_from_str(&name.into()),
             visibility: Visibility::Default,
+            repr: None,
+            attrs: Vec::new(),
             values: Vec::new(),
             additional: Vec::new(),
         }
@@ -63,6 +70,122 @@ impl<'a, P: Parent> GenEnum<'a, P> {
         self
     }
 
+    /// Set the visibility of the enum. This can be used to give a generated helper type the same visibility as the type that is being derived.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Fooz");
+    /// let parse = Parse::new("pub(crate) struct Fooz {}".parse().unwrap())?;
+    /// generator
+    ///     .generate_enum("Foo")
+    ///     .with_visibility(parse.visibility().clone());
+    /// # generator.assert_eq("pub (crate) enum Foo { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// Generates:
+    /// ```ignore
+    /// pub(crate) enum Foo {}
+    /// ```
+    pub fn with_visibility(&mut self, visibility: Visibility) -> &mut Self {
+        self.visibility = visibility;
+        self
+    }
+
+    /// Add a `#[repr(...)]` attribute to the enum, e.g. `u8`. This is useful for tag enums with explicit discriminants, see [`EnumValue::with_discriminant`].
+    ///
+    /// `repr` must be a comma separated list of representations, e.g. `u8`, `C` or `C, u8`. An error is returned for anything else, e.g. `Vec<u8>`.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Fooz");
+    /// {
+    ///     let mut enumgen = generator.generate_enum("Tag");
+    ///     enumgen.with_repr("u8")?;
+    ///     # assert!(enumgen.with_repr("Vec<u8>").is_err());
+    ///     enumgen
+    ///         .add_value("A")
+    ///         .make_zst()
+    ///         .with_discriminant(Literal::u8_unsuffixed(1));
+    ///     enumgen
+    ///         .add_value("B")
+    ///         .make_zst()
+    ///         .with_discriminant(Literal::u8_unsuffixed(5));
+    /// }
+    /// # generator.assert_eq("# [repr (u8)] enum Tag { A = 1 , B = 5 , }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// Generates:
+    /// ```
+    /// #[repr(u8)]
+    /// enum Tag {
+    ///     A = 1,
+    ///     B = 5,
+    /// }
+    /// ```
+    ///
+    /// [`EnumValue::with_discriminant`]: struct.EnumValue.html#method.with_discriminant
+    pub fn with_repr(&mut self, repr: impl AsRef<str>) -> Result<&mut Self> {
+        let mut builder = StreamBuilder::new();
+        builder.push_parsed(repr.as_ref())?;
+        validate_repr(&builder.stream, repr.as_ref())?;
+        self.repr = Some(builder);
+        Ok(self)
+    }
+
+    /// Add a `#[derive(...)]` attribute with the given derives.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Fooz");
+    /// generator
+    ///     .generate_enum("Foo")
+    ///     .with_derive(["Debug", "Clone"])?;
+    /// # generator.assert_eq("# [derive (Debug , Clone)] enum Foo { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// Generates:
+    /// ```ignore
+    /// #[derive(Debug, Clone)]
+    /// enum Foo {}
+    /// ```
+    pub fn with_derive<ITER>(&mut self, derives: ITER) -> Result<&mut Self>
+    where
+        ITER: IntoIterator,
+        ITER::Item: AsRef<str>,
+    {
+        let derives: Vec<String> = derives
+            .into_iter()
+            .map(|derive| derive.as_ref().to_owned())
+            .collect();
+        self.with_attr(format!("derive({})", derives.join(", ")))
+    }
+
+    /// Add an outer attribute to the enum. `attr` is the content of the attribute, without the `#[...]`.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Fooz");
+    /// generator
+    ///     .generate_enum("Foo")
+    ///     .make_pub()
+    ///     .with_attr("allow(dead_code)")?;
+    /// # generator.assert_eq("# [allow (dead_code)] pub enum Foo { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// Generates:
+    /// ```ignore
+    /// #[allow(dead_code)]
+    /// pub enum Foo {}
+    /// ```
+    pub fn with_attr(&mut self, attr: impl Into<AttrTokens>) -> Result<&mut Self> {
+        self.attrs.push(attr.into().into_attribute(false)?);
+        Ok(self)
+    }
+
     /// Add an enum value
     ///
     /// Returns a builder for the value that's similar to GenStruct
@@ -72,12 +195,30 @@ impl<'a, P: Parent> GenEnum<'a, P> {
     }
 
     /// Add an `impl <name> for <enum>`
-    pub fn impl_for(&mut self, name: impl Into<StringOrIdent>) -> ImplFor<Self> {
-        ImplFor::new(self, name.into(), None)
+    ///
+    /// ```
+    /// # use virtue::prelude::Generator;
+    /// # let mut generator = Generator::with_name("Fooz");
+    /// let mut foo = generator.generate_enum("Foo");
+    /// foo.add_value("A").make_zst();
+    /// foo.impl_for("Default")
+    ///     .generate_fn("default")
+    ///     .with_return_type("Self")
+    ///     .body(|b| {
+    ///         b.push_parsed("Self::A")?;
+    ///         Ok(())
+    ///     })?;
+    /// # drop(foo);
+    /// # generator.assert_eq("enum Foo { A , } impl Default for Foo { fn default () ->Self { Self :: A } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn impl_for(&mut self, name: impl Into<StringOrIdent>) -> ImplFor<'_, Self> {
+        let type_name = self.name.clone().into();
+        ImplFor::new(self, type_name, Some(name.into()))
     }
 
     /// Generate an `impl <name>` implementation. See [`Impl`] for more information.
-    pub fn r#impl(&mut self) -> Impl<Self> {
+    pub fn r#impl(&mut self) -> Impl<'_, Self> {
         Impl::with_parent_name(self)
     }
 
@@ -86,7 +227,7 @@ impl<'a, P: Parent> GenEnum<'a, P> {
     /// Alias for [`impl`] which doesn't need a `r#` prefix.
     ///
     /// [`impl`]: #method.impl
-    pub fn generate_impl(&mut self) -> Impl<Self> {
+    pub fn generate_impl(&mut self) -> Impl<'_, Self> {
         Impl::with_parent_name(self)
     }
 }
@@ -107,14 +248,31 @@ impl<'a, P: Parent> Parent for GenEnum<'a, P> {
     fn generic_constraints(&self) -> Option<&crate::parse::GenericConstraints> {
         None
     }
+
+    fn automatically_derived(&self) -> bool {
+        self.parent.automatically_derived()
+    }
 }
 
 impl<'a, P: Parent> Drop for GenEnum<'a, P> {
     fn drop(&mut self) {
         let mut builder = StreamBuilder::new();
-        if self.visibility == Visibility::Pub {
-            builder.ident_str("pub");
+        if let Some(repr) = self.repr.take() {
+            builder
+                .punct('#')
+                .group(Delimiter::Bracket, |b| {
+                    b.ident_str("repr").group(Delimiter::Parenthesis, |b| {
+                        b.append(repr);
+                        Ok(())
+                    })?;
+                    Ok(())
+                })
+                .expect("Could not build enum repr");
         }
+        for attr in std::mem::take(&mut self.attrs) {
+            builder.append(attr);
+        }
+        self.visibility.append_to(&mut builder);
         builder
             .ident_str("enum")
             .ident(self.name.clone())
@@ -134,34 +292,69 @@ impl<'a, P: Parent> Drop for GenEnum<'a, P> {
     }
 }
 
+/// Check that `tokens` is a list of representations like `u8`, `C, u8` or `align(8)`.
+fn validate_repr(tokens: &TokenStream, repr: &str) -> Result {
+    let mut expect_ident = true;
+    let mut is_empty = true;
+    for token in tokens.clone() {
+        let valid = match &token {
+            TokenTree::Ident(_) if expect_ident => {
+                expect_ident = false;
+                true
+            }
+            TokenTree::Punct(p) if !expect_ident && p.as_char() == ',' => {
+                expect_ident = true;
+                true
+            }
+            // e.g. the `(8)` of `align(8)`
+            TokenTree::Group(g) if !expect_ident && g.delimiter() == Delimiter::Parenthesis => true,
+            _ => false,
+        };
+        if !valid {
+            return Err(Error::custom_at(
+                format!("Invalid repr `{}`, expected e.g. `u8` or `C`", repr),
+                token.span(),
+            ));
+        }
+        is_empty = false;
+    }
+    if is_empty {
+        return Err(Error::custom("A repr can not be empty"));
+    }
+    Ok(())
+}
+
 fn build_value(builder: &mut StreamBuilder, value: &EnumValue) -> Result {
+    for attr in &value.attrs {
+        builder.extend(attr.stream.clone());
+    }
     builder.ident(value.name.clone());
 
     match value.value_type {
         ValueType::Named => builder.group(Delimiter::Brace, |b| {
             for field in &value.fields {
-                if field.vis == Visibility::Pub {
-                    b.ident_str("pub");
-                }
-                b.ident_str(&field.name)
-                    .punct(':')
-                    .push_parsed(&field.ty)?
-                    .punct(',');
+                field.vis.append_to(b);
+                b.ident_str(&field.name).punct(':');
+                field.ty.append_to(b)?;
+                b.punct(',');
             }
             Ok(())
         })?,
         ValueType::Unnamed => builder.group(Delimiter::Parenthesis, |b| {
             for field in &value.fields {
-                if field.vis == Visibility::Pub {
-                    b.ident_str("pub");
-                }
-                b.push_parsed(&field.ty)?.punct(',');
+                field.vis.append_to(b);
+                field.ty.append_to(b)?;
+                b.punct(',');
             }
             Ok(())
         })?,
         ValueType::Zst => builder,
     };
 
+    if let Some(discriminant) = &value.discriminant {
+        builder.punct('=');
+        discriminant.append_to(builder)?;
+    }
     builder.punct(',');
 
     Ok(())
@@ -169,19 +362,88 @@ fn build_value(builder: &mut StreamBuilder, value: &EnumValue) -> Result {
 
 pub struct EnumValue {
     name: Ident,
+    attrs: Vec<StreamBuilder>,
     fields: Vec<EnumField>,
     value_type: ValueType,
+    discriminant: Option<ExprTokens>,
 }
 
 impl EnumValue {
     fn new(name: impl Into<String>) -> Self {
         Self {
-            name: Ident::new(name.into().as_str(), Span::call_site()),
+            name: ident_from_str(&name.into()),
+            attrs: Vec::new(),
             fields: Vec::new(),
             value_type: ValueType::Named,
+            discriminant: None,
         }
     }
 
+    /// Add an attribute to this value, e.g. `cfg(feature = "std")`.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Fooz");
+    /// {
+    ///     let mut enumgen = generator.generate_enum("Foo");
+    ///     enumgen
+    ///         .add_value("Io")
+    ///         .with_doc(" An IO error")
+    ///         .with_attr("cfg(feature = \"std\")")?
+    ///         .add_field("", "std::io::Error")
+    ///         .make_tuple();
+    ///     enumgen.add_value("Other").make_zst();
+    /// }
+    /// # generator.assert_eq("enum Foo { # [doc = \" An IO error\"] # [cfg (feature = \"std\")] Io (std :: io :: Error ,) , Other , }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// Generates:
+    /// ```ignore
+    /// enum Foo {
+    ///     /// An IO error
+    ///     #[cfg(feature = "std")]
+    ///     Io(std::io::Error),
+    ///     Other,
+    /// }
+    /// ```
+    pub fn with_attr(&mut self, attr: impl Into<AttrTokens>) -> Result<&mut Self> {
+        self.attrs.push(attr.into().into_attribute(false)?);
+        Ok(self)
+    }
+
+    /// Add a doc comment to this value. This is the same as a `/// <doc>` comment, so the doc usually starts with a space. See [`with_attr`] for an example.
+    ///
+    /// [`with_attr`]: #method.with_attr
+    pub fn with_doc(&mut self, doc: impl AsRef<str>) -> &mut Self {
+        let mut builder = StreamBuilder::new();
+        builder
+            .punct('#')
+            .group(Delimiter::Bracket, |b| {
+                b.ident_str("doc").punct('=').lit_str(doc.as_ref());
+                Ok(())
+            })
+            .expect("Could not build doc attribute");
+        self.attrs.push(builder);
+        self
+    }
+
+    /// Set the explicit discriminant of this value, e.g. `A = 1`. See [`GenEnum::with_repr`] for an example.
+    ///
+    /// [`GenEnum::with_repr`]: struct.GenEnum.html#method.with_repr
+    pub fn with_discriminant(&mut self, discriminant: Literal) -> &mut Self {
+        self.discriminant = Some(discriminant.into());
+        self
+    }
+
+    /// Set the explicit discriminant of this value to an expression, e.g. `A = BASE + 1`. Tokens are added as-is, e.g. to copy the [`EnumVariant::value_expr`] of a parsed variant, and a string is parsed when the enum is generated.
+    ///
+    /// [`EnumVariant::value_expr`]: ../parse/struct.EnumVariant.html#structfield.value_expr
+    pub fn with_discriminant_expr(&mut self, discriminant: impl Into<ExprTokens>) -> &mut Self {
+        self.discriminant = Some(discriminant.into());
+        self
+    }
+
     /// Make the struct a zero-sized type (no fields)
     ///
     /// Any fields will be ignored
@@ -201,7 +463,7 @@ impl EnumValue {
     /// Add a *private* field to the struct. For adding a public field, see `add_pub_field`
     ///
     /// Names are ignored when the Struct's fields are unnamed
-    pub fn add_field(&mut self, name: impl Into<String>, ty: impl Into<String>) -> &mut Self {
+    pub fn add_field(&mut self, name: impl Into<String>, ty: impl Into<TypeTokens>) -> &mut Self {
         self.fields.push(EnumField {
             name: name.into(),
             vis: Visibility::Default,
@@ -213,7 +475,11 @@ impl EnumValue {
     /// Add a *public* field to the struct. For adding a public field, see `add_field`
     ///
     /// Names are ignored when the Struct's fields are unnamed
-    pub fn add_pub_field(&mut self, name: impl Into<String>, ty: impl Into<String>) -> &mut Self {
+    pub fn add_pub_field(
+        &mut self,
+        name: impl Into<String>,
+        ty: impl Into<TypeTokens>,
+    ) -> &mut Self {
         self.fields.push(EnumField {
             name: name.into(),
             vis: Visibility::Pub,
@@ -226,7 +492,7 @@ impl EnumValue {
 struct EnumField {
     name: String,
     vis: Visibility,
-    ty: String,
+    ty: TypeTokens,
 }
 
 enum ValueType {
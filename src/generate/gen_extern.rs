@@ -0,0 +1,117 @@
+use super::{generate_item::FnParent, FnBuilder, Parent, StreamBuilder, TypeTokens};
+use crate::prelude::{Delimiter, Result};
+
+/// Builder for generating an `extern "<abi>" { ... }` block with function and static declarations.
+///
+/// ```
+/// # use virtue::prelude::Generator;
+/// # let mut generator = Generator::with_name("Foo");
+/// let mut block = generator.generate_extern_block("C");
+/// block
+///     .generate_fn("foo_free")
+///     .with_arg("ptr", "*mut Foo")
+///     .declaration()?;
+/// block.add_static("FOO_COUNT", "usize")?;
+/// # drop(block);
+/// # generator.assert_eq("extern \"C\" { fn foo_free (ptr : * mut Foo) ; static FOO_COUNT : usize ; }");
+/// # Ok::<_, virtue::Error>(())
+/// ```
+///
+/// Generates:
+/// ```ignore
+/// extern "C" {
+///     fn foo_free(ptr: *mut Foo);
+///     static FOO_COUNT: usize;
+/// }
+/// ```
+pub struct GenExtern<'a, P: Parent> {
+    parent: &'a mut P,
+    abi: String,
+    items: Vec<StreamBuilder>,
+}
+
+impl<'a, P: Parent> GenExtern<'a, P> {
+    pub(crate) fn new(parent: &'a mut P, abi: impl Into<String>) -> Self {
+        Self {
+            parent,
+            abi: abi.into(),
+            items: Vec::new(),
+        }
+    }
+
+    /// Declare a function in this block. The function has to be completed with [`FnBuilder::declaration`].
+    ///
+    /// [`FnBuilder::declaration`]: struct.FnBuilder.html#method.declaration
+    pub fn generate_fn(&mut self, name: impl Into<String>) -> FnBuilder<'_, Self> {
+        FnBuilder::new(self, name)
+    }
+
+    /// Declare a `static <name>: <ty>;` in this block.
+    pub fn add_static(
+        &mut self,
+        name: impl AsRef<str>,
+        ty: impl Into<TypeTokens>,
+    ) -> Result<&mut Self> {
+        self.push_static(false, name.as_ref(), ty.into())
+    }
+
+    /// Declare a `static mut <name>: <ty>;` in this block.
+    pub fn add_static_mut(
+        &mut self,
+        name: impl AsRef<str>,
+        ty: impl Into<TypeTokens>,
+    ) -> Result<&mut Self> {
+        self.push_static(true, name.as_ref(), ty.into())
+    }
+
+    fn push_static(&mut self, is_mut: bool, name: &str, ty: TypeTokens) -> Result<&mut Self> {
+        let mut builder = StreamBuilder::new();
+        builder.ident_str("static");
+        if is_mut {
+            builder.ident_str("mut");
+        }
+        builder.push_parsed(name)?.punct(':');
+        ty.append_to(&mut builder)?;
+        builder.punct(';');
+        self.items.push(builder);
+        Ok(self)
+    }
+}
+
+impl<P: Parent> FnParent for GenExtern<'_, P> {
+    fn append(&mut self, _fn_definition: StreamBuilder, _fn_body: StreamBuilder) -> Result {
+        Err(crate::Error::custom(
+            "Functions in an extern block can not have a body, use `.declaration()` instead",
+        ))
+    }
+
+    fn append_declaration(&mut self, fn_declaration: StreamBuilder) -> Result {
+        self.items.push(fn_declaration);
+        Ok(())
+    }
+
+    fn undeclared_target_generics(&self) -> Option<&crate::parse::Generics> {
+        self.parent.generics()
+    }
+}
+
+impl<P: Parent> Drop for GenExtern<'_, P> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            return;
+        }
+        let mut builder = StreamBuilder::new();
+        builder
+            .ident_str("extern")
+            .lit_str(&self.abi)
+            .group(Delimiter::Brace, |group| {
+                for item in std::mem::take(&mut self.items) {
+                    group.append(item);
+                }
+                Ok(())
+            })
+            .unwrap();
+
+        self.parent.append(builder);
+    }
+}
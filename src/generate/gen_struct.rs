@@ -1,6 +1,8 @@
-use super::{Impl, ImplFor, Parent, StreamBuilder, StringOrIdent};
-use crate::parse::Visibility;
-use crate::prelude::{Delimiter, Ident, Span};
+use super::stream_builder::ident_from_str;
+use super::{AttrTokens, Impl, ImplFor, Parent, StreamBuilder, StringOrIdent, TypeTokens};
+use crate::parse::{Generic, GenericConstraints, Generics, Visibility};
+use crate::prelude::{Delimiter, Ident};
+use crate::{Error, Result};
 
 /// Builder to generate a struct.
 /// Defaults to a struct with named fields `struct <Name> { <field>: <ty>, ... }`
@@ -8,23 +10,166 @@ pub struct GenStruct<'a, P: Parent> {
     parent: &'a mut P,
     name: Ident,
     visibility: Visibility,
+    attrs: Vec<StreamBuilder>,
     fields: Vec<StructField>,
     additional: Vec<StreamBuilder>,
     struct_type: StructType,
+    generics: Option<Generics>,
+    generic_constraints: Option<GenericConstraints>,
+    carries_target_generics: bool,
 }
 
 impl<'a, P: Parent> GenStruct<'a, P> {
     pub(crate) fn new(parent: &'a mut P, name: impl Into<String>) -> Self {
         Self {
             parent,
-            name: Ident::new(name.into().as_str(), Span::call_site()),
+            name: ident_from_str(&name.into()),
             visibility: Visibility::Default,
+            attrs: Vec::new(),
             fields: Vec::new(),
             additional: Vec::new(),
             struct_type: StructType::Named,
+            generics: None,
+            generic_constraints: None,
+            carries_target_generics: false,
         }
     }
 
+    /// The name of the `PhantomData` field that is added by [`carry_target_generics`]
+    ///
+    /// [`carry_target_generics`]: #method.carry_target_generics
+    pub const PHANTOM_FIELD: &'static str = "_marker";
+
+    /// Give the struct the generics and where clause of the struct or enum that is being derived, e.g. for a visitor or builder that is generic over the same types.
+    ///
+    /// A field `_marker: PhantomData<(&'a (), T)>` with all lifetimes and type generics is added after the other fields, so every generic is used. A zero-sized struct becomes a tuple struct with only this field, e.g. `struct Foo<T>(PhantomData<T>);`. Impls of this struct, like [`impl_for`] and [`impl`], get the same generics.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = TokenStream::from_str("struct Foo<'a, T: Clone, const N: usize> { a: &'a [T; N] }").unwrap();
+    /// let (mut generator, _, _) = Parse::new(input)?.into_generator();
+    /// let mut visitor = generator.generate_struct("FooVisitor");
+    /// visitor.carry_target_generics().add_field("count", "usize");
+    /// visitor
+    ///     .r#impl()
+    ///     .generate_fn("new")
+    ///     .with_return_type("Self")
+    ///     .body(|b| {
+    ///         b.push_parsed("Self { count: 0, _marker: ::core::marker::PhantomData }")?;
+    ///         Ok(())
+    ///     })?;
+    /// # drop(visitor);
+    ///
+    /// // struct FooVisitor<'a, T: Clone, const N: usize> {
+    /// //     count: usize,
+    /// //     _marker: ::core::marker::PhantomData<(&'a (), T)>,
+    /// // }
+    /// // impl<'a, T: Clone, const N: usize> FooVisitor<'a, T, N> {
+    /// //     fn new() -> Self { Self { count: 0, _marker: ::core::marker::PhantomData } }
+    /// // }
+    /// # generator.assert_eq("struct FooVisitor < 'a , T : Clone , const N : usize > { \
+    /// #     count : usize , _marker : :: core :: marker :: PhantomData < (& 'a () , T ,) > , } \
+    /// #     impl < 'a , T : Clone , const N : usize > FooVisitor < 'a , T , N > { \
+    /// #     fn new () ->Self { Self { count : 0 , _marker : :: core :: marker :: PhantomData } } }");
+    /// # generator.finish()?;
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// If the target has no lifetimes or type generics, the field is `_marker: PhantomData<()>`, so the code that constructs the struct does not depend on the generics of the target. Items that are not generated inside of a [`Generator`], like the structs of a [`BodyScope`], have no generics to carry.
+    ///
+    /// [`impl_for`]: #method.impl_for
+    /// [`impl`]: #method.impl
+    /// [`Generator`]: struct.Generator.html
+    /// [`BodyScope`]: struct.BodyScope.html
+    pub fn carry_target_generics(&mut self) -> &mut Self {
+        self.generics = self.parent.generics().cloned();
+        self.generic_constraints = self.parent.generic_constraints().cloned();
+        self.carries_target_generics = true;
+        self
+    }
+
+    /// Add a where clause to the struct, e.g. `T: Clone`. The constraint is added after the constraints of [`carry_target_generics`], and impls of this struct get the same where clause.
+    ///
+    /// The where clause is placed where rust expects it for every shape of struct: before the fields of a struct with named fields, and after the fields of a tuple struct, in front of the `;`:
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = TokenStream::from_str("struct Foo<T> { t: T }").unwrap();
+    /// let (mut generator, _, _) = Parse::new(input)?.into_generator();
+    /// generator
+    ///     .generate_struct("Named")
+    ///     .carry_target_generics()
+    ///     .with_where_clause("T: Clone")?
+    ///     .add_field("t", "T");
+    /// generator
+    ///     .generate_struct("Tuple")
+    ///     .carry_target_generics()
+    ///     .with_where_clause("T: Clone")?
+    ///     .make_tuple()
+    ///     .add_field("t", "T");
+    /// generator
+    ///     .generate_struct("Unit")
+    ///     .with_where_clause("u8: Clone")?
+    ///     .make_zst();
+    ///
+    /// // struct Named<T> where T: Clone { t: T, _marker: PhantomData<(T,)> }
+    /// // struct Tuple<T>(T, PhantomData<(T,)>) where T: Clone;
+    /// // struct Unit where u8: Clone;
+    /// # generator.assert_eq("struct Named < T > where T : Clone { \
+    /// #     t : T , _marker : :: core :: marker :: PhantomData < (T ,) > , } \
+    /// #     struct Tuple < T > (T , :: core :: marker :: PhantomData < (T ,) > ,) where T : Clone ; \
+    /// #     struct Unit where u8 : Clone ;");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`carry_target_generics`]: #method.carry_target_generics
+    pub fn with_where_clause(&mut self, constraint: impl AsRef<str>) -> Result<&mut Self> {
+        self.generic_constraints
+            .get_or_insert_with(GenericConstraints::default)
+            .push_parsed_constraint(constraint)?;
+        Ok(self)
+    }
+
+    /// Modify the where clause of the struct, e.g. to add a bound to every generic that was added with [`carry_target_generics`]. The callback is only called if the struct has generics.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = TokenStream::from_str("struct Foo<A, B> where A: Copy { a: A, b: B }").unwrap();
+    /// let (mut generator, _, _) = Parse::new(input)?.into_generator();
+    /// generator
+    ///     .generate_struct("FooRef")
+    ///     .carry_target_generics()
+    ///     .modify_generic_constraints(|generics, constraints| {
+    ///         for generic in generics.iter_generics() {
+    ///             constraints.push_constraint(generic, "Clone")?;
+    ///         }
+    ///         Ok(())
+    ///     })?
+    ///     .make_zst();
+    ///
+    /// // struct FooRef<A, B>(PhantomData<(A, B)>) where A: Copy, A: Clone, B: Clone;
+    /// # generator.assert_eq("struct FooRef < A , B > (:: core :: marker :: PhantomData < (A , B ,) > ,) \
+    /// #     where A : Copy , A : Clone , B : Clone ;");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`carry_target_generics`]: #method.carry_target_generics
+    pub fn modify_generic_constraints<CB>(&mut self, cb: CB) -> Result<&mut Self>
+    where
+        CB: FnOnce(&Generics, &mut GenericConstraints) -> Result,
+    {
+        if let Some(generics) = &self.generics {
+            let constraints = self
+                .generic_constraints
+                .get_or_insert_with(GenericConstraints::default);
+            cb(generics, constraints)?;
+        }
+        Ok(self)
+    }
+
     /// Make the struct a zero-sized type (no fields)
     ///
     /// Any fields will be ignored
@@ -81,6 +226,80 @@ impl<'a, P: Parent> GenStruct<'a, P> {
         self
     }
 
+    /// Set the visibility of the struct. This can be used to give a generated helper type the same visibility as the type that is being derived.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Fooz");
+    /// let parse = Parse::new("pub(crate) struct Fooz {}".parse().unwrap())?;
+    /// generator
+    ///     .generate_struct("Foo")
+    ///     .with_visibility(parse.visibility().clone());
+    /// # generator.assert_eq("pub (crate) struct Foo { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// Generates:
+    /// ```ignore
+    /// pub(crate) struct Foo {}
+    /// ```
+    pub fn with_visibility(&mut self, visibility: Visibility) -> &mut Self {
+        self.visibility = visibility;
+        self
+    }
+
+    /// Add a `#[derive(...)]` attribute with the given derives.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Fooz");
+    /// generator
+    ///     .generate_struct("Foo")
+    ///     .with_derive(["Debug", "Clone"])?;
+    /// # generator.assert_eq("# [derive (Debug , Clone)] struct Foo { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// Generates:
+    /// ```ignore
+    /// #[derive(Debug, Clone)]
+    /// struct Foo {}
+    /// ```
+    pub fn with_derive<ITER>(&mut self, derives: ITER) -> Result<&mut Self>
+    where
+        ITER: IntoIterator,
+        ITER::Item: AsRef<str>,
+    {
+        let derives: Vec<String> = derives
+            .into_iter()
+            .map(|derive| derive.as_ref().to_owned())
+            .collect();
+        self.with_attr(format!("derive({})", derives.join(", ")))
+    }
+
+    /// Add an outer attribute to the struct. `attr` is the content of the attribute, without the `#[...]`.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Fooz");
+    /// generator
+    ///     .generate_struct("Foo")
+    ///     .make_pub()
+    ///     .with_attr("allow(dead_code)")?;
+    /// # generator.assert_eq("# [allow (dead_code)] pub struct Foo { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// Generates:
+    /// ```ignore
+    /// #[allow(dead_code)]
+    /// pub struct Foo {}
+    /// ```
+    pub fn with_attr(&mut self, attr: impl Into<AttrTokens>) -> Result<&mut Self> {
+        self.attrs.push(attr.into().into_attribute(false)?);
+        Ok(self)
+    }
+
     /// Add a *private* field to the struct. For adding a public field, see `add_pub_field`
     ///
     /// Names are ignored when the Struct's fields are unnamed
@@ -103,11 +322,12 @@ impl<'a, P: Parent> GenStruct<'a, P> {
     ///     baz: String,
     /// };
     /// ```
-    pub fn add_field(&mut self, name: impl Into<String>, ty: impl Into<String>) -> &mut Self {
+    pub fn add_field(&mut self, name: impl Into<String>, ty: impl Into<TypeTokens>) -> &mut Self {
         self.fields.push(StructField {
             name: name.into(),
             vis: Visibility::Default,
             ty: ty.into(),
+            attrs: Vec::new(),
         });
         self
     }
@@ -115,22 +335,115 @@ impl<'a, P: Parent> GenStruct<'a, P> {
     /// Add a *public* field to the struct. For adding a public field, see `add_field`
     ///
     /// Names are ignored when the Struct's fields are unnamed
-    pub fn add_pub_field(&mut self, name: impl Into<String>, ty: impl Into<String>) -> &mut Self {
+    pub fn add_pub_field(
+        &mut self,
+        name: impl Into<String>,
+        ty: impl Into<TypeTokens>,
+    ) -> &mut Self {
         self.fields.push(StructField {
             name: name.into(),
             vis: Visibility::Pub,
             ty: ty.into(),
+            attrs: Vec::new(),
+        });
+        self
+    }
+
+    /// Add a field with the given visibility to the struct, e.g. `pub(crate)`. This is useful for tuple structs, where every field has its own visibility.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Fooz");
+    /// let parse = Parse::new("pub(crate) struct Fooz {}".parse().unwrap())?;
+    /// generator
+    ///     .generate_struct("Foo")
+    ///     .make_tuple()
+    ///     .add_field_with_visibility("0", "u8", parse.visibility().clone())
+    ///     .add_field("1", "u16");
+    /// # generator.assert_eq("struct Foo (pub (crate) u8 , u16 ,) ;");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// Generates:
+    /// ```ignore
+    /// struct Foo(pub(crate) u8, u16);
+    /// ```
+    pub fn add_field_with_visibility(
+        &mut self,
+        name: impl Into<String>,
+        ty: impl Into<TypeTokens>,
+        visibility: Visibility,
+    ) -> &mut Self {
+        self.fields.push(StructField {
+            name: name.into(),
+            vis: visibility,
+            ty: ty.into(),
+            attrs: Vec::new(),
         });
         self
     }
 
+    /// Add an outer attribute to the last added field, e.g. `cfg(feature = "std")`. `attr` is the content of the attribute, without the `#[...]`.
+    ///
+    /// Returns an error if no field has been added yet.
+    ///
+    /// ```
+    /// # use virtue::prelude::Generator;
+    /// # let mut generator = Generator::with_name("Fooz");
+    /// generator
+    ///     .generate_struct("Foo")
+    ///     .add_field("bar", "u16")
+    ///     .with_field_attr("cfg(feature = \"std\")")?
+    ///     .add_field("baz", "String");
+    /// # generator.assert_eq("struct Foo { # [cfg (feature = \"std\")] bar : u16 , baz : String , }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// Generates:
+    /// ```ignore
+    /// struct Foo {
+    ///     #[cfg(feature = "std")]
+    ///     bar: u16,
+    ///     baz: String,
+    /// }
+    /// ```
+    pub fn with_field_attr(&mut self, attr: impl Into<AttrTokens>) -> Result<&mut Self> {
+        let attr = attr.into().into_attribute(false)?;
+        match self.fields.last_mut() {
+            Some(field) => field.attrs.push(attr),
+            None => {
+                return Err(Error::custom(
+                    "`with_field_attr` needs a field, add one with `add_field` first",
+                ))
+            }
+        }
+        Ok(self)
+    }
+
     /// Add an `impl <name> for <struct>`
-    pub fn impl_for(&mut self, name: impl Into<StringOrIdent>) -> ImplFor<Self> {
-        ImplFor::new(self, name.into(), None)
+    ///
+    /// ```
+    /// # use virtue::prelude::Generator;
+    /// # let mut generator = Generator::with_name("Fooz");
+    /// let mut foo = generator.generate_struct("Foo");
+    /// foo.make_zst().impl_for("Default")
+    ///     .generate_fn("default")
+    ///     .with_return_type("Self")
+    ///     .body(|b| {
+    ///         b.push_parsed("Foo")?;
+    ///         Ok(())
+    ///     })?;
+    /// # drop(foo);
+    /// # generator.assert_eq("struct Foo ; impl Default for Foo { fn default () ->Self { Foo } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn impl_for(&mut self, name: impl Into<StringOrIdent>) -> ImplFor<'_, Self> {
+        let type_name = self.name.clone().into();
+        ImplFor::new(self, type_name, Some(name.into()))
     }
 
     /// Generate an `impl <name>` implementation. See [`Impl`] for more information.
-    pub fn r#impl(&mut self) -> Impl<Self> {
+    pub fn r#impl(&mut self) -> Impl<'_, Self> {
         Impl::with_parent_name(self)
     }
 
@@ -139,7 +452,7 @@ impl<'a, P: Parent> GenStruct<'a, P> {
     /// Alias for [`impl`] which doesn't need a `r#` prefix.
     ///
     /// [`impl`]: #method.impl
-    pub fn generate_impl(&mut self) -> Impl<Self> {
+    pub fn generate_impl(&mut self) -> Impl<'_, Self> {
         Impl::with_parent_name(self)
     }
 }
@@ -153,51 +466,91 @@ impl<'a, P: Parent> Parent for GenStruct<'a, P> {
         &self.name
     }
 
-    fn generics(&self) -> Option<&crate::parse::Generics> {
-        None
+    fn generics(&self) -> Option<&Generics> {
+        self.generics.as_ref()
     }
 
-    fn generic_constraints(&self) -> Option<&crate::parse::GenericConstraints> {
-        None
+    fn generic_constraints(&self) -> Option<&GenericConstraints> {
+        self.generic_constraints.as_ref()
+    }
+
+    fn automatically_derived(&self) -> bool {
+        self.parent.automatically_derived()
     }
 }
 
 impl<'a, P: Parent> Drop for GenStruct<'a, P> {
     fn drop(&mut self) {
         let mut builder = StreamBuilder::new();
-        if self.visibility == Visibility::Pub {
-            builder.ident_str("pub");
+        for attr in std::mem::take(&mut self.attrs) {
+            builder.append(attr);
         }
+        self.visibility.append_to(&mut builder);
         builder.ident_str("struct").ident(self.name.clone());
+        if let Some(generics) = &self.generics {
+            builder.append(generics.impl_generics());
+        }
+        let where_clause = self
+            .generic_constraints
+            .as_ref()
+            .map(GenericConstraints::where_clause)
+            .unwrap_or_default();
+        let phantom = if self.carries_target_generics {
+            Some(phantom_data(self.generics.as_ref()))
+        } else {
+            None
+        };
+        let struct_type = match self.struct_type {
+            StructType::Zst if phantom.is_some() => StructType::Unnamed,
+            struct_type => struct_type,
+        };
 
-        match self.struct_type {
-            StructType::Named => builder
-                .group(Delimiter::Brace, |b| {
-                    for field in &self.fields {
-                        if field.vis == Visibility::Pub {
-                            b.ident_str("pub");
+        match struct_type {
+            StructType::Named => {
+                builder.append(where_clause);
+                builder
+                    .group(Delimiter::Brace, |b| {
+                        for field in &self.fields {
+                            field.append_attrs(b);
+                            field.vis.append_to(b);
+                            b.ident_str(&field.name).punct(':');
+                            field.ty.append_to(b)?;
+                            b.punct(',');
                         }
-                        b.ident_str(&field.name)
-                            .punct(':')
-                            .push_parsed(&field.ty)?
-                            .punct(',');
-                    }
-                    Ok(())
-                })
-                .expect("Could not build struct"),
-            StructType::Unnamed => builder
-                .group(Delimiter::Parenthesis, |b| {
-                    for field in &self.fields {
-                        if field.vis == Visibility::Pub {
-                            b.ident_str("pub");
+                        if let Some(phantom) = phantom {
+                            b.ident_str(Self::PHANTOM_FIELD)
+                                .punct(':')
+                                .append(phantom)
+                                .punct(',');
                         }
-                        b.push_parsed(&field.ty)?.punct(',');
-                    }
-                    Ok(())
-                })
-                .expect("Could not build struct")
-                .punct(';'),
-            StructType::Zst => builder.punct(';'),
+                        Ok(())
+                    })
+                    .expect("Could not build struct")
+            }
+            StructType::Unnamed => {
+                builder
+                    .group(Delimiter::Parenthesis, |b| {
+                        if self.struct_type != StructType::Zst {
+                            for field in &self.fields {
+                                field.append_attrs(b);
+                                field.vis.append_to(b);
+                                field.ty.append_to(b)?;
+                                b.punct(',');
+                            }
+                        }
+                        if let Some(phantom) = phantom {
+                            b.append(phantom).punct(',');
+                        }
+                        Ok(())
+                    })
+                    .expect("Could not build struct");
+                builder.append(where_clause);
+                builder.punct(';')
+            }
+            StructType::Zst => {
+                builder.append(where_clause);
+                builder.punct(';')
+            }
         };
 
         for additional in std::mem::take(&mut self.additional) {
@@ -207,6 +560,34 @@ impl<'a, P: Parent> Drop for GenStruct<'a, P> {
     }
 }
 
+/// `::core::marker::PhantomData<(&'a (), T, ..)>` with all lifetimes and type generics
+fn phantom_data(generics: Option<&Generics>) -> StreamBuilder {
+    let mut builder = StreamBuilder::new();
+    builder
+        .push_parsed("::core::marker::PhantomData")
+        .expect("Could not build PhantomData")
+        .punct('<')
+        .group(Delimiter::Parenthesis, |b| {
+            for generic in generics.iter().flat_map(|generics| generics.iter()) {
+                match generic {
+                    Generic::Lifetime(lt) => {
+                        b.punct('&').lifetime(lt.ident.clone());
+                        b.group(Delimiter::Parenthesis, |_| Ok(()))?.punct(',');
+                    }
+                    Generic::Generic(gen) => {
+                        b.ident(gen.ident.clone()).punct(',');
+                    }
+                    Generic::Const(_) => {}
+                }
+            }
+            Ok(())
+        })
+        .expect("Could not build PhantomData")
+        .punct('>');
+    builder
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
 enum StructType {
     Named,
     Unnamed,
@@ -216,5 +597,14 @@ enum StructType {
 struct StructField {
     name: String,
     vis: Visibility,
-    ty: String,
+    ty: TypeTokens,
+    attrs: Vec<StreamBuilder>,
+}
+
+impl StructField {
+    fn append_attrs(&self, builder: &mut StreamBuilder) {
+        for attr in &self.attrs {
+            builder.extend(attr.stream.clone());
+        }
+    }
 }
@@ -1,15 +1,27 @@
-use super::StreamBuilder;
+use super::{AttrTokens, PatternBuilder, StreamBuilder, TypeTokens};
 use crate::{
-    parse::Visibility,
+    parse::{Attribute, Generic, Generics, Lifetime, Visibility},
     prelude::{Delimiter, Result},
 };
 
+/// Whether an item should be generated, returned by the closure of [`FnBuilder::body_opt`] and [`GenConst::with_value_opt`].
+///
+/// [`FnBuilder::body_opt`]: struct.FnBuilder.html#method.body_opt
+/// [`GenConst::with_value_opt`]: struct.GenConst.html#method.with_value_opt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenerationOutcome {
+    /// Generate the item with the tokens that were written by the closure.
+    Generate,
+    /// Do not generate the item at all. The tokens that were written by the closure are discarded.
+    Skip,
+}
+
 /// A builder for constants.
 pub struct GenConst<'a> {
     consts: &'a mut Vec<StreamBuilder>,
-    attrs: Vec<String>,
+    attrs: Vec<AttrTokens>,
     name: String,
-    ty: String,
+    ty: TypeTokens,
     vis: Visibility,
 }
 
@@ -17,7 +29,7 @@ impl<'a> GenConst<'a> {
     pub(crate) fn new(
         consts: &'a mut Vec<StreamBuilder>,
         name: impl Into<String>,
-        ty: impl Into<String>,
+        ty: impl Into<TypeTokens>,
     ) -> Self {
         Self {
             consts,
@@ -36,8 +48,22 @@ impl<'a> GenConst<'a> {
     }
 
     /// Add an outer attribute
+    ///
+    /// ```
+    /// # use virtue::prelude::Generator;
+    /// # let mut generator = Generator::with_name("Bar");
+    /// generator.impl_for("Foo")
+    ///          .generate_const("BAR", "u8")
+    ///          .with_attr("doc(hidden)")
+    ///          .with_value(|b| {
+    ///             b.push_parsed("5")?;
+    ///             Ok(())
+    ///          })?;
+    /// # generator.assert_eq("impl Foo for Bar { # [doc (hidden)] const BAR : u8 = 5 ; }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
     #[must_use]
-    pub fn with_attr(mut self, attr: impl Into<String>) -> Self {
+    pub fn with_attr(mut self, attr: impl Into<AttrTokens>) -> Self {
         self.attrs.push(attr.into());
         self
     }
@@ -64,35 +90,205 @@ impl<'a> GenConst<'a> {
     /// }
     /// ```
     pub fn with_value<F>(self, f: F) -> Result
+    where
+        F: FnOnce(&mut StreamBuilder) -> Result,
+    {
+        let (consts, builder) = self.build(f)?;
+        consts.push(builder);
+        Ok(())
+    }
+
+    /// Complete the constant definition like [`with_value`], but the callback can decide to not generate the constant at all by returning [`GenerationOutcome::Skip`].
+    ///
+    /// ```
+    /// # use virtue::prelude::Generator;
+    /// # use virtue::generate::GenerationOutcome;
+    /// # let mut generator = Generator::with_name("Bar");
+    /// # let has_size_attribute = false;
+    /// generator.impl_for("Foo")
+    ///          .generate_const("SIZE", "usize")
+    ///          .with_value_opt(|b| {
+    ///             if !has_size_attribute {
+    ///                 return Ok(GenerationOutcome::Skip);
+    ///             }
+    ///             b.push_parsed("5")?;
+    ///             Ok(GenerationOutcome::Generate)
+    ///          })?;
+    /// # generator.assert_eq("impl Foo for Bar { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`with_value`]: #method.with_value
+    pub fn with_value_opt<F>(self, f: F) -> Result<GenerationOutcome>
+    where
+        F: FnOnce(&mut StreamBuilder) -> Result<GenerationOutcome>,
+    {
+        let mut outcome = GenerationOutcome::Generate;
+        let (consts, builder) = self.build(|b| {
+            outcome = f(b)?;
+            Ok(())
+        })?;
+        if outcome == GenerationOutcome::Generate {
+            consts.push(builder);
+        }
+        Ok(outcome)
+    }
+
+    fn build<F>(self, f: F) -> Result<(&'a mut Vec<StreamBuilder>, StreamBuilder)>
     where
         F: FnOnce(&mut StreamBuilder) -> Result,
     {
         let mut builder = StreamBuilder::new();
 
         for attr in self.attrs {
-            builder
-                .punct('#')
-                .punct('!')
-                .group(Delimiter::Bracket, |builder| {
-                    builder.push_parsed(attr)?;
-                    Ok(())
-                })?;
+            builder.append(attr.into_attribute(false)?);
         }
 
-        if self.vis == Visibility::Pub {
-            builder.ident_str("pub");
-        }
+        self.vis.append_to(&mut builder);
 
         builder
             .ident_str("const")
             .push_parsed(self.name)?
-            .punct(':')
-            .push_parsed(self.ty)?
-            .punct('=');
+            .punct(':');
+        self.ty.append_to(&mut builder)?;
+        builder.punct('=');
         f(&mut builder)?;
         builder.punct(';');
 
-        self.consts.push(builder);
+        Ok((self.consts, builder))
+    }
+}
+
+/// A builder for associated types, e.g. generic associated types like `type Item<'a> = &'a u8 where Self: 'a;`. Created with [`ImplFor::generate_type`].
+///
+/// ```
+/// # use virtue::prelude::Generator;
+/// # let mut generator = Generator::with_name("Foo");
+/// generator
+///     .impl_for("LendingIterator")
+///     .generate_type("Item")
+///     .with_lifetime("a")
+///     .with_where("Self: 'a")
+///     .with_value("&'a u8")?;
+/// # generator.assert_eq("impl LendingIterator for Foo { type Item < 'a > = &'a u8 where Self : 'a ; }");
+/// # Ok::<_, virtue::Error>(())
+/// ```
+///
+/// Generates:
+/// ```ignore
+/// impl LendingIterator for <struct or enum> {
+///     type Item<'a> = &'a u8 where Self: 'a;
+/// }
+/// ```
+///
+/// [`ImplFor::generate_type`]: struct.ImplFor.html#method.generate_type
+pub struct GenType<'a> {
+    types: &'a mut Vec<StreamBuilder>,
+    attrs: Vec<AttrTokens>,
+    name: String,
+    lifetimes: Vec<String>,
+    generics: Vec<(String, Vec<String>)>,
+    where_clauses: Vec<String>,
+}
+
+impl<'a> GenType<'a> {
+    pub(crate) fn new(types: &'a mut Vec<StreamBuilder>, name: impl Into<String>) -> Self {
+        Self {
+            types,
+            attrs: Vec::new(),
+            name: name.into(),
+            lifetimes: Vec::new(),
+            generics: Vec::new(),
+            where_clauses: Vec::new(),
+        }
+    }
+
+    /// Add an outer attribute
+    #[must_use]
+    pub fn with_attr(mut self, attr: impl Into<AttrTokens>) -> Self {
+        self.attrs.push(attr.into());
+        self
+    }
+
+    /// Add a lifetime parameter, e.g. `with_lifetime("a")` for `type Item<'a>`. Lifetimes are declared before generic parameters.
+    #[must_use]
+    pub fn with_lifetime(mut self, name: impl Into<String>) -> Self {
+        self.lifetimes.push(name.into());
+        self
+    }
+
+    /// Add a generic parameter, e.g. `with_generic("T")` for `type Output<T>`
+    #[must_use]
+    pub fn with_generic(mut self, name: impl Into<String>) -> Self {
+        self.generics.push((name.into(), Vec::new()));
+        self
+    }
+
+    /// Add a generic parameter with bounds, e.g. `with_generic_deps("T", ["Clone"])` for `type Output<T: Clone>`
+    #[must_use]
+    pub fn with_generic_deps<DEP, I>(mut self, name: impl Into<String>, dependencies: DEP) -> Self
+    where
+        DEP: IntoIterator<Item = I>,
+        I: Into<String>,
+    {
+        self.generics.push((
+            name.into(),
+            dependencies.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    /// Add a where clause, e.g. `with_where("Self: 'a")`. The where clauses are added after the value, which is where rust expects them for generic associated types.
+    #[must_use]
+    pub fn with_where(mut self, constraint: impl Into<String>) -> Self {
+        self.where_clauses.push(constraint.into());
+        self
+    }
+
+    /// Complete the type definition with the given value, e.g. `&'a u8`.
+    pub fn with_value(self, value: impl Into<TypeTokens>) -> Result {
+        let mut builder = StreamBuilder::new();
+        for attr in self.attrs {
+            builder.append(attr.into_attribute(false)?);
+        }
+        builder.ident_str("type").ident_str(&self.name);
+        if !self.lifetimes.is_empty() || !self.generics.is_empty() {
+            builder.punct('<');
+            let mut is_first = true;
+            for lifetime in &self.lifetimes {
+                if is_first {
+                    is_first = false;
+                } else {
+                    builder.punct(',');
+                }
+                builder.lifetime_str(lifetime);
+            }
+            for (generic, dependencies) in &self.generics {
+                if is_first {
+                    is_first = false;
+                } else {
+                    builder.punct(',');
+                }
+                builder.ident_str(generic);
+                for (idx, dependency) in dependencies.iter().enumerate() {
+                    builder.punct(if idx == 0 { ':' } else { '+' });
+                    builder.push_parsed(dependency)?;
+                }
+            }
+            builder.punct('>');
+        }
+        builder.punct('=');
+        value.into().append_to(&mut builder)?;
+        for (idx, constraint) in self.where_clauses.iter().enumerate() {
+            if idx == 0 {
+                builder.ident_str("where");
+            } else {
+                builder.punct(',');
+            }
+            builder.push_parsed(constraint)?;
+        }
+        builder.punct(';');
+        self.types.push(builder);
         Ok(())
     }
 }
@@ -102,16 +298,24 @@ pub struct FnBuilder<'a, P> {
     parent: &'a mut P,
     name: String,
 
-    attrs: Vec<String>,
+    attrs: Vec<FnAttr>,
     is_async: bool,
+    extern_abi: Option<String>,
+    target_lifetimes: bool,
     lifetimes: Vec<(String, Vec<String>)>,
     generics: Vec<(String, Vec<String>)>,
     self_arg: FnSelfArg,
-    args: Vec<(String, String)>,
-    return_type: Option<String>,
+    self_arg_trailing_comma: bool,
+    args: Vec<(FnArgName, TypeTokens)>,
+    return_type: Option<TypeTokens>,
     vis: Visibility,
 }
 
+enum FnAttr {
+    Parsed(AttrTokens),
+    Inherited(Attribute),
+}
+
 impl<'a, P: FnParent> FnBuilder<'a, P> {
     pub(super) fn new(parent: &'a mut P, name: impl Into<String>) -> Self {
         Self {
@@ -119,9 +323,12 @@ impl<'a, P: FnParent> FnBuilder<'a, P> {
             name: name.into(),
             attrs: Vec::new(),
             is_async: false,
+            extern_abi: None,
+            target_lifetimes: false,
             lifetimes: Vec::new(),
             generics: Vec::new(),
             self_arg: FnSelfArg::None,
+            self_arg_trailing_comma: true,
             args: Vec::new(),
             return_type: None,
             vis: Visibility::Default,
@@ -130,8 +337,79 @@ impl<'a, P: FnParent> FnBuilder<'a, P> {
 
     /// Add an outer attribute
     #[must_use]
-    pub fn with_attr(mut self, attr: impl Into<String>) -> Self {
-        self.attrs.push(attr.into());
+    pub fn with_attr(mut self, attr: impl Into<AttrTokens>) -> Self {
+        self.attrs.push(FnAttr::Parsed(attr.into()));
+        self
+    }
+
+    /// Copy the `attributes` for which `filter` returns `true` onto this function. The attributes are emitted as-is, so errors in them will point at the original source. Inner attributes like `#![allow(unused)]` are never copied, because they do not belong to the struct or enum.
+    ///
+    /// This can be used to propagate e.g. `#[cfg(...)]`, `#[allow(...)]` or `#[track_caller]` from the struct or enum to the generated functions.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = TokenStream::from_str("#[doc = \"Foo\"] #[cfg(feature = \"foo\")] struct Foo;").unwrap();
+    /// let (mut generator, attributes, _) = Parse::new(input)?.into_generator();
+    /// generator
+    ///     .r#impl()
+    ///     .generate_fn("foo")
+    ///     .inherit_attrs(&attributes, |attr| attr.path_is("cfg"))
+    ///     .body(|_| Ok(()))?;
+    /// # generator.assert_eq("impl Foo { # [cfg (feature = \"foo\")] fn foo () { } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    #[must_use]
+    pub fn inherit_attrs(
+        mut self,
+        attributes: &[Attribute],
+        mut filter: impl FnMut(&Attribute) -> bool,
+    ) -> Self {
+        for attribute in attributes.iter().filter(|attribute| !attribute.is_inner) {
+            if filter(attribute) {
+                self.attrs.push(FnAttr::Inherited(attribute.clone()));
+            }
+        }
+        self
+    }
+
+    /// Add a `#[no_mangle]` attribute, so the function can be called from other languages.
+    ///
+    /// This is usually combined with [`with_extern_abi`]. In the 2024 edition of Rust this attribute has to be written as `#[unsafe(no_mangle)]`, use [`with_attr`] for this instead.
+    ///
+    /// ```
+    /// # use virtue::prelude::Generator;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator
+    ///     .generate_fn("foo_new") // fn foo_new()
+    ///     .no_mangle() // #[no_mangle] fn foo_new()
+    ///     .with_extern_abi("C") // #[no_mangle] extern "C" fn foo_new()
+    /// # .body(|_| Ok(())).unwrap();
+    /// # generator.assert_eq("# [no_mangle] extern \"C\" fn foo_new () { }");
+    /// ```
+    ///
+    /// [`with_extern_abi`]: #method.with_extern_abi
+    /// [`with_attr`]: #method.with_attr
+    #[must_use]
+    pub fn no_mangle(self) -> Self {
+        self.with_attr("no_mangle")
+    }
+
+    /// Set the ABI of the function, e.g. `extern "C" fn`. By default the function will use the Rust ABI.
+    ///
+    /// ```
+    /// # use virtue::prelude::Generator;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator
+    ///     .r#impl()
+    ///     .generate_fn("foo") // fn foo()
+    ///     .with_extern_abi("C") // extern "C" fn foo()
+    /// # .body(|_| Ok(())).unwrap();
+    /// # generator.assert_eq("impl Foo { extern \"C\" fn foo () { } }");
+    /// ```
+    #[must_use]
+    pub fn with_extern_abi(mut self, abi: impl Into<String>) -> Self {
+        self.extern_abi = Some(abi.into());
         self
     }
 
@@ -153,6 +431,31 @@ impl<'a, P: FnParent> FnBuilder<'a, P> {
         self
     }
 
+    /// Add the lifetimes of the struct or enum that is being derived to this function, so the signature can use them, e.g. `fn borrow<'a>(foo: &Foo<'a>) -> &'a str`.
+    ///
+    /// The lifetimes are only added if they are not declared by the surrounding `impl` block already, which is the case for free functions generated with [`Generator::generate_fn`]. Inside of an `impl` block they are already in scope, and nothing is added.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo").with_lifetime("a");
+    /// let self_type = generator.self_type();
+    /// generator
+    ///     .generate_fn("borrow") // fn borrow()
+    ///     .with_target_lifetimes() // fn borrow<'a>()
+    ///     .with_arg("foo", format!("&{}", self_type)) // fn borrow<'a>(foo: &Foo<'a>)
+    ///     .with_return_type("&'a str") // fn borrow<'a>(foo: &Foo<'a>) -> &'a str
+    /// # .body(|b| { b.push_parsed("foo.0")?; Ok(()) })?;
+    /// # generator.assert_eq("fn borrow < 'a > (foo : & Foo < 'a >) ->&'a str { foo . 0 }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`Generator::generate_fn`]: struct.Generator.html#method.generate_fn
+    #[must_use]
+    pub fn with_target_lifetimes(mut self) -> Self {
+        self.target_lifetimes = true;
+        self
+    }
+
     /// Make the function async
     ///
     /// ```
@@ -215,6 +518,8 @@ impl<'a, P: FnParent> FnBuilder<'a, P> {
     /// # .body(|_| Ok(())).unwrap();
     /// # generator.assert_eq("impl Foo { fn foo < D > () { } }");
     /// ```
+    ///
+    /// The generics and lifetimes of a function can not have the same name as the generics of the surrounding impl block, e.g. `T` in `impl<T> Foo<T>`. Completing the function returns an error if they do.
     #[must_use]
     pub fn with_generic(mut self, name: impl Into<String>) -> Self {
         self.generics.push((name.into(), Vec::new()));
@@ -281,8 +586,97 @@ impl<'a, P: FnParent> FnBuilder<'a, P> {
     /// # generator.assert_eq("impl Foo { fn foo (a : u32 , b : u32) { } }");
     /// ```
     #[must_use]
-    pub fn with_arg(mut self, name: impl Into<String>, ty: impl Into<String>) -> Self {
-        self.args.push((name.into(), ty.into()));
+    pub fn with_arg(mut self, name: impl Into<String>, ty: impl Into<TypeTokens>) -> Self {
+        self.args.push((FnArgName::Name(name.into()), ty.into()));
+        self
+    }
+
+    /// Add a mutable argument with a `name` and a `ty`.
+    ///
+    /// ```
+    /// # use virtue::prelude::Generator;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator
+    ///     .r#impl()
+    ///     .generate_fn("foo") // fn foo()
+    ///     .with_mut_arg("a", "u32") // fn foo(mut a: u32)
+    /// # .body(|_| Ok(())).unwrap();
+    /// # generator.assert_eq("impl Foo { fn foo (mut a : u32) { } }");
+    /// ```
+    #[must_use]
+    pub fn with_mut_arg(mut self, name: impl Into<String>, ty: impl Into<TypeTokens>) -> Self {
+        self.args.push((FnArgName::Mut(name.into()), ty.into()));
+        self
+    }
+
+    /// Add an argument that destructures its value with a `pattern` of type `ty`.
+    ///
+    /// ```
+    /// # use virtue::prelude::Generator;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator
+    ///     .r#impl()
+    ///     .generate_fn("foo") // fn foo()
+    ///     .with_pattern_arg("(a, b)", "(u8, u8)") // fn foo((a, b): (u8, u8))
+    ///     .with_pattern_arg("_", "PhantomData<u8>") // fn foo((a, b): (u8, u8), _: PhantomData<u8>)
+    /// # .body(|_| Ok(())).unwrap();
+    /// # generator.assert_eq("impl Foo { fn foo ((a , b) : (u8 , u8) , _ : PhantomData < u8 >) { } }");
+    /// ```
+    #[must_use]
+    pub fn with_pattern_arg(
+        mut self,
+        pattern: impl Into<String>,
+        ty: impl Into<TypeTokens>,
+    ) -> Self {
+        self.args
+            .push((FnArgName::Pattern(pattern.into()), ty.into()));
+        self
+    }
+
+    /// Add an argument that destructures its value with a [`PatternBuilder`] of type `ty`.
+    ///
+    /// ```
+    /// # use virtue::prelude::Generator;
+    /// # use virtue::generate::PatternBuilder;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator
+    ///     .r#impl()
+    ///     .generate_fn("foo") // fn foo()
+    ///     .with_arg_pattern(
+    ///         PatternBuilder::reference(PatternBuilder::tuple([
+    ///             PatternBuilder::binding("a")?,
+    ///             PatternBuilder::wildcard(),
+    ///         ])),
+    ///         "&(u8, u8)",
+    ///     ) // fn foo(&(a, _): &(u8, u8))
+    /// # .body(|_| Ok(()))?;
+    /// # generator.assert_eq("impl Foo { fn foo (& (a , _) : & (u8 , u8)) { } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    #[must_use]
+    pub fn with_arg_pattern(mut self, pattern: PatternBuilder, ty: impl Into<TypeTokens>) -> Self {
+        self.args.push((FnArgName::Built(pattern), ty.into()));
+        self
+    }
+
+    /// Set whether a comma is emitted after the `self` argument when the function has no other arguments. Defaults to `true`.
+    ///
+    /// A comma is always emitted between `self` and any following arguments.
+    ///
+    /// ```
+    /// # use virtue::prelude::{Generator, FnSelfArg};
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator
+    ///     .r#impl()
+    ///     .generate_fn("foo")
+    ///     .with_self_arg(FnSelfArg::RefSelf) // fn foo(&self,)
+    ///     .with_self_arg_trailing_comma(false) // fn foo(&self)
+    /// # .body(|_| Ok(())).unwrap();
+    /// # generator.assert_eq("impl Foo { fn foo (& self) { } }");
+    /// ```
+    #[must_use]
+    pub fn with_self_arg_trailing_comma(mut self, trailing_comma: bool) -> Self {
+        self.self_arg_trailing_comma = trailing_comma;
         self
     }
 
@@ -299,7 +693,7 @@ impl<'a, P: FnParent> FnBuilder<'a, P> {
     /// # generator.assert_eq("impl Foo { fn foo () ->u32 { } }");
     /// ```
     #[must_use]
-    pub fn with_return_type(mut self, ret_type: impl Into<String>) -> Self {
+    pub fn with_return_type(mut self, ret_type: impl Into<TypeTokens>) -> Self {
         self.return_type = Some(ret_type.into());
         self
     }
@@ -311,6 +705,30 @@ impl<'a, P: FnParent> FnBuilder<'a, P> {
         self
     }
 
+    /// Set the visibility of the function, e.g. to give it the same visibility as the target with [`Generator::target_visibility`].
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = TokenStream::from_str("pub(crate) struct Foo;").unwrap();
+    /// let (mut generator, _, _) = Parse::new(input)?.into_generator();
+    /// let visibility = generator.target_visibility().clone();
+    /// generator
+    ///     .r#impl()
+    ///     .generate_fn("foo")
+    ///     .with_visibility(visibility) // pub(crate) fn foo()
+    ///     .body(|_| Ok(()))?;
+    /// # generator.assert_eq("impl Foo { pub (crate) fn foo () { } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`Generator::target_visibility`]: struct.Generator.html#method.target_visibility
+    #[must_use]
+    pub fn with_visibility(mut self, visibility: Visibility) -> Self {
+        self.vis = visibility;
+        self
+    }
+
     /// Complete the function definition. This function takes a callback that will form the body of the function.
     ///
     /// ```
@@ -329,110 +747,302 @@ impl<'a, P: FnParent> FnBuilder<'a, P> {
     /// // }
     /// # generator.assert_eq("impl Foo { fn foo () { println ! (\"hello world\") ; } }");
     /// ```
+    ///
+    /// If the signature or `body_builder` returns an error, the function is not added, and the `impl` block it belongs to is not emitted either.
     pub fn body(
         self,
         body_builder: impl FnOnce(&mut StreamBuilder) -> crate::Result,
     ) -> crate::Result {
+        let (parent, signature) = self.into_signature();
+        let result = signature.and_then(|builder| {
+            let mut body_stream = StreamBuilder::new();
+            body_builder(&mut body_stream)?;
+            Ok((builder, body_stream))
+        });
+        match result {
+            Ok((builder, body_stream)) => parent.append(builder, body_stream),
+            Err(error) => {
+                parent.poison();
+                Err(error)
+            }
+        }
+    }
+
+    /// Complete the function definition like [`body`], but the callback can decide to not generate the function at all by returning [`GenerationOutcome::Skip`].
+    ///
+    /// This is useful when the callback inspects attributes to decide if the function is needed.
+    ///
+    /// ```
+    /// # use virtue::prelude::Generator;
+    /// # use virtue::generate::GenerationOutcome;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// # let is_skipped = true;
+    /// generator
+    ///     .r#impl()
+    ///     .generate_fn("foo")
+    ///     .body_opt(|b| {
+    ///         if is_skipped {
+    ///             return Ok(GenerationOutcome::Skip);
+    ///         }
+    ///         b.push_parsed("println!(\"hello world\");")?;
+    ///         Ok(GenerationOutcome::Generate)
+    ///     })?;
+    /// # generator.assert_eq("impl Foo { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`body`]: #method.body
+    pub fn body_opt(
+        self,
+        body_builder: impl FnOnce(&mut StreamBuilder) -> crate::Result<GenerationOutcome>,
+    ) -> crate::Result<GenerationOutcome> {
+        let (parent, signature) = self.into_signature();
+        let result = signature.and_then(|builder| {
+            let mut body_stream = StreamBuilder::new();
+            let outcome = body_builder(&mut body_stream)?;
+            Ok((builder, body_stream, outcome))
+        });
+        match result {
+            Ok((builder, body_stream, outcome)) => {
+                if outcome == GenerationOutcome::Generate {
+                    parent.append(builder, body_stream)?;
+                }
+                Ok(outcome)
+            }
+            Err(error) => {
+                parent.poison();
+                Err(error)
+            }
+        }
+    }
+
+    /// Complete the function as a declaration without a body, e.g. `fn foo(a: u32);`.
+    ///
+    /// This is only valid in an `extern` block, see [`Generator::generate_extern_block`].
+    ///
+    /// ```
+    /// # use virtue::prelude::Generator;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator
+    ///     .generate_extern_block("C")
+    ///     .generate_fn("foo") // fn foo()
+    ///     .with_arg("a", "u32") // fn foo(a: u32)
+    ///     .declaration()?; // fn foo(a: u32);
+    /// # generator.assert_eq("extern \"C\" { fn foo (a : u32) ; }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`Generator::generate_extern_block`]: struct.Generator.html#method.generate_extern_block
+    pub fn declaration(self) -> crate::Result {
+        let (parent, signature) = self.into_signature();
+        match signature {
+            Ok(mut builder) => {
+                builder.punct(';');
+                parent.append_declaration(builder)
+            }
+            Err(error) => {
+                parent.poison();
+                Err(error)
+            }
+        }
+    }
+
+    /// The parent, and the signature of the function or the error that building it returned. The parent is returned either way, so it can be poisoned on an error.
+    fn into_signature(self) -> (&'a mut P, crate::Result<StreamBuilder>) {
         let FnBuilder {
             parent,
             name,
             attrs,
             is_async,
+            extern_abi,
+            target_lifetimes,
             lifetimes,
             generics,
             self_arg,
+            self_arg_trailing_comma,
             args,
             return_type,
             vis,
         } = self;
 
-        let mut builder = StreamBuilder::new();
+        let signature = (|| {
+            let mut builder = StreamBuilder::new();
 
-        // attrs
-        for attr in attrs {
-            builder.punct('#').group(Delimiter::Bracket, |builder| {
-                builder.push_parsed(attr)?;
-                Ok(())
-            })?;
-        }
+            // attrs
+            for attr in attrs {
+                match attr {
+                    FnAttr::Parsed(attr) => {
+                        builder.append(attr.into_attribute(false)?);
+                    }
+                    FnAttr::Inherited(attr) => {
+                        builder.push(attr.punct).push(attr.tokens);
+                    }
+                }
+            }
 
-        // function name; `fn name`
-        if vis == Visibility::Pub {
-            builder.ident_str("pub");
-        }
-        if is_async {
-            builder.ident_str("async");
-        }
-        builder.ident_str("fn");
-        builder.ident_str(name);
+            // function name; `fn name`
+            vis.append_to(&mut builder);
+            if is_async {
+                builder.ident_str("async");
+            }
+            if let Some(abi) = extern_abi {
+                builder.ident_str("extern").lit_str(abi);
+            }
+            builder.ident_str("fn");
+            builder.ident_str(&name);
 
-        // lifetimes; `<'a: 'b, D: Display>`
-        if !lifetimes.is_empty() || !generics.is_empty() {
-            builder.punct('<');
-            let mut is_first = true;
-            for (lifetime, dependencies) in lifetimes {
-                if is_first {
-                    is_first = false;
-                } else {
-                    builder.punct(',');
+            let target_lifetimes: Vec<&Lifetime> = match parent.undeclared_target_generics() {
+                Some(target_generics) if target_lifetimes => {
+                    target_generics.iter_lifetimes().collect()
                 }
-                builder.lifetime_str(lifetime.as_ref());
-                if !dependencies.is_empty() {
-                    for (idx, dependency) in dependencies.into_iter().enumerate() {
-                        builder.punct(if idx == 0 { ':' } else { '+' });
-                        builder.lifetime_str(dependency.as_ref());
-                    }
+                _ => Vec::new(),
+            };
+
+            // `impl<T> Foo<T> { fn foo<T>() {} }` does not compile, and rustc can only point at the generated code
+            let mut declared = parent.declared_generics();
+            declared.extend(target_lifetimes.iter().map(|lt| format!("'{}", lt.ident)));
+            let names = lifetimes
+                .iter()
+                .map(|(lifetime, _)| format!("'{}", lifetime))
+                .chain(generics.iter().map(|(generic, _)| generic.clone()));
+            for generic in names {
+                if declared.contains(&generic) {
+                    return Err(crate::Error::custom(format!(
+                    "The generic `{}` of `fn {}` is already declared",
+                    generic, name
+                ))
+                .with_help(
+                    "the generics of the impl block can be used in the function without declaring them again, or rename the generic of the function",
+                ));
                 }
+                declared.push(generic);
             }
-            for (generic, dependencies) in generics {
-                if is_first {
-                    is_first = false;
-                } else {
-                    builder.punct(',');
+
+            // lifetimes; `<'a: 'b, D: Display>`
+            if !target_lifetimes.is_empty() || !lifetimes.is_empty() || !generics.is_empty() {
+                builder.punct('<');
+                let mut is_first = true;
+                for lifetime in target_lifetimes {
+                    if is_first {
+                        is_first = false;
+                    } else {
+                        builder.punct(',');
+                    }
+                    Generic::Lifetime(lifetime.clone())
+                        .append_to_result_with_constraints(&mut builder);
+                }
+                for (lifetime, dependencies) in lifetimes {
+                    if is_first {
+                        is_first = false;
+                    } else {
+                        builder.punct(',');
+                    }
+                    builder.lifetime_str(lifetime.as_ref());
+                    if !dependencies.is_empty() {
+                        for (idx, dependency) in dependencies.into_iter().enumerate() {
+                            builder.punct(if idx == 0 { ':' } else { '+' });
+                            builder.lifetime_str(dependency.as_ref());
+                        }
+                    }
                 }
-                builder.ident_str(&generic);
-                if !dependencies.is_empty() {
-                    for (idx, dependency) in dependencies.into_iter().enumerate() {
-                        builder.punct(if idx == 0 { ':' } else { '+' });
-                        builder.push_parsed(&dependency)?;
+                for (generic, dependencies) in generics {
+                    if is_first {
+                        is_first = false;
+                    } else {
+                        builder.punct(',');
+                    }
+                    builder.ident_str(&generic);
+                    if !dependencies.is_empty() {
+                        for (idx, dependency) in dependencies.into_iter().enumerate() {
+                            builder.punct(if idx == 0 { ':' } else { '+' });
+                            builder.push_parsed(&dependency)?;
+                        }
                     }
                 }
+                builder.punct('>');
             }
-            builder.punct('>');
-        }
 
-        // Arguments; `(&self, foo: &Bar)`
-        builder.group(Delimiter::Parenthesis, |arg_stream| {
-            if let Some(self_arg) = self_arg.into_token_tree() {
-                arg_stream.append(self_arg);
-                arg_stream.punct(',');
-            }
-            for (idx, (arg_name, arg_ty)) in args.into_iter().enumerate() {
-                if idx != 0 {
-                    arg_stream.punct(',');
+            // Arguments; `(&self, foo: &Bar)`
+            builder.group(Delimiter::Parenthesis, |arg_stream| {
+                if let Some(self_arg) = self_arg.into_token_tree() {
+                    arg_stream.append(self_arg);
+                    if self_arg_trailing_comma || !args.is_empty() {
+                        arg_stream.punct(',');
+                    }
                 }
-                arg_stream.push_parsed(&arg_name)?;
-                arg_stream.punct(':');
-                arg_stream.push_parsed(&arg_ty)?;
+                for (idx, (arg_name, arg_ty)) in args.into_iter().enumerate() {
+                    if idx != 0 {
+                        arg_stream.punct(',');
+                    }
+                    match arg_name {
+                        FnArgName::Name(name) | FnArgName::Pattern(name) => {
+                            arg_stream.push_parsed(&name)?;
+                        }
+                        FnArgName::Mut(name) => {
+                            arg_stream.ident_str("mut").push_parsed(&name)?;
+                        }
+                        FnArgName::Built(pattern) => {
+                            arg_stream.pattern(&pattern);
+                        }
+                    }
+                    arg_stream.punct(':');
+                    arg_ty.append_to(arg_stream)?;
+                }
+                Ok(())
+            })?;
+
+            // Return type: `-> ResultType`
+            if let Some(return_type) = return_type {
+                builder.puncts("->");
+                return_type.append_to(&mut builder)?;
             }
-            Ok(())
-        })?;
 
-        // Return type: `-> ResultType`
-        if let Some(return_type) = return_type {
-            builder.puncts("->");
-            builder.push_parsed(&return_type)?;
-        }
+            Ok(builder)
+        })();
+        (parent, signature)
+    }
+}
 
-        let mut body_stream = StreamBuilder::new();
-        body_builder(&mut body_stream)?;
+enum FnArgName {
+    Name(String),
+    Mut(String),
+    Pattern(String),
+    Built(PatternBuilder),
+}
 
-        parent.append(builder, body_stream)
-    }
+/// The names of the lifetimes and generics in `generics`, for [`FnParent::declared_generics`]
+pub(super) fn generic_names(generics: Option<&Generics>) -> Vec<String> {
+    generics
+        .iter()
+        .flat_map(|generics| generics.iter())
+        .map(|generic| match generic {
+            Generic::Lifetime(lt) => format!("'{}", lt.ident),
+            generic => generic.ident().to_string(),
+        })
+        .collect()
 }
 
 pub trait FnParent {
     fn append(&mut self, fn_definition: StreamBuilder, fn_body: StreamBuilder) -> Result;
+
+    /// The generics of the target type that are not declared by this parent, see [`FnBuilder::with_target_lifetimes`].
+    fn undeclared_target_generics(&self) -> Option<&Generics> {
+        None
+    }
+
+    /// The names of the generics that this parent declares for its functions, e.g. `'a` and `T` for `impl<'a, T> Foo<'a, T>`. Lifetimes start with a `'`.
+    fn declared_generics(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn append_declaration(&mut self, _fn_declaration: StreamBuilder) -> Result {
+        Err(crate::Error::custom(
+            "Functions without a body can only be declared in an extern block",
+        ))
+    }
+
+    /// Called when a function fails to build, so a parent that emits a block on drop can leave out the half-built block.
+    fn poison(&mut self) {}
 }
 
 /// The `self` argument of a function
@@ -1,7 +1,8 @@
+use super::stream_builder::ident_from_str;
 use super::{GenEnum, GenStruct, Impl, Parent, StreamBuilder};
 use crate::{
     parse::Visibility,
-    prelude::{Delimiter, Ident, Span},
+    prelude::{Delimiter, Ident},
     Result,
 };
 
@@ -18,7 +19,7 @@ impl<'a, P: Parent> GenerateMod<'a, P> {
     pub(crate) fn new(parent: &'a mut P, name: impl Into<String>) -> Self {
         Self {
             parent,
-            name: Ident::new(name.into().as_str(), Span::call_site()),
+            name: ident_from_str(&name.into()),
             uses: Vec::new(),
             vis: Visibility::Default,
             content: StreamBuilder::new(),
@@ -44,17 +45,17 @@ impl<'a, P: Parent> GenerateMod<'a, P> {
     }
 
     /// Generate a struct with the given name. See [`GenStruct`] for more info.
-    pub fn generate_struct(&mut self, name: impl Into<String>) -> GenStruct<Self> {
+    pub fn generate_struct(&mut self, name: impl Into<String>) -> GenStruct<'_, Self> {
         GenStruct::new(self, name)
     }
 
     /// Generate an enum with the given name. See [`GenEnum`] for more info.
-    pub fn generate_enum(&mut self, name: impl Into<String>) -> GenEnum<Self> {
+    pub fn generate_enum(&mut self, name: impl Into<String>) -> GenEnum<'_, Self> {
         GenEnum::new(self, name)
     }
 
     /// Generate an `impl <name>` implementation. See [`Impl`] for more information.
-    pub fn r#impl(&mut self, name: impl Into<String>) -> Impl<Self> {
+    pub fn r#impl(&mut self, name: impl Into<String>) -> Impl<'_, Self> {
         Impl::new(self, name)
     }
 
@@ -63,7 +64,7 @@ impl<'a, P: Parent> GenerateMod<'a, P> {
     /// Alias for [`impl`] which doesn't need a `r#` prefix.
     ///
     /// [`impl`]: #method.impl
-    pub fn generate_impl(&mut self, name: impl Into<String>) -> Impl<Self> {
+    pub fn generate_impl(&mut self, name: impl Into<String>) -> Impl<'_, Self> {
         Impl::new(self, name)
     }
 }
@@ -71,9 +72,7 @@ impl<'a, P: Parent> GenerateMod<'a, P> {
 impl<'a, P: Parent> Drop for GenerateMod<'a, P> {
     fn drop(&mut self) {
         let mut builder = StreamBuilder::new();
-        if self.vis == Visibility::Pub {
-            builder.ident_str("pub");
-        }
+        self.vis.append_to(&mut builder);
         builder
             .ident_str("mod")
             .ident(self.name.clone())
@@ -106,4 +105,8 @@ impl<P: Parent> Parent for GenerateMod<'_, P> {
     fn generic_constraints(&self) -> Option<&crate::parse::GenericConstraints> {
         None
     }
+
+    fn automatically_derived(&self) -> bool {
+        self.parent.automatically_derived()
+    }
 }
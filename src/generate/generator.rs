@@ -1,6 +1,14 @@
-use super::{GenEnum, GenStruct, GenerateMod, Impl, ImplFor, StreamBuilder, StringOrIdent};
-use crate::parse::{GenericConstraints, Generics};
-use crate::prelude::{Ident, TokenStream};
+use super::{
+    generate_item::FnParent, post_process::PostProcess, FnBuilder, GenEnum, GenExtern, GenStruct,
+    GenerateMod, GenerationStats, Impl, ImplFor, Provenance, StreamBuilder, StringOrIdent,
+    TargetType, TypeTokens, Warning, STATS_ENV_VAR,
+};
+use crate::parse::{utils::ident_eq, GenericConstraints, Generics, SimpleGeneric, Visibility};
+use crate::prelude::{Delimiter, Ident, Span, TokenStream, TokenTree};
+use crate::utils::CratePath;
+use crate::Error;
+
+type FinishHook = Box<dyn FnOnce(&GenerationStats)>;
 
 #[must_use]
 /// The generator is used to generate code.
@@ -10,9 +18,34 @@ use crate::prelude::{Ident, TokenStream};
 /// [`impl_for`]: #method.impl_for
 pub struct Generator {
     name: Ident,
+    visibility: Visibility,
     generics: Option<Generics>,
     generic_constraints: Option<GenericConstraints>,
+    suppressed_lints: Vec<String>,
+    automatically_derived: bool,
+    no_std: bool,
+    on_finish: Vec<FinishHook>,
+    post_process: Vec<PostProcess>,
+    warnings: Vec<Warning>,
+    uses: Vec<StreamBuilder>,
+    target_path: Option<String>,
     stream: StreamBuilder,
+    deferred: StreamBuilder,
+}
+
+/// The position to insert an item at, see [`Generator::insert_at`].
+///
+/// [`Generator::insert_at`]: struct.Generator.html#method.insert_at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    /// Before all items that were generated so far. `use` statements added with [`Generator::add_use`] are still emitted first.
+    ///
+    /// [`Generator::add_use`]: struct.Generator.html#method.add_use
+    Start,
+    /// After all items that were generated so far, but before the items generated in [`Generator::defer`].
+    ///
+    /// [`Generator::defer`]: struct.Generator.html#method.defer
+    End,
 }
 
 impl Generator {
@@ -23,22 +56,139 @@ impl Generator {
     ) -> Self {
         Self {
             name,
+            visibility: Visibility::Default,
             generics,
             generic_constraints,
+            suppressed_lints: Vec::new(),
+            automatically_derived: false,
+            no_std: false,
+            on_finish: Vec::new(),
+            post_process: Vec::new(),
+            warnings: Vec::new(),
+            uses: Vec::new(),
+            target_path: None,
             stream: StreamBuilder::new(),
+            deferred: StreamBuilder::new(),
         }
     }
 
+    pub(crate) fn with_target_visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
     /// Return the name for the struct or enum that this is going to be implemented on.
     pub fn target_name(&self) -> Ident {
         self.name.clone()
     }
 
+    /// Return the type of the struct or enum that this is going to be implemented on, including its generics, e.g. `Foo<'a, T>`.
+    ///
+    /// Inside of an `impl` block this is the same as `Self`. This can be used in places where `Self` is not available, e.g. in the signature of a free function.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo").with_lifetime("a");
+    /// assert_eq!(generator.self_type(), "Foo < 'a >");
+    /// # generator.finish()?;
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn self_type(&self) -> String {
+        self.target_type().to_string()
+    }
+
+    /// Return the type of the struct or enum that this is going to be implemented on, to add it to a [`StreamBuilder`] with [`StreamBuilder::self_type`] or [`StreamBuilder::self_type_turbofish`]. See [`TargetType`] for more information.
+    ///
+    /// [`StreamBuilder::self_type`]: struct.StreamBuilder.html#method.self_type
+    /// [`StreamBuilder::self_type_turbofish`]: struct.StreamBuilder.html#method.self_type_turbofish
+    /// [`TargetType`]: struct.TargetType.html
+    pub fn target_type(&self) -> TargetType {
+        let mut path = StreamBuilder::new();
+        self.target_type_name()
+            .append_to(&mut path)
+            .expect("target path is validated in set_target_path");
+        TargetType::new(path, self.generics.clone())
+    }
+
+    /// Generate a type alias for the struct or enum that this is going to be implemented on, e.g. `type Self_<'a, T> = Foo<'a, T>;`, so generated code can name it with a fixed name.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo").with_lifetime("a");
+    /// generator.generate_self_alias("Self_");
+    ///
+    /// // will output:
+    /// // type Self_<'a> = Foo<'a>;
+    /// # generator.assert_eq("type Self_ < 'a > = Foo < 'a > ;");
+    /// ```
+    ///
+    /// The generics of the alias have no bounds, because the bounds of a type alias are not checked.
+    pub fn generate_self_alias(&mut self, name: impl AsRef<str>) -> &mut Self {
+        let alias = self.target_type().alias(name.as_ref());
+        self.append_item(alias)
+            .expect("Suppressed lints are validated in `suppress_lints`");
+        self
+    }
+
+    /// Refer to the struct or enum through the module path `prefix`, e.g. `crate::module`. This is needed when the generated code is placed in a different module than the target, e.g. in a module made with [`generate_mod`].
+    ///
+    /// The path is used by [`impl_for`], [`impl`] and [`self_type`]. [`target_name`] still returns the bare name, so it can be used to e.g. name generated functions.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator.set_target_path("crate::module")?;
+    /// generator.impl_for("Bar");
+    /// assert_eq!(generator.self_type(), "crate :: module :: Foo");
+    /// assert_eq!(generator.target_name().to_string(), "Foo");
+    /// # generator.assert_eq("impl Bar for crate :: module :: Foo { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`generate_mod`]: #method.generate_mod
+    /// [`impl_for`]: #method.impl_for
+    /// [`impl`]: #method.impl
+    /// [`self_type`]: #method.self_type
+    /// [`target_name`]: #method.target_name
+    pub fn set_target_path(&mut self, prefix: impl AsRef<str>) -> crate::Result<&mut Self> {
+        let prefix = prefix.as_ref().trim().trim_end_matches("::").trim_end();
+        let mut builder = StreamBuilder::new();
+        builder.push_parsed(prefix)?;
+        if prefix.is_empty() || !is_path(&builder.stream) {
+            return Err(Error::custom(format!(
+                "Expected a module path like `crate::module`, found `{}`",
+                prefix
+            )));
+        }
+        self.target_path = Some(prefix.to_string());
+        Ok(self)
+    }
+
+    /// The name of the target, prefixed with the path of [`set_target_path`]
+    ///
+    /// [`set_target_path`]: #method.set_target_path
+    fn target_type_name(&self) -> StringOrIdent {
+        match &self.target_path {
+            Some(path) => StringOrIdent::String(format!("{}::{}", path, self.name)),
+            None => StringOrIdent::Ident(self.name.clone()),
+        }
+    }
+
+    /// Return the visibility of the struct or enum that this is going to be implemented on.
+    ///
+    /// This can be passed to e.g. [`GenStruct::with_visibility`] to give a generated helper type the same visibility as the target.
+    ///
+    /// [`GenStruct::with_visibility`]: struct.GenStruct.html#method.with_visibility
+    pub fn target_visibility(&self) -> &Visibility {
+        &self.visibility
+    }
+
     /// Generate an `impl <target_name>` implementation. See [`Impl`] for more information.
     ///
     /// This will default to the type that is associated with this generator. If you need to generate an impl for another type you can use `impl_for_other_type`
-    pub fn r#impl(&mut self) -> Impl<Self> {
-        Impl::with_parent_name(self)
+    pub fn r#impl(&mut self) -> Impl<'_, Self> {
+        let name = self.target_type_name().to_string();
+        Impl::new(self, name)
     }
 
     /// Generate an `impl <target_name>` implementation. See [`Impl`] for more information.
@@ -46,19 +196,25 @@ impl Generator {
     /// Alias for [`impl`] which doesn't need a `r#` prefix.
     ///
     /// [`impl`]: #method.impl
-    pub fn generate_impl(&mut self) -> Impl<Self> {
-        Impl::with_parent_name(self)
+    pub fn generate_impl(&mut self) -> Impl<'_, Self> {
+        self.r#impl()
     }
 
     /// Generate an `for <trait_name> for <target_name>` implementation. See [ImplFor] for more information.
     ///
     /// This will default to the type that is associated with this generator. If you need to generate an impl for another type you can use `impl_trait_for_other_type`
-    pub fn impl_for(&mut self, trait_name: impl Into<String>) -> ImplFor<Self> {
-        ImplFor::new(
-            self,
-            self.name.clone().into(),
-            Some(trait_name.into().into()),
-        )
+    ///
+    /// The trait can be given as an [`Ident`], e.g. one that was parsed from an attribute like `#[derive_with(SomeTrait)]`. The ident keeps its span, so compiler errors about the trait point at the user's tokens.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// let trait_name = Ident::new("SomeTrait", Span::call_site());
+    /// generator.impl_for(trait_name);
+    /// # generator.assert_eq("impl SomeTrait for Foo { }");
+    /// ```
+    pub fn impl_for(&mut self, trait_name: impl Into<StringOrIdent>) -> ImplFor<'_, Self> {
+        ImplFor::new(self, self.target_type_name(), Some(trait_name.into()))
     }
 
     /// Generate an `impl <type_name>` block. See [ImplFor] for more information.
@@ -71,7 +227,10 @@ impl Generator {
     /// // impl Foo { }
     /// # generator.assert_eq("impl Foo { }");
     /// ```
-    pub fn impl_for_other_type(&mut self, type_name: impl Into<StringOrIdent>) -> ImplFor<Self> {
+    pub fn impl_for_other_type(
+        &mut self,
+        type_name: impl Into<StringOrIdent>,
+    ) -> ImplFor<'_, Self> {
         ImplFor::new(self, type_name.into(), None)
     }
 
@@ -89,7 +248,7 @@ impl Generator {
         &mut self,
         trait_name: impl Into<StringOrIdent>,
         type_name: impl Into<StringOrIdent>,
-    ) -> ImplFor<Self> {
+    ) -> ImplFor<'_, Self> {
         ImplFor::new(self, type_name.into(), Some(trait_name.into()))
     }
 
@@ -121,35 +280,458 @@ impl Generator {
     /// // impl<'a, 'b> Foo<'b> for Bar<'a> where 'b: 'a { }
     /// # generator.assert_eq("impl < 'b , 'a > Foo < 'b > for Bar < 'a > where 'b : 'a { }");
     /// ```
+    ///
+    /// For the reverse bounds use [`ImplFor::existing_lifetimes_depend_on_new`], and to only add the lifetimes to the impl or to the trait use [`ImplFor::with_lifetime_placement`].
+    ///
+    /// [`ImplFor::existing_lifetimes_depend_on_new`]: struct.ImplFor.html#method.existing_lifetimes_depend_on_new
+    /// [`ImplFor::with_lifetime_placement`]: struct.ImplFor.html#method.with_lifetime_placement
     pub fn impl_for_with_lifetimes<ITER, T>(
         &mut self,
         trait_name: T,
         lifetimes: ITER,
-    ) -> ImplFor<Self>
+    ) -> ImplFor<'_, Self>
     where
         ITER: IntoIterator,
         ITER::Item: Into<String>,
         T: Into<StringOrIdent>,
     {
-        ImplFor::new(self, self.name.clone().into(), Some(trait_name.into()))
+        ImplFor::new(self, self.target_type_name(), Some(trait_name.into()))
             .with_lifetimes(lifetimes)
     }
 
+    /// Generate an `impl <trait_name><..generics> for <target_name>` implementation, where the generic arguments of the trait are generics of the target. See [ImplFor] for more information.
+    ///
+    /// The names are looked up in the generics of the struct or enum, so this returns an error if one of them is not a type or const generic of the target.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let input = "struct Bar<T, U> { t: T, u: U }".parse().unwrap();
+    /// # let (mut generator, _, _) = Parse::new(input).unwrap().into_generator();
+    /// let _ = generator.impl_for_with_generics("Foo", ["U"])?;
+    ///
+    /// // will output:
+    /// // impl<T, U> Foo<U> for Bar<T, U> { }
+    /// # generator.assert_eq("impl < T , U > Foo < U > for Bar < T , U > { }");
+    /// assert!(generator.impl_for_with_generics("Foo", ["V"]).is_err());
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn impl_for_with_generics<ITER, T>(
+        &mut self,
+        trait_name: T,
+        generics: ITER,
+    ) -> crate::Result<ImplFor<'_, Self>>
+    where
+        ITER: IntoIterator,
+        ITER::Item: AsRef<str>,
+        T: Into<StringOrIdent>,
+    {
+        let mut names = Vec::new();
+        for name in generics {
+            names.push(self.target_generic_name(name.as_ref(), None)?);
+        }
+        Ok(
+            ImplFor::new(self, self.target_type_name(), Some(trait_name.into()))
+                .with_trait_generics(names),
+        )
+    }
+
+    /// Same as [`impl_for_with_generics`], but takes the generics as parsed from the input, e.g. from [`Generics::iter_generics`].
+    ///
+    /// [`impl_for_with_generics`]: #method.impl_for_with_generics
+    /// [`Generics::iter_generics`]: ../parse/struct.Generics.html#method.iter_generics
+    pub fn impl_for_with_simple_generics<'g, ITER, T>(
+        &mut self,
+        trait_name: T,
+        generics: ITER,
+    ) -> crate::Result<ImplFor<'_, Self>>
+    where
+        ITER: IntoIterator<Item = &'g SimpleGeneric>,
+        T: Into<StringOrIdent>,
+    {
+        let mut names = Vec::new();
+        for generic in generics {
+            names.push(
+                self.target_generic_name(&generic.ident.to_string(), Some(generic.ident.span()))?,
+            );
+        }
+        Ok(
+            ImplFor::new(self, self.target_type_name(), Some(trait_name.into()))
+                .with_trait_generics(names),
+        )
+    }
+
+    /// Generate an `impl <trait_name><..generic_args> for <target_name>` implementation, where the generic arguments of the trait are any types, e.g. the `Rhs` of `PartialEq<Rhs>` that was given in an attribute like `#[my_eq(other = "OtherType")]`. The arguments can be strings or parsed tokens, see [`TypeTokens`]. The generics of the target are added to the impl as usual.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let input = "struct Foo<T> { t: T }".parse().unwrap();
+    /// # let (mut generator, _, _) = Parse::new(input).unwrap().into_generator();
+    /// let _ = generator.impl_for_parameterized("PartialEq", ["OtherType<T>"])?;
+    ///
+    /// // will output:
+    /// // impl<T> PartialEq<OtherType<T>> for Foo<T> { }
+    /// # generator.assert_eq("impl < T > PartialEq < OtherType < T > > for Foo < T > { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// Returns an error if one of the arguments is not valid rust code.
+    ///
+    /// [`TypeTokens`]: struct.TypeTokens.html
+    pub fn impl_for_parameterized<ITER, T>(
+        &mut self,
+        trait_name: T,
+        generic_args: ITER,
+    ) -> crate::Result<ImplFor<'_, Self>>
+    where
+        ITER: IntoIterator,
+        ITER::Item: Into<TypeTokens>,
+        T: Into<StringOrIdent>,
+    {
+        let mut args = Vec::new();
+        for arg in generic_args {
+            let mut builder = StreamBuilder::new();
+            arg.into().append_to(&mut builder)?;
+            args.push(builder);
+        }
+        Ok(
+            ImplFor::new(self, self.target_type_name(), Some(trait_name.into()))
+                .with_trait_args(args),
+        )
+    }
+
+    fn target_generic_name(&self, name: &str, span: Option<Span>) -> crate::Result<String> {
+        let generic = self.generics.as_ref().and_then(|generics| {
+            generics
+                .iter()
+                .find(|generic| !generic.is_lifetime() && ident_eq(generic.ident(), name))
+        });
+        match generic {
+            Some(generic) => Ok(generic.ident().to_string()),
+            None => Err(Error::Custom {
+                error: format!("`{}` is not a generic of `{}`", name, self.name),
+                span,
+            }),
+        }
+    }
+
     /// Generate a struct with the given name. See [`GenStruct`] for more info.
-    pub fn generate_struct(&mut self, name: impl Into<String>) -> GenStruct<Self> {
+    pub fn generate_struct(&mut self, name: impl Into<String>) -> GenStruct<'_, Self> {
         GenStruct::new(self, name)
     }
 
     /// Generate an enum with the given name. See [`GenEnum`] for more info.
-    pub fn generate_enum(&mut self, name: impl Into<String>) -> GenEnum<Self> {
+    pub fn generate_enum(&mut self, name: impl Into<String>) -> GenEnum<'_, Self> {
         GenEnum::new(self, name)
     }
 
     /// Generate a `mod <name> { ... }`. See [`GenerateMod`] for more info.
-    pub fn generate_mod(&mut self, mod_name: impl Into<String>) -> GenerateMod<Self> {
+    pub fn generate_mod(&mut self, mod_name: impl Into<String>) -> GenerateMod<'_, Self> {
         GenerateMod::new(self, mod_name)
     }
 
+    /// Generate an `extern "<abi>" { ... }` block. See [`GenExtern`] for more info.
+    pub fn generate_extern_block(&mut self, abi: impl Into<String>) -> GenExtern<'_, Self> {
+        GenExtern::new(self, abi)
+    }
+
+    /// Generate a free function, e.g. a `#[no_mangle] pub extern "C" fn` shim. See [`FnBuilder`] for more info.
+    ///
+    /// ```
+    /// # use virtue::prelude::Generator;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator
+    ///     .generate_fn("foo_new")
+    ///     .make_pub()
+    ///     .no_mangle()
+    ///     .with_extern_abi("C")
+    ///     .with_return_type("*mut Foo")
+    ///     .body(|b| {
+    ///         b.push_parsed("Box::into_raw(Box::new(Foo::default()))")?;
+    ///         Ok(())
+    ///     })?;
+    /// # generator.assert_eq("# [no_mangle] pub extern \"C\" fn foo_new () ->* mut Foo { Box :: into_raw (Box :: new (Foo :: default ())) }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn generate_fn(&mut self, name: impl Into<String>) -> FnBuilder<'_, Self> {
+        FnBuilder::new(self, name)
+    }
+
+    /// Allow the given lints on every top-level item that this generator generates, e.g. impls, mods, structs and functions.
+    ///
+    /// This should be called before generating any items, as items that are already generated will not be changed.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator.suppress_lints(["dead_code", "clippy::needless_lifetimes"])?;
+    /// generator.generate_struct("FooHelper");
+    /// generator.r#impl();
+    ///
+    /// // will output:
+    /// // #[allow(dead_code, clippy::needless_lifetimes)]
+    /// // struct FooHelper { }
+    /// // #[allow(dead_code, clippy::needless_lifetimes)]
+    /// // impl Foo { }
+    /// # generator.assert_eq("# [allow (dead_code , clippy :: needless_lifetimes)] struct FooHelper { } \
+    /// #     # [allow (dead_code , clippy :: needless_lifetimes)] impl Foo { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn suppress_lints<ITER>(&mut self, lints: ITER) -> crate::Result<&mut Self>
+    where
+        ITER: IntoIterator,
+        ITER::Item: AsRef<str>,
+    {
+        for lint in lints {
+            let lint = lint.as_ref();
+            // make sure the lint can be parsed, so `append_item` does not fail later on
+            StreamBuilder::new().push_parsed(lint)?;
+            if !self.suppressed_lints.iter().any(|l| l == lint) {
+                self.suppressed_lints.push(lint.to_owned());
+            }
+        }
+        Ok(self)
+    }
+
+    /// Set whether generated impl blocks get the `#[automatically_derived]` attribute. Defaults to `false`.
+    ///
+    /// This can be overwritten for a single impl with [`ImplFor::set_automatically_derived`] and [`Impl::set_automatically_derived`].
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator.set_automatically_derived(true);
+    /// generator.impl_for("Bar");
+    /// generator.impl_for("Baz").set_automatically_derived(false);
+    ///
+    /// // will output:
+    /// // #[automatically_derived]
+    /// // impl Bar for Foo { }
+    /// // impl Baz for Foo { }
+    /// # generator.assert_eq("# [automatically_derived] impl Bar for Foo { } impl Baz for Foo { }");
+    /// ```
+    ///
+    /// [`ImplFor::set_automatically_derived`]: struct.ImplFor.html#method.set_automatically_derived
+    /// [`Impl::set_automatically_derived`]: struct.Impl.html#method.set_automatically_derived
+    pub fn set_automatically_derived(&mut self, automatically_derived: bool) -> &mut Self {
+        self.automatically_derived = automatically_derived;
+        self
+    }
+
+    /// Generate code for a `#![no_std]` crate. Paths to items of `alloc` like `Box` or `String` go through [`alloc_path`], which is `::alloc` instead of `::std` when this is set. The crate that the code is generated in needs an `extern crate alloc;` for this.
+    ///
+    /// The code that virtue generates itself, e.g. the traits implemented by [`generate_debug_impl`] or the builders of the `patterns` feature, only references items of `core` through `::core`, which works with and without `std`.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator.no_std(true);
+    /// let alloc = generator.alloc_path();
+    /// generator
+    ///     .r#impl()
+    ///     .generate_fn("boxed")
+    ///     .with_self_arg(FnSelfArg::TakeSelf)
+    ///     .with_return_type(format!("{}::boxed::Box<Self>", alloc))
+    ///     .body(|b| {
+    ///         b.push_parsed(format!("{}::boxed::Box::new(self)", alloc))?;
+    ///         Ok(())
+    ///     })?;
+    /// # generator.assert_eq("impl Foo { fn boxed (self ,) ->:: alloc :: boxed :: Box < Self > { :: alloc :: boxed :: Box :: new (self) } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`alloc_path`]: #method.alloc_path
+    /// [`generate_debug_impl`]: #method.generate_debug_impl
+    pub fn no_std(&mut self, no_std: bool) -> &mut Self {
+        self.no_std = no_std;
+        self
+    }
+
+    /// Returns `true` if code is generated for a `#![no_std]` crate, see [`no_std`].
+    ///
+    /// [`no_std`]: #method.no_std
+    pub fn is_no_std(&self) -> bool {
+        self.no_std
+    }
+
+    /// The path of the crate that contains `Box`, `String` and `Vec`: `::alloc` if [`no_std`] is set, `::std` otherwise. See also [`StreamBuilder::alloc_path`].
+    ///
+    /// [`no_std`]: #method.no_std
+    /// [`StreamBuilder::alloc_path`]: struct.StreamBuilder.html#method.alloc_path
+    pub fn alloc_path(&self) -> &'static str {
+        StreamBuilder::alloc_path(self.no_std)
+    }
+
+    /// Append a top-level item to the stream, with the suppressed lints.
+    fn append_item(&mut self, item: StreamBuilder) -> crate::Result {
+        let item = self.with_suppressed_lints(item)?;
+        self.stream.append(item);
+        Ok(())
+    }
+
+    /// Insert an item at the given [`Position`]. The item can be a [`StreamBuilder`] or a [`TokenStream`].
+    ///
+    /// Builders like [`impl_for`] add their item when they are dropped, so the order of the output depends on the order in which the builders go out of scope. This can be used to e.g. put a helper type before the impls that use it.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use virtue::generate::Position;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator.impl_for("Bar");
+    /// let mut helper = StreamBuilder::new();
+    /// helper.push_parsed("struct FooHelper;")?;
+    /// generator.insert_at(Position::Start, helper)?;
+    ///
+    /// // will output:
+    /// // struct FooHelper;
+    /// // impl Bar for Foo { }
+    /// # generator.assert_eq("struct FooHelper ; impl Bar for Foo { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`impl_for`]: #method.impl_for
+    pub fn insert_at(
+        &mut self,
+        position: Position,
+        item: impl Into<TokenStream>,
+    ) -> crate::Result<&mut Self> {
+        let mut item_builder = StreamBuilder::new();
+        item_builder.extend(item.into());
+        match position {
+            Position::Start => {
+                let mut builder = self.with_suppressed_lints(item_builder)?;
+                builder.append(std::mem::take(&mut self.stream));
+                self.stream = builder;
+            }
+            Position::End => self.append_item(item_builder)?,
+        }
+        Ok(self)
+    }
+
+    /// All items that are generated in `f` are emitted after every other item, regardless of the order in which they were generated.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator.defer(|generator| {
+    ///     generator.impl_for("Last");
+    ///     Ok(())
+    /// })?;
+    /// generator.impl_for("First");
+    ///
+    /// // will output:
+    /// // impl First for Foo { }
+    /// // impl Last for Foo { }
+    /// # generator.assert_eq("impl First for Foo { } impl Last for Foo { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn defer<F>(&mut self, f: F) -> crate::Result<&mut Self>
+    where
+        F: FnOnce(&mut Self) -> crate::Result,
+    {
+        let stream = std::mem::take(&mut self.stream);
+        let result = f(self);
+        let mut deferred = std::mem::replace(&mut self.stream, stream);
+        // keep the provenance in the order that it was generated in
+        self.stream.provenance.append(&mut deferred.provenance);
+        self.deferred.append(deferred);
+        result?;
+        Ok(self)
+    }
+
+    /// The generated code so far, in the order of [`finish`].
+    ///
+    /// [`finish`]: #method.finish
+    fn output(&self) -> TokenStream {
+        let mut stream = TokenStream::new();
+        for r#use in &self.uses {
+            stream.extend(r#use.stream.clone());
+        }
+        stream.extend(self.stream.stream.clone());
+        stream.extend(self.deferred.stream.clone());
+        stream
+    }
+
+    /// Prefix `item` with an `#[allow(...)]` of the suppressed lints, if any.
+    fn with_suppressed_lints(&self, item: StreamBuilder) -> crate::Result<StreamBuilder> {
+        let mut builder = StreamBuilder::new();
+        if !self.suppressed_lints.is_empty() {
+            let lints = self.suppressed_lints.join(", ");
+            builder.punct('#').group(Delimiter::Bracket, |builder| {
+                builder
+                    .ident_str("allow")
+                    .group(Delimiter::Parenthesis, |builder| {
+                        builder.push_parsed(&lints)?;
+                        Ok(())
+                    })?;
+                Ok(())
+            })?;
+        }
+        builder.append(item);
+        Ok(builder)
+    }
+
+    /// Add a top-level `use ...;`. The use statements are emitted by [`finish`] before all other generated items.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator.add_use("std::fmt::Write")?;
+    /// generator.r#impl();
+    ///
+    /// // will output:
+    /// // use std::fmt::Write;
+    /// // impl Foo { }
+    /// # assert_eq!(generator.finish()?.to_string(), "use std :: fmt :: Write ; impl Foo { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`finish`]: #method.finish
+    pub fn add_use(&mut self, r#use: impl AsRef<str>) -> crate::Result {
+        let mut builder = StreamBuilder::new();
+        builder.ident_str("use").push_parsed(r#use)?.punct(';');
+        let builder = self.with_suppressed_lints(builder)?;
+        self.uses.push(builder);
+        Ok(())
+    }
+
+    /// Add a top-level `pub use ...;`, see [`add_use`].
+    ///
+    /// This is useful to re-export types that are generated in a hidden module next to the target type:
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator.add_pub_use("__foo_builder::FooBuilder")?;
+    /// generator
+    ///     .generate_mod("__foo_builder")
+    ///     .generate_struct("FooBuilder")
+    ///     .make_pub();
+    ///
+    /// // will output:
+    /// // pub use __foo_builder::FooBuilder;
+    /// // mod __foo_builder {
+    /// //     pub struct FooBuilder { }
+    /// // }
+    /// # assert_eq!(
+    /// #     generator.finish()?.to_string(),
+    /// #     "pub use __foo_builder :: FooBuilder ; mod __foo_builder { pub struct FooBuilder { } }"
+    /// # );
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`add_use`]: #method.add_use
+    pub fn add_pub_use(&mut self, r#use: impl AsRef<str>) -> crate::Result {
+        let mut builder = StreamBuilder::new();
+        builder
+            .ident_str("pub")
+            .ident_str("use")
+            .push_parsed(r#use)?
+            .punct(';');
+        let builder = self.with_suppressed_lints(builder)?;
+        self.uses.push(builder);
+        Ok(())
+    }
+
     /// Export the current stream to a file, making it very easy to debug the output of a derive macro.
     /// This will try to find rust's `target` directory, and write `target/generated/<crate_name>/<name>_<file_postfix>.rs`.
     ///
@@ -157,6 +739,30 @@ impl Generator {
     ///
     /// The outputted file is unformatted. Use `cargo fmt -- target/generated/<crate_name>/<file>.rs` to format the file.
     pub fn export_to_file(&self, crate_name: &str, file_postfix: &str) -> bool {
+        let file_name = format!("{}_{}.rs", self.target_name(), file_postfix);
+        self.write_generated_file(crate_name, &file_name, self.output().to_string())
+    }
+
+    /// The provenance that was recorded with [`StreamBuilder::provenance`] so far, in the order that the tokens were generated.
+    ///
+    /// [`StreamBuilder::provenance`]: struct.StreamBuilder.html#method.provenance
+    pub fn provenance(&self) -> &[Provenance] {
+        &self.stream.provenance
+    }
+
+    /// Export the [`provenance`] to a file next to the file of [`export_to_file`]: `target/generated/<crate_name>/<name>_<file_postfix>.provenance.txt`. Every entry lists the label and span of the input item, followed by the tokens that were generated for it.
+    ///
+    /// Will return `true` if the file is written, `false` otherwise.
+    ///
+    /// [`provenance`]: #method.provenance
+    /// [`export_to_file`]: #method.export_to_file
+    pub fn export_provenance_to_file(&self, crate_name: &str, file_postfix: &str) -> bool {
+        let file_name = format!("{}_{}.provenance.txt", self.target_name(), file_postfix);
+        let contents: Vec<String> = self.provenance().iter().map(|p| p.to_string()).collect();
+        self.write_generated_file(crate_name, &file_name, contents.join("\n"))
+    }
+
+    fn write_generated_file(&self, crate_name: &str, file_name: &str, contents: String) -> bool {
         use std::io::Write;
 
         if let Ok(var) = std::env::var("CARGO_MANIFEST_DIR") {
@@ -171,9 +777,9 @@ impl Generator {
                         if std::fs::create_dir_all(&path).is_err() {
                             return false;
                         }
-                        path.push(format!("{}_{}.rs", self.target_name(), file_postfix));
+                        path.push(file_name);
                         if let Ok(mut file) = std::fs::File::create(path) {
-                            let _ = file.write_all(self.stream.stream.to_string().as_bytes());
+                            let _ = file.write_all(contents.as_bytes());
                             return true;
                         }
                     }
@@ -188,9 +794,237 @@ impl Generator {
         false
     }
 
+    /// Register a hook that is called with [`GenerationStats`] about the generated code when [`finish`] is called. This can be used to keep track of how much code a derive generates over time.
+    ///
+    /// The statistics are only computed if a hook is registered, or if the [`STATS_ENV_VAR`] (`VIRTUE_STATS`) environment variable is set. In the latter case a summary is printed to stderr.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator.on_finish(|stats| {
+    ///     assert_eq!(stats.impl_count, 1);
+    ///     assert_eq!(stats.fn_count, 1);
+    ///     assert_eq!(stats.items[0].header, "impl Bar for Foo");
+    /// });
+    /// generator
+    ///     .impl_for("Bar")
+    ///     .generate_fn("bar")
+    ///     .body(|_| Ok(()))?;
+    /// generator.finish()?;
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`finish`]: #method.finish
+    /// [`STATS_ENV_VAR`]: constant.STATS_ENV_VAR.html
+    pub fn on_finish(&mut self, hook: impl FnOnce(&GenerationStats) + 'static) -> &mut Self {
+        self.on_finish.push(Box::new(hook));
+        self
+    }
+
+    /// Register a pass over the generated code that is run by [`finish`], e.g. to prefix paths, wrap the code in a `#[cfg(...)]` or remove duplicate attributes. This allows rewrites that affect all generated code, without every builder having to know about them.
+    ///
+    /// The passes are run in the order they were added. The warnings of [`warn`] are added after all passes, and the code that is compared by [`assert_eq`] is the code before the passes.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator.impl_for("Bar");
+    /// generator.add_post_process(|stream| {
+    ///     let mut wrapped: TokenStream = "#[cfg(feature = \"bar\")] const _: () =".parse().unwrap();
+    ///     wrapped.extend([
+    ///         TokenTree::Group(Group::new(Delimiter::Brace, stream)),
+    ///         TokenTree::Punct(Punct::new(';', Spacing::Alone)),
+    ///     ]);
+    ///     Ok(wrapped)
+    /// });
+    /// let stream = generator.finish()?;
+    /// assert_eq!(
+    ///     stream.to_string(),
+    ///     "# [cfg (feature = \"bar\")] const _ : () = { impl Bar for Foo { } } ;"
+    /// );
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`finish`]: #method.finish
+    /// [`warn`]: #method.warn
+    /// [`assert_eq`]: #method.assert_eq
+    pub fn add_post_process(
+        &mut self,
+        pass: impl FnOnce(TokenStream) -> crate::Result<TokenStream> + 'static,
+    ) -> &mut Self {
+        self.post_process.push(Box::new(pass));
+        self
+    }
+
+    /// Replace every absolute path to the crate `crate_name` in the generated code with `path`, e.g. to support a `#[bincode(crate = "my_crate::bincode")]` attribute for users that only depend on a crate that re-exports `bincode`.
+    ///
+    /// The code can be generated with paths like `::bincode::Encode`, which will become `my_crate::bincode::Encode`. Paths that only end in `crate_name`, like `crate::bincode`, are not replaced. This is a pass of [`add_post_process`], so it also rewrites the code of the passes that were added before it.
+    ///
+    /// Use [`set_crate_path_tokens`] for a [`CratePath`] that was parsed from the attribute with [`parse_crate_attribute`].
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator.set_crate_path("bincode", "::my_crate::bincode")?;
+    /// generator
+    ///     .impl_for("::bincode::Encode")
+    ///     .generate_fn("encode")
+    ///     .with_return_type("::bincode::Result")
+    ///     .body(|b| {
+    ///         b.push_parsed("::bincode::ok()")?;
+    ///         Ok(())
+    ///     })?;
+    /// let stream = generator.finish()?;
+    /// assert_eq!(
+    ///     stream.to_string(),
+    ///     "impl :: my_crate :: bincode :: Encode for Foo { \
+    ///      fn encode () ->:: my_crate :: bincode :: Result { :: my_crate :: bincode :: ok () } }"
+    /// );
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`add_post_process`]: #method.add_post_process
+    /// [`set_crate_path_tokens`]: #method.set_crate_path_tokens
+    /// [`CratePath`]: ../utils/struct.CratePath.html
+    /// [`parse_crate_attribute`]: ../utils/fn.parse_crate_attribute.html
+    pub fn set_crate_path(
+        &mut self,
+        crate_name: impl Into<String>,
+        path: impl AsRef<str>,
+    ) -> crate::Result<&mut Self> {
+        let crate_name = crate_name.into();
+        let path = CratePath::parse(&crate_name, path.as_ref())?;
+        Ok(self.set_crate_path_tokens(crate_name, path))
+    }
+
+    /// Like [`set_crate_path`], but with a [`CratePath`] that was parsed from a `#[prefix(crate = "...")]` attribute with [`parse_crate_attribute`].
+    ///
+    /// [`set_crate_path`]: #method.set_crate_path
+    /// [`CratePath`]: ../utils/struct.CratePath.html
+    /// [`parse_crate_attribute`]: ../utils/fn.parse_crate_attribute.html
+    pub fn set_crate_path_tokens(
+        &mut self,
+        crate_name: impl Into<String>,
+        path: CratePath,
+    ) -> &mut Self {
+        let crate_name = crate_name.into();
+        let path = path.into_tokens();
+        self.add_post_process(move |stream| {
+            Ok(super::post_process::rewrite_crate_path(
+                stream,
+                &crate_name,
+                &path,
+            ))
+        })
+    }
+
+    /// Emit a warning at the given span, e.g. for a deprecated attribute key. Unlike an [`Error`], a warning does not stop the compilation.
+    ///
+    /// Stable rust does not allow proc macros to emit warnings, so the warning is emitted as the use of a `#[deprecated(note = "<message>")]` constant. rustc will show it as `use of deprecated constant ...: <message>`. With the `nightly` feature, the warning is emitted with the unstable `proc_macro::Diagnostic` API instead, which shows just the message.
+    ///
+    /// The warnings are emitted by [`finish`]. Use [`finish_with_diagnostics`] to also get a list of them, e.g. for testing.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator.warn("`#[foo(old)]` is deprecated, use `#[foo(new)]` instead", Span::call_site());
+    /// let (_stream, warnings) = generator.finish_with_diagnostics()?;
+    /// assert_eq!(warnings.len(), 1);
+    /// assert_eq!(warnings[0].message, "`#[foo(old)]` is deprecated, use `#[foo(new)]` instead");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`Error`]: ../enum.Error.html
+    /// [`finish`]: #method.finish
+    /// [`finish_with_diagnostics`]: #method.finish_with_diagnostics
+    pub fn warn(&mut self, message: impl Into<String>, span: Span) -> &mut Self {
+        self.warnings.push(Warning::new(message.into(), span));
+        self
+    }
+
     /// Consume the contents of this generator. This *must* be called, or else the generator will panic on drop.
-    pub fn finish(mut self) -> crate::prelude::Result<TokenStream> {
-        Ok(std::mem::take(&mut self.stream).stream)
+    pub fn finish(self) -> crate::prelude::Result<TokenStream> {
+        self.finish_with(TokenStream::new())
+    }
+
+    /// Consume the contents of this generator like [`finish`], and add them to the end of `stream`.
+    ///
+    /// The generated items are moved into `stream` instead of being collected in a new [`TokenStream`] first, which avoids a copy of all generated code for large items.
+    ///
+    /// [`finish`]: #method.finish
+    pub fn finish_into(self, stream: &mut TokenStream) -> crate::prelude::Result {
+        self.finish_into_with_diagnostics(stream).map(|_| ())
+    }
+
+    /// Consume the contents of this generator like [`finish`], and return them after `original`.
+    ///
+    /// This is useful for attribute macros, which have to return the original item together with the generated code:
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// let item = TokenStream::from_str("struct Foo;").unwrap();
+    /// let (mut generator, _, _) = Parse::new(item.clone())?.into_generator();
+    /// generator.r#impl();
+    /// let stream = generator.finish_with(item)?;
+    /// assert_eq!(stream.to_string(), "struct Foo ; impl Foo { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`finish`]: #method.finish
+    pub fn finish_with(self, original: TokenStream) -> crate::prelude::Result<TokenStream> {
+        let mut stream = original;
+        self.finish_into(&mut stream)?;
+        Ok(stream)
+    }
+
+    /// Same as [`finish`], but also returns the warnings that were emitted with [`warn`].
+    ///
+    /// [`finish`]: #method.finish
+    /// [`warn`]: #method.warn
+    pub fn finish_with_diagnostics(self) -> crate::prelude::Result<(TokenStream, Vec<Warning>)> {
+        let mut stream = TokenStream::new();
+        let warnings = self.finish_into_with_diagnostics(&mut stream)?;
+        Ok((stream, warnings))
+    }
+
+    fn finish_into_with_diagnostics(
+        mut self,
+        stream: &mut TokenStream,
+    ) -> crate::prelude::Result<Vec<Warning>> {
+        let print_summary = std::env::var_os(STATS_ENV_VAR).is_some();
+        if print_summary || !self.on_finish.is_empty() {
+            let stats = GenerationStats::new(self.target_name().to_string(), &self.output());
+            if print_summary {
+                stats.print_summary();
+            }
+            for hook in std::mem::take(&mut self.on_finish) {
+                hook(&stats);
+            }
+        }
+        if self.post_process.is_empty() {
+            for r#use in std::mem::take(&mut self.uses) {
+                stream.extend(r#use.stream);
+            }
+            stream.extend(std::mem::take(&mut self.stream).stream);
+            stream.extend(std::mem::take(&mut self.deferred).stream);
+        } else {
+            let mut generated = TokenStream::new();
+            for r#use in std::mem::take(&mut self.uses) {
+                generated.extend(r#use.stream);
+            }
+            generated.extend(std::mem::take(&mut self.stream).stream);
+            generated.extend(std::mem::take(&mut self.deferred).stream);
+            for pass in std::mem::take(&mut self.post_process) {
+                generated = pass(generated)?;
+            }
+            stream.extend(generated);
+        }
+        let warnings = std::mem::take(&mut self.warnings);
+        for warning in &warnings {
+            warning.emit(stream);
+        }
+        Ok(warnings)
     }
 }
 
@@ -216,13 +1050,41 @@ impl Generator {
     }
     /// Assert that the generated code in this generator matches the given string. This is useful for testing purposes in combination with the `with_name` function.
     pub fn assert_eq(&self, expected: &str) {
-        assert_eq!(expected, self.stream.stream.to_string());
+        let output = self.output();
+        if expected != output.to_string() {
+            if let Some(report) = crate::utils::token_diff(expected, &output) {
+                panic!("{}", report);
+            }
+        }
+        assert_eq!(expected, output.to_string());
     }
+
+    /// Assert that the generated code in this generator matches the given tokens, e.g. built with [`quote_like!`]. Whitespace and the spacing of punctuation are ignored, so `Vec<T>::new` matches the `Vec < T >:: new` that the generator emits.
+    ///
+    /// [`quote_like!`]: ../macro.quote_like.html
+    pub fn assert_eq_tokens(&self, expected: TokenStream) {
+        let output = self.output();
+        if let Some(report) = crate::utils::token_diff(&expected.to_string(), &output) {
+            panic!("{}", report);
+        }
+    }
+}
+
+/// Returns `true` if `stream` only contains idents and `:`, e.g. `crate::module` or `::my_crate`
+fn is_path(stream: &TokenStream) -> bool {
+    stream.clone().into_iter().all(|token| match token {
+        TokenTree::Ident(_) => true,
+        TokenTree::Punct(punct) => punct.as_char() == ':',
+        _ => false,
+    })
 }
 
 impl Drop for Generator {
     fn drop(&mut self) {
-        if !self.stream.stream.is_empty() && !std::thread::panicking() {
+        let is_empty = self.uses.is_empty()
+            && self.stream.stream.is_empty()
+            && self.deferred.stream.is_empty();
+        if !is_empty && !std::thread::panicking() {
             eprintln!("WARNING: Generator dropped but the stream is not empty. Please call `.finish()` on the generator");
         }
     }
@@ -230,7 +1092,8 @@ impl Drop for Generator {
 
 impl super::Parent for Generator {
     fn append(&mut self, builder: StreamBuilder) {
-        self.stream.append(builder);
+        self.append_item(builder)
+            .expect("Suppressed lints are validated in `suppress_lints`");
     }
 
     fn name(&self) -> &Ident {
@@ -244,6 +1107,25 @@ impl super::Parent for Generator {
     fn generic_constraints(&self) -> Option<&GenericConstraints> {
         self.generic_constraints.as_ref()
     }
+
+    fn automatically_derived(&self) -> bool {
+        self.automatically_derived
+    }
+}
+
+impl FnParent for Generator {
+    fn append(&mut self, fn_definition: StreamBuilder, fn_body: StreamBuilder) -> crate::Result {
+        let mut builder = fn_definition;
+        builder.group(Delimiter::Brace, |body| {
+            *body = fn_body;
+            Ok(())
+        })?;
+        self.append_item(builder)
+    }
+
+    fn undeclared_target_generics(&self) -> Option<&Generics> {
+        self.generics.as_ref()
+    }
 }
 
 #[cfg(test)]
@@ -309,4 +1191,336 @@ mod test {
             .collect::<String>()
         );
     }
+
+    #[test]
+    fn impl_for_ref_self() {
+        let mut generator = Generator::new(
+            Ident::new("StructOrEnum", Span::call_site()),
+            Generics::try_take(&mut token_stream("<'alpha, T: Clone>")).unwrap(),
+            None,
+        );
+        let _ = generator.impl_for("Foo").for_ref_self("a");
+        let _ = generator
+            .impl_for_with_lifetimes("Bar", ["de"])
+            .for_ref_self("a");
+        let output = generator.finish().unwrap();
+        assert_eq!(
+            output
+                .into_iter()
+                .map(|v| v.to_string())
+                .collect::<String>(),
+            token_stream(
+                "impl<'a, 'alpha, T: Clone> Foo for &'a StructOrEnum<'alpha, T> { }
+                 impl<'de, 'a, 'alpha, T: Clone> Bar<'de> for &'a StructOrEnum<'alpha, T> { }"
+            )
+            .map(|v| v.to_string())
+            .collect::<String>()
+        );
+    }
+
+    #[test]
+    fn impl_attributes() {
+        let mut generator =
+            Generator::new(Ident::new("StructOrEnum", Span::call_site()), None, None);
+        {
+            let mut impl_for = generator.impl_for("Foo");
+            impl_for.impl_outer_attr("cfg(test)").unwrap();
+            impl_for.impl_inner_attr("allow(unused)").unwrap();
+        }
+        {
+            let mut r#impl = generator.r#impl();
+            r#impl.attr("cfg").unwrap().args("test").unwrap();
+            r#impl.inner_attr("allow").unwrap().args("unused").unwrap();
+        }
+        let output = generator.finish().unwrap();
+        assert_eq!(
+            output.to_string(),
+            "# [cfg (test)] impl Foo for StructOrEnum { # ! [allow (unused)] } \
+             # [cfg (test)] impl StructOrEnum { # ! [allow (unused)] }"
+        );
+    }
+
+    #[test]
+    fn fn_with_target_lifetimes() {
+        let mut generator = Generator::new(
+            Ident::new("StructOrEnum", Span::call_site()),
+            Generics::try_take(&mut token_stream("<'a: 'b, 'b, T>")).unwrap(),
+            None,
+        );
+        generator
+            .generate_fn("free")
+            .with_target_lifetimes()
+            .with_generic("U")
+            .body(|_| Ok(()))
+            .unwrap();
+        // inside of an impl block the lifetimes are already declared
+        generator
+            .r#impl()
+            .generate_fn("method")
+            .with_target_lifetimes()
+            .body(|_| Ok(()))
+            .unwrap();
+        let output = generator.finish().unwrap();
+        assert_eq!(
+            output.to_string(),
+            "fn free < 'a : 'b , 'b , U > () { } \
+             impl < 'a : 'b , 'b , T > StructOrEnum < 'a , 'b , T > { fn method () { } }"
+        );
+    }
+
+    #[test]
+    fn impl_for_with_simple_generics() {
+        let generics = Generics::try_take(&mut token_stream("<'a, T, const N: usize>"))
+            .unwrap()
+            .unwrap();
+        let mut generator = Generator::new(
+            Ident::new("StructOrEnum", Span::call_site()),
+            Some(generics.clone()),
+            None,
+        );
+        let _ = generator
+            .impl_for_with_simple_generics("Foo", generics.iter_generics())
+            .unwrap();
+        let _ = generator.impl_for_with_generics("Bar", ["N", "T"]).unwrap();
+        // lifetimes are not type or const generics
+        assert!(generator.impl_for_with_generics("Baz", ["a"]).is_err());
+        let output = generator.finish().unwrap();
+        assert_eq!(
+            output.to_string(),
+            "impl < 'a , T , const N : usize > Foo < T > for StructOrEnum < 'a , T , N > { } \
+             impl < 'a , T , const N : usize > Bar < N , T > for StructOrEnum < 'a , T , N > { }"
+        );
+    }
+
+    #[test]
+    fn finish_with_diagnostics() {
+        let mut generator = Generator::with_name("StructOrEnum");
+        let _ = generator.impl_for("Foo");
+        generator.warn("`old` is deprecated", Span::call_site());
+        let (output, warnings) = generator.finish_with_diagnostics().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            output.to_string(),
+            "impl Foo for StructOrEnum { } \
+             const _ : () = { # [deprecated (note = \"`old` is deprecated\")] const WARNING : () = () ; WARNING } ;"
+        );
+    }
+
+    #[test]
+    fn set_target_path() {
+        let generics = Generics::try_take(&mut token_stream("<T>")).unwrap();
+        let mut generator = Generator::new(
+            Ident::new("StructOrEnum", Span::call_site()),
+            generics,
+            None,
+        );
+        generator.set_target_path("super::inner::").unwrap();
+        assert_eq!(
+            generator.self_type(),
+            "super :: inner :: StructOrEnum < T >"
+        );
+        let _ = generator.r#impl();
+        let _ = generator.impl_for_with_lifetimes("Foo", ["a"]);
+        assert_eq!(
+            generator.finish().unwrap().to_string(),
+            "impl < T > super :: inner :: StructOrEnum < T > { } \
+             impl < 'a , T > Foo < 'a > for super :: inner :: StructOrEnum < T > { }"
+        );
+
+        let mut generator = Generator::with_name("StructOrEnum");
+        assert!(generator.set_target_path("").is_err());
+        assert!(generator.set_target_path("a::<T>").is_err());
+        assert_eq!(generator.self_type(), "StructOrEnum");
+    }
+
+    #[test]
+    fn finish_into() {
+        let mut stream = token_stream("struct Foo ;").collect::<TokenStream>();
+        let mut generator = Generator::with_name("Foo");
+        generator.add_use("core::fmt").unwrap();
+        let _ = generator.r#impl();
+        generator.warn("old", Span::call_site());
+        generator.finish_into(&mut stream).unwrap();
+        let expected = "struct Foo ; use core :: fmt ; impl Foo { }";
+        assert!(stream.to_string().starts_with(expected));
+        assert!(stream.to_string().contains("deprecated"));
+
+        let mut stream = token_stream("struct Foo ;").collect::<TokenStream>();
+        crate::Error::custom("a")
+            .with_help("b")
+            .append_to_token_stream(&mut stream);
+        assert_eq!(
+            stream.to_string(),
+            "struct Foo ; compile_error ! { \"a\" } compile_error ! { \"help: b\" }"
+        );
+    }
+
+    #[test]
+    fn span_impl_header_at_target() {
+        use crate::parse::Parse;
+
+        let input = token_stream("\n\n    struct Foo<T> where T: Clone { t: T }");
+        let (mut generator, _, _) = Parse::new(input.collect()).unwrap().into_generator();
+        let _ = generator.impl_for("Trait").span_impl_header_at_target(true);
+        let _ = generator.r#impl().span_impl_header_at_target(true);
+        let _ = generator.impl_for("Default");
+        let stream = generator.finish().unwrap();
+        assert_eq!(
+            stream.to_string(),
+            "impl < T > Trait for Foo < T > where T : Clone { } \
+             impl < T > Foo < T > where T : Clone { } \
+             impl < T > Default for Foo < T > where T : Clone { }"
+        );
+
+        let positions: Vec<_> = stream
+            .into_iter()
+            .map(|token| {
+                let start = token.span().start();
+                (start.line, start.column)
+            })
+            .collect();
+        // `impl < T > Trait for Foo` has the span of `Foo`, the `< T >` after it the span of the generics
+        assert_eq!(&positions[..7], &[(3, 11); 7]);
+        assert_eq!(&positions[7..10], &[(3, 15); 3]);
+        // the where clause keeps its spans
+        assert_eq!(&positions[11..14], &[(3, 24), (3, 25), (3, 27)]);
+        assert_eq!(&positions[15..20], &[(3, 11); 5]);
+        assert_eq!(&positions[20..23], &[(3, 15); 3]);
+        // by default the `impl` has the call_site span
+        assert_ne!(positions[28], (3, 11));
+    }
+
+    #[test]
+    fn post_process() {
+        let mut generator = Generator::with_name("Foo");
+        generator.set_crate_path("bar", "::baz::bar").unwrap();
+        generator.add_post_process(|stream| {
+            let mut builder = StreamBuilder::new();
+            builder.push_parsed("use ::bar::A;").unwrap();
+            builder.extend(stream);
+            Ok(builder.stream)
+        });
+        let _ = generator.impl_for("::bar::Bar");
+        generator.warn("old", Span::call_site());
+        let stream = generator.finish().unwrap().to_string();
+        // passes that were added after `set_crate_path` are not rewritten
+        assert!(stream.starts_with("use :: bar :: A ; impl :: baz :: bar :: Bar for Foo { }"));
+        assert!(stream.contains("deprecated"));
+
+        let mut generator = Generator::with_name("Foo");
+        assert!(generator.set_crate_path("bar", "").is_err());
+        assert!(generator.set_crate_path("bar", "baz::<T>").is_err());
+        generator.add_post_process(|_| Err(crate::Error::custom("failed")));
+        let _ = generator.r#impl();
+        assert!(generator.finish().is_err());
+    }
+
+    #[test]
+    fn build_impl() {
+        let mut generator = Generator::with_name("Foo").with_lifetime("a");
+        let mut r#impl = generator.r#impl();
+        r#impl.attr("inline").unwrap();
+        let block = r#impl.build();
+        assert_eq!(
+            block.stream.to_string(),
+            "# [inline] impl < 'a > Foo < 'a > { }"
+        );
+        let block = generator.impl_for("Bar").build();
+        assert_eq!(
+            block.stream.to_string(),
+            "impl < 'a > Bar for Foo < 'a > { }"
+        );
+        // the built blocks are not emitted
+        generator.assert_eq("");
+    }
+
+    #[test]
+    fn impl_for_parameterized() {
+        let mut generator = Generator::with_name("Foo").with_lifetime("a");
+        let _ = generator
+            .impl_for_parameterized("PartialEq", ["&'a str"])
+            .unwrap();
+        let _ = generator
+            .impl_for_parameterized("Add", ["Self", "u8"])
+            .unwrap()
+            .with_trait_generics(["T"]);
+        let _ = generator
+            .impl_for("Mul")
+            .with_trait_generics(["T"])
+            .with_trait_args(vec![StreamBuilder::new().ident_str("Self").clone()])
+            .with_trait_generics(["U"]);
+        generator.assert_eq(
+            "impl < 'a > PartialEq < &'a str > for Foo < 'a > { } \
+             impl < 'a > Add < Self , u8 , T > for Foo < 'a > { } \
+             impl < 'a > Mul < T , Self , U > for Foo < 'a > { }",
+        );
+
+        assert!(generator
+            .impl_for_parameterized("PartialEq", ["("])
+            .is_err());
+        generator.assert_eq(
+            "impl < 'a > PartialEq < &'a str > for Foo < 'a > { } \
+             impl < 'a > Add < Self , u8 , T > for Foo < 'a > { } \
+             impl < 'a > Mul < T , Self , U > for Foo < 'a > { }",
+        );
+    }
+
+    #[test]
+    fn fn_generics_shadowing_impl_generics() {
+        let (mut generator, _, _) = crate::parse::Parse::new(
+            token_stream("struct Foo<'a, T> { a: &'a T }").collect::<TokenStream>(),
+        )
+        .unwrap()
+        .into_generator();
+        let error = generator
+            .r#impl()
+            .generate_fn("foo")
+            .with_generic("T")
+            .body(|_| Ok(()))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The generic `T` of `fn foo` is already declared\n\
+             help: the generics of the impl block can be used in the function without declaring them again, or rename the generic of the function"
+        );
+        assert!(generator
+            .impl_for("Bar")
+            .generate_fn("bar")
+            .with_lifetime("a")
+            .body(|_| Ok(()))
+            .is_err());
+        assert!(generator
+            .impl_for_with_lifetimes("Decode", ["de"])
+            .generate_fn("decode")
+            .with_generic_deps("D", ["Decoder<'de>"])
+            .with_lifetime("de")
+            .body(|_| Ok(()))
+            .is_err());
+        assert!(generator
+            .r#impl()
+            .generate_fn("baz")
+            .with_generic("U")
+            .with_generic("U")
+            .body(|_| Ok(()))
+            .is_err());
+
+        // free functions and lifetimes that are only passed to the trait do not declare anything
+        generator
+            .impl_for_with_lifetimes("Borrow", ["a"])
+            .with_lifetime_placement(crate::generate::LifetimePlacement::TraitOnly)
+            .generate_fn("borrow")
+            .with_lifetime("b")
+            .body(|_| Ok(()))
+            .unwrap();
+        generator
+            .generate_fn("free")
+            .with_generic("T")
+            .body(|_| Ok(()))
+            .unwrap();
+        // the impl blocks of the functions that failed are not emitted
+        generator.assert_eq(
+            "impl < 'a , T > Borrow < 'a > for Foo < 'a , T > { fn borrow < 'b > () { } } \
+             fn free < T > () { }",
+        );
+    }
 }
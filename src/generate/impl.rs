@@ -1,4 +1,7 @@
-use super::{generate_item::FnParent, FnBuilder, GenConst, Generator, Parent, StreamBuilder};
+use super::{
+    generate_item::{generic_names, FnParent},
+    AttrTokens, FnBuilder, GenAttr, GenConst, Generator, Parent, StreamBuilder, TypeTokens,
+};
 use crate::{
     parse::{GenericConstraints, Generics},
     prelude::{Delimiter, Result},
@@ -9,64 +12,95 @@ use crate::{
 pub struct Impl<'a, P: Parent> {
     parent: &'a mut P,
     outer_attr: Vec<StreamBuilder>,
+    automatically_derived: Option<bool>,
     inner_attr: Vec<StreamBuilder>,
     name: String,
     // pub(super) group: StreamBuilder,
     consts: Vec<StreamBuilder>,
     custom_generic_constraints: Option<GenericConstraints>,
-    fns: Vec<(StreamBuilder, StreamBuilder)>,
+    items: Vec<StreamBuilder>,
+    span_header_at_target: bool,
+    built: bool,
+    poisoned: bool,
 }
 
 impl<'a, P: Parent> Impl<'a, P> {
     pub(super) fn with_parent_name(parent: &'a mut P) -> Self {
         Self {
             outer_attr: Vec::new(),
+            automatically_derived: None,
             inner_attr: Vec::new(),
             name: parent.name().to_string(),
             parent,
             consts: Vec::new(),
             custom_generic_constraints: None,
-            fns: Vec::new(),
+            items: Vec::new(),
+            span_header_at_target: false,
+            built: false,
+            poisoned: false,
         }
     }
 
     pub(super) fn new(parent: &'a mut P, name: impl Into<String>) -> Self {
         Self {
             outer_attr: Vec::new(),
+            automatically_derived: None,
             inner_attr: Vec::new(),
             parent,
             name: name.into(),
             consts: Vec::new(),
             custom_generic_constraints: None,
-            fns: Vec::new(),
+            items: Vec::new(),
+            span_header_at_target: false,
+            built: false,
+            poisoned: false,
         }
     }
 
+    /// Set whether this impl gets the `#[automatically_derived]` attribute. By default this follows [`Generator::set_automatically_derived`].
+    ///
+    /// [`Generator::set_automatically_derived`]: struct.Generator.html#method.set_automatically_derived
+    pub fn set_automatically_derived(&mut self, automatically_derived: bool) -> &mut Self {
+        self.automatically_derived = Some(automatically_derived);
+        self
+    }
+
+    /// Give the `impl<...> Name` tokens of this impl the span of the name of the target type in the input, and the `<...>` after the name the span of its generics, so errors about the impl point at the definition of the target type instead of at the derive. By default the generated tokens have the `call_site` span.
+    ///
+    /// See [`ImplFor::span_impl_header_at_target`] for more information.
+    ///
+    /// [`ImplFor::span_impl_header_at_target`]: struct.ImplFor.html#method.span_impl_header_at_target
+    pub fn span_impl_header_at_target(mut self, span_at_target: bool) -> Self {
+        self.span_header_at_target = span_at_target;
+        self
+    }
+
     /// Add a outer attribute to the trait implementation
-    pub fn impl_outer_attr(&mut self, attr: impl AsRef<str>) -> Result {
-        let mut builder = StreamBuilder::new();
-        builder.punct('#').group(Delimiter::Bracket, |builder| {
-            builder.push_parsed(attr)?;
-            Ok(())
-        })?;
-        self.outer_attr.push(builder);
+    pub fn impl_outer_attr(&mut self, attr: impl Into<AttrTokens>) -> Result {
+        self.outer_attr.push(attr.into().into_attribute(false)?);
         Ok(())
     }
 
     /// Add a inner attribute to the trait implementation
-    pub fn impl_inner_attr(&mut self, attr: impl AsRef<str>) -> Result {
-        let mut builder = StreamBuilder::new();
-        builder
-            .punct('#')
-            .punct('!')
-            .group(Delimiter::Brace, |builder| {
-                builder.push_parsed(attr)?;
-                Ok(())
-            })?;
-        self.inner_attr.push(builder);
+    pub fn impl_inner_attr(&mut self, attr: impl Into<AttrTokens>) -> Result {
+        self.inner_attr.push(attr.into().into_attribute(true)?);
         Ok(())
     }
 
+    /// Add an outer attribute with the given path to the trait implementation, e.g. `cfg`. See [`GenAttr`] for how to add arguments.
+    ///
+    /// [`GenAttr`]: struct.GenAttr.html
+    pub fn attr(&mut self, path: impl AsRef<str>) -> Result<GenAttr<'_>> {
+        GenAttr::attached(&mut self.outer_attr, false, path)
+    }
+
+    /// Add an inner attribute with the given path to the trait implementation, e.g. `allow`. See [`GenAttr`] for how to add arguments.
+    ///
+    /// [`GenAttr`]: struct.GenAttr.html
+    pub fn inner_attr(&mut self, path: impl AsRef<str>) -> Result<GenAttr<'_>> {
+        GenAttr::attached(&mut self.inner_attr, true, path)
+    }
+
     /// Add a function to the trait implementation.
     ///
     /// `generator.impl().generate_fn("bar")` results in code like:
@@ -78,10 +112,31 @@ impl<'a, P: Parent> Impl<'a, P> {
     /// ```
     ///
     /// See [`FnBuilder`] for more options, as well as information on how to fill the function body.
-    pub fn generate_fn(&mut self, name: impl Into<String>) -> FnBuilder<Self> {
+    pub fn generate_fn(&mut self, name: impl Into<String>) -> FnBuilder<'_, Self> {
         FnBuilder::new(self, name)
     }
 
+    /// Add an arbitrary item to the impl, e.g. a macro invocation. Items are emitted in the same order as the functions added with [`generate_fn`].
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// let mut r#impl = generator.r#impl();
+    /// let mut item = StreamBuilder::new();
+    /// item.push_parsed("my_macro!();")?;
+    /// r#impl.push_item(item);
+    /// r#impl.generate_fn("foo").body(|_| Ok(()))?;
+    /// # drop(r#impl);
+    /// # generator.assert_eq("impl Foo { my_macro ! () ; fn foo () { } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`generate_fn`]: #method.generate_fn
+    pub fn push_item(&mut self, item: StreamBuilder) -> &mut Self {
+        self.items.push(item);
+        self
+    }
+
     /// Add a const to the trait implementation
     /// ```
     /// # use virtue::prelude::Generator;
@@ -101,9 +156,21 @@ impl<'a, P: Parent> Impl<'a, P> {
     /// impl Foo for <struct or enum> {
     ///     const BAR: u8 = 5;
     /// }
-    pub fn generate_const(&mut self, name: impl Into<String>, ty: impl Into<String>) -> GenConst {
+    pub fn generate_const(
+        &mut self,
+        name: impl Into<String>,
+        ty: impl Into<TypeTokens>,
+    ) -> GenConst<'_> {
         GenConst::new(&mut self.consts, name, ty)
     }
+
+    /// Return the assembled impl block instead of adding it to the parent when this builder is dropped. Like [`ImplFor::build`], the block does not have the `#[allow(...)]` of `Generator::suppress_lints` until it is passed to `Generator::insert_at`. See [`ImplFor::build`] for more information.
+    ///
+    /// [`ImplFor::build`]: struct.ImplFor.html#method.build
+    pub fn build(mut self) -> StreamBuilder {
+        self.built = true;
+        self.assemble()
+    }
 }
 
 impl<'a> Impl<'a, Generator> {
@@ -153,29 +220,70 @@ impl<'a> Impl<'a, Generator> {
 
 impl<'a, P: Parent> FnParent for Impl<'a, P> {
     fn append(&mut self, fn_definition: StreamBuilder, fn_body: StreamBuilder) -> Result {
-        self.fns.push((fn_definition, fn_body));
+        let mut builder = fn_definition;
+        builder.group(Delimiter::Brace, |body| {
+            *body = fn_body;
+            Ok(())
+        })?;
+        self.items.push(builder);
         Ok(())
     }
+
+    fn poison(&mut self) {
+        self.poisoned = true;
+    }
+
+    fn declared_generics(&self) -> Vec<String> {
+        generic_names(self.parent.generics())
+    }
 }
 
 impl<'a, P: Parent> Drop for Impl<'a, P> {
     fn drop(&mut self) {
-        if std::thread::panicking() {
+        if std::thread::panicking() || self.built || self.poisoned {
             return;
         }
+        let builder = self.assemble();
+        self.parent.append(builder);
+    }
+}
+
+impl<P: Parent> Impl<'_, P> {
+    /// The attributes, header and items of this impl
+    fn assemble(&mut self) -> StreamBuilder {
         let mut builder = StreamBuilder::new();
+        let automatically_derived = self
+            .automatically_derived
+            .unwrap_or_else(|| self.parent.automatically_derived());
+        if automatically_derived {
+            builder
+                .punct('#')
+                .group(Delimiter::Bracket, |builder| {
+                    builder.ident_str("automatically_derived");
+                    Ok(())
+                })
+                .unwrap();
+        }
         for attr in std::mem::take(&mut self.outer_attr) {
             builder.append(attr);
         }
-        builder.ident_str("impl");
-
+        let mut header = StreamBuilder::new();
+        header.ident_str("impl");
         if let Some(generics) = self.parent.generics() {
-            builder.append(generics.impl_generics());
+            header.append(generics.impl_generics());
+        }
+        header.push_parsed(&self.name).unwrap();
+        if self.span_header_at_target {
+            header.set_span_on_all_tokens(self.parent.name().span());
         }
-        builder.push_parsed(&self.name).unwrap();
+        builder.append(header);
 
         if let Some(generics) = self.parent.generics() {
-            builder.append(generics.type_generics());
+            if self.span_header_at_target {
+                builder.append(generics.type_generics_at_target());
+            } else {
+                builder.append(generics.type_generics());
+            }
         }
         if let Some(generic_constraints) = self.custom_generic_constraints.take() {
             builder.append(generic_constraints.where_clause());
@@ -191,19 +299,12 @@ impl<'a, P: Parent> Drop for Impl<'a, P> {
                 for r#const in std::mem::take(&mut self.consts) {
                     builder.append(r#const);
                 }
-                for (fn_def, fn_body) in std::mem::take(&mut self.fns) {
-                    builder.append(fn_def);
-                    builder
-                        .group(Delimiter::Brace, |body| {
-                            *body = fn_body;
-                            Ok(())
-                        })
-                        .unwrap();
+                for item in std::mem::take(&mut self.items) {
+                    builder.append(item);
                 }
                 Ok(())
             })
             .unwrap();
-
-        self.parent.append(builder);
+        builder
     }
 }
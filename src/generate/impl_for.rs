@@ -1,23 +1,48 @@
-use super::{generate_item::FnParent, FnBuilder, GenConst, Parent, StreamBuilder, StringOrIdent};
+use super::{
+    generate_item::{generic_names, FnParent},
+    AttrTokens, FnBuilder, GenAttr, GenConst, GenType, Parent, StreamBuilder, StringOrIdent,
+    TypeTokens,
+};
 use crate::{
     parse::{GenericConstraints, Generics},
     prelude::{Delimiter, Result},
 };
 
+/// Where the lifetimes of [`Generator::impl_for_with_lifetimes`] are added, see [`ImplFor::with_lifetime_placement`].
+///
+/// [`Generator::impl_for_with_lifetimes`]: struct.Generator.html#method.impl_for_with_lifetimes
+/// [`ImplFor::with_lifetime_placement`]: struct.ImplFor.html#method.with_lifetime_placement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LifetimePlacement {
+    /// Declare the lifetimes on the impl and pass them to the trait, e.g. `impl<'de> Decode<'de> for Foo`. This is the default.
+    ImplAndTrait,
+    /// Only pass the lifetimes to the trait, e.g. `impl Decode<'static> for Foo` or `impl<'a> Borrow<'a> for Foo<'a>` where `'a` is a lifetime of the target.
+    TraitOnly,
+    /// Only declare the lifetimes on the impl, e.g. `impl<'de> Decode for Foo` with a bound like `T: Borrow<'de>` in the where clause.
+    ImplOnly,
+}
+
 #[must_use]
 /// A helper struct for implementing a trait for a given struct or enum.
 pub struct ImplFor<'a, P: Parent> {
     generator: &'a mut P,
     outer_attr: Vec<StreamBuilder>,
+    automatically_derived: Option<bool>,
     inner_attr: Vec<StreamBuilder>,
     type_name: StringOrIdent,
     trait_name: Option<StringOrIdent>,
     lifetimes: Option<Vec<String>>,
-    generics: Option<Vec<String>>,
+    lifetime_placement: LifetimePlacement,
+    ref_self_lifetime: Option<String>,
+    /// The generic arguments of the trait, in the order in which they were added
+    trait_args: Vec<StreamBuilder>,
     consts: Vec<StreamBuilder>,
     custom_generic_constraints: Option<GenericConstraints>,
     impl_types: Vec<StreamBuilder>,
-    fns: Vec<(StreamBuilder, StreamBuilder)>,
+    items: Vec<StreamBuilder>,
+    span_header_at_target: bool,
+    built: bool,
+    poisoned: bool,
 }
 
 impl<'a, P: Parent> ImplFor<'a, P> {
@@ -29,15 +54,21 @@ impl<'a, P: Parent> ImplFor<'a, P> {
         Self {
             generator,
             outer_attr: Vec::new(),
+            automatically_derived: None,
             inner_attr: Vec::new(),
             trait_name,
             type_name,
             lifetimes: None,
-            generics: None,
+            lifetime_placement: LifetimePlacement::ImplAndTrait,
+            ref_self_lifetime: None,
+            trait_args: Vec::new(),
             consts: Vec::new(),
             custom_generic_constraints: None,
             impl_types: Vec::new(),
-            fns: Vec::new(),
+            items: Vec::new(),
+            span_header_at_target: false,
+            built: false,
+            poisoned: false,
         }
     }
 
@@ -51,6 +82,14 @@ impl<'a, P: Parent> ImplFor<'a, P> {
         self
     }
 
+    /// Internal helper function to add generic arguments to the trait, in the same order as [`with_trait_generics`]
+    ///
+    /// [`with_trait_generics`]: #method.with_trait_generics
+    pub(crate) fn with_trait_args(mut self, args: Vec<StreamBuilder>) -> Self {
+        self.trait_args.extend(args);
+        self
+    }
+
     /// Make the new lifetimes added by `Generator::impl_for_with_lifetimes` depend on the existing lifetimes from the original derive.
     ///
     /// See [`impl_for_with_lifetimes`] for more information.
@@ -58,7 +97,32 @@ impl<'a, P: Parent> ImplFor<'a, P> {
     /// Calling this method in any other context has no effect.
     ///
     /// [`impl_for_with_lifetimes`]: struct.Generator.html#method.impl_for_with_lifetimes
-    pub fn new_lifetimes_depend_on_existing(mut self) -> Self {
+    pub fn new_lifetimes_depend_on_existing(self) -> Self {
+        self.with_lifetime_dependencies(true)
+    }
+
+    /// Make the existing lifetimes from the original derive depend on the new lifetimes added by `Generator::impl_for_with_lifetimes`. This is the reverse of [`new_lifetimes_depend_on_existing`].
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Bar").with_lifetime("a");
+    /// // given a derive on `struct<'a> Bar<'a>`
+    /// generator.impl_for_with_lifetimes("Foo", ["b"]).existing_lifetimes_depend_on_new();
+    ///
+    /// // will output:
+    /// // impl<'b, 'a> Foo<'b> for Bar<'a> where 'a: 'b { }
+    /// # generator.assert_eq("impl < 'b , 'a > Foo < 'b > for Bar < 'a > where 'a : 'b { }");
+    /// ```
+    ///
+    /// Calling this method in any other context has no effect.
+    ///
+    /// [`new_lifetimes_depend_on_existing`]: #method.new_lifetimes_depend_on_existing
+    pub fn existing_lifetimes_depend_on_new(self) -> Self {
+        self.with_lifetime_dependencies(false)
+    }
+
+    /// Add `'new: 'old` for every new and existing lifetime, or `'old: 'new` if `new_depends_on_existing` is `false`
+    fn with_lifetime_dependencies(mut self, new_depends_on_existing: bool) -> Self {
         if let Some(new_lt) = &self.lifetimes {
             if let Some(generics) = self.generator.generics() {
                 let constraints = self.custom_generic_constraints.get_or_insert_with(|| {
@@ -69,9 +133,14 @@ impl<'a, P: Parent> ImplFor<'a, P> {
                 });
                 for old_lt in generics.iter_lifetimes() {
                     for new_lt in new_lt {
+                        let constraint = if new_depends_on_existing {
+                            format!("'{}: '{}", new_lt, old_lt.ident)
+                        } else {
+                            format!("'{}: '{}", old_lt.ident, new_lt)
+                        };
                         constraints
-                            .push_parsed_constraint(format!("'{}: '{}", new_lt, old_lt.ident))
-                            .expect("Could not ensure new lifetimes depend on existing lifetimes");
+                            .push_parsed_constraint(constraint)
+                            .expect("Could not add lifetime dependencies");
                     }
                 }
             }
@@ -79,7 +148,82 @@ impl<'a, P: Parent> ImplFor<'a, P> {
         self
     }
 
-    /// Add generic parameters to the trait implementation.
+    /// Set where the lifetimes added by `Generator::impl_for_with_lifetimes` appear. By default they are declared on the impl and passed to the trait.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// use virtue::generate::LifetimePlacement;
+    /// # let mut generator = Generator::with_name("Bar").with_lifetime("a");
+    /// // given a derive on `struct<'a> Bar<'a>`
+    /// generator
+    ///     .impl_for_with_lifetimes("Borrowed", ["a"])
+    ///     .with_lifetime_placement(LifetimePlacement::TraitOnly);
+    /// generator
+    ///     .impl_for_with_lifetimes("Decode", ["de"])
+    ///     .with_lifetime_placement(LifetimePlacement::ImplOnly)
+    ///     .new_lifetimes_depend_on_existing();
+    ///
+    /// // will output:
+    /// // impl<'a> Borrowed<'a> for Bar<'a> { }
+    /// // impl<'de, 'a> Decode for Bar<'a> where 'de: 'a { }
+    /// # generator.assert_eq("impl < 'a > Borrowed < 'a > for Bar < 'a > { } \
+    /// #     impl < 'de , 'a > Decode for Bar < 'a > where 'de : 'a { }");
+    /// ```
+    ///
+    /// Calling this method in any other context has no effect.
+    pub fn with_lifetime_placement(mut self, placement: LifetimePlacement) -> Self {
+        self.lifetime_placement = placement;
+        self
+    }
+
+    /// Give the `impl<...> Trait for Name` tokens of this impl the span of the name of the target type in the input, and the `<...>` after the name the span of its generics. By default the generated tokens have the `call_site` span.
+    ///
+    /// With this, errors about the impl, like a bound of the trait that the target type does not satisfy, point at the definition of the target type instead of at the derive. The tokens of the where clause keep their own spans.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = TokenStream::from_str("struct Foo<T> { t: T }").unwrap();
+    /// let (mut generator, _, _) = Parse::new(input)?.into_generator();
+    /// let _ = generator.impl_for("Copy").span_impl_header_at_target(true);
+    ///
+    /// // impl<T> Copy for Foo<T> { }
+    /// // ^^^^^^^^^^^^^^^^^^^^^^^ these tokens have the span of `Foo`
+    /// # generator.assert_eq("impl < T > Copy for Foo < T > { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn span_impl_header_at_target(mut self, span_at_target: bool) -> Self {
+        self.span_header_at_target = span_at_target;
+        self
+    }
+
+    /// Implement the trait for a reference to the type, `&'lifetime Type`, instead of the type itself. The lifetime is added to the impl generics.
+    ///
+    /// Note: the lifetime should _not_ have the leading apostrophe.
+    ///
+    /// ```
+    /// # use virtue::prelude::Generator;
+    /// # let mut generator = Generator::with_name("Bar");
+    /// generator.impl_for("Foo")
+    ///          .for_ref_self("a");
+    /// # generator.assert_eq("impl < 'a > Foo for & 'a Bar { }");
+    /// ```
+    ///
+    /// Generates:
+    /// ```ignore
+    /// impl<'a> Foo for &'a <struct or enum> {
+    /// }
+    /// ```
+    ///
+    /// Inside of the generated functions, `Self` will be `&'a <struct or enum>`.
+    pub fn for_ref_self(mut self, lifetime: impl Into<String>) -> Self {
+        self.ref_self_lifetime = Some(lifetime.into());
+        self
+    }
+
+    /// Add generic parameters to the trait implementation. They come after the lifetimes of the trait, in the order in which they are added, e.g. after the arguments of [`Generator::impl_for_parameterized`].
+    ///
+    /// [`Generator::impl_for_parameterized`]: struct.Generator.html#method.impl_for_parameterized
     ///```
     /// # use virtue::prelude::Generator;
     /// # let mut generator = Generator::with_name("Bar");
@@ -100,35 +244,48 @@ impl<'a, P: Parent> ImplFor<'a, P> {
         ITER: IntoIterator,
         ITER::Item: Into<String>,
     {
-        self.generics = Some(generics.into_iter().map(Into::into).collect());
+        for generic in generics {
+            let mut builder = StreamBuilder::new();
+            builder.ident_str(generic.into());
+            self.trait_args.push(builder);
+        }
+        self
+    }
+
+    /// Set whether this impl gets the `#[automatically_derived]` attribute. By default this follows [`Generator::set_automatically_derived`].
+    ///
+    /// [`Generator::set_automatically_derived`]: struct.Generator.html#method.set_automatically_derived
+    pub fn set_automatically_derived(&mut self, automatically_derived: bool) -> &mut Self {
+        self.automatically_derived = Some(automatically_derived);
         self
     }
 
     /// Add a outer attribute to the trait implementation
-    pub fn impl_outer_attr(&mut self, attr: impl AsRef<str>) -> Result {
-        let mut builder = StreamBuilder::new();
-        builder.punct('#').group(Delimiter::Bracket, |builder| {
-            builder.push_parsed(attr)?;
-            Ok(())
-        })?;
-        self.outer_attr.push(builder);
+    pub fn impl_outer_attr(&mut self, attr: impl Into<AttrTokens>) -> Result {
+        self.outer_attr.push(attr.into().into_attribute(false)?);
         Ok(())
     }
 
     /// Add a inner attribute to the trait implementation
-    pub fn impl_inner_attr(&mut self, attr: impl AsRef<str>) -> Result {
-        let mut builder = StreamBuilder::new();
-        builder
-            .punct('#')
-            .punct('!')
-            .group(Delimiter::Brace, |builder| {
-                builder.push_parsed(attr)?;
-                Ok(())
-            })?;
-        self.inner_attr.push(builder);
+    pub fn impl_inner_attr(&mut self, attr: impl Into<AttrTokens>) -> Result {
+        self.inner_attr.push(attr.into().into_attribute(true)?);
         Ok(())
     }
 
+    /// Add an outer attribute with the given path to the trait implementation, e.g. `cfg`. See [`GenAttr`] for how to add arguments.
+    ///
+    /// [`GenAttr`]: struct.GenAttr.html
+    pub fn attr(&mut self, path: impl AsRef<str>) -> Result<GenAttr<'_>> {
+        GenAttr::attached(&mut self.outer_attr, false, path)
+    }
+
+    /// Add an inner attribute with the given path to the trait implementation, e.g. `allow`. See [`GenAttr`] for how to add arguments.
+    ///
+    /// [`GenAttr`]: struct.GenAttr.html
+    pub fn inner_attr(&mut self, path: impl AsRef<str>) -> Result<GenAttr<'_>> {
+        GenAttr::attached(&mut self.inner_attr, true, path)
+    }
+
     /// Add a const to the trait implementation
     /// ```
     /// # use virtue::prelude::Generator;
@@ -148,7 +305,11 @@ impl<'a, P: Parent> ImplFor<'a, P> {
     /// impl Foo for <struct or enum> {
     ///     const BAR: u8 = 5;
     /// }
-    pub fn generate_const(&mut self, name: impl Into<String>, ty: impl Into<String>) -> GenConst {
+    pub fn generate_const(
+        &mut self,
+        name: impl Into<String>,
+        ty: impl Into<TypeTokens>,
+    ) -> GenConst<'_> {
         GenConst::new(&mut self.consts, name, ty)
     }
 
@@ -163,10 +324,32 @@ impl<'a, P: Parent> ImplFor<'a, P> {
     /// ```
     ///
     /// See [`FnBuilder`] for more options, as well as information on how to fill the function body.
-    pub fn generate_fn(&mut self, name: impl Into<String>) -> FnBuilder<ImplFor<'a, P>> {
+    pub fn generate_fn(&mut self, name: impl Into<String>) -> FnBuilder<'_, ImplFor<'a, P>> {
         FnBuilder::new(self, name)
     }
 
+    /// Add an arbitrary item to the impl, e.g. a macro invocation. Items are emitted in the same order as the functions added with [`generate_fn`].
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Bar");
+    /// let mut impl_for = generator.impl_for("Foo");
+    /// impl_for.generate_fn("foo").body(|_| Ok(()))?;
+    /// let mut item = StreamBuilder::new();
+    /// item.push_parsed("delegate! { to self.0 { fn bar(&self); } }")?;
+    /// impl_for.push_item(item);
+    /// impl_for.generate_fn("baz").body(|_| Ok(()))?;
+    /// # drop(impl_for);
+    /// # generator.assert_eq("impl Foo for Bar { fn foo () { } delegate ! { to self . 0 { fn bar (& self) ; } } fn baz () { } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`generate_fn`]: #method.generate_fn
+    pub fn push_item(&mut self, item: StreamBuilder) -> &mut Self {
+        self.items.push(item);
+        self
+    }
+
     /// Add a type to the impl
     ///
     /// `generator.impl_for("Foo").impl_type("Bar", "u8")` results in code like:
@@ -176,18 +359,41 @@ impl<'a, P: Parent> ImplFor<'a, P> {
     ///     type Bar = u8;
     /// }
     /// ```
-    pub fn impl_type(&mut self, name: impl AsRef<str>, value: impl AsRef<str>) -> Result {
+    ///
+    /// Use [`generate_type`] for types with generics or where clauses.
+    ///
+    /// [`generate_type`]: #method.generate_type
+    pub fn impl_type(&mut self, name: impl AsRef<str>, value: impl Into<TypeTokens>) -> Result {
         let mut builder = StreamBuilder::new();
-        builder
-            .ident_str("type")
-            .push_parsed(name)?
-            .punct('=')
-            .push_parsed(value)?
-            .punct(';');
+        builder.ident_str("type").push_parsed(name)?.punct('=');
+        value.into().append_to(&mut builder)?;
+        builder.punct(';');
         self.impl_types.push(builder);
         Ok(())
     }
 
+    /// Add a type with generics or where clauses to the impl, e.g. a generic associated type. See [`GenType`] for more information.
+    ///
+    /// ```
+    /// # use virtue::prelude::Generator;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator
+    ///     .impl_for("Family")
+    ///     .generate_type("Member")
+    ///     .with_attr("allow(unused)")
+    ///     .with_lifetime("a")
+    ///     .with_generic_deps("T", ["Clone", "'a"])
+    ///     .with_where("Self: 'a")
+    ///     .with_where("T: Default")
+    ///     .with_value("Vec<&'a T>")?;
+    /// # generator.assert_eq("impl Family for Foo { # [allow (unused)] \
+    /// #     type Member < 'a , T : Clone + 'a > = Vec <&'a T > where Self : 'a , T : Default ; }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn generate_type(&mut self, name: impl Into<String>) -> GenType<'_> {
+        GenType::new(&mut self.impl_types, name)
+    }
+
     ///
     /// Modify the generic constraints of a type.
     /// This can be used to add additional type constraints to your implementation.
@@ -230,21 +436,113 @@ impl<'a, P: Parent> ImplFor<'a, P> {
         }
         Ok(self)
     }
+
+    /// Return the assembled impl block instead of adding it to the parent when this builder is dropped. The block can then be inspected, wrapped or duplicated, e.g. to emit the same impl for two configurations:
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// use virtue::generate::Position;
+    ///
+    /// # let mut generator = Generator::with_name("Foo");
+    /// let mut imp = generator.impl_for("Encode");
+    /// imp.generate_fn("encode").body(|b| {
+    ///     b.push_parsed("bar()")?;
+    ///     Ok(())
+    /// })?;
+    /// let block = imp.build();
+    ///
+    /// for target in ["unix", "windows"] {
+    ///     let mut item = StreamBuilder::new();
+    ///     item.push_parsed(format!("#[cfg({})]", target))?;
+    ///     item.append(block.clone());
+    ///     generator.insert_at(Position::End, item)?;
+    /// }
+    ///
+    /// // #[cfg(unix)]
+    /// // impl Encode for Foo { fn encode() { bar() } }
+    /// // #[cfg(windows)]
+    /// // impl Encode for Foo { fn encode() { bar() } }
+    /// # generator.assert_eq("# [cfg (unix)] impl Encode for Foo { fn encode () { bar () } } \
+    /// #     # [cfg (windows)] impl Encode for Foo { fn encode () { bar () } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// The header of the block uses the generics and where clause of the parent at the time this is called.
+    ///
+    /// Unlike a block that is added on drop, the returned block does not have the `#[allow(...)]` of [`Generator::suppress_lints`]. [`Generator::insert_at`] adds it when the block is inserted, so it is not added twice.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use virtue::generate::Position;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator.suppress_lints(["dead_code"])?;
+    /// let block = generator.impl_for("Bar").build();
+    /// assert_eq!(TokenStream::from(block.clone()).to_string(), "impl Bar for Foo { }");
+    /// generator.insert_at(Position::End, block)?;
+    /// # generator.assert_eq("# [allow (dead_code)] impl Bar for Foo { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`Generator::suppress_lints`]: struct.Generator.html#method.suppress_lints
+    /// [`Generator::insert_at`]: struct.Generator.html#method.insert_at
+    pub fn build(mut self) -> StreamBuilder {
+        self.built = true;
+        self.assemble()
+    }
 }
 
 impl<'a, P: Parent> FnParent for ImplFor<'a, P> {
     fn append(&mut self, fn_definition: StreamBuilder, fn_body: StreamBuilder) -> Result {
-        self.fns.push((fn_definition, fn_body));
+        let mut builder = fn_definition;
+        builder.group(Delimiter::Brace, |body| {
+            *body = fn_body;
+            Ok(())
+        })?;
+        self.items.push(builder);
         Ok(())
     }
+
+    fn poison(&mut self) {
+        self.poisoned = true;
+    }
+
+    fn declared_generics(&self) -> Vec<String> {
+        let mut names = generic_names(self.generator.generics());
+        if self.lifetime_placement != LifetimePlacement::TraitOnly {
+            let lifetimes = self.lifetimes.iter().flatten();
+            names.extend(lifetimes.map(|lt| format!("'{}", lt)));
+        }
+        names.extend(self.ref_self_lifetime.iter().map(|lt| format!("'{}", lt)));
+        names
+    }
 }
 
 impl<P: Parent> Drop for ImplFor<'_, P> {
     fn drop(&mut self) {
-        if std::thread::panicking() {
+        if std::thread::panicking() || self.built || self.poisoned {
             return;
         }
+        let builder = self.assemble();
+        self.generator.append(builder);
+    }
+}
+
+impl<P: Parent> ImplFor<'_, P> {
+    /// The attributes, header and items of this impl
+    fn assemble(&mut self) -> StreamBuilder {
         let mut builder = StreamBuilder::new();
+        let automatically_derived = self
+            .automatically_derived
+            .unwrap_or_else(|| self.generator.automatically_derived());
+        if automatically_derived {
+            builder
+                .punct('#')
+                .group(Delimiter::Bracket, |builder| {
+                    builder.ident_str("automatically_derived");
+                    Ok(())
+                })
+                .unwrap();
+        }
         for attr in std::mem::take(&mut self.outer_attr) {
             builder.append(attr);
         }
@@ -262,61 +560,76 @@ impl<P: Parent> Drop for ImplFor<'_, P> {
                 for r#const in std::mem::take(&mut self.consts) {
                     builder.append(r#const);
                 }
-                for (fn_def, fn_body) in std::mem::take(&mut self.fns) {
-                    builder.append(fn_def);
-                    builder
-                        .group(Delimiter::Brace, |body| {
-                            *body = fn_body;
-                            Ok(())
-                        })
-                        .unwrap();
+                for item in std::mem::take(&mut self.items) {
+                    builder.append(item);
                 }
                 Ok(())
             })
             .unwrap();
-
-        self.generator.append(builder);
+        builder
     }
-}
 
-impl<P: Parent> ImplFor<'_, P> {
     fn generate_impl_definition(&mut self, builder: &mut StreamBuilder) {
+        let mut header = StreamBuilder::new();
+        self.generate_impl_header(&mut header);
+        if self.span_header_at_target {
+            header.set_span_on_all_tokens(self.generator.name().span());
+        }
+        builder.append(header);
+        if let Some(generics) = &self.generator.generics() {
+            if self.span_header_at_target {
+                builder.append(generics.type_generics_at_target());
+            } else {
+                builder.append(generics.type_generics());
+            }
+        }
+        if let Some(generic_constraints) = self.custom_generic_constraints.take() {
+            builder.append(generic_constraints.where_clause());
+        } else if let Some(generic_constraints) = &self.generator.generic_constraints() {
+            builder.append(generic_constraints.where_clause());
+        }
+    }
+
+    /// Everything up to and including the type name, e.g. `impl<'a, T> Trait<'a> for Name`
+    fn generate_impl_header(&self, builder: &mut StreamBuilder) {
         builder.ident_str("impl");
-        if let Some(lifetimes) = &self.lifetimes {
+        let mut impl_lifetimes = match self.lifetime_placement {
+            LifetimePlacement::TraitOnly => Vec::new(),
+            _ => self.lifetimes.clone().unwrap_or_default(),
+        };
+        impl_lifetimes.extend(self.ref_self_lifetime.clone());
+        if !impl_lifetimes.is_empty() {
             if let Some(generics) = self.generator.generics() {
-                builder.append(generics.impl_generics_with_additional_lifetimes(lifetimes));
+                builder.append(generics.impl_generics_with_additional_lifetimes(&impl_lifetimes));
             } else {
-                append_lifetimes_and_generics(builder, lifetimes, &[]);
+                append_lifetimes_and_generics(builder, &impl_lifetimes, &[]);
             }
         } else if let Some(generics) = self.generator.generics() {
             builder.append(generics.impl_generics());
         }
         if let Some(t) = &self.trait_name {
-            builder.push_parsed(t.to_string()).unwrap();
+            t.append_to(builder).unwrap();
 
-            let lifetimes = self.lifetimes.as_deref().unwrap_or_default();
-            let generics = self.generics.as_deref().unwrap_or_default();
-            append_lifetimes_and_generics(builder, lifetimes, generics);
+            let lifetimes = match self.lifetime_placement {
+                LifetimePlacement::ImplOnly => &[],
+                _ => self.lifetimes.as_deref().unwrap_or_default(),
+            };
+            append_lifetimes_and_generics(builder, lifetimes, &self.trait_args);
             builder.ident_str("for");
         }
-        builder.push_parsed(self.type_name.to_string()).unwrap();
-        if let Some(generics) = &self.generator.generics() {
-            builder.append(generics.type_generics());
-        }
-        if let Some(generic_constraints) = self.custom_generic_constraints.take() {
-            builder.append(generic_constraints.where_clause());
-        } else if let Some(generic_constraints) = &self.generator.generic_constraints() {
-            builder.append(generic_constraints.where_clause());
+        if let Some(lifetime) = &self.ref_self_lifetime {
+            builder.punct('&').lifetime_str(lifetime);
         }
+        self.type_name.append_to(builder).unwrap();
     }
 }
 
 fn append_lifetimes_and_generics(
     builder: &mut StreamBuilder,
     lifetimes: &[String],
-    generics: &[String],
+    args: &[StreamBuilder],
 ) {
-    if lifetimes.is_empty() && generics.is_empty() {
+    if lifetimes.is_empty() && args.is_empty() {
         return;
     }
 
@@ -329,11 +642,11 @@ fn append_lifetimes_and_generics(
         builder.lifetime_str(lt);
     }
 
-    for (idx, gen) in generics.iter().enumerate() {
+    for (idx, arg) in args.iter().enumerate() {
         if idx > 0 || !lifetimes.is_empty() {
             builder.punct(',');
         }
-        builder.ident_str(gen);
+        builder.append(arg.clone());
     }
 
     builder.punct('>');
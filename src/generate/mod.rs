@@ -12,14 +12,32 @@
 //! [`Generator::finish()`]: struct.Generator.html#method.finish
 //! [`TokenStream`]: ../prelude/struct.TokenStream.html
 
+mod accessors;
+mod attr_tokens;
+mod body_scope;
+mod bounds;
+mod diagnostic;
+mod family;
+mod field_context;
+mod fmt_impl;
+mod gen_attr;
 mod gen_enum;
+mod gen_extern;
 mod gen_struct;
 mod generate_item;
 mod generate_mod;
 mod generator;
 mod r#impl;
 mod impl_for;
+mod pattern;
+mod post_process;
+mod provenance;
+mod stats;
 mod stream_builder;
+mod tagged_enum;
+mod target_type;
+mod type_builder;
+mod type_tokens;
 
 use crate::{
     parse::{GenericConstraints, Generics},
@@ -27,14 +45,33 @@ use crate::{
 };
 use std::fmt;
 
+pub use self::accessors::AccessorConfig;
+pub use self::attr_tokens::AttrTokens;
+pub use self::body_scope::BodyScope;
+pub use self::bounds::{FieldBound, InferredBounds};
+pub use self::diagnostic::Warning;
+pub use self::family::{FamilyAnalysis, TraitFamily};
+pub use self::field_context::FieldContext;
+pub use self::fmt_impl::DisplayStrategy;
+pub use self::gen_attr::GenAttr;
 pub use self::gen_enum::GenEnum;
+pub use self::gen_extern::GenExtern;
 pub use self::gen_struct::GenStruct;
-pub use self::generate_item::{FnBuilder, FnSelfArg, GenConst};
+pub use self::generate_item::{FnBuilder, FnSelfArg, GenConst, GenType, GenerationOutcome};
 pub use self::generate_mod::GenerateMod;
-pub use self::generator::Generator;
-pub use self::impl_for::ImplFor;
+pub use self::generator::{Generator, Position};
+pub use self::impl_for::{ImplFor, LifetimePlacement};
+pub use self::pattern::PatternBuilder;
+pub use self::provenance::Provenance;
 pub use self::r#impl::Impl;
+pub use self::stats::{GenerationStats, ItemStats, STATS_ENV_VAR};
 pub use self::stream_builder::{PushParseError, StreamBuilder};
+pub use self::tagged_enum::{TagConfig, TagValue};
+pub use self::target_type::TargetType;
+pub use self::type_builder::TypeBuilder;
+pub use self::type_tokens::{ExprTokens, TypeTokens};
+
+pub(crate) use self::fmt_impl::unraw;
 
 /// Helper trait to make it possible to nest several builders. Internal use only.
 #[allow(missing_docs)]
@@ -43,6 +80,10 @@ pub trait Parent {
     fn name(&self) -> &Ident;
     fn generics(&self) -> Option<&Generics>;
     fn generic_constraints(&self) -> Option<&GenericConstraints>;
+
+    fn automatically_derived(&self) -> bool {
+        false
+    }
 }
 
 /// Helper enum to differentiate between a [`Ident`] or a [`String`].
@@ -63,6 +104,21 @@ impl fmt::Display for StringOrIdent {
     }
 }
 
+impl StringOrIdent {
+    /// Add this to `builder`. An `Ident` is added as-is so it keeps its span, and errors about it point at the original tokens. A `String` is parsed.
+    pub(crate) fn append_to(&self, builder: &mut StreamBuilder) -> crate::Result {
+        match self {
+            Self::String(s) => {
+                builder.push_parsed(s)?;
+            }
+            Self::Ident(i) => {
+                builder.ident(i.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
 impl From<String> for StringOrIdent {
     fn from(s: String) -> Self {
         Self::String(s)
@@ -73,6 +129,11 @@ impl From<Ident> for StringOrIdent {
         Self::Ident(i)
     }
 }
+impl<'a> From<&'a String> for StringOrIdent {
+    fn from(s: &'a String) -> Self {
+        Self::String(s.clone())
+    }
+}
 impl<'a> From<&'a str> for StringOrIdent {
     fn from(s: &'a str) -> Self {
         Self::String(s.to_owned())
@@ -0,0 +1,480 @@
+use super::StreamBuilder;
+use crate::parse::Fields;
+use crate::prelude::{Delimiter, Ident, Literal, TokenTree};
+use crate::{Error, Result};
+use std::fmt;
+
+/// A builder for patterns, e.g. the left side of a `let`, the arms of a `match` or the arguments of a function.
+///
+/// Patterns are built from the inside out, so every pattern is valid by construction:
+///
+/// ```
+/// # use virtue::generate::PatternBuilder;
+/// // Foo::Bar { a, b: ref b, .. }
+/// let pattern = PatternBuilder::struct_pattern_with_rest(
+///     "Foo::Bar",
+///     [
+///         ("a", PatternBuilder::binding("a")?),
+///         ("b", PatternBuilder::ref_binding("b")?),
+///     ],
+/// )?;
+/// assert_eq!(pattern.to_string(), "Foo :: Bar { a , b : ref b , .. }");
+///
+/// // Some(value @ (1 | 2))
+/// let pattern = PatternBuilder::tuple_struct(
+///     "Some",
+///     [PatternBuilder::binding_at(
+///         "value",
+///         PatternBuilder::or([
+///             PatternBuilder::literal(virtue::prelude::Literal::u8_unsuffixed(1)),
+///             PatternBuilder::literal(virtue::prelude::Literal::u8_unsuffixed(2)),
+///         ]),
+///     )?],
+/// )?;
+/// assert_eq!(pattern.to_string(), "Some (value @ (1 | 2))");
+/// # Ok::<_, virtue::Error>(())
+/// ```
+///
+/// A pattern can be added to a [`StreamBuilder`] with [`StreamBuilder::pattern`], or used as a function argument with [`FnBuilder::with_arg_pattern`].
+///
+/// [`StreamBuilder::pattern`]: struct.StreamBuilder.html#method.pattern
+/// [`FnBuilder::with_arg_pattern`]: struct.FnBuilder.html#method.with_arg_pattern
+#[derive(Clone)]
+pub struct PatternBuilder {
+    stream: StreamBuilder,
+    /// The name of the binding if this pattern is a plain binding like `a`, so `Foo { a: a }` can be written as `Foo { a }`
+    binding: Option<String>,
+    /// Set for or-patterns, which need parentheses when nested in `a @ ..` or `&..`
+    is_or: bool,
+}
+
+impl PatternBuilder {
+    fn build(f: impl FnOnce(&mut StreamBuilder) -> Result) -> Result<Self> {
+        let mut builder = StreamBuilder::new();
+        f(&mut builder)?;
+        Ok(Self {
+            stream: builder,
+            binding: None,
+            is_or: false,
+        })
+    }
+
+    fn build_infallible(f: impl FnOnce(&mut StreamBuilder)) -> Self {
+        Self::build(|b| {
+            f(b);
+            Ok(())
+        })
+        .expect("Could not build pattern")
+    }
+
+    /// The wildcard pattern `_`
+    pub fn wildcard() -> Self {
+        Self::build_infallible(|b| {
+            b.ident_str("_");
+        })
+    }
+
+    /// The rest pattern `..`, e.g. for `(a, ..)`
+    pub fn rest() -> Self {
+        Self::build_infallible(|b| {
+            b.puncts("..");
+        })
+    }
+
+    /// A binding, e.g. `a`. Returns an error if `name` is not an identifier.
+    pub fn binding(name: impl AsRef<str>) -> Result<Self> {
+        let ident = binding_ident(name.as_ref())?;
+        let binding = ident.to_string();
+        Ok(Self::build_infallible(|b| {
+            b.ident(ident);
+        })
+        .with_binding(binding))
+    }
+
+    /// A mutable binding, e.g. `mut a`. Returns an error if `name` is not an identifier.
+    pub fn mut_binding(name: impl AsRef<str>) -> Result<Self> {
+        let ident = binding_ident(name.as_ref())?;
+        Ok(Self::build_infallible(|b| {
+            b.ident_str("mut").ident(ident);
+        }))
+    }
+
+    /// A binding by reference, e.g. `ref a`. Returns an error if `name` is not an identifier.
+    pub fn ref_binding(name: impl AsRef<str>) -> Result<Self> {
+        let ident = binding_ident(name.as_ref())?;
+        Ok(Self::build_infallible(|b| {
+            b.ident_str("ref").ident(ident);
+        }))
+    }
+
+    /// A binding of a value that also matches `pattern`, e.g. `a @ (1 | 2)`. Returns an error if `name` is not an identifier.
+    pub fn binding_at(name: impl AsRef<str>, pattern: PatternBuilder) -> Result<Self> {
+        let ident = binding_ident(name.as_ref())?;
+        Ok(Self::build_infallible(|b| {
+            b.ident(ident).punct('@');
+            pattern.append_nested(b);
+        }))
+    }
+
+    /// A literal, e.g. `5` or `"foo"`
+    pub fn literal(literal: Literal) -> Self {
+        Self::build_infallible(|b| {
+            b.push(literal);
+        })
+    }
+
+    /// A reference pattern, e.g. `&a`
+    pub fn reference(pattern: PatternBuilder) -> Self {
+        Self::build_infallible(|b| {
+            b.punct('&');
+            pattern.append_nested(b);
+        })
+    }
+
+    /// A mutable reference pattern, e.g. `&mut a`
+    pub fn reference_mut(pattern: PatternBuilder) -> Self {
+        Self::build_infallible(|b| {
+            b.punct('&').ident_str("mut");
+            pattern.append_nested(b);
+        })
+    }
+
+    /// A tuple pattern, e.g. `(a, b)`. A tuple with a single pattern will be written as `(a,)`.
+    pub fn tuple(patterns: impl IntoIterator<Item = PatternBuilder>) -> Self {
+        Self::build_infallible(|b| {
+            b.group(Delimiter::Parenthesis, |b| {
+                append_list(b, patterns, true);
+                Ok(())
+            })
+            .expect("Could not build pattern");
+        })
+    }
+
+    /// A path pattern, e.g. a unit struct `Foo` or a unit variant `Foo::Bar`
+    pub fn path(path: impl AsRef<str>) -> Result<Self> {
+        Self::build(|b| {
+            b.push_parsed(path)?;
+            Ok(())
+        })
+    }
+
+    /// A tuple struct or tuple variant pattern, e.g. `Foo::Bar(a, b)`
+    pub fn tuple_struct(
+        path: impl AsRef<str>,
+        patterns: impl IntoIterator<Item = PatternBuilder>,
+    ) -> Result<Self> {
+        Self::build(|b| {
+            b.push_parsed(path)?.group(Delimiter::Parenthesis, |b| {
+                append_list(b, patterns, false);
+                Ok(())
+            })?;
+            Ok(())
+        })
+    }
+
+    /// A struct or struct variant pattern, e.g. `Foo::Bar { a, b: _ }`. The fields of a tuple struct are named by their index, e.g. `Foo { 0: a }`. Returns an error if a name is not an identifier or an index.
+    ///
+    /// A field with a [`binding`] of the same name is written as a shorthand, e.g. `a` instead of `a: a`.
+    ///
+    /// [`binding`]: #method.binding
+    pub fn struct_pattern<N: AsRef<str>>(
+        path: impl AsRef<str>,
+        fields: impl IntoIterator<Item = (N, PatternBuilder)>,
+    ) -> Result<Self> {
+        Self::build_struct_pattern(path, fields, false)
+    }
+
+    /// A struct or struct variant pattern that ignores the remaining fields, e.g. `Foo::Bar { a, .. }`. See [`struct_pattern`].
+    ///
+    /// [`struct_pattern`]: #method.struct_pattern
+    pub fn struct_pattern_with_rest<N: AsRef<str>>(
+        path: impl AsRef<str>,
+        fields: impl IntoIterator<Item = (N, PatternBuilder)>,
+    ) -> Result<Self> {
+        Self::build_struct_pattern(path, fields, true)
+    }
+
+    fn build_struct_pattern<N: AsRef<str>>(
+        path: impl AsRef<str>,
+        fields: impl IntoIterator<Item = (N, PatternBuilder)>,
+        rest: bool,
+    ) -> Result<Self> {
+        Self::build(|b| {
+            b.push_parsed(path)?.group(Delimiter::Brace, |b| {
+                for (name, pattern) in fields {
+                    let name = name.as_ref();
+                    if pattern.binding.as_deref() != Some(name) {
+                        b.push(field_name(name)?).punct(':');
+                    }
+                    // the shorthand `a` is the binding itself, which keeps its provenance
+                    b.pattern(&pattern).punct(',');
+                }
+                if rest {
+                    b.puncts("..");
+                }
+                Ok(())
+            })?;
+            Ok(())
+        })
+    }
+
+    /// A pattern that binds every field of `fields`, e.g. `Foo { a, b }` or `Foo(field_0, field_1)`. The names of the bindings are given by [`Fields::bindings`] with the given `prefix`, so they match the names used by the code that uses the fields. Returns an error if `prefix` is not valid.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// use virtue::generate::PatternBuilder;
+    ///
+    /// let input = TokenStream::from_str("enum Foo { A(u8, u16), B { b: u8 } }").unwrap();
+    /// let (_, _, body) = Parse::new(input)?.into_generator();
+    /// # let variants = match body { Body::Enum(body) => body.variants, _ => unreachable!() };
+    /// let a = PatternBuilder::destructure("Self::A", variants[0].fields.as_ref().unwrap(), "field_")?;
+    /// assert_eq!(a.to_string(), "Self :: A (field_0 , field_1)");
+    /// let b = PatternBuilder::destructure("Self::B", variants[1].fields.as_ref().unwrap(), "field_")?;
+    /// assert_eq!(b.to_string(), "Self :: B { b , }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// Every binding is recorded as the [`provenance`] of its field, with the name of the field as label.
+    ///
+    /// [`Fields::bindings`]: ../parse/enum.Fields.html#method.bindings
+    /// [`provenance`]: struct.StreamBuilder.html#method.provenance
+    pub fn destructure(path: impl AsRef<str>, fields: &Fields, prefix: &str) -> Result<Self> {
+        let bindings = fields
+            .bindings(prefix)?
+            .into_iter()
+            .zip(fields.iter())
+            .enumerate()
+            .map(|(idx, (binding, (ident, field)))| {
+                let (label, span) = match ident {
+                    Some(ident) => (ident.to_string(), ident.span()),
+                    None => (idx.to_string(), field.span()),
+                };
+                Self::build_infallible(|b| {
+                    b.provenance(label, span, |b| {
+                        b.ident(binding.clone());
+                        Ok(())
+                    })
+                    .expect("Could not build pattern");
+                })
+                .with_binding(binding.to_string())
+            });
+        match fields {
+            Fields::Tuple(_) => Self::tuple_struct(path, bindings),
+            Fields::Struct(_) => {
+                let names: Vec<String> = fields
+                    .iter()
+                    .filter_map(|(ident, _)| ident.map(ToString::to_string))
+                    .collect();
+                Self::struct_pattern(path, names.into_iter().zip(bindings))
+            }
+        }
+    }
+
+    fn with_binding(mut self, binding: String) -> Self {
+        self.binding = Some(binding);
+        self
+    }
+
+    /// An or-pattern, e.g. `A | B`
+    pub fn or(patterns: impl IntoIterator<Item = PatternBuilder>) -> Self {
+        let mut count = 0;
+        let mut pattern = Self::build_infallible(|b| {
+            for pattern in patterns {
+                if count > 0 {
+                    b.punct('|');
+                }
+                b.pattern(&pattern);
+                count += 1;
+            }
+        });
+        pattern.is_or = count > 1;
+        pattern
+    }
+
+    fn append_nested(&self, builder: &mut StreamBuilder) {
+        if self.is_or {
+            builder
+                .group(Delimiter::Parenthesis, |b| {
+                    b.pattern(self);
+                    Ok(())
+                })
+                .expect("Could not build pattern");
+        } else {
+            builder.pattern(self);
+        }
+    }
+}
+
+/// Parse `name` as the identifier of a binding, e.g. `a` or `r#type`
+pub(super) fn binding_ident(name: &str) -> Result<Ident> {
+    parse_ident(name)?.ok_or_else(|| {
+        Error::custom(format!(
+            "Invalid binding `{}`, expected an identifier like `a`",
+            name
+        ))
+    })
+}
+
+/// `name` as a single identifier, or `None` if it is anything else
+fn parse_ident(name: &str) -> Result<Option<Ident>> {
+    let mut builder = StreamBuilder::new();
+    builder.push_parsed(name)?;
+    let mut tokens = builder.stream.into_iter();
+    match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Ident(ident)), None) => Ok(Some(ident)),
+        _ => Ok(None),
+    }
+}
+
+/// The name of a field in a struct pattern, an identifier like `a` or an index like `0`
+fn field_name(name: &str) -> Result<TokenTree> {
+    if let Ok(index) = name.parse::<usize>() {
+        return Ok(Literal::usize_unsuffixed(index).into());
+    }
+    match parse_ident(name)? {
+        Some(ident) => Ok(ident.into()),
+        None => Err(Error::custom(format!(
+            "Invalid field name `{}`, expected an identifier like `a` or an index like `0`",
+            name
+        ))),
+    }
+}
+
+/// `a, b, c`, with a trailing comma for a single item if `single_trailing_comma` is set
+fn append_list(
+    builder: &mut StreamBuilder,
+    patterns: impl IntoIterator<Item = PatternBuilder>,
+    single_trailing_comma: bool,
+) {
+    let mut count = 0;
+    for (idx, pattern) in patterns.into_iter().enumerate() {
+        if idx > 0 {
+            builder.punct(',');
+        }
+        builder.pattern(&pattern);
+        count += 1;
+    }
+    if count == 1 && single_trailing_comma {
+        builder.punct(',');
+    }
+}
+
+impl fmt::Display for PatternBuilder {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.stream.stream)
+    }
+}
+
+impl StreamBuilder {
+    /// Add the given pattern to the stream. See [`PatternBuilder`] for more information.
+    ///
+    /// The [`provenance`] records of the parts of the pattern are kept.
+    ///
+    /// [`provenance`]: #method.provenance
+    pub fn pattern(&mut self, pattern: &PatternBuilder) -> &mut Self {
+        self.append(pattern.stream.clone())
+    }
+}
+
+#[test]
+fn test_pattern_builder() {
+    assert_eq!(PatternBuilder::wildcard().to_string(), "_");
+    assert_eq!(
+        PatternBuilder::tuple([
+            PatternBuilder::binding("a").unwrap(),
+            PatternBuilder::rest()
+        ])
+        .to_string(),
+        "(a , ..)"
+    );
+    assert_eq!(
+        PatternBuilder::reference_mut(PatternBuilder::mut_binding("a").unwrap()).to_string(),
+        "& mut mut a"
+    );
+    assert_eq!(
+        PatternBuilder::struct_pattern("Foo", [("a", PatternBuilder::binding("b").unwrap())])
+            .unwrap()
+            .to_string(),
+        "Foo { a : b , }"
+    );
+    assert_eq!(
+        PatternBuilder::struct_pattern(
+            "Foo",
+            [
+                ("0", PatternBuilder::wildcard()),
+                ("1", PatternBuilder::binding("b").unwrap())
+            ]
+        )
+        .unwrap()
+        .to_string(),
+        "Foo { 0 : _ , 1 : b , }"
+    );
+    for name in ["", "a b", "1x", "-1", "a::b"] {
+        assert!(
+            PatternBuilder::struct_pattern("Foo", [(name, PatternBuilder::wildcard())]).is_err(),
+            "{}",
+            name
+        );
+    }
+    assert_eq!(
+        PatternBuilder::struct_pattern::<&str>("Foo", [])
+            .unwrap()
+            .to_string(),
+        "Foo { }"
+    );
+    assert_eq!(
+        PatternBuilder::or([
+            PatternBuilder::path("Foo::A").unwrap(),
+            PatternBuilder::tuple_struct("Foo::B", [PatternBuilder::wildcard()]).unwrap(),
+        ])
+        .to_string(),
+        "Foo :: A | Foo :: B (_)"
+    );
+    assert_eq!(
+        PatternBuilder::reference(PatternBuilder::or([PatternBuilder::wildcard()])).to_string(),
+        "& _"
+    );
+    assert!(PatternBuilder::path("Foo(").is_err());
+
+    assert_eq!(
+        PatternBuilder::binding("r#type").unwrap().to_string(),
+        "r#type"
+    );
+    assert!(PatternBuilder::binding("").is_err());
+    assert!(PatternBuilder::binding("a b").is_err());
+    assert!(PatternBuilder::mut_binding("1").is_err());
+    assert!(PatternBuilder::ref_binding("a::b").is_err());
+    assert!(PatternBuilder::binding_at("(", PatternBuilder::wildcard()).is_err());
+}
+
+#[test]
+fn test_pattern_provenance() {
+    use crate::parse::Parse;
+
+    let input = crate::token_stream("enum Foo { A(u8, u16), B { b: u8 } }").collect();
+    let variants = match Parse::new(input).unwrap() {
+        Parse::Enum { body, .. } => body.variants,
+        _ => unreachable!(),
+    };
+    let a = PatternBuilder::destructure("Self::A", variants[0].fields.as_ref().unwrap(), "field_")
+        .unwrap();
+    let b = PatternBuilder::destructure("Self::B", variants[1].fields.as_ref().unwrap(), "field_")
+        .unwrap();
+
+    let mut builder = StreamBuilder::new();
+    builder.pattern(&PatternBuilder::or([a, b]));
+    let provenance: Vec<(String, String)> = builder
+        .provenance
+        .iter()
+        .map(|p| (p.label.clone(), p.tokens.to_string()))
+        .collect();
+    assert_eq!(
+        provenance,
+        [
+            ("0".to_owned(), "field_0".to_owned()),
+            ("1".to_owned(), "field_1".to_owned()),
+            ("b".to_owned(), "b".to_owned()),
+        ]
+    );
+}
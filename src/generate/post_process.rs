@@ -0,0 +1,121 @@
+use crate::parse::utils::ident_eq;
+use crate::prelude::{Group, Spacing, TokenStream, TokenTree};
+use crate::Result;
+
+/// A pass over the final [`TokenStream`], see [`Generator::add_post_process`].
+///
+/// [`Generator::add_post_process`]: struct.Generator.html#method.add_post_process
+pub(super) type PostProcess = Box<dyn FnOnce(TokenStream) -> Result<TokenStream>>;
+
+/// Replace every absolute path to the crate `crate_name`, like the `::bincode` in `::bincode::Encode`, with `path`. The tokens of `path` get the span of the replaced crate name.
+///
+/// Paths that only end in `crate_name`, like `foo::bincode` or `crate::bincode`, are not replaced.
+pub(super) fn rewrite_crate_path(
+    stream: TokenStream,
+    crate_name: &str,
+    path: &TokenStream,
+) -> TokenStream {
+    let tokens: Vec<TokenTree> = stream.into_iter().collect();
+    let mut result = Vec::with_capacity(tokens.len());
+    let mut idx = 0;
+    while idx < tokens.len() {
+        if is_crate_path(&tokens, idx, crate_name) {
+            let span = tokens[idx + 2].span();
+            result.extend(path.clone().into_iter().map(|mut token| {
+                token.set_span(span);
+                token
+            }));
+            idx += 3;
+            continue;
+        }
+        match &tokens[idx] {
+            TokenTree::Group(group) => {
+                let stream = rewrite_crate_path(group.stream(), crate_name, path);
+                let mut new_group = Group::new(group.delimiter(), stream);
+                new_group.set_span(group.span());
+                result.push(TokenTree::Group(new_group));
+            }
+            token => result.push(token.clone()),
+        }
+        idx += 1;
+    }
+    result.into_iter().collect()
+}
+
+/// Returns `true` if `tokens[idx..]` starts with `::<crate_name>` at the start of a path
+fn is_crate_path(tokens: &[TokenTree], idx: usize, crate_name: &str) -> bool {
+    let is_punct = |idx: usize, c: char| matches!(tokens.get(idx), Some(TokenTree::Punct(punct)) if punct.as_char() == c);
+    let is_colons = matches!(&tokens[idx], TokenTree::Punct(punct) if punct.as_char() == ':' && punct.spacing() == Spacing::Joint)
+        && is_punct(idx + 1, ':');
+    let is_crate_name =
+        matches!(tokens.get(idx + 2), Some(TokenTree::Ident(ident)) if ident_eq(ident, crate_name));
+    if !is_colons || !is_crate_name {
+        return false;
+    }
+    if idx == 0 {
+        return true;
+    }
+    // `foo::bincode` and `<T>::bincode` continue a path, but `as ::bincode`, `-> ::bincode` and `=> ::bincode` do not
+    let continues_path = match &tokens[idx - 1] {
+        TokenTree::Ident(ident) => !is_keyword(&ident.to_string()),
+        TokenTree::Punct(punct) if punct.as_char() == '>' => {
+            idx < 2 || !(is_punct(idx - 2, '-') || is_punct(idx - 2, '='))
+        }
+        _ => false,
+    };
+    !continues_path
+}
+
+/// The keywords that can be followed by a path
+fn is_keyword(ident: &str) -> bool {
+    matches!(
+        ident,
+        "as" | "box"
+            | "const"
+            | "dyn"
+            | "else"
+            | "for"
+            | "if"
+            | "impl"
+            | "in"
+            | "let"
+            | "match"
+            | "move"
+            | "mut"
+            | "pub"
+            | "ref"
+            | "return"
+            | "static"
+            | "type"
+            | "unsafe"
+            | "use"
+            | "where"
+            | "while"
+    )
+}
+
+#[test]
+fn test_rewrite_crate_path() {
+    use crate::token_stream;
+
+    let rewrite = |input: &str| {
+        let path = token_stream("::my_crate::reexport::bincode").collect();
+        rewrite_crate_path(token_stream(input).collect(), "bincode", &path).to_string()
+    };
+    assert_eq!(
+        rewrite("impl ::bincode::Encode for Foo { fn a() -> ::bincode::Result { <T as ::bincode::Encode>::x() } }"),
+        "impl :: my_crate :: reexport :: bincode :: Encode for Foo { \
+         fn a () -> :: my_crate :: reexport :: bincode :: Result { \
+         < T as :: my_crate :: reexport :: bincode :: Encode >:: x () } }"
+    );
+    assert_eq!(
+        rewrite("match a { _ => ::bincode::x(), } let b: ::bincode::B = c;"),
+        "match a { _ => :: my_crate :: reexport :: bincode :: x () , } \
+         let b : :: my_crate :: reexport :: bincode :: B = c ;"
+    );
+    // only absolute paths to the crate are rewritten
+    assert_eq!(
+        rewrite("foo::bincode::A; crate::bincode::B; <T>::bincode; ::bincode_derive::C; bincode::D"),
+        "foo :: bincode :: A ; crate :: bincode :: B ; < T >:: bincode ; :: bincode_derive :: C ; bincode :: D"
+    );
+}
@@ -0,0 +1,26 @@
+use crate::prelude::{Span, TokenStream};
+use std::fmt;
+
+/// Records which part of the input generated a fragment of the output. See [`StreamBuilder::provenance`].
+///
+/// [`StreamBuilder::provenance`]: struct.StreamBuilder.html#method.provenance
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Provenance {
+    /// A description of the input item, e.g. `Foo::a` for the field `a` of `Foo`
+    pub label: String,
+    /// The span of the input item
+    pub span: Span,
+    /// The tokens that were generated for the input item
+    pub tokens: TokenStream,
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "{} ({:?}):\n    {}",
+            self.label, self.span, self.tokens
+        )
+    }
+}
@@ -0,0 +1,199 @@
+use crate::parse::utils::ident_eq;
+use crate::prelude::{Delimiter, TokenStream, TokenTree};
+use std::fmt;
+
+/// The environment variable that enables printing a [`GenerationStats`] summary for every [`Generator::finish`].
+///
+/// [`Generator::finish`]: struct.Generator.html#method.finish
+pub const STATS_ENV_VAR: &str = "VIRTUE_STATS";
+
+/// Statistics about the code that a [`Generator`] produced. See [`Generator::on_finish`].
+///
+/// Tokens are counted recursively: a group like `{ ... }` counts as one token plus the tokens inside of it.
+///
+/// [`Generator`]: struct.Generator.html
+/// [`Generator::on_finish`]: struct.Generator.html#method.on_finish
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct GenerationStats {
+    /// The name of the struct or enum that the code was generated for
+    pub target_name: String,
+    /// The amount of `impl` blocks, including the ones nested in generated modules
+    pub impl_count: usize,
+    /// The amount of functions, including the ones inside of `impl` blocks
+    pub fn_count: usize,
+    /// The total amount of generated tokens
+    pub total_tokens: usize,
+    /// The top-level items that were generated, in order
+    pub items: Vec<ItemStats>,
+}
+
+/// Statistics of a single top-level generated item. See [`GenerationStats`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ItemStats {
+    /// The header of the item without its attributes, e.g. `impl Clone for Foo`
+    pub header: String,
+    /// The amount of tokens in this item, including its attributes
+    pub tokens: usize,
+}
+
+impl GenerationStats {
+    pub(crate) fn new(target_name: String, stream: &TokenStream) -> Self {
+        let mut stats = Self {
+            target_name,
+            impl_count: 0,
+            fn_count: 0,
+            total_tokens: 0,
+            items: Vec::new(),
+        };
+        stats.count(stream);
+
+        let mut header = Vec::new();
+        let mut item_tokens = 0;
+        let mut tokens = stream.clone().into_iter().peekable();
+        while let Some(token) = tokens.next() {
+            item_tokens += token_count(&token);
+            let is_attribute = header.is_empty()
+                && match &token {
+                    TokenTree::Punct(p) => p.as_char() == '#' || p.as_char() == '!',
+                    TokenTree::Group(g) => g.delimiter() == Delimiter::Bracket,
+                    _ => false,
+                };
+            let is_end = match &token {
+                TokenTree::Group(g) => g.delimiter() == Delimiter::Brace,
+                TokenTree::Punct(p) => p.as_char() == ';',
+                _ => false,
+            };
+            if is_end {
+                // `struct Foo(u8);` or `const FOO: Foo = Foo { .. };`
+                if let Some(TokenTree::Punct(p)) = tokens.peek() {
+                    if p.as_char() == ';' {
+                        item_tokens += 1;
+                        tokens.next();
+                    }
+                }
+                stats.items.push(ItemStats {
+                    header: std::mem::take(&mut header)
+                        .into_iter()
+                        .collect::<TokenStream>()
+                        .to_string(),
+                    tokens: std::mem::take(&mut item_tokens),
+                });
+            } else if !is_attribute {
+                header.push(token);
+            }
+        }
+        if item_tokens > 0 {
+            stats.items.push(ItemStats {
+                header: header.into_iter().collect::<TokenStream>().to_string(),
+                tokens: item_tokens,
+            });
+        }
+        stats
+    }
+
+    fn count(&mut self, stream: &TokenStream) {
+        let mut previous: Option<TokenTree> = None;
+        let mut tokens = stream.clone().into_iter().peekable();
+        while let Some(token) = tokens.next() {
+            self.total_tokens += 1;
+            match &token {
+                TokenTree::Group(group) => self.count(&group.stream()),
+                TokenTree::Ident(ident) => match ident.to_string().as_str() {
+                    // `impl` at the start of an item, and not e.g. `-> impl Trait`
+                    "impl" if is_item_start(previous.as_ref()) => self.impl_count += 1,
+                    // `fn foo`, and not a function pointer like `fn(u8)`
+                    "fn" if matches!(tokens.peek(), Some(TokenTree::Ident(_))) => {
+                        self.fn_count += 1
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+            previous = Some(token);
+        }
+    }
+
+    /// Print a summary of these statistics to stderr.
+    pub fn print_summary(&self) {
+        eprintln!("{}", self);
+    }
+}
+
+impl fmt::Display for GenerationStats {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "virtue: {} generated {} tokens, {} impl(s), {} fn(s)",
+            self.target_name, self.total_tokens, self.impl_count, self.fn_count
+        )?;
+        for item in &self.items {
+            write!(fmt, "\n  {:>6} tokens: {}", item.tokens, item.header)?;
+        }
+        Ok(())
+    }
+}
+
+fn is_item_start(previous: Option<&TokenTree>) -> bool {
+    match previous {
+        None => true,
+        Some(TokenTree::Group(g)) => matches!(g.delimiter(), Delimiter::Brace | Delimiter::Bracket),
+        Some(TokenTree::Punct(p)) => p.as_char() == ';',
+        Some(TokenTree::Ident(i)) => ident_eq(i, "unsafe"),
+        Some(TokenTree::Literal(_)) => false,
+    }
+}
+
+fn token_count(token: &TokenTree) -> usize {
+    match token {
+        TokenTree::Group(group) => {
+            1 + group
+                .stream()
+                .into_iter()
+                .map(|t| token_count(&t))
+                .sum::<usize>()
+        }
+        _ => 1,
+    }
+}
+
+#[test]
+fn test_generation_stats() {
+    use std::str::FromStr;
+
+    let stream = TokenStream::from_str(
+        r#"
+        #[automatically_derived]
+        impl Foo for Bar {
+            fn foo(&self, f: fn(u8) -> u8) -> impl Iterator<Item = u8> { todo!() }
+            fn bar() {}
+        }
+        struct Baz(u8);
+        const BAZ: Baz = Baz { 0: 5 };
+        mod inner {
+            unsafe impl Send for Baz {}
+        }
+        "#,
+    )
+    .unwrap();
+    let stats = GenerationStats::new(String::from("Bar"), &stream);
+    assert_eq!(stats.impl_count, 2);
+    assert_eq!(stats.fn_count, 2);
+    let headers: Vec<&str> = stats.items.iter().map(|i| i.header.as_str()).collect();
+    assert_eq!(
+        headers,
+        [
+            "impl Foo for Bar",
+            "struct Baz (u8)",
+            "const BAZ : Baz = Baz",
+            "mod inner"
+        ]
+    );
+    assert_eq!(
+        stats.items.iter().map(|i| i.tokens).sum::<usize>(),
+        stats.total_tokens
+    );
+    // `struct Baz(u8);` is 5 tokens, including the `u8` inside of the group
+    assert_eq!(stats.items[1].tokens, 5);
+}
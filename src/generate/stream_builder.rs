@@ -1,14 +1,18 @@
+use super::pattern::binding_ident;
+use super::{FnSelfArg, PatternBuilder, Provenance, TypeTokens};
 use crate::prelude::{
     Delimiter, Group, Ident, LexError, Literal, Punct, Result, Spacing, Span, TokenStream,
     TokenTree,
 };
+use std::fmt;
 use std::str::FromStr;
 
 /// A helper struct build around a [TokenStream] to make it easier to build code.
 #[must_use]
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct StreamBuilder {
     pub(crate) stream: TokenStream,
+    pub(crate) provenance: Vec<Provenance>,
 }
 
 impl StreamBuilder {
@@ -16,6 +20,7 @@ impl StreamBuilder {
     pub fn new() -> Self {
         Self {
             stream: TokenStream::new(),
+            provenance: Vec::new(),
         }
     }
 
@@ -28,6 +33,7 @@ impl StreamBuilder {
     /// Append another StreamBuilder to the current StreamBuilder.
     pub fn append(&mut self, builder: StreamBuilder) -> &mut Self {
         self.stream.extend(builder.stream);
+        self.provenance.extend(builder.provenance);
         self
     }
 
@@ -49,6 +55,22 @@ impl StreamBuilder {
         Ok(self)
     }
 
+    /// The path of the `core` crate, `::core`. Code that only uses items of `core` works in crates with and without `std`, e.g. `format!("{}::fmt::Debug", StreamBuilder::core_path())`.
+    pub fn core_path() -> &'static str {
+        "::core"
+    }
+
+    /// The path of the crate that contains `Box`, `String` and `Vec`: `::alloc` in a `#![no_std]` crate, `::std` otherwise. See [`Generator::no_std`].
+    ///
+    /// [`Generator::no_std`]: struct.Generator.html#method.no_std
+    pub fn alloc_path(no_std: bool) -> &'static str {
+        if no_std {
+            "::alloc"
+        } else {
+            "::std"
+        }
+    }
+
     /// Push a single ident to the stream. An ident is any word that a code file may contain, e.g. `fn`, `struct`, `where`, names of functions and structs, etc.
     pub fn ident(&mut self, ident: Ident) -> &mut Self {
         self.stream.extend([TokenTree::Ident(ident)]);
@@ -56,11 +78,11 @@ impl StreamBuilder {
     }
 
     /// Push a single ident to the stream. An ident is any word that a code file may contain, e.g. `fn`, `struct`, `where`, names of functions and structs, etc.
+    ///
+    /// Raw identifiers like `r#type` are supported as well.
     pub fn ident_str(&mut self, ident: impl AsRef<str>) -> &mut Self {
-        self.stream.extend([TokenTree::Ident(Ident::new(
-            ident.as_ref(),
-            Span::call_site(),
-        ))]);
+        self.stream
+            .extend([TokenTree::Ident(ident_from_str(ident.as_ref()))]);
         self
     }
 
@@ -75,9 +97,61 @@ impl StreamBuilder {
         inner(&mut stream)?;
         self.stream
             .extend([TokenTree::Group(Group::new(delim, stream.stream))]);
+        self.provenance.extend(stream.provenance);
         Ok(self)
     }
 
+    /// Add the tokens of the `inner` callback, and record that they were generated for the input item at `span`, e.g. a field or a variant.
+    ///
+    /// The records are kept when this builder is added to a parent builder, and can be inspected with [`Generator::provenance`] or written to a file with [`Generator::export_provenance_to_file`]. This makes it easier to find out which part of the input generated which part of a large derive.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = TokenStream::from_str("struct Foo { a: u8 }").unwrap();
+    /// let (mut generator, _, body) = Parse::new(input)?.into_generator();
+    /// # let fields = match body { Body::Struct(body) => body.fields.unwrap(), _ => unreachable!() };
+    /// generator
+    ///     .impl_for("Encode")
+    ///     .generate_fn("encode")
+    ///     .with_self_arg(FnSelfArg::RefSelf)
+    ///     .body(|b| {
+    ///         for (ident, _) in fields.iter() {
+    ///             let ident = ident.unwrap();
+    ///             b.provenance(format!("Foo::{}", ident), ident.span(), |b| {
+    ///                 b.push_parsed(format!("self.{}.encode();", ident))?;
+    ///                 Ok(())
+    ///             })?;
+    ///         }
+    ///         Ok(())
+    ///     })?;
+    /// assert_eq!(generator.provenance()[0].label, "Foo::a");
+    /// assert_eq!(generator.provenance()[0].tokens.to_string(), "self . a . encode () ;");
+    /// # generator.finish()?;
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`Generator::provenance`]: struct.Generator.html#method.provenance
+    /// [`Generator::export_provenance_to_file`]: struct.Generator.html#method.export_provenance_to_file
+    pub fn provenance<FN>(
+        &mut self,
+        label: impl Into<String>,
+        span: Span,
+        inner: FN,
+    ) -> crate::Result<&mut Self>
+    where
+        FN: FnOnce(&mut StreamBuilder) -> crate::Result<()>,
+    {
+        let mut builder = StreamBuilder::new();
+        inner(&mut builder)?;
+        self.provenance.push(Provenance {
+            label: label.into(),
+            span,
+            tokens: builder.stream.clone(),
+        });
+        Ok(self.append(builder))
+    }
+
     /// Add a single punctuation to the stream. Puncts are single-character tokens like `.`, `<`, `#`, etc
     ///
     /// Note that this should not be used for multi-punct constructions like `::` or `->`. For that use [`puncts`] instead.
@@ -148,6 +222,420 @@ impl StreamBuilder {
         self
     }
 
+    /// Add a byte string literal (`&'static [u8; N]`) to the stream, e.g. `b"foo"`.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator
+    ///     .r#impl()
+    ///     .generate_const("TABLE", "&[u8]")
+    ///     .with_value(|b| {
+    ///         b.lit_byte_string(&[b'a', 0, 255]);
+    ///         Ok(())
+    ///     })?;
+    /// # generator.assert_eq("impl Foo { const TABLE : & [u8] = b\"a\\0\\xFF\" ; }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn lit_byte_string(&mut self, bytes: &[u8]) -> &mut Self {
+        self.stream
+            .extend([TokenTree::Literal(Literal::byte_string(bytes))]);
+        self
+    }
+
+    /// Add an array of `usize` values to the stream, e.g. `[1, 2, 3]`.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator
+    ///     .r#impl()
+    ///     .generate_const("OFFSETS", "[usize; 3]")
+    ///     .with_value(|b| {
+    ///         b.lit_array_usize(&[0, 4, 10]);
+    ///         Ok(())
+    ///     })?;
+    /// # generator.assert_eq("impl Foo { const OFFSETS : [usize ; 3] = [0 , 4 , 10] ; }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn lit_array_usize(&mut self, values: &[usize]) -> &mut Self {
+        let mut stream = TokenStream::new();
+        for (idx, value) in values.iter().enumerate() {
+            if idx > 0 {
+                stream.extend([TokenTree::Punct(Punct::new(',', Spacing::Alone))]);
+            }
+            stream.extend([TokenTree::Literal(Literal::usize_unsuffixed(*value))]);
+        }
+        self.stream
+            .extend([TokenTree::Group(Group::new(Delimiter::Bracket, stream))]);
+        self
+    }
+
+    /// Add an array to the stream, e.g. `[a, b, c]`. The `item` callback is called for every value in `values` to add the tokens of that item.
+    ///
+    /// This builds the tokens directly, which is a lot faster than building a large string and passing it to [`push_parsed`].
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// let hashes: Vec<u32> = vec![0xdead, 0xbeef];
+    /// generator
+    ///     .r#impl()
+    ///     .generate_const("HASHES", "[(&str, u32); 2]")
+    ///     .with_value(|b| {
+    ///         b.lit_array(hashes.iter().enumerate(), |b, (idx, hash)| {
+    ///             b.group(Delimiter::Parenthesis, |b| {
+    ///                 b.lit_str(format!("item{}", idx))
+    ///                     .punct(',')
+    ///                     .push(Literal::u32_suffixed(*hash));
+    ///                 Ok(())
+    ///             })?;
+    ///             Ok(())
+    ///         })?;
+    ///         Ok(())
+    ///     })?;
+    /// # generator.assert_eq("impl Foo { const HASHES : [(& str , u32) ; 2] = [(\"item0\" , 57005u32) , (\"item1\" , 48879u32)] ; }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`push_parsed`]: #method.push_parsed
+    pub fn lit_array<ITER, FN>(&mut self, values: ITER, mut item: FN) -> crate::Result<&mut Self>
+    where
+        ITER: IntoIterator,
+        FN: FnMut(&mut StreamBuilder, ITER::Item) -> crate::Result,
+    {
+        self.group(Delimiter::Bracket, |b| {
+            for (idx, value) in values.into_iter().enumerate() {
+                if idx > 0 {
+                    b.punct(',');
+                }
+                item(b, value)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Add a `match` on `self` without any arms. This is the body of any function taking `self` on an enum without variants, e.g. `enum Never {}`.
+    ///
+    /// This will be `match *self {}` for `&self` and `&mut self`, and `match self {}` for `self` and `mut self`. An error is returned for [`FnSelfArg::None`].
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Never");
+    /// generator
+    ///     .r#impl()
+    ///     .generate_fn("get")
+    ///     .with_self_arg(FnSelfArg::RefSelf)
+    ///     .with_return_type("u32")
+    ///     .body(|b| {
+    ///         b.match_uninhabited(&FnSelfArg::RefSelf)?;
+    ///         Ok(())
+    ///     })?;
+    /// # generator.assert_eq("impl Never { fn get (& self ,) ->u32 { match * self { } } }");
+    /// # let mut generator = Generator::with_name("Never");
+    /// # generator.r#impl().generate_fn("get").body(|b| {
+    /// #     assert!(b.match_uninhabited(&FnSelfArg::None).is_err());
+    /// #     Ok(())
+    /// # })?;
+    /// # generator.assert_eq("impl Never { fn get () { } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// See [`EnumBody::is_uninhabited`].
+    ///
+    /// [`FnSelfArg::None`]: enum.FnSelfArg.html#variant.None
+    /// [`EnumBody::is_uninhabited`]: ../parse/struct.EnumBody.html#method.is_uninhabited
+    pub fn match_uninhabited(&mut self, self_arg: &FnSelfArg) -> crate::Result<&mut Self> {
+        let deref = match self_arg {
+            FnSelfArg::None => return Err(crate::Error::custom(
+                "Can not match on an uninhabited `self` in a function without a `self` argument",
+            )),
+            FnSelfArg::TakeSelf | FnSelfArg::MutTakeSelf => false,
+            FnSelfArg::RefSelf | FnSelfArg::MutSelf => true,
+        };
+        self.ident_str("match");
+        if deref {
+            self.punct('*');
+        }
+        self.ident_str("self");
+        self.group(Delimiter::Brace, |_| Ok(()))
+    }
+
+    /// Add a `_ => body,` arm to a `match` if `condition` is `true`.
+    ///
+    /// A match on an enum of another crate that is marked `#[non_exhaustive]` does not compile without a wildcard arm, while the same arm on a local enum, or on one that is not `#[non_exhaustive]`, causes an `unreachable_patterns` warning. Use [`AttributeAccess::has_non_exhaustive`] on the attributes of the enum to decide if it is needed:
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// // The definition of an enum of another crate, e.g. passed to an attribute macro
+    /// let input = TokenStream::from_str("#[non_exhaustive] enum Kind { A, B }").unwrap();
+    /// let attributes = match Parse::new(input)? {
+    ///     Parse::Enum { attributes, .. } => attributes,
+    ///     _ => unreachable!(),
+    /// };
+    /// let is_foreign = true;
+    ///
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator
+    ///     .r#impl()
+    ///     .generate_fn("kind_index")
+    ///     .with_arg("kind", "Kind")
+    ///     .with_return_type("u8")
+    ///     .body(|b| {
+    ///         b.ident_str("match").ident_str("kind").group(Delimiter::Brace, |b| {
+    ///             b.push_parsed("Kind::A => 0, Kind::B => 1,")?;
+    ///             b.wildcard_arm_if(attributes.has_non_exhaustive() && is_foreign, |b| {
+    ///                 b.push_parsed("2")?;
+    ///                 Ok(())
+    ///             })?;
+    ///             Ok(())
+    ///         })?;
+    ///         Ok(())
+    ///     })?;
+    /// # generator.assert_eq("impl Foo { fn kind_index (kind : Kind) ->u8 { \
+    /// #     match kind { Kind :: A => 0 , Kind :: B => 1 , _ =>2 , } } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`AttributeAccess::has_non_exhaustive`]: ../parse/trait.AttributeAccess.html#method.has_non_exhaustive
+    pub fn wildcard_arm_if<FN>(&mut self, condition: bool, body: FN) -> crate::Result<&mut Self>
+    where
+        FN: FnOnce(&mut StreamBuilder) -> crate::Result<()>,
+    {
+        if condition {
+            self.ident_str("_").puncts("=>");
+            body(self)?;
+            self.punct(',');
+        }
+        Ok(self)
+    }
+
+    /// Add a `let name = value;` statement. The `value` closure writes the expression, the `=` and `;` are added by this function.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator
+    ///     .r#impl()
+    ///     .generate_fn("foo")
+    ///     .body(|b| {
+    ///         b.let_binding("a", |b| {
+    ///             b.push_parsed("decode(reader)?")?;
+    ///             Ok(())
+    ///         })?
+    ///         .let_typed("b", "u32", |b| {
+    ///             b.push_parsed("5")?;
+    ///             Ok(())
+    ///         })?;
+    ///         Ok(())
+    ///     })?;
+    /// # generator.assert_eq("impl Foo { fn foo () { let a = decode (reader) ? ; let b : u32 = 5 ; } }");
+    /// # let mut generator = Generator::with_name("Foo");
+    /// # generator.r#impl().generate_fn("foo").body(|b| {
+    /// #     assert!(b.let_typed("b", "Vec<(u32", |_| Ok(())).is_err());
+    /// #     assert!(b.let_binding("a", |b| { b.push_parsed("(")?; Ok(()) }).is_err());
+    /// #     assert!(b.let_binding("mut a", |_| Ok(())).is_err());
+    /// #     assert!(b.let_typed("1x", "u32", |_| Ok(())).is_err());
+    /// #     Ok(())
+    /// # })?;
+    /// # generator.assert_eq("impl Foo { fn foo () { } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// Returns an error if `name` is not an identifier like `a`. Nothing is added to the stream if the `value` closure returns an error.
+    pub fn let_binding<FN>(&mut self, name: impl AsRef<str>, value: FN) -> crate::Result<&mut Self>
+    where
+        FN: FnOnce(&mut StreamBuilder) -> crate::Result<()>,
+    {
+        let mut builder = StreamBuilder::new();
+        builder
+            .ident_str("let")
+            .ident(binding_ident(name.as_ref())?)
+            .let_value(value)?;
+        Ok(self.append(builder))
+    }
+
+    /// Add a `let name: ty = value;` statement. Returns an error if `name` is not an identifier. Nothing is added to the stream if `ty` can not be parsed or the `value` closure returns an error. See [`let_binding`].
+    ///
+    /// [`let_binding`]: #method.let_binding
+    pub fn let_typed<FN>(
+        &mut self,
+        name: impl AsRef<str>,
+        ty: impl Into<TypeTokens>,
+        value: FN,
+    ) -> crate::Result<&mut Self>
+    where
+        FN: FnOnce(&mut StreamBuilder) -> crate::Result<()>,
+    {
+        let mut builder = StreamBuilder::new();
+        builder
+            .ident_str("let")
+            .ident(binding_ident(name.as_ref())?)
+            .punct(':');
+        ty.into().append_to(&mut builder)?;
+        builder.let_value(value)?;
+        Ok(self.append(builder))
+    }
+
+    /// Add a `let pattern = value else { else_body };` statement.
+    ///
+    /// The `else_body` has to diverge, e.g. with `return` or `panic!()`. Nothing is added to the stream if either closure returns an error. Note that `let else` requires rust 1.65 or newer in the crate that uses your derive.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use virtue::generate::PatternBuilder;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator
+    ///     .r#impl()
+    ///     .generate_fn("foo")
+    ///     .body(|b| {
+    ///         let pattern = PatternBuilder::tuple_struct("Some", [PatternBuilder::binding("a")?])?;
+    ///         b.let_else(
+    ///             &pattern,
+    ///             |b| {
+    ///                 b.push_parsed("self.a")?;
+    ///                 Ok(())
+    ///             },
+    ///             |b| {
+    ///                 b.push_parsed("return")?;
+    ///                 Ok(())
+    ///             },
+    ///         )?;
+    ///         Ok(())
+    ///     })?;
+    /// # generator.assert_eq("impl Foo { fn foo () { let Some (a) = self . a else { return } ; } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn let_else<VALUE, ELSE>(
+        &mut self,
+        pattern: &PatternBuilder,
+        value: VALUE,
+        else_body: ELSE,
+    ) -> crate::Result<&mut Self>
+    where
+        VALUE: FnOnce(&mut StreamBuilder) -> crate::Result<()>,
+        ELSE: FnOnce(&mut StreamBuilder) -> crate::Result<()>,
+    {
+        let mut builder = StreamBuilder::new();
+        builder.ident_str("let").pattern(pattern).punct('=');
+        value(&mut builder)?;
+        builder
+            .ident_str("else")
+            .group(Delimiter::Brace, else_body)?
+            .punct(';');
+        Ok(self.append(builder))
+    }
+
+    /// Add a fully-qualified call to a trait method: `<ty as trait_path>::method(args)`. The `args` closure writes the arguments, including the `,` between them.
+    ///
+    /// Generated code should prefer this over `value.method()`, because a method call would call an inherent method or a method of another trait with the same name instead, if the type has one.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator
+    ///     .impl_for("Encode")
+    ///     .generate_fn("encode")
+    ///     .with_self_arg(FnSelfArg::RefSelf)
+    ///     .with_arg("encoder", "&mut E")
+    ///     .body(|b| {
+    ///         b.qualified_call("u32", "::bincode::Encode", "encode", |b| {
+    ///             b.push_parsed("&self.a, encoder")?;
+    ///             Ok(())
+    ///         })?;
+    ///         Ok(())
+    ///     })?;
+    /// // <u32 as ::bincode::Encode>::encode(&self.a, encoder)
+    /// # generator.assert_eq("impl Encode for Foo { fn encode (& self , encoder : & mut E) { < u32 as :: bincode :: Encode > ::encode (& self . a , encoder) } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn qualified_call<FN>(
+        &mut self,
+        ty: impl Into<TypeTokens>,
+        trait_path: impl AsRef<str>,
+        method: impl AsRef<str>,
+        args: FN,
+    ) -> crate::Result<&mut Self>
+    where
+        FN: FnOnce(&mut StreamBuilder) -> crate::Result<()>,
+    {
+        self.punct('<');
+        ty.into().append_to(self)?;
+        self.ident_str("as")
+            .push_parsed(trait_path)?
+            .punct('>')
+            .puncts("::")
+            .ident_str(method)
+            .group(Delimiter::Parenthesis, args)
+    }
+
+    /// Add a macro invocation: `path!(args)`, `path![args]` or `path!{args}`, depending on `delim`. The `args` closure writes the arguments of the macro.
+    ///
+    /// Use an absolute path like `::core::panic` for macros of `core` and `std`, so the generated code does not call a macro with the same name that the user defined.
+    ///
+    /// An error is returned for `Delimiter::None`, which is not a valid macro invocation. Nothing is added to the stream if `path` can not be parsed or the `args` closure returns an error.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator
+    ///     .r#impl()
+    ///     .generate_fn("names")
+    ///     .with_return_type("Vec<&'static str>")
+    ///     .body(|b| {
+    ///         b.macro_call("::core::debug_assert", Delimiter::Parenthesis, |b| {
+    ///             b.push_parsed("::core::matches!(self, Foo { .. })")?;
+    ///             Ok(())
+    ///         })?
+    ///         .punct(';');
+    ///         b.macro_call("vec", Delimiter::Bracket, |b| {
+    ///             b.lit_str("a").punct(',').lit_str("b");
+    ///             Ok(())
+    ///         })?;
+    ///         Ok(())
+    ///     })?;
+    /// # generator.assert_eq("impl Foo { fn names () ->Vec <&'static str > { \
+    /// #     :: core :: debug_assert ! (:: core :: matches ! (self , Foo { .. })) ; \
+    /// #     vec ! [\"a\" , \"b\"] } }");
+    /// # let mut generator = Generator::with_name("Foo");
+    /// # generator.r#impl().generate_fn("foo").body(|b| {
+    /// #     assert!(b.macro_call("vec", Delimiter::None, |_| Ok(())).is_err());
+    /// #     assert!(b.macro_call("vec", Delimiter::Bracket, |b| { b.push_parsed("(")?; Ok(()) }).is_err());
+    /// #     Ok(())
+    /// # })?;
+    /// # generator.assert_eq("impl Foo { fn foo () { } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn macro_call<FN>(
+        &mut self,
+        path: impl AsRef<str>,
+        delim: Delimiter,
+        args: FN,
+    ) -> crate::Result<&mut Self>
+    where
+        FN: FnOnce(&mut StreamBuilder) -> crate::Result<()>,
+    {
+        if delim == Delimiter::None {
+            return Err(crate::Error::custom(
+                "A macro call needs `()`, `[]` or `{}` around its arguments",
+            ));
+        }
+        let mut builder = StreamBuilder::new();
+        builder.push_parsed(path)?.punct('!').group(delim, args)?;
+        Ok(self.append(builder))
+    }
+
+    /// `= value;`
+    fn let_value<FN>(&mut self, value: FN) -> crate::Result<&mut Self>
+    where
+        FN: FnOnce(&mut StreamBuilder) -> crate::Result<()>,
+    {
+        self.punct('=');
+        value(self)?;
+        Ok(self.punct(';'))
+    }
+
     /// Set the given span on all tokens in the stream. This span is used by rust for e.g. compiler errors, to indicate the position of the error.
     ///
     /// Normally your derive will report an error on the derive, e.g.:
@@ -173,6 +661,12 @@ impl StreamBuilder {
     }
 }
 
+impl From<StreamBuilder> for TokenStream {
+    fn from(builder: StreamBuilder) -> Self {
+        builder.stream
+    }
+}
+
 /// Failed to parse the code passed to [`StreamBuilder::push_parsed`]
 ///
 /// [`StreamBuilder::push_parsed`]: struct.StreamBuilder.html#method.push_parsed
@@ -183,3 +677,27 @@ pub struct PushParseError {
     /// The code that was being parsed
     pub code: String,
 }
+
+impl fmt::Display for PushParseError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if self.code.is_empty() {
+            write!(fmt, "Could not parse code")
+        } else {
+            write!(fmt, "Could not parse `{}`", self.code)
+        }
+    }
+}
+
+impl std::error::Error for PushParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Create an ident at the call site. A raw ident like `r#type` is created with `Ident::new_raw`, because `Ident::new` does not accept them.
+pub(crate) fn ident_from_str(ident: &str) -> Ident {
+    match ident.strip_prefix("r#") {
+        Some(raw) => Ident::new_raw(raw, Span::call_site()),
+        None => Ident::new(ident, Span::call_site()),
+    }
+}
@@ -0,0 +1,222 @@
+use super::{Generator, StreamBuilder};
+use crate::parse::{Attribute, EnumBody};
+use crate::prelude::{Delimiter, Literal};
+use crate::{Error, Result};
+
+/// How the tags of [`Generator::generate_tag_enum`] are numbered.
+///
+/// [`Generator::generate_tag_enum`]: struct.Generator.html#method.generate_tag_enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagValue {
+    /// Every tag has the same discriminant as its variant. Explicit discriminants like `A = 5` are copied to the tag enum, and rust numbers the other tags the same way it numbers the variants.
+    Discriminant,
+    /// The tags are numbered by the position of the variant, starting at `0`. Explicit discriminants are ignored.
+    Ordinal,
+}
+
+/// The tag enum that [`Generator::generate_tag_enum`] generates.
+///
+/// [`Generator::generate_tag_enum`]: struct.Generator.html#method.generate_tag_enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TagConfig<'a> {
+    /// The name of the tag enum, e.g. `"FooTag"`
+    pub name: &'a str,
+    /// The integer type of the tags, e.g. `"u8"`. This is the `#[repr(...)]` of the tag enum and the argument of `from_tag`.
+    pub repr: &'a str,
+    /// How the tags are numbered
+    pub value: TagValue,
+}
+
+impl Generator {
+    /// Generate a fieldless tag enum with a variant for every variant of `body`, to encode and decode which variant a value is, e.g. for a serialization format:
+    ///
+    /// - The tag enum, which derives `Clone`, `Copy`, `PartialEq`, `Eq`, `Hash` and `Debug`, with the `#[repr(...)]` of [`TagConfig::repr`]
+    /// - `fn from_tag(tag: <repr>) -> Option<Tag>` on the tag enum, which returns `None` for unknown tags
+    /// - `fn tag(&self) -> Tag` on the target enum
+    ///
+    /// The tag can be encoded with `tag as <repr>`. The tag enum and the functions have the same visibility as the target enum, and the tags keep the `#[cfg(...)]` attributes of their variants.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// use virtue::generate::{TagConfig, TagValue};
+    ///
+    /// let input = TokenStream::from_str("pub enum Foo { A(u32), B { b: u8 } = 5, C }").unwrap();
+    /// let (mut generator, _, body) = Parse::new(input)?.into_generator();
+    /// # let body = match body { Body::Enum(body) => body, _ => unreachable!() };
+    /// generator.generate_tag_enum(
+    ///     &body,
+    ///     TagConfig { name: "FooTag", repr: "u8", value: TagValue::Discriminant },
+    /// )?;
+    ///
+    /// // #[repr(u8)]
+    /// // #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+    /// // pub enum FooTag { A, B = 5, C }
+    /// //
+    /// // impl FooTag {
+    /// //     pub fn from_tag(tag: u8) -> Option<Self> {
+    /// //         match tag {
+    /// //             tag if tag == Self::A as u8 => Some(Self::A),
+    /// //             tag if tag == Self::B as u8 => Some(Self::B),
+    /// //             tag if tag == Self::C as u8 => Some(Self::C),
+    /// //             _ => None,
+    /// //         }
+    /// //     }
+    /// // }
+    /// //
+    /// // impl Foo {
+    /// //     pub fn tag(&self) -> FooTag {
+    /// //         match self {
+    /// //             Self::A { .. } => FooTag::A,
+    /// //             Self::B { .. } => FooTag::B,
+    /// //             Self::C { .. } => FooTag::C,
+    /// //         }
+    /// //     }
+    /// // }
+    /// # generator.assert_eq("# [repr (u8)] # [derive (Clone , Copy , PartialEq , Eq , Hash , Debug)] \
+    /// #     pub enum FooTag { A , B = 5 , C , } \
+    /// #     impl FooTag { pub fn from_tag (tag : u8) ->Option < Self > { match tag { \
+    /// #         tag if tag == Self :: A as u8 => Some (Self :: A) , \
+    /// #         tag if tag == Self :: B as u8 => Some (Self :: B) , \
+    /// #         tag if tag == Self :: C as u8 => Some (Self :: C) , _ => None , } } } \
+    /// #     impl Foo { pub fn tag (& self) ->FooTag { match self { \
+    /// #         Self :: A { .. } => FooTag :: A , Self :: B { .. } => FooTag :: B , Self :: C { .. } => FooTag :: C , } } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// Explicit discriminants that are expressions, like `A = BASE + 1`, are copied as-is, so they have to be valid outside of the target enum as well.
+    ///
+    /// [`TagConfig::repr`]: struct.TagConfig.html#structfield.repr
+    pub fn generate_tag_enum(&mut self, body: &EnumBody, config: TagConfig) -> Result {
+        if body.is_uninhabited() {
+            return Err(Error::custom_at(
+                "Can not generate a tag enum for an enum without variants",
+                self.target_name().span(),
+            ));
+        }
+        let visibility = self.target_visibility().clone();
+        let cfgs: Vec<_> = body
+            .variants
+            .iter()
+            .map(|variant| Attribute::cfg_of(&variant.attributes))
+            .collect();
+
+        let mut tag_enum = self.generate_enum(config.name.to_owned());
+        tag_enum
+            .with_visibility(visibility.clone())
+            .with_derive(["Clone", "Copy", "PartialEq", "Eq", "Hash", "Debug"])?
+            .with_repr(config.repr)?;
+        for (idx, (variant, cfg)) in body.variants.iter().zip(&cfgs).enumerate() {
+            let value = tag_enum.add_value(variant.name.to_string());
+            value.make_zst();
+            if let Some(cfg) = cfg {
+                let mut attr = StreamBuilder::new();
+                attr.ident_str("cfg").group(Delimiter::Parenthesis, |b| {
+                    b.extend(cfg.clone());
+                    Ok(())
+                })?;
+                value.with_attr(attr)?;
+            }
+            match config.value {
+                TagValue::Ordinal => {
+                    value.with_discriminant(Literal::usize_unsuffixed(idx));
+                }
+                TagValue::Discriminant => {
+                    if let Some(literal) = &variant.value {
+                        value.with_discriminant(literal.clone());
+                    } else if let Some(expr) = &variant.value_expr {
+                        value.with_discriminant_expr(expr);
+                    }
+                }
+            }
+        }
+        tag_enum
+            .r#impl()
+            .generate_fn("from_tag")
+            .with_visibility(visibility.clone())
+            .with_arg("tag", config.repr.to_owned())
+            .with_return_type("Option<Self>")
+            .body(|b| {
+                b.ident_str("match").ident_str("tag");
+                b.group(Delimiter::Brace, |b| {
+                    for (variant, cfg) in body.variants.iter().zip(&cfgs) {
+                        if let Some(cfg) = cfg {
+                            b.cfg_attribute(cfg.clone());
+                        }
+                        b.push_parsed(format!(
+                            "tag if tag == Self::{0} as {1} => Some(Self::{0}),",
+                            variant.name, config.repr
+                        ))?;
+                    }
+                    b.push_parsed("_ => None,")?;
+                    Ok(())
+                })?;
+                Ok(())
+            })?;
+        drop(tag_enum);
+
+        self.r#impl()
+            .generate_fn("tag")
+            .with_visibility(visibility)
+            .with_self_arg(super::FnSelfArg::RefSelf)
+            .with_self_arg_trailing_comma(false)
+            .with_return_type(config.name.to_owned())
+            .body(|b| {
+                b.ident_str("match").ident_str("self");
+                b.group(Delimiter::Brace, |b| {
+                    for (variant, cfg) in body.variants.iter().zip(&cfgs) {
+                        if let Some(cfg) = cfg {
+                            b.cfg_attribute(cfg.clone());
+                        }
+                        b.push_parsed(format!(
+                            "Self::{0} {{ .. }} => {1}::{0},",
+                            variant.name, config.name
+                        ))?;
+                    }
+                    Ok(())
+                })?;
+                Ok(())
+            })?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_generate_tag_enum() {
+    use crate::parse::{Body, Parse};
+    use crate::token_stream;
+
+    let parse = |input: &str| {
+        let (generator, _, body) =
+            Parse::new(token_stream(input).collect::<proc_macro2::TokenStream>())
+                .unwrap()
+                .into_generator();
+        match body {
+            Body::Enum(body) => (generator, body),
+            Body::Struct(_) => unreachable!(),
+        }
+    };
+    let config = TagConfig {
+        name: "Tag",
+        repr: "u16",
+        value: TagValue::Ordinal,
+    };
+
+    let (mut generator, body) = parse("enum Foo<T> { A(T) = 5, #[cfg(test)] B, r#C { c: u8 } }");
+    generator.generate_tag_enum(&body, config).unwrap();
+    generator.assert_eq(
+        "# [repr (u16)] # [derive (Clone , Copy , PartialEq , Eq , Hash , Debug)] \
+         enum Tag { A = 0 , # [cfg (test)] B = 1 , r#C = 2 , } \
+         impl Tag { fn from_tag (tag : u16) ->Option < Self > { match tag { \
+         tag if tag == Self :: A as u16 => Some (Self :: A) , \
+         # [cfg (test)] tag if tag == Self :: B as u16 => Some (Self :: B) , \
+         tag if tag == Self :: r#C as u16 => Some (Self :: r#C) , _ => None , } } } \
+         impl < T > Foo < T > { fn tag (& self) ->Tag { match self { \
+         Self :: A { .. } => Tag :: A , # [cfg (test)] Self :: B { .. } => Tag :: B , \
+         Self :: r#C { .. } => Tag :: r#C , } } }",
+    );
+
+    let (mut generator, body) = parse("enum Foo { }");
+    assert!(generator.generate_tag_enum(&body, config).is_err());
+    generator.assert_eq("");
+}
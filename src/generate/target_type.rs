@@ -0,0 +1,158 @@
+use super::{Generator, StreamBuilder};
+use crate::parse::{Generic, Generics};
+use crate::prelude::{Punct, Spacing};
+
+/// The type of the struct or enum that is derived, e.g. `Foo<'a, T>`, to name it in generated code where `Self` can not be used. Created with [`Generator::target_type`], and added to a [`StreamBuilder`] with [`StreamBuilder::self_type`] or [`StreamBuilder::self_type_turbofish`].
+///
+/// The type is a copy of the name and generics of the target, so it can be used in a builder of the same generator:
+///
+/// ```
+/// # use virtue::prelude::*;
+/// # let input = "struct Foo<T> { t: T }".parse().unwrap();
+/// # let (mut generator, _, _) = Parse::new(input).unwrap().into_generator();
+/// let target = generator.target_type();
+/// generator
+///     .generate_fn("make")
+///     .with_generic_deps("T", ["Default"])
+///     .with_return_type(target.to_string())
+///     .body(|b| {
+///         b.self_type_turbofish(&target).push_parsed("::new(T::default())")?;
+///         Ok(())
+///     })?;
+///
+/// // fn make<T: Default>() -> Foo<T> {
+/// //     Foo::<T>::new(T::default())
+/// // }
+/// # generator.assert_eq("fn make < T : Default > () ->Foo < T > { \
+/// #     Foo :: < T > :: new (T :: default ()) }");
+/// # Ok::<_, virtue::Error>(())
+/// ```
+///
+/// [`Generator::target_type`]: struct.Generator.html#method.target_type
+/// [`StreamBuilder::self_type`]: struct.StreamBuilder.html#method.self_type
+/// [`StreamBuilder::self_type_turbofish`]: struct.StreamBuilder.html#method.self_type_turbofish
+#[derive(Clone)]
+pub struct TargetType {
+    path: StreamBuilder,
+    generics: Option<Generics>,
+}
+
+impl TargetType {
+    pub(super) fn new(path: StreamBuilder, generics: Option<Generics>) -> Self {
+        Self { path, generics }
+    }
+
+    fn append_to(&self, builder: &mut StreamBuilder, turbofish: bool) {
+        builder.append(self.path.clone());
+        if let Some(generics) = &self.generics {
+            if turbofish {
+                builder.push(Punct::new(':', Spacing::Joint));
+                builder.push(Punct::new(':', Spacing::Alone));
+            }
+            builder.append(generics.type_generics());
+        }
+    }
+
+    /// `type <name><generics> = <type>;`. The generics are declared without their bounds, because the bounds of a type alias are not checked.
+    pub(super) fn alias(&self, name: &str) -> StreamBuilder {
+        let mut builder = StreamBuilder::new();
+        builder.ident_str("type").ident_str(name);
+        if let Some(generics) = &self.generics {
+            builder.punct('<');
+            for (idx, generic) in generics.iter().enumerate() {
+                if idx > 0 {
+                    builder.punct(',');
+                }
+                match generic {
+                    Generic::Lifetime(lt) => {
+                        builder.lifetime(lt.ident.clone());
+                    }
+                    Generic::Generic(generic) => {
+                        builder.ident(generic.ident.clone());
+                    }
+                    Generic::Const(generic) => {
+                        builder
+                            .ident(generic.const_token.clone())
+                            .ident(generic.ident.clone())
+                            .punct(':')
+                            .extend(generic.constraints.iter().cloned());
+                    }
+                }
+            }
+            builder.punct('>');
+        }
+        builder.punct('=');
+        self.append_to(&mut builder, false);
+        builder.punct(';');
+        builder
+    }
+}
+
+impl std::fmt::Display for TargetType {
+    /// The type as it is written in type position, e.g. `Foo < 'a , T >`
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut builder = StreamBuilder::new();
+        self.append_to(&mut builder, false);
+        builder.stream.fmt(fmt)
+    }
+}
+
+impl<'a> From<&'a Generator> for TargetType {
+    fn from(generator: &'a Generator) -> Self {
+        generator.target_type()
+    }
+}
+
+impl<'a> From<&'a TargetType> for TargetType {
+    fn from(target: &'a TargetType) -> Self {
+        target.clone()
+    }
+}
+
+impl StreamBuilder {
+    /// Add the type of the target in type position, e.g. `Foo<'a, T>`. This is the same as `Self` inside of an impl of the target.
+    pub fn self_type(&mut self, target: impl Into<TargetType>) -> &mut Self {
+        target.into().append_to(self, false);
+        self
+    }
+
+    /// Add the type of the target in expression position, e.g. `Foo::<'a, T>`, to call an associated function with explicit generics like `Foo::<'a, T>::new()`.
+    pub fn self_type_turbofish(&mut self, target: impl Into<TargetType>) -> &mut Self {
+        target.into().append_to(self, true);
+        self
+    }
+}
+
+#[test]
+fn test_target_type() {
+    use crate::parse::Parse;
+    use crate::token_stream;
+
+    let (mut generator, _, _) = Parse::new(
+        token_stream("struct Foo<'a, T: Clone, const N: usize> where T: 'a { t: [&'a T; N] }")
+            .collect::<proc_macro2::TokenStream>(),
+    )
+    .unwrap()
+    .into_generator();
+    let mut builder = StreamBuilder::new();
+    builder
+        .self_type(&generator)
+        .punct(';')
+        .self_type_turbofish(&generator);
+    assert_eq!(
+        builder.stream.to_string(),
+        "Foo < 'a , T , N > ; Foo :: < 'a , T , N >"
+    );
+    generator.generate_self_alias("Self_");
+    generator.assert_eq("type Self_ < 'a , T , const N : usize > = Foo < 'a , T , N > ;");
+
+    let mut generator = Generator::with_name("Bar");
+    generator.set_target_path("crate::module").unwrap();
+    let target = generator.target_type();
+    assert_eq!(target.to_string(), "crate :: module :: Bar");
+    let mut builder = StreamBuilder::new();
+    builder.self_type_turbofish(&target);
+    assert_eq!(builder.stream.to_string(), "crate :: module :: Bar");
+    generator.generate_self_alias("Self_");
+    generator.assert_eq("type Self_ = crate :: module :: Bar ;");
+}
@@ -0,0 +1,338 @@
+use super::StreamBuilder;
+use crate::prelude::Delimiter;
+use crate::utils::parse_type;
+use crate::{Error, Result};
+use std::fmt;
+
+/// A builder for types that are assembled from multiple parts, e.g. trait objects like `dyn Trait + Send + 'static`.
+///
+/// Formatting these types with strings is easy to get wrong, e.g. `&dyn Trait + Send` is not a valid type and has to be written as `&(dyn Trait + Send)`. The builder adds these parentheses when needed:
+///
+/// ```
+/// # use virtue::generate::TypeBuilder;
+/// // Box<dyn Fn(&[u8]) -> Result<(), E> + Send>
+/// let ty = TypeBuilder::dyn_trait("Fn(&[u8]) -> Result<(), E>")?
+///     .plus("Send")?
+///     .boxed();
+/// assert_eq!(ty.to_string(), ":: std :: boxed :: Box < dyn Fn (& [u8]) -> Result < () , E > + Send >");
+///
+/// // &'a (dyn Trait + 'static)
+/// let ty = TypeBuilder::dyn_trait("Trait")?
+///     .lifetime("static")?
+///     .reference(Some("a"));
+/// assert_eq!(ty.to_string(), "& 'a (dyn Trait + 'static)");
+/// # Ok::<_, virtue::Error>(())
+/// ```
+///
+/// A type can be added to a [`StreamBuilder`] with [`StreamBuilder::ty`], or used in any place that accepts a type as a string with `to_string()`.
+///
+/// [`StreamBuilder::ty`]: struct.StreamBuilder.html#method.ty
+#[derive(Clone)]
+pub struct TypeBuilder {
+    /// The innermost type, e.g. `dyn Trait + Send`
+    stream: StreamBuilder,
+    /// Set for trait objects with additional bounds like `dyn A + B`, which need parentheses behind a `&`
+    has_bounds: bool,
+    /// The `Box`es and references around the innermost type, from the inside out. These are only added when the type is built, so a bound that is added with [`plus`] or [`lifetime`] after e.g. [`boxed`] still goes to the trait object.
+    ///
+    /// [`plus`]: #method.plus
+    /// [`lifetime`]: #method.lifetime
+    /// [`boxed`]: #method.boxed
+    wrappers: Vec<Wrapper>,
+    /// Set by [`no_std`] to reference `Box` through `::alloc`
+    ///
+    /// [`no_std`]: #method.no_std
+    no_std: bool,
+}
+
+#[derive(Clone)]
+enum Wrapper {
+    Boxed,
+    Reference {
+        lifetime: Option<String>,
+        mutable: bool,
+    },
+}
+
+impl TypeBuilder {
+    fn build(f: impl FnOnce(&mut StreamBuilder)) -> Self {
+        let mut builder = StreamBuilder::new();
+        f(&mut builder);
+        Self {
+            stream: builder,
+            has_bounds: false,
+            wrappers: Vec::new(),
+            no_std: false,
+        }
+    }
+
+    /// Parse a type, e.g. `Vec<u8>`. See [`utils::parse_type`] for the checks that are done on `ty`.
+    ///
+    /// [`utils::parse_type`]: ../utils/fn.parse_type.html
+    pub fn parsed(ty: impl AsRef<str>) -> Result<Self> {
+        let tokens = parse_type(ty.as_ref())?;
+        Ok(Self::build(|b| {
+            b.extend(tokens);
+        }))
+    }
+
+    /// A trait object, e.g. `dyn Trait` or `dyn Fn(u8) -> u8`. Add more bounds with [`plus`] and [`lifetime`].
+    ///
+    /// [`plus`]: #method.plus
+    /// [`lifetime`]: #method.lifetime
+    pub fn dyn_trait(trait_path: impl AsRef<str>) -> Result<Self> {
+        let tokens = parse_type(trait_path.as_ref())?;
+        Ok(Self::build(|b| {
+            b.ident_str("dyn").extend(tokens);
+        }))
+    }
+
+    /// Add a trait bound, e.g. `dyn Trait` becomes `dyn Trait + Send`. The bound is added to the trait object, also when this type was already [`boxed`] or made a reference, e.g. `Box<dyn Trait>` becomes `Box<dyn Trait + Send>`.
+    ///
+    /// [`boxed`]: #method.boxed
+    pub fn plus(mut self, bound: impl AsRef<str>) -> Result<Self> {
+        let tokens = parse_type(bound.as_ref())?;
+        self.stream.punct('+').extend(tokens);
+        self.has_bounds = true;
+        Ok(self)
+    }
+
+    /// Add a lifetime bound, e.g. `dyn Trait` becomes `dyn Trait + 'static` for `lifetime("static")` or `lifetime("'static")`. Like [`plus`], the bound is added to the trait object. Returns an error if `lifetime` is not a valid lifetime.
+    ///
+    /// [`plus`]: #method.plus
+    pub fn lifetime(mut self, lifetime: impl AsRef<str>) -> Result<Self> {
+        let name = lifetime_name(lifetime.as_ref())?;
+        self.stream.punct('+').lifetime_str(name);
+        self.has_bounds = true;
+        Ok(self)
+    }
+
+    /// Reference `Box` through `::alloc` instead of `::std`, for code in a `#![no_std]` crate. This is usually [`Generator::is_no_std`], see [`Generator::no_std`].
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use virtue::generate::TypeBuilder;
+    /// let mut generator = Generator::with_name("Foo");
+    /// generator.no_std(true);
+    /// let ty = TypeBuilder::dyn_trait("Any")?
+    ///     .no_std(generator.is_no_std())
+    ///     .boxed();
+    /// assert_eq!(ty.to_string(), ":: alloc :: boxed :: Box < dyn Any >");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`Generator::is_no_std`]: struct.Generator.html#method.is_no_std
+    /// [`Generator::no_std`]: struct.Generator.html#method.no_std
+    pub fn no_std(mut self, no_std: bool) -> Self {
+        self.no_std = no_std;
+        self
+    }
+
+    /// Wrap this type in a `Box`, e.g. `dyn Trait` becomes `::std::boxed::Box<dyn Trait>`. The path of `Box` is given by [`StreamBuilder::alloc_path`], see [`no_std`].
+    ///
+    /// [`StreamBuilder::alloc_path`]: struct.StreamBuilder.html#method.alloc_path
+    /// [`no_std`]: #method.no_std
+    pub fn boxed(mut self) -> Self {
+        self.wrappers.push(Wrapper::Boxed);
+        self
+    }
+
+    /// A reference to this type with an optional lifetime, e.g. `dyn Trait` becomes `&'a dyn Trait` for `reference(Some("a"))` or `reference(Some("'a"))`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `lifetime` is not a valid lifetime.
+    pub fn reference(self, lifetime: Option<&str>) -> Self {
+        self.with_reference(lifetime, false)
+    }
+
+    /// A mutable reference to this type with an optional lifetime, e.g. `dyn Trait` becomes `&mut dyn Trait` for `reference_mut(None)`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `lifetime` is not a valid lifetime.
+    pub fn reference_mut(self, lifetime: Option<&str>) -> Self {
+        self.with_reference(lifetime, true)
+    }
+
+    fn with_reference(mut self, lifetime: Option<&str>, mutable: bool) -> Self {
+        let lifetime = lifetime.map(|lifetime| match lifetime_name(lifetime) {
+            Ok(name) => name.to_owned(),
+            Err(error) => panic!("{}", error),
+        });
+        self.wrappers.push(Wrapper::Reference { lifetime, mutable });
+        self
+    }
+
+    /// The tokens of this type, with the `Box`es and references around the innermost type
+    fn to_builder(&self) -> StreamBuilder {
+        let mut inner = self.stream.clone();
+        let mut has_bounds = self.has_bounds;
+        for wrapper in &self.wrappers {
+            let mut builder = StreamBuilder::new();
+            match wrapper {
+                Wrapper::Boxed => {
+                    builder
+                        .push_parsed(StreamBuilder::alloc_path(self.no_std))
+                        .expect("Could not build type")
+                        .push_parsed("::boxed::Box")
+                        .expect("Could not build type")
+                        .punct('<')
+                        .append(inner)
+                        .punct('>');
+                }
+                Wrapper::Reference { lifetime, mutable } => {
+                    builder.punct('&');
+                    if let Some(lifetime) = lifetime {
+                        builder.lifetime_str(lifetime);
+                    }
+                    if *mutable {
+                        builder.ident_str("mut");
+                    }
+                    if has_bounds {
+                        builder
+                            .group(Delimiter::Parenthesis, |b| {
+                                b.append(inner);
+                                Ok(())
+                            })
+                            .expect("Could not build type");
+                    } else {
+                        builder.append(inner);
+                    }
+                }
+            }
+            inner = builder;
+            has_bounds = false;
+        }
+        inner
+    }
+}
+
+/// The name of a lifetime without the `'`, e.g. `a` for both `a` and `'a`. Returns an error if the name is not an identifier.
+fn lifetime_name(lifetime: &str) -> Result<&str> {
+    let name = lifetime.strip_prefix('\'').unwrap_or(lifetime);
+    let mut chars = name.chars();
+    let is_valid = matches!(chars.next(), Some(c) if c == '_' || c.is_alphabetic())
+        && chars.all(|c| c == '_' || c.is_alphanumeric());
+    if is_valid {
+        Ok(name)
+    } else {
+        Err(Error::custom(format!(
+            "Invalid lifetime `{}`, expected e.g. `a` or `'static`",
+            lifetime
+        )))
+    }
+}
+
+impl fmt::Display for TypeBuilder {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.to_builder().stream)
+    }
+}
+
+impl StreamBuilder {
+    /// Add the given type to the stream. See [`TypeBuilder`] for more information.
+    ///
+    /// The [`provenance`] records of the parts of the type are kept.
+    ///
+    /// [`provenance`]: #method.provenance
+    pub fn ty(&mut self, ty: &TypeBuilder) -> &mut Self {
+        self.append(ty.to_builder())
+    }
+}
+
+#[test]
+fn test_type_builder() {
+    assert_eq!(
+        TypeBuilder::parsed("Vec<u8>").unwrap().boxed().to_string(),
+        ":: std :: boxed :: Box < Vec < u8 > >"
+    );
+    assert_eq!(
+        TypeBuilder::dyn_trait("Any")
+            .unwrap()
+            .lifetime("static")
+            .unwrap()
+            .boxed()
+            .reference_mut(None)
+            .to_string(),
+        "& mut :: std :: boxed :: Box < dyn Any + 'static >"
+    );
+    assert_eq!(
+        TypeBuilder::dyn_trait("Any")
+            .unwrap()
+            .reference(Some("a"))
+            .to_string(),
+        "& 'a dyn Any"
+    );
+    assert!(TypeBuilder::dyn_trait("Fn(u8) -> u8, u8").is_err());
+    assert!(TypeBuilder::dyn_trait("Any")
+        .unwrap()
+        .plus("Send,")
+        .is_err());
+    assert!(TypeBuilder::parsed("Vec<u8").is_err());
+
+    assert_eq!(
+        TypeBuilder::dyn_trait("Any")
+            .unwrap()
+            .lifetime("'static")
+            .unwrap()
+            .reference(Some("'a"))
+            .to_string(),
+        "& 'a (dyn Any + 'static)"
+    );
+    assert_eq!(
+        TypeBuilder::dyn_trait("A")
+            .unwrap()
+            .boxed()
+            .plus("B")
+            .unwrap()
+            .to_string(),
+        ":: std :: boxed :: Box < dyn A + B >"
+    );
+    assert_eq!(
+        TypeBuilder::dyn_trait("A")
+            .unwrap()
+            .boxed()
+            .reference(None)
+            .lifetime("static")
+            .unwrap()
+            .to_string(),
+        "& :: std :: boxed :: Box < dyn A + 'static >"
+    );
+    assert_eq!(
+        TypeBuilder::dyn_trait("A")
+            .unwrap()
+            .reference(None)
+            .plus("B")
+            .unwrap()
+            .to_string(),
+        "& (dyn A + B)"
+    );
+
+    assert_eq!(
+        TypeBuilder::parsed("u8")
+            .unwrap()
+            .no_std(true)
+            .boxed()
+            .to_string(),
+        ":: alloc :: boxed :: Box < u8 >"
+    );
+    assert_eq!(
+        TypeBuilder::dyn_trait("A")
+            .unwrap()
+            .lifetime("_")
+            .unwrap()
+            .to_string(),
+        "dyn A + '_"
+    );
+    for lifetime in ["", "'", "1a", "a b", "'a'"] {
+        assert!(
+            TypeBuilder::dyn_trait("A")
+                .unwrap()
+                .lifetime(lifetime)
+                .is_err(),
+            "{}",
+            lifetime
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use super::{StreamBuilder, TypeBuilder};
+use crate::parse::UnnamedField;
+use crate::prelude::{Literal, TokenStream, TokenTree};
+use crate::Result;
+use std::borrow::Cow;
+use std::fmt;
+
+/// A type that is passed to a builder, e.g. to [`FnBuilder::with_return_type`].
+///
+/// This can be created from a string, which will be parsed, or from tokens that were already parsed, e.g. a [`StreamBuilder`], a `Vec<TokenTree>` or the type of an [`UnnamedField`]. Tokens are added to the output as-is, so they keep their spans and errors in the type point at the original source.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// # use std::str::FromStr;
+/// let input = TokenStream::from_str("struct Foo { a: Vec<u8> }").unwrap();
+/// let (mut generator, _, body) = Parse::new(input)?.into_generator();
+/// # let fields = match &body { Body::Struct(body) => body.fields.as_ref().unwrap(), _ => unreachable!() };
+/// let (_, field) = fields.get(0).unwrap();
+/// generator
+///     .r#impl()
+///     .generate_fn("into_a")
+///     .with_self_arg(FnSelfArg::TakeSelf)
+///     .with_return_type(field) // the tokens of `Vec<u8>`
+///     .body(|b| {
+///         b.push_parsed("self.a")?;
+///         Ok(())
+///     })?;
+/// # generator.assert_eq("impl Foo { fn into_a (self ,) ->Vec < u8 > { self . a } }");
+/// # Ok::<_, virtue::Error>(())
+/// ```
+///
+/// [`FnBuilder::with_return_type`]: struct.FnBuilder.html#method.with_return_type
+#[derive(Clone)]
+pub struct TypeTokens(CodeOrTokens);
+
+/// An expression that is passed to a builder, e.g. to [`EnumValue::with_discriminant_expr`] or [`GenAttr::value`].
+///
+/// Like [`TypeTokens`], this can be created from a string, which will be parsed, or from tokens that were already parsed, e.g. a [`StreamBuilder`], a `Vec<TokenTree>` or a [`Literal`]. Tokens are added to the output as-is, so they keep their spans.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// # use std::str::FromStr;
+/// let input = TokenStream::from_str("#[repr(u8)] enum Foo { A = BASE + 1, B }").unwrap();
+/// let (mut generator, _, body) = Parse::new(input)?.into_generator();
+/// # let body = match body { Body::Enum(body) => body, _ => unreachable!() };
+/// let mut tag = generator.generate_enum("FooTag");
+/// for variant in &body.variants {
+///     let value = tag.add_value(variant.name.to_string());
+///     value.make_zst();
+///     if let Some(expr) = &variant.value_expr {
+///         value.with_discriminant_expr(expr); // the tokens of `BASE + 1`
+///     }
+/// }
+/// # drop(tag);
+/// # generator.assert_eq("enum FooTag { A = BASE + 1 , B , }");
+/// # Ok::<_, virtue::Error>(())
+/// ```
+///
+/// [`EnumValue::with_discriminant_expr`]: struct.EnumValue.html#method.with_discriminant_expr
+/// [`GenAttr::value`]: struct.GenAttr.html#method.value
+/// [`Literal`]: ../prelude/struct.Literal.html
+#[derive(Clone)]
+pub struct ExprTokens(CodeOrTokens);
+
+#[derive(Clone)]
+enum CodeOrTokens {
+    Code(String),
+    Tokens(StreamBuilder),
+}
+
+impl CodeOrTokens {
+    fn append_to(&self, builder: &mut StreamBuilder) -> Result {
+        match self {
+            CodeOrTokens::Code(code) => {
+                builder.push_parsed(code)?;
+            }
+            CodeOrTokens::Tokens(tokens) => {
+                builder.append(tokens.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for CodeOrTokens {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CodeOrTokens::Code(code) => code.fmt(fmt),
+            CodeOrTokens::Tokens(tokens) => tokens.stream.fmt(fmt),
+        }
+    }
+}
+
+impl TypeTokens {
+    /// Add the type to `builder`, parsing it first if it was created from a string
+    pub(crate) fn append_to(&self, builder: &mut StreamBuilder) -> Result {
+        self.0.append_to(builder)
+    }
+}
+
+impl ExprTokens {
+    /// Add the expression to `builder`, parsing it first if it was created from a string
+    pub(crate) fn append_to(&self, builder: &mut StreamBuilder) -> Result {
+        self.0.append_to(builder)
+    }
+}
+
+/// The conversions from code and tokens that [`TypeTokens`] and [`ExprTokens`] share
+macro_rules! impl_from_code_and_tokens {
+    ($ty:ident) => {
+        impl fmt::Display for $ty {
+            fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                self.0.fmt(fmt)
+            }
+        }
+
+        impl From<String> for $ty {
+            fn from(code: String) -> Self {
+                Self(CodeOrTokens::Code(code))
+            }
+        }
+
+        impl<'a> From<&'a String> for $ty {
+            fn from(code: &'a String) -> Self {
+                Self(CodeOrTokens::Code(code.clone()))
+            }
+        }
+
+        impl<'a> From<&'a str> for $ty {
+            fn from(code: &'a str) -> Self {
+                Self(CodeOrTokens::Code(code.to_owned()))
+            }
+        }
+
+        impl<'a> From<Cow<'a, str>> for $ty {
+            fn from(code: Cow<'a, str>) -> Self {
+                Self(CodeOrTokens::Code(code.into_owned()))
+            }
+        }
+
+        impl From<TokenStream> for $ty {
+            fn from(tokens: TokenStream) -> Self {
+                let mut builder = StreamBuilder::new();
+                builder.extend(tokens);
+                builder.into()
+            }
+        }
+
+        impl From<StreamBuilder> for $ty {
+            fn from(builder: StreamBuilder) -> Self {
+                Self(CodeOrTokens::Tokens(builder))
+            }
+        }
+
+        impl From<Vec<TokenTree>> for $ty {
+            fn from(tokens: Vec<TokenTree>) -> Self {
+                tokens.into_iter().collect::<TokenStream>().into()
+            }
+        }
+
+        impl<'a> From<&'a [TokenTree]> for $ty {
+            fn from(tokens: &'a [TokenTree]) -> Self {
+                tokens.iter().cloned().collect::<TokenStream>().into()
+            }
+        }
+
+        impl<'a> From<&'a Vec<TokenTree>> for $ty {
+            fn from(tokens: &'a Vec<TokenTree>) -> Self {
+                tokens.as_slice().into()
+            }
+        }
+    };
+}
+
+impl_from_code_and_tokens!(TypeTokens);
+impl_from_code_and_tokens!(ExprTokens);
+
+impl From<Literal> for ExprTokens {
+    fn from(literal: Literal) -> Self {
+        TokenStream::from(TokenTree::Literal(literal)).into()
+    }
+}
+
+impl<'a> From<&'a UnnamedField> for TypeTokens {
+    fn from(field: &'a UnnamedField) -> Self {
+        field.r#type.as_slice().into()
+    }
+}
+
+impl<'a> From<&'a TypeBuilder> for TypeTokens {
+    fn from(ty: &'a TypeBuilder) -> Self {
+        let mut builder = StreamBuilder::new();
+        builder.ty(ty);
+        builder.into()
+    }
+}
+
+impl From<TypeBuilder> for TypeTokens {
+    fn from(ty: TypeBuilder) -> Self {
+        (&ty).into()
+    }
+}
+
+#[test]
+fn test_type_tokens() {
+    use crate::token_stream;
+
+    let to_string = |ty: TypeTokens| {
+        let mut builder = StreamBuilder::new();
+        ty.append_to(&mut builder)
+            .map(|_| builder.stream.to_string())
+    };
+    assert_eq!(to_string("Vec<u8>".into()).unwrap(), "Vec < u8 >");
+    assert!(to_string("Vec<(u8".into()).is_err());
+    let tokens: Vec<TokenTree> = token_stream("Option<&'a str>").collect();
+    assert_eq!(to_string(tokens.into()).unwrap(), "Option <&'a str >");
+    assert_eq!(
+        to_string(TypeBuilder::dyn_trait("Any").unwrap().boxed().into()).unwrap(),
+        ":: std :: boxed :: Box < dyn Any >"
+    );
+
+    let expr_to_string = |expr: ExprTokens| {
+        let mut builder = StreamBuilder::new();
+        expr.append_to(&mut builder)
+            .map(|_| builder.stream.to_string())
+    };
+    assert_eq!(expr_to_string("a + 1".into()).unwrap(), "a + 1");
+    assert!(expr_to_string("(a".into()).is_err());
+    assert_eq!(
+        expr_to_string(Literal::u8_unsuffixed(5).into()).unwrap(),
+        "5"
+    );
+    let tokens: Vec<TokenTree> = token_stream("-(BASE * 2)").collect();
+    assert_eq!(expr_to_string((&tokens).into()).unwrap(), "- (BASE * 2)");
+}
+
+#[test]
+fn test_type_tokens_provenance() {
+    use crate::prelude::Span;
+
+    let mut ty = StreamBuilder::new();
+    ty.provenance("Foo::a", Span::call_site(), |b| {
+        b.push_parsed("Vec<u8>")?;
+        Ok(())
+    })
+    .unwrap();
+    let ty: TypeTokens = ty.into();
+
+    let mut builder = StreamBuilder::new();
+    ty.append_to(&mut builder).unwrap();
+    assert_eq!(builder.provenance.len(), 1);
+    assert_eq!(builder.provenance[0].label, "Foo::a");
+}
@@ -68,23 +68,32 @@
 //! }
 //! ```
 #![warn(missing_docs)]
+#![cfg_attr(
+    all(feature = "nightly", not(any(test, feature = "proc-macro2"))),
+    feature(proc_macro_diagnostic)
+)]
 
 mod error;
 
 pub mod generate;
 pub mod parse;
+#[cfg(feature = "patterns")]
+pub mod patterns;
+#[cfg(feature = "syn-interop")]
+pub mod syn_interop;
 pub mod utils;
 
 /// Result alias for virtue's errors
 pub type Result<T = ()> = std::result::Result<T, Error>;
 
-pub use self::error::Error;
+pub use self::error::{Error, ItemKind};
 
 /// Useful includes
 pub mod prelude {
     pub use crate::generate::{FnSelfArg, Generator, StreamBuilder};
     pub use crate::parse::{
-        AttributeAccess, Body, EnumVariant, Fields, FromAttribute, Parse, UnnamedField,
+        AttributeAccess, AttributeAccessMut, Body, EnumVariant, Fields, FromAttribute, Parse,
+        UnnamedField,
     };
     pub use crate::{Error, Result};
 
@@ -97,6 +106,41 @@ pub mod prelude {
     pub use proc_macro::*;
 }
 
+/// Build a [`TokenStream`] from literal rust code, e.g. to compare the output of a derive with [`Generator::assert_eq_tokens`] in a test. Unlike the string passed to [`Generator::assert_eq`], the code is checked and highlighted by the editor, and its spacing does not have to match the output of `to_string()`.
+///
+/// The tokens are taken as-is, so there is no interpolation like in `quote!`, and `$` can not be used.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// let mut generator = Generator::with_name("Foo");
+/// generator.impl_for("Default").generate_fn("default").with_return_type("Self").body(|b| {
+///     b.push_parsed("Self { a: 0 }")?;
+///     Ok(())
+/// })?;
+/// generator.assert_eq_tokens(virtue::quote_like! {
+///     impl Default for Foo {
+///         fn default() -> Self {
+///             Self { a: 0 }
+///         }
+///     }
+/// });
+/// # Ok::<_, virtue::Error>(())
+/// ```
+///
+/// This is only available with the `proc-macro2` feature, because a `proc_macro::TokenStream` can not be created outside of a proc macro.
+///
+/// [`TokenStream`]: prelude/struct.TokenStream.html
+/// [`Generator::assert_eq_tokens`]: generate/struct.Generator.html#method.assert_eq_tokens
+/// [`Generator::assert_eq`]: generate/struct.Generator.html#method.assert_eq
+#[cfg(any(test, feature = "proc-macro2"))]
+#[macro_export]
+macro_rules! quote_like {
+    ($($tokens:tt)*) => {
+        <$crate::prelude::TokenStream as ::core::str::FromStr>::from_str(::core::stringify!($($tokens)*))
+            .expect("quote_like! could not parse its tokens")
+    };
+}
+
 #[cfg(test)]
 pub(crate) fn token_stream(
     s: &str,
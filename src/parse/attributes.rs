@@ -1,5 +1,6 @@
 use super::utils::*;
-use crate::prelude::{Delimiter, Group, Punct, TokenTree};
+use crate::generate::StreamBuilder;
+use crate::prelude::{Delimiter, Group, Punct, Spacing, Span, TokenStream, TokenTree};
 use crate::{Error, Result};
 use std::iter::Peekable;
 
@@ -11,6 +12,14 @@ pub struct Attribute {
     pub location: AttributeLocation,
     /// The punct token of the attribute. This will always be `Punct('#')`
     pub punct: Punct,
+    /// `true` if this is an inner attribute like `#![allow(unused)]`, `false` for an outer attribute like `#[allow(unused)]`.
+    ///
+    /// Inner attributes apply to the item that surrounds them, e.g. the module that an attribute macro was placed in, and not to the struct or enum that follows them. They are ignored by [`cfg_of`] and [`AttributeAccess`], but can be emitted again with [`to_token_stream`].
+    ///
+    /// [`cfg_of`]: #method.cfg_of
+    /// [`AttributeAccess`]: trait.AttributeAccess.html
+    /// [`to_token_stream`]: #method.to_token_stream
+    pub is_inner: bool,
     /// The group of tokens of the attribute. You can parse this to get your custom attributes.
     pub tokens: Group,
 }
@@ -37,6 +46,15 @@ pub enum AttributeLocation {
     /// }
     /// ```
     Field,
+    /// The attribute is on a function, see [`Function`]
+    ///
+    /// [`Function`]: struct.Function.html
+    Function,
+    /// The attribute is on a function argument
+    /// ```ignore
+    /// fn foo(#[attr] a: u8) {}
+    /// ```
+    Argument,
 }
 
 impl Attribute {
@@ -47,12 +65,14 @@ impl Attribute {
         let mut result = Vec::new();
 
         while let Some(punct) = consume_punct_if(input, '#') {
+            let is_inner = consume_punct_if(input, '!').is_some();
             match input.peek() {
                 Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Bracket => {
-                    let group = assume_group(input.next());
+                    let group = assume_group(input.next())?;
                     result.push(Attribute {
                         location,
                         punct,
+                        is_inner,
                         tokens: group,
                     });
                 }
@@ -62,7 +82,7 @@ impl Attribute {
                         expected: format!("[] bracket, got {:?}", g.delimiter()),
                     });
                 }
-                Some(TokenTree::Punct(p)) if p.as_char() == '#' => {
+                Some(TokenTree::Punct(p)) if p.as_char() == '#' && !is_inner => {
                     // sometimes with empty lines of doc comments, we get two #'s in a row
                     // Just ignore this
                 }
@@ -71,6 +91,157 @@ impl Attribute {
         }
         Ok(result)
     }
+
+    /// Unwrap the groups without delimiters at the start of an item, before and between its attributes, e.g. the group around an `$item:item` that a `macro_rules!` puts a `#[derive(...)]` in front of, or the groups that a macro that re-emits attributes wraps them in. Groups inside of these groups are unwrapped as well.
+    ///
+    /// The tokens after the attributes, starting at the visibility or the `struct` or `enum` keyword, are returned unchanged.
+    pub(crate) fn unwrap_none_groups(input: impl IntoIterator<Item = TokenTree>) -> Vec<TokenTree> {
+        let mut input = input.into_iter();
+        let mut result = Self::unwrap_leading_none_groups(&mut input);
+        result.extend(input);
+        result
+    }
+
+    /// Like [`unwrap_none_groups`], but only take the attributes and the first token after them from `input`, together with the rest of the groups they were unwrapped from. The remaining tokens are left in `input`.
+    ///
+    /// [`unwrap_none_groups`]: #method.unwrap_none_groups
+    pub(crate) fn unwrap_leading_none_groups(
+        input: &mut impl Iterator<Item = TokenTree>,
+    ) -> Vec<TokenTree> {
+        // the tokens of unwrapped groups that still have to be handled, in reverse order
+        let mut pending: Vec<TokenTree> = Vec::new();
+        let mut result = Vec::new();
+        while let Some(token) = pending.pop().or_else(|| input.next()) {
+            match token {
+                TokenTree::Group(group) if group.delimiter() == Delimiter::None => {
+                    let start = pending.len();
+                    pending.extend(group.stream());
+                    pending[start..].reverse();
+                }
+                TokenTree::Punct(punct) if punct.as_char() == '#' || punct.as_char() == '!' => {
+                    result.push(TokenTree::Punct(punct));
+                }
+                TokenTree::Group(group) if group.delimiter() == Delimiter::Bracket => {
+                    result.push(TokenTree::Group(group));
+                }
+                token => {
+                    result.push(token);
+                    break;
+                }
+            }
+        }
+        result.extend(pending.into_iter().rev());
+        result
+    }
+
+    /// Returns `true` if the path of this attribute is `path`, e.g. `#[cfg(test)]` has the path `cfg`. Only the first segment of the path is checked, so `#[rustfmt::skip]` has the path `rustfmt`.
+    pub fn path_is(&self, path: &str) -> bool {
+        matches!(self.tokens.stream().into_iter().next(), Some(TokenTree::Ident(ident)) if ident_eq(&ident, path))
+    }
+
+    /// The tokens of this attribute as they were parsed, e.g. `#[cfg(test)]` or `#![allow(unused)]`.
+    ///
+    /// This can be used to emit attributes again that a macro does not handle itself, e.g. the inner attributes of an attribute macro on a module.
+    pub fn to_token_stream(&self) -> TokenStream {
+        let mut builder = StreamBuilder::new();
+        builder.push(self.punct.clone());
+        if self.is_inner {
+            let mut bang = Punct::new('!', Spacing::Alone);
+            bang.set_span(self.punct.span());
+            builder.push(bang);
+        }
+        builder.push(self.tokens.clone());
+        builder.stream
+    }
+
+    /// Returns the predicate of a `#[cfg(...)]` attribute, e.g. `feature = "foo"` for `#[cfg(feature = "foo")]`.
+    ///
+    /// For a `#[cfg_attr(predicate, cfg(inner))]` attribute this returns `any(not(predicate), all(inner))`, because the item only depends on `inner` if `predicate` is enabled. Other attributes, and `cfg_attr` attributes without a `cfg`, return `None`.
+    ///
+    /// This can be used to put the same `#[cfg(...)]` on generated code as on the field or variant that it was generated for. See also [`FieldContext::with_cfg`].
+    ///
+    /// [`FieldContext::with_cfg`]: ../generate/struct.FieldContext.html#method.with_cfg
+    pub fn as_cfg(&self) -> Option<TokenStream> {
+        let mut tokens = self.tokens.stream().into_iter();
+        let ident = match tokens.next() {
+            Some(TokenTree::Ident(ident)) => ident,
+            _ => return None,
+        };
+        let group = match tokens.next() {
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => group,
+            _ => return None,
+        };
+        if ident_eq(&ident, "cfg") {
+            return Some(group.stream());
+        }
+        if !ident_eq(&ident, "cfg_attr") {
+            return None;
+        }
+
+        // `cfg_attr(predicate, attr, attr, ...)`
+        let mut parts = vec![Vec::new()];
+        for token in group.stream() {
+            match token {
+                TokenTree::Punct(p) if p.as_char() == ',' => parts.push(Vec::new()),
+                token => parts.last_mut().unwrap().push(token),
+            }
+        }
+        let predicate = parts.remove(0);
+        let inner: Vec<TokenStream> = parts
+            .into_iter()
+            .filter_map(|part| match part.as_slice() {
+                [TokenTree::Ident(ident), TokenTree::Group(group)]
+                    if ident_eq(ident, "cfg") && group.delimiter() == Delimiter::Parenthesis =>
+                {
+                    Some(group.stream())
+                }
+                _ => None,
+            })
+            .collect();
+        if inner.is_empty() {
+            return None;
+        }
+
+        let mut builder = StreamBuilder::new();
+        builder
+            .ident_str("any")
+            .group(Delimiter::Parenthesis, |b| {
+                b.ident_str("not")
+                    .group(Delimiter::Parenthesis, |b| {
+                        b.extend(predicate);
+                        Ok(())
+                    })?
+                    .punct(',');
+                append_all(b, inner);
+                Ok(())
+            })
+            .expect("Could not build cfg");
+        Some(builder.stream)
+    }
+
+    /// Returns the combined predicate of all `#[cfg(...)]` and `#[cfg_attr(..., cfg(...))]` in `attributes`, see [`as_cfg`]. Multiple predicates are combined with `all(...)`. Inner attributes are ignored.
+    ///
+    /// [`as_cfg`]: #method.as_cfg
+    pub fn cfg_of(attributes: &[Attribute]) -> Option<TokenStream> {
+        let mut predicates: Vec<TokenStream> = attributes
+            .iter()
+            .filter(|attribute| !attribute.is_inner)
+            .filter_map(Self::as_cfg)
+            .collect();
+        match predicates.len() {
+            0 => None,
+            1 => predicates.pop(),
+            _ => {
+                let mut builder = StreamBuilder::new();
+                append_all(&mut builder, predicates);
+                Some(builder.stream)
+            }
+        }
+    }
+
+    fn parse<T: FromAttribute>(&self) -> Result<Option<T>> {
+        T::parse_with_location(&self.tokens, self.location, self.tokens.span())
+    }
 }
 
 #[test]
@@ -96,6 +267,92 @@ fn test_attributes_try_take() {
     }
 }
 
+/// `all(a, b, ...)`
+fn append_all(builder: &mut StreamBuilder, predicates: Vec<TokenStream>) {
+    builder
+        .ident_str("all")
+        .group(Delimiter::Parenthesis, |b| {
+            for (idx, predicate) in predicates.into_iter().enumerate() {
+                if idx > 0 {
+                    b.punct(',');
+                }
+                b.extend(predicate);
+            }
+            Ok(())
+        })
+        .expect("Could not build cfg");
+}
+
+#[test]
+fn test_inner_attributes() {
+    use crate::token_stream;
+
+    let stream = &mut token_stream("#![allow(unused)] #![cfg(test)] #[cfg(unix)] struct Foo;");
+    let attributes = Attribute::try_take(AttributeLocation::Container, stream).unwrap();
+    let inner: Vec<bool> = attributes.iter().map(|attr| attr.is_inner).collect();
+    assert_eq!(inner, [true, true, false]);
+    assert_eq!(
+        attributes[0].to_token_stream().to_string(),
+        "#! [allow (unused)]"
+    );
+    assert_eq!(
+        attributes[2].to_token_stream().to_string(),
+        "# [cfg (unix)]"
+    );
+    assert_eq!(Attribute::cfg_of(&attributes).unwrap().to_string(), "unix");
+    match stream.next().unwrap() {
+        TokenTree::Ident(i) => assert_eq!(i, "struct"),
+        x => panic!("Expected ident, found {:?}", x),
+    }
+
+    let stream = &mut token_stream("#! struct Foo;");
+    assert!(Attribute::try_take(AttributeLocation::Container, stream).is_err());
+}
+
+#[test]
+fn test_attribute_as_cfg() {
+    use crate::token_stream;
+
+    let stream = &mut token_stream(
+        "#[cfg(feature = \"a\")] #[cfg_attr(test, cfg(unix), allow(unused))] #[cfg_attr(test, allow(unused))] #[doc = \"cfg\"] struct Foo;",
+    );
+    let attributes = Attribute::try_take(AttributeLocation::Container, stream).unwrap();
+    let cfgs: Vec<Option<String>> = attributes
+        .iter()
+        .map(|attr| attr.as_cfg().map(|cfg| cfg.to_string()))
+        .collect();
+    assert_eq!(
+        cfgs,
+        [
+            Some("feature = \"a\"".to_owned()),
+            Some("any (not (test) , all (unix))".to_owned()),
+            None,
+            None,
+        ]
+    );
+    assert_eq!(
+        Attribute::cfg_of(&attributes).unwrap().to_string(),
+        "all (feature = \"a\" , any (not (test) , all (unix)))"
+    );
+    assert_eq!(
+        Attribute::cfg_of(&attributes[..1]).unwrap().to_string(),
+        "feature = \"a\""
+    );
+    assert!(Attribute::cfg_of(&attributes[2..]).is_none());
+}
+
+#[test]
+fn test_attribute_path_is() {
+    use crate::token_stream;
+
+    let stream = &mut token_stream("#[cfg(test)] #[doc = \"foo\"] #[rustfmt::skip] struct Foo;");
+    let attributes = Attribute::try_take(AttributeLocation::Container, stream).unwrap();
+    assert!(attributes[0].path_is("cfg"));
+    assert!(!attributes[0].path_is("doc"));
+    assert!(attributes[1].path_is("doc"));
+    assert!(attributes[2].path_is("rustfmt"));
+}
+
 /// Helper trait for [`AttributeAccess`] methods.
 ///
 /// This can be implemented on your own type to make parsing easier.
@@ -108,9 +365,26 @@ fn test_attributes_try_take() {
 pub trait FromAttribute: Sized {
     /// Try to parse the given group into your own type. Return `Ok(None)` if the parsing failed or if the attribute was not this type.
     fn parse(group: &Group) -> Result<Option<Self>>;
+
+    /// Try to parse the given group into your own type, knowing the [`AttributeLocation`] and [`Span`] of the attribute.
+    ///
+    /// This is what [`AttributeAccess`] calls. By default this calls [`parse`]. Implement this if the same attribute should behave differently on e.g. a container and a field, or if your errors should point at the attribute.
+    ///
+    /// [`AttributeAccess`]: trait.AttributeAccess.html
+    /// [`parse`]: #tymethod.parse
+    fn parse_with_location(
+        group: &Group,
+        location: AttributeLocation,
+        span: Span,
+    ) -> Result<Option<Self>> {
+        let _ = (location, span);
+        Self::parse(group)
+    }
 }
 
-/// Bring useful methods to access attributes of an element.
+/// Bring useful methods to access attributes of an element. Inner attributes like `#![prefix(...)]` are skipped, see [`Attribute::is_inner`].
+///
+/// [`Attribute::is_inner`]: struct.Attribute.html#structfield.is_inner
 pub trait AttributeAccess {
     /// Check to see if has the given attribute. See [`FromAttribute`] for more information.
     ///
@@ -121,12 +395,38 @@ pub trait AttributeAccess {
     ///
     /// **note**: Will immediately return `Err(_)` on the first error `T` returns.
     fn get_attribute<T: FromAttribute>(&self) -> Result<Option<T>>;
+
+    /// Returns `true` if there is a `#[non_exhaustive]` attribute, e.g. on a struct, an enum or a variant.
+    ///
+    /// Matches on a `#[non_exhaustive]` enum of another crate need a `_ => ...` arm, see [`StreamBuilder::wildcard_arm_if`].
+    ///
+    /// [`StreamBuilder::wildcard_arm_if`]: ../generate/struct.StreamBuilder.html#method.wildcard_arm_if
+    fn has_non_exhaustive(&self) -> bool {
+        matches!(self.get_attribute::<NonExhaustive>(), Ok(Some(_)))
+    }
 }
 
-impl AttributeAccess for Vec<Attribute> {
+/// The `#[non_exhaustive]` attribute, see [`AttributeAccess::has_non_exhaustive`]
+///
+/// [`AttributeAccess::has_non_exhaustive`]: trait.AttributeAccess.html#method.has_non_exhaustive
+struct NonExhaustive;
+
+impl FromAttribute for NonExhaustive {
+    fn parse(group: &Group) -> Result<Option<Self>> {
+        let mut tokens = group.stream().into_iter();
+        Ok(match (tokens.next(), tokens.next()) {
+            (Some(TokenTree::Ident(ident)), None) if ident_eq(&ident, "non_exhaustive") => {
+                Some(Self)
+            }
+            _ => None,
+        })
+    }
+}
+
+impl AttributeAccess for [Attribute] {
     fn has_attribute<T: FromAttribute + PartialEq<T>>(&self, attrib: T) -> Result<bool> {
-        for attribute in self.iter() {
-            if let Some(attribute) = T::parse(&attribute.tokens)? {
+        for attribute in self.iter().filter(|attribute| !attribute.is_inner) {
+            if let Some(attribute) = attribute.parse::<T>()? {
                 if attribute == attrib {
                     return Ok(true);
                 }
@@ -136,11 +436,275 @@ impl AttributeAccess for Vec<Attribute> {
     }
 
     fn get_attribute<T: FromAttribute>(&self) -> Result<Option<T>> {
-        for attribute in self.iter() {
-            if let Some(attribute) = T::parse(&attribute.tokens)? {
+        for attribute in self.iter().filter(|attribute| !attribute.is_inner) {
+            if let Some(attribute) = attribute.parse::<T>()? {
                 return Ok(Some(attribute));
             }
         }
         Ok(None)
     }
 }
+
+impl AttributeAccess for Vec<Attribute> {
+    fn has_attribute<T: FromAttribute + PartialEq<T>>(&self, attrib: T) -> Result<bool> {
+        self.as_slice().has_attribute(attrib)
+    }
+
+    fn get_attribute<T: FromAttribute>(&self) -> Result<Option<T>> {
+        self.as_slice().get_attribute()
+    }
+}
+
+impl AttributeAccess for Box<[Attribute]> {
+    fn has_attribute<T: FromAttribute + PartialEq<T>>(&self, attrib: T) -> Result<bool> {
+        (**self).has_attribute(attrib)
+    }
+
+    fn get_attribute<T: FromAttribute>(&self) -> Result<Option<T>> {
+        (**self).get_attribute()
+    }
+}
+
+impl AttributeAccess for Attribute {
+    fn has_attribute<T: FromAttribute + PartialEq<T>>(&self, attrib: T) -> Result<bool> {
+        std::slice::from_ref(self).has_attribute(attrib)
+    }
+
+    fn get_attribute<T: FromAttribute>(&self) -> Result<Option<T>> {
+        std::slice::from_ref(self).get_attribute()
+    }
+}
+
+impl AttributeAccess for Option<&Attribute> {
+    fn has_attribute<T: FromAttribute + PartialEq<T>>(&self, attrib: T) -> Result<bool> {
+        self.map_or(Ok(false), |attribute| attribute.has_attribute(attrib))
+    }
+
+    fn get_attribute<T: FromAttribute>(&self) -> Result<Option<T>> {
+        self.map_or(Ok(None), |attribute| attribute.get_attribute())
+    }
+}
+
+/// Methods to remove attributes from a list, e.g. for an attribute macro that has to strip its own helper attributes like `#[my_macro(skip)]` from the fields before it emits the item again. Inner attributes are never removed.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// # use std::str::FromStr;
+/// #[derive(PartialEq)]
+/// struct Skip;
+///
+/// impl FromAttribute for Skip {
+///     fn parse(group: &Group) -> Result<Option<Self>> {
+///         Ok(Some(Skip).filter(|_| group.stream().to_string() == "my_macro (skip)"))
+///     }
+/// }
+///
+/// let input = TokenStream::from_str("struct Foo { #[my_macro(skip)] #[doc = \"A\"] a: u8 }").unwrap();
+/// let mut fields = match Parse::new(input)? {
+///     Parse::Struct { body, .. } => body.fields,
+///     _ => unreachable!(),
+/// };
+/// # let fields = match &mut fields { Some(Fields::Struct(fields)) => fields, _ => unreachable!() };
+/// let attributes = &mut fields[0].1.attributes;
+/// assert!(attributes.take_attribute::<Skip>()?.is_some());
+/// assert_eq!(attributes.len(), 1); // only `#[doc = "A"]` is left
+/// # Ok::<_, virtue::Error>(())
+/// ```
+pub trait AttributeAccessMut: AttributeAccess {
+    /// Remove the first attribute that returns `Some(T)`, and return its value. See [`FromAttribute`] for more information.
+    ///
+    /// **note**: Will immediately return `Err(_)` on the first error `T` returns. The attributes are not changed in that case.
+    fn take_attribute<T: FromAttribute>(&mut self) -> Result<Option<T>>;
+
+    /// Remove all attributes that return `Some(T)`, and return their values in order. See [`FromAttribute`] for more information.
+    ///
+    /// **note**: Will immediately return `Err(_)` on the first error `T` returns. The attributes are not changed in that case.
+    fn remove_attribute<T: FromAttribute>(&mut self) -> Result<Vec<T>>;
+}
+
+impl AttributeAccessMut for Vec<Attribute> {
+    fn take_attribute<T: FromAttribute>(&mut self) -> Result<Option<T>> {
+        for idx in 0..self.len() {
+            if self[idx].is_inner {
+                continue;
+            }
+            if let Some(value) = self[idx].parse::<T>()? {
+                self.remove(idx);
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    fn remove_attribute<T: FromAttribute>(&mut self) -> Result<Vec<T>> {
+        let mut parsed = Vec::with_capacity(self.len());
+        for attribute in self.iter() {
+            parsed.push(if attribute.is_inner {
+                None
+            } else {
+                attribute.parse::<T>()?
+            });
+        }
+        let mut result = Vec::new();
+        let mut parsed = parsed.into_iter();
+        self.retain(|_| match parsed.next().flatten() {
+            Some(value) => {
+                result.push(value);
+                false
+            }
+            None => true,
+        });
+        Ok(result)
+    }
+}
+
+impl AttributeAccessMut for Box<[Attribute]> {
+    fn take_attribute<T: FromAttribute>(&mut self) -> Result<Option<T>> {
+        with_vec(self, |attributes| attributes.take_attribute())
+    }
+
+    fn remove_attribute<T: FromAttribute>(&mut self) -> Result<Vec<T>> {
+        with_vec(self, |attributes| attributes.remove_attribute())
+    }
+}
+
+/// Call `f` with the attributes as a `Vec`, and store the result back in `attributes`
+fn with_vec<R>(attributes: &mut Box<[Attribute]>, f: impl FnOnce(&mut Vec<Attribute>) -> R) -> R {
+    let mut vec = std::mem::take(attributes).into_vec();
+    let result = f(&mut vec);
+    *attributes = vec.into_boxed_slice();
+    result
+}
+
+#[test]
+fn test_from_attribute_with_location() {
+    use crate::token_stream;
+
+    #[derive(PartialEq, Debug)]
+    enum Skip {
+        Container,
+        Field,
+    }
+
+    impl FromAttribute for Skip {
+        fn parse(_: &Group) -> Result<Option<Self>> {
+            unreachable!("parse_with_location is implemented")
+        }
+
+        fn parse_with_location(
+            group: &Group,
+            location: AttributeLocation,
+            span: Span,
+        ) -> Result<Option<Self>> {
+            if group.stream().to_string() != "skip" {
+                return Ok(None);
+            }
+            match location {
+                AttributeLocation::Container => Ok(Some(Skip::Container)),
+                AttributeLocation::Field => Ok(Some(Skip::Field)),
+                _ => Err(Error::custom_at("skip is not allowed on variants", span)),
+            }
+        }
+    }
+
+    let container =
+        Attribute::try_take(AttributeLocation::Container, &mut token_stream("#[skip]")).unwrap();
+    assert_eq!(
+        container.get_attribute::<Skip>().unwrap(),
+        Some(Skip::Container)
+    );
+    let field =
+        Attribute::try_take(AttributeLocation::Field, &mut token_stream("#[skip]")).unwrap();
+    assert!(field.has_attribute(Skip::Field).unwrap());
+    let variant =
+        Attribute::try_take(AttributeLocation::Variant, &mut token_stream("#[skip]")).unwrap();
+    assert!(variant.get_attribute::<Skip>().is_err());
+}
+
+#[test]
+fn test_has_non_exhaustive() {
+    use crate::parse::Parse;
+    use crate::token_stream;
+
+    let parse = |input: &str| {
+        Parse::new(token_stream(input).collect::<proc_macro2::TokenStream>()).unwrap()
+    };
+    let (attributes, body) =
+        match parse("#[non_exhaustive] enum Foo { A, #[non_exhaustive] B { a: u8 } }") {
+            Parse::Enum {
+                attributes, body, ..
+            } => (attributes, body),
+            _ => unreachable!(),
+        };
+    assert!(attributes.has_non_exhaustive());
+    assert!(!body.variants[0].attributes.has_non_exhaustive());
+    assert!(body.variants[1].attributes.has_non_exhaustive());
+
+    match parse("#[derive(Debug)] #[doc = \"non_exhaustive\"] struct Foo;") {
+        Parse::Struct { attributes, .. } => assert!(!attributes.has_non_exhaustive()),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_attribute_access_mut() {
+    use crate::token_stream;
+
+    #[derive(PartialEq, Debug)]
+    struct Rename(String);
+
+    impl FromAttribute for Rename {
+        fn parse(group: &Group) -> Result<Option<Self>> {
+            let text = group.stream().to_string();
+            if text == "rename" {
+                return Err(Error::custom("rename needs a value"));
+            }
+            Ok(text
+                .strip_prefix("rename = ")
+                .map(|name| Rename(name.trim_matches('"').to_owned())))
+        }
+    }
+
+    let take = |input: &str| {
+        Attribute::try_take(AttributeLocation::Field, &mut token_stream(input)).unwrap()
+    };
+    let mut attributes =
+        take("#![rename = \"inner\"] #[rename = \"a\"] #[doc = \"x\"] #[rename = \"b\"]");
+    assert!(attributes[..2]
+        .has_attribute(Rename("a".to_owned()))
+        .unwrap());
+    assert!(!attributes[2..]
+        .has_attribute(Rename("a".to_owned()))
+        .unwrap());
+    assert_eq!(
+        attributes.last().get_attribute::<Rename>().unwrap(),
+        Some(Rename("b".to_owned()))
+    );
+    assert_eq!(attributes[0].get_attribute::<Rename>().unwrap(), None);
+    assert_eq!(None.get_attribute::<Rename>().unwrap(), None);
+    assert!(!None.has_non_exhaustive());
+
+    assert_eq!(
+        attributes.take_attribute::<Rename>().unwrap(),
+        Some(Rename("a".to_owned()))
+    );
+    assert_eq!(attributes.len(), 3);
+    let mut copy = attributes.clone();
+    assert_eq!(
+        copy.remove_attribute::<Rename>().unwrap(),
+        [Rename("b".to_owned())]
+    );
+    assert_eq!(copy.len(), 2);
+    assert!(copy[0].is_inner);
+    assert!(copy[1].path_is("doc"));
+
+    let mut attributes = take("#[rename = \"a\"] #[rename] #[doc = \"x\"]");
+    assert!(attributes.remove_attribute::<Rename>().is_err());
+    assert_eq!(attributes.len(), 3);
+    assert_eq!(
+        attributes.take_attribute::<Rename>().unwrap(),
+        Some(Rename("a".to_owned()))
+    );
+    assert!(attributes.take_attribute::<Rename>().is_err());
+    assert_eq!(attributes.len(), 2);
+}
@@ -1,6 +1,7 @@
 use super::attributes::AttributeLocation;
 use super::{utils::*, Attribute, Visibility};
 use crate::prelude::{Delimiter, Ident, Literal, Span, TokenTree};
+use crate::utils::case::Case;
 use crate::{Error, Result};
 use std::iter::Peekable;
 
@@ -20,15 +21,15 @@ impl StructBody {
             }
             token => return Error::wrong_token(token, "group or punct"),
         }
-        let group = assume_group(input.next());
-        let mut stream = group.stream().into_iter().peekable();
+        let group = assume_group(input.next())?;
+        let tokens = group.stream().into_iter().collect();
         let fields = match group.delimiter() {
             Delimiter::Brace => {
-                let fields = UnnamedField::parse_with_name(&mut stream)?;
+                let fields = UnnamedField::parse_with_name(tokens)?;
                 Some(Fields::Struct(fields))
             }
             Delimiter::Parenthesis => {
-                let fields = UnnamedField::parse(&mut stream)?;
+                let fields = UnnamedField::parse(tokens)?;
                 Some(Fields::Tuple(fields))
             }
             found => {
@@ -40,6 +41,30 @@ impl StructBody {
         };
         Ok(StructBody { fields })
     }
+
+    /// Returns `true` if this is a unit struct without brackets, e.g. `struct Foo;`
+    pub fn is_unit(&self) -> bool {
+        self.fields.is_none()
+    }
+
+    /// Returns `true` if this struct has braces without any fields, e.g. `struct Foo {}`
+    pub fn is_empty_braced(&self) -> bool {
+        matches!(&self.fields, Some(Fields::Struct(fields)) if fields.is_empty())
+    }
+}
+
+#[test]
+fn test_struct_body_is_unit() {
+    use crate::token_stream;
+
+    let body = |input: &str| StructBody::take(&mut token_stream(input)).unwrap();
+    assert!(body(";").is_unit());
+    assert!(!body(";").is_empty_braced());
+    assert!(body("{}").is_empty_braced());
+    assert!(!body("{}").is_unit());
+    assert!(!body("()").is_unit());
+    assert!(!body("()").is_empty_braced());
+    assert!(!body("{ a: u8 }").is_empty_braced());
 }
 
 #[test]
@@ -63,7 +88,7 @@ fn test_struct_body_take() {
 
     let (ident, field) = fields.get(1).unwrap();
     assert_eq!(ident.unwrap(), "baz");
-    assert_eq!(field.vis, Visibility::Pub);
+    assert!(matches!(field.vis, Visibility::Restricted(_)));
     assert_eq!(field.type_string(), "u32");
 
     let (ident, field) = fields.get(2).unwrap();
@@ -89,7 +114,7 @@ fn test_struct_body_take() {
 
     let (ident, field) = fields.get(1).unwrap();
     assert!(ident.is_none());
-    assert_eq!(field.vis, Visibility::Pub);
+    assert!(matches!(field.vis, Visibility::Restricted(_)));
     assert_eq!(field.type_string(), "u32");
 
     let (ident, field) = fields.get(2).unwrap();
@@ -155,6 +180,18 @@ fn issue_77() {
     };
     assert_eq!(t.len(), 1);
     assert_eq!(t[0].r#type[0].to_string(), "(u8 ,)");
+
+    // `(crate::Foo)` is a parenthesized type, not a `pub(crate)` restriction
+    let stream = &mut token_stream("struct A(pub (crate::Foo), u8);");
+    super::DataType::take(stream).unwrap();
+    let body = StructBody::take(stream).unwrap();
+    let Some(Fields::Tuple(t)) = body.fields else {
+        panic!("Fields is not a tuple")
+    };
+    assert_eq!(t.len(), 2);
+    assert_eq!(t[0].vis, Visibility::Pub);
+    assert_eq!(t[0].r#type[0].to_string(), "(crate :: Foo)");
+    assert_eq!(t[1].r#type[0].to_string(), "u8");
 }
 
 /// The body of an enum
@@ -175,7 +212,7 @@ impl EnumBody {
             }
             token => return Error::wrong_token(token, "group or ;"),
         }
-        let group = assume_group(input.next());
+        let group = assume_group(input.next())?;
         let mut variants = Vec::new();
         let stream = &mut group.stream().into_iter().peekable();
         while stream.peek().is_some() {
@@ -189,14 +226,14 @@ impl EnumBody {
             let mut value = None;
 
             if let Some(TokenTree::Group(_)) = stream.peek() {
-                let group = assume_group(stream.next());
-                let stream = &mut group.stream().into_iter().peekable();
+                let group = assume_group(stream.next())?;
+                let tokens = group.stream().into_iter().collect();
                 match group.delimiter() {
                     Delimiter::Brace => {
-                        fields = Some(Fields::Struct(UnnamedField::parse_with_name(stream)?));
+                        fields = Some(Fields::Struct(UnnamedField::parse_with_name(tokens)?));
                     }
                     Delimiter::Parenthesis => {
-                        fields = Some(Fields::Tuple(UnnamedField::parse(stream)?));
+                        fields = Some(Fields::Tuple(UnnamedField::parse(tokens)?));
                     }
                     delim => {
                         return Err(Error::InvalidRustSyntax {
@@ -206,28 +243,13 @@ impl EnumBody {
                     }
                 }
             }
+            let mut value_expr = None;
             match stream.peek() {
                 Some(TokenTree::Punct(p)) if p.as_char() == '=' => {
-                    assume_punct(stream.next(), '=');
-                    match stream.next() {
-                        Some(TokenTree::Literal(lit)) => {
-                            value = Some(lit);
-                        }
-                        Some(TokenTree::Punct(p)) if p.as_char() == '-' => match stream.next() {
-                            Some(TokenTree::Literal(lit)) => {
-                                match lit.to_string().parse::<i64>() {
-                                    Ok(val) => value = Some(Literal::i64_unsuffixed(-val)),
-                                    Err(_) => {
-                                        return Err(Error::custom_at(
-                                            "parse::<i64> failed",
-                                            lit.span(),
-                                        ))
-                                    }
-                                };
-                            }
-                            token => return Error::wrong_token(token.as_ref(), "literal"),
-                        },
-                        token => return Error::wrong_token(token.as_ref(), "literal"),
+                    let eq = assume_punct(stream.next(), '=')?;
+                    match parse_discriminant(stream, eq.span())? {
+                        Discriminant::Literal(lit) => value = Some(lit),
+                        Discriminant::Expr(tokens) => value_expr = Some(tokens),
                     }
                 }
                 Some(TokenTree::Punct(p)) if p.as_char() == ',' => {
@@ -245,12 +267,181 @@ impl EnumBody {
                 name: ident,
                 fields,
                 value,
-                attributes,
+                value_expr,
+                attributes: attributes.into_boxed_slice(),
             });
         }
 
         Ok(EnumBody { variants })
     }
+
+    /// Returns `true` if this enum has no variants, e.g. `enum Never {}`. Values of this enum can not exist.
+    ///
+    /// Functions taking `self` can be implemented with [`StreamBuilder::match_uninhabited`].
+    ///
+    /// [`StreamBuilder::match_uninhabited`]: ../generate/struct.StreamBuilder.html#method.match_uninhabited
+    pub fn is_uninhabited(&self) -> bool {
+        self.variants.is_empty()
+    }
+
+    /// Returns the variant of this enum if it has exactly one variant.
+    pub fn single_variant(&self) -> Option<&EnumVariant> {
+        match self.variants.as_slice() {
+            [variant] => Some(variant),
+            _ => None,
+        }
+    }
+
+    /// Returns the discriminant of every variant, in the same order as [`variants`]. Variants without an explicit value are numbered like rust does: the first variant is `0`, every other variant is the previous discriminant plus one.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = TokenStream::from_str("enum Foo { A = -3, B, C, D = 0x10, E }").unwrap();
+    /// let (_, _, body) = Parse::new(input)?.into_generator();
+    /// # let body = match body { Body::Enum(body) => body, _ => unreachable!() };
+    /// let discriminants = body.resolved_discriminants()?;
+    /// let values: Vec<i128> = discriminants.iter().map(|d| d.value).collect();
+    /// assert_eq!(values, [-3, -2, -1, 16, 17]);
+    /// assert_eq!(discriminants[4].literal().to_string(), "17");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// Returns an error if a value is not an integer literal, e.g. an expression like `1 << 2` or a const, because those can only be evaluated by the compiler. An error is also returned if a discriminant does not fit in an `i128`.
+    ///
+    /// Variants with a `#[cfg(...)]` are counted as well, so implicit discriminants after a variant that is disabled differ from the ones that the compiler assigns.
+    ///
+    /// [`variants`]: #structfield.variants
+    pub fn resolved_discriminants(&self) -> Result<Vec<ResolvedDiscriminant>> {
+        let mut result: Vec<ResolvedDiscriminant> = Vec::with_capacity(self.variants.len());
+        for variant in &self.variants {
+            let value = if let Some(literal) = &variant.value {
+                parse_int_literal(literal)?
+            } else if let Some(expr) = &variant.value_expr {
+                let span = expr
+                    .first()
+                    .map_or_else(|| variant.name.span(), TokenTree::span);
+                return Err(Error::custom_at(
+                    "Only integer literals are supported as discriminant",
+                    span,
+                ));
+            } else {
+                match result.last() {
+                    Some(previous) => previous.value.checked_add(1).ok_or_else(|| {
+                        Error::custom_at("Enum discriminant overflowed", variant.name.span())
+                    })?,
+                    None => 0,
+                }
+            };
+            result.push(ResolvedDiscriminant { value });
+        }
+        Ok(result)
+    }
+}
+
+/// The discriminant of an enum variant, see [`EnumBody::resolved_discriminants`].
+///
+/// [`EnumBody::resolved_discriminants`]: struct.EnumBody.html#method.resolved_discriminants
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct ResolvedDiscriminant {
+    /// The value of the discriminant
+    pub value: i128,
+}
+
+impl ResolvedDiscriminant {
+    /// The value as an unsuffixed [`Literal`], e.g. to emit it in a match arm.
+    pub fn literal(&self) -> Literal {
+        Literal::i128_unsuffixed(self.value)
+    }
+}
+
+/// Parse an integer literal like `5`, `-3`, `0x1F`, `1_000` or `5u8`.
+fn parse_int_literal(literal: &Literal) -> Result<i128> {
+    parse_int(&literal.to_string(), literal.span())
+}
+
+fn parse_int(text: &str, span: Span) -> Result<i128> {
+    let error = || Error::custom_at("Only integer literals are supported as discriminant", span);
+    let text = text.replace('_', "");
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text.as_str()),
+    };
+    let (radix, digits) = match text.get(..2) {
+        Some("0x") => (16, &text[2..]),
+        Some("0o") => (8, &text[2..]),
+        Some("0b") => (2, &text[2..]),
+        _ => (10, text),
+    };
+    // Hexadecimal digits can be `e` or `f`, so the suffix is found by its `i` or `u`.
+    let digits = match digits.find(&['i', 'u'][..]) {
+        Some(idx) => &digits[..idx],
+        None => digits,
+    };
+    if digits.is_empty() {
+        return Err(error());
+    }
+    let value = u128::from_str_radix(digits, radix).map_err(|_| error())?;
+    let value = if negative && value == i128::MIN.unsigned_abs() {
+        Some(i128::MIN)
+    } else if negative {
+        i128::try_from(value).ok().map(|value| -value)
+    } else {
+        i128::try_from(value).ok()
+    };
+    value.ok_or_else(|| Error::custom_at("Enum discriminant overflowed", span))
+}
+
+enum Discriminant {
+    Literal(Literal),
+    Expr(Vec<TokenTree>),
+}
+
+/// Take the discriminant after the `=` of a variant, up to the next `,`.
+///
+/// `macro_rules!` wrap `$value:literal` and `$value:expr` in a group without delimiters, which can also contain a negative literal like `-1`. These groups are unwrapped, so a literal is always returned as a [`Literal`]. Other expressions, e.g. a path to a const, are returned as-is.
+fn parse_discriminant(
+    stream: &mut Peekable<impl Iterator<Item = TokenTree>>,
+    eq_span: Span,
+) -> Result<Discriminant> {
+    let mut tokens = Vec::new();
+    while let Some(token) = stream.peek() {
+        if matches!(token, TokenTree::Punct(p) if p.as_char() == ',') {
+            break;
+        }
+        tokens.push(stream.next().unwrap());
+    }
+    let tokens = unwrap_none_group(tokens);
+    match tokens.as_slice() {
+        [] => Err(Error::custom_at(
+            "Expected a discriminant after `=`",
+            eq_span,
+        )),
+        [TokenTree::Literal(lit)] => Ok(Discriminant::Literal(lit.clone())),
+        [TokenTree::Punct(minus), rest] if minus.as_char() == '-' => {
+            match unwrap_none_group(vec![rest.clone()]).as_slice() {
+                [TokenTree::Literal(lit)] => {
+                    let value = parse_int(&format!("-{}", lit), lit.span())?;
+                    Ok(Discriminant::Literal(Literal::i128_unsuffixed(value)))
+                }
+                _ => Ok(Discriminant::Expr(tokens)),
+            }
+        }
+        _ => Ok(Discriminant::Expr(tokens)),
+    }
+}
+
+/// Returns the contents of `tokens` if it is a single group without delimiters, repeatedly
+fn unwrap_none_group(mut tokens: Vec<TokenTree>) -> Vec<TokenTree> {
+    loop {
+        match tokens.as_slice() {
+            [TokenTree::Group(group)] if group.delimiter() == Delimiter::None => {
+                tokens = group.stream().into_iter().collect();
+            }
+            _ => return tokens,
+        }
+    }
 }
 
 #[test]
@@ -263,6 +454,14 @@ fn test_enum_body_take() {
     assert_eq!(ident, "Foo");
     let body = EnumBody::take(stream).unwrap();
     assert!(body.variants.is_empty());
+    assert!(body.is_uninhabited());
+    assert!(body.single_variant().is_none());
+
+    let stream = &mut token_stream("enum Foo { Bar(u8) }");
+    super::DataType::take(stream).unwrap();
+    let body = EnumBody::take(stream).unwrap();
+    assert!(!body.is_uninhabited());
+    assert_eq!(body.single_variant().unwrap().name, "Bar");
 
     let stream = &mut token_stream("enum Foo { Bar, Baz(u8), Blah { a: u32, b: u128 } }");
     let (data_type, ident) = super::DataType::take(stream).unwrap();
@@ -270,6 +469,8 @@ fn test_enum_body_take() {
     assert_eq!(ident, "Foo");
     let body = EnumBody::take(stream).unwrap();
     assert_eq!(3, body.variants.len());
+    assert!(!body.is_uninhabited());
+    assert!(body.single_variant().is_none());
 
     assert_eq!(body.variants[0].name, "Bar");
     assert!(body.variants[0].fields.is_none());
@@ -351,10 +552,117 @@ fn test_enum_body_take() {
 
     assert_eq!(body.variants[2].name, "Without");
     assert!(body.variants[2].fields.is_none());
+
+    let shapes: Vec<VariantShape> = body.variants.iter().map(EnumVariant::shape).collect();
+    assert_eq!(
+        shapes,
+        [
+            VariantShape::Tuple(0),
+            VariantShape::Struct(0),
+            VariantShape::Unit
+        ]
+    );
+    assert!(body.variants.iter().all(|variant| !variant.has_fields()));
+}
+
+#[test]
+fn test_enum_discriminant_none_groups() {
+    use crate::prelude::{Group, TokenStream};
+    use crate::token_stream;
+
+    let none_group = |code: &str| {
+        TokenTree::Group(Group::new(
+            Delimiter::None,
+            token_stream(code).collect::<TokenStream>(),
+        ))
+    };
+    // enum Foo { A = $a, B = $b, C = $c, D = -$d, E = $e }
+    let mut variants = TokenStream::new();
+    for (name, value) in [("A", "1"), ("B", "-2"), ("C", "MY_CONST")] {
+        variants.extend(token_stream(&format!("{} =", name)));
+        variants.extend([none_group(value)]);
+        variants.extend(token_stream(","));
+    }
+    variants.extend(token_stream("D = -"));
+    variants.extend([none_group("4")]);
+    variants.extend(token_stream(", E ="));
+    variants.extend([none_group("1 << 2")]);
+    let mut stream = token_stream("enum Foo").collect::<TokenStream>();
+    stream.extend([TokenTree::Group(Group::new(Delimiter::Brace, variants))]);
+    let stream = &mut stream.into_iter().peekable();
+    super::DataType::take(stream).unwrap();
+    let body = EnumBody::take(stream).unwrap();
+
+    assert_eq!(body.variants[0].get_integer(), 1);
+    assert_eq!(body.variants[1].get_integer(), -2);
+    assert!(body.variants[2].value.is_none());
+    let expr = body.variants[2].value_expr.as_ref().unwrap();
+    assert_eq!(expr.len(), 1);
+    assert_eq!(expr[0].to_string(), "MY_CONST");
+    assert_eq!(body.variants[3].get_integer(), -4);
+    let expr = body.variants[4].value_expr.as_ref().unwrap();
+    assert_eq!(expr.len(), 4);
+
+    let stream = &mut token_stream("enum Foo { A = Self::B as isize, B = }");
+    super::DataType::take(stream).unwrap();
+    assert!(EnumBody::take(stream).is_err());
+    let stream = &mut token_stream("enum Foo { A = Self::B as isize, B = 2 }");
+    super::DataType::take(stream).unwrap();
+    let body = EnumBody::take(stream).unwrap();
+    assert_eq!(body.variants[0].value_expr.as_ref().unwrap().len(), 6);
+    assert_eq!(body.variants[1].get_integer(), 2);
+}
+
+#[test]
+fn test_resolved_discriminants() {
+    use crate::token_stream;
+
+    let resolve = |code: &str| {
+        let stream = &mut token_stream(code);
+        super::DataType::take(stream).unwrap();
+        EnumBody::take(stream)
+            .unwrap()
+            .resolved_discriminants()
+            .map(|discriminants| discriminants.iter().map(|d| d.value).collect::<Vec<_>>())
+    };
+    assert_eq!(
+        resolve("enum Foo { A, B(u8), C { c: u8 } }").unwrap(),
+        [0, 1, 2]
+    );
+    assert_eq!(
+        resolve("enum Foo { A = -3, B, C, D = 1_000u16, E, F = 0b101, G = -0x10, H }").unwrap(),
+        [-3, -2, -1, 1000, 1001, 5, -16, -15]
+    );
+    assert_eq!(
+        resolve("enum Foo { A = 170141183460469231731687303715884105727 }").unwrap(),
+        [i128::MAX]
+    );
+    assert_eq!(
+        resolve("enum Foo { A = -170141183460469231731687303715884105728 }").unwrap(),
+        [i128::MIN]
+    );
+    assert!(resolve("enum Foo { A = 170141183460469231731687303715884105727, B }").is_err());
+    assert!(resolve("enum Foo { A = 170141183460469231731687303715884105728 }").is_err());
+    assert!(resolve("enum Foo { A = 1 << 2 }").is_err());
+    assert!(resolve("enum Foo { A = MY_CONST }").is_err());
+    assert!(resolve("enum Foo { A = 1.5 }").is_err());
+    assert!(resolve("enum Foo { A = 'a' }").is_err());
+    assert!(resolve("enum Foo { }").unwrap().is_empty());
+
+    let stream = &mut token_stream("enum Foo { A = -2 }");
+    super::DataType::take(stream).unwrap();
+    let discriminants = EnumBody::take(stream)
+        .unwrap()
+        .resolved_discriminants()
+        .unwrap();
+    assert_eq!(discriminants[0].literal().to_string(), "-2");
 }
 
 /// A variant of an enum
+///
+/// This struct is `#[non_exhaustive]` so that new properties of a variant can be added without a breaking change.
 #[derive(Debug)]
+#[non_exhaustive]
 pub struct EnumVariant {
     /// The name of the variant
     pub name: Ident,
@@ -364,10 +672,107 @@ pub struct EnumVariant {
     /// - `Baz = 5`
     /// - `Baz(i32) = 5`
     /// - `Baz { a: i32} = 5`
-    /// In either case this value will be `Some(Literal::i32(5))`
+    ///
+    /// In either case this value will be `Some(Literal::i32(5))`. A negative value like `-5` is also a single literal.
+    ///
+    /// This is `None` if the value is not a literal, see [`value_expr`].
+    ///
+    /// [`value_expr`]: #structfield.value_expr
     pub value: Option<Literal>,
-    /// The attributes of this variant
-    pub attributes: Vec<Attribute>,
+    /// The value of this variant if it is an expression other than a literal, e.g. `Baz = MY_CONST` or `Baz = 1 << 2`.
+    ///
+    /// A value that is passed to a `macro_rules!` as `$value:literal` or `$value:expr` is unwrapped from its group without delimiters, so this is `None` for `Baz = $value` with a literal `$value`.
+    pub value_expr: Option<Vec<TokenTree>>,
+    /// The attributes of this variant. These are stored as a boxed slice, which does not allocate when there are no attributes.
+    pub attributes: Box<[Attribute]>,
+}
+
+impl EnumVariant {
+    /// Returns the name of this variant converted to the given case, e.g. for implementing a `rename_all` attribute.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use virtue::utils::case::Case;
+    /// # use std::str::FromStr;
+    /// let parse = Parse::new(TokenStream::from_str("enum Foo { HTTPRequest }").unwrap()).unwrap();
+    /// let (_, _, body) = parse.into_generator();
+    /// # let body = match body { Body::Enum(body) => body, _ => unreachable!() };
+    /// assert_eq!(body.variants[0].name_with_case(Case::Snake), "http_request");
+    /// ```
+    pub fn name_with_case(&self, case: Case) -> String {
+        case.convert(&self.name.to_string())
+    }
+
+    /// Returns the shape of this variant, and the amount of fields it has.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use virtue::parse::VariantShape;
+    /// # use std::str::FromStr;
+    /// let input = TokenStream::from_str("enum Foo { A, B(), C(u8, u16), D { a: u8 } }").unwrap();
+    /// let (_, _, body) = Parse::new(input)?.into_generator();
+    /// # let body = match body { Body::Enum(body) => body, _ => unreachable!() };
+    /// let shapes: Vec<VariantShape> = body.variants.iter().map(|v| v.shape()).collect();
+    /// assert_eq!(
+    ///     shapes,
+    ///     [
+    ///         VariantShape::Unit,
+    ///         VariantShape::Tuple(0),
+    ///         VariantShape::Tuple(2),
+    ///         VariantShape::Struct(1),
+    ///     ]
+    /// );
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn shape(&self) -> VariantShape {
+        match &self.fields {
+            None => VariantShape::Unit,
+            Some(Fields::Tuple(fields)) => VariantShape::Tuple(fields.len()),
+            Some(Fields::Struct(fields)) => VariantShape::Struct(fields.len()),
+        }
+    }
+
+    /// Returns `true` if this variant has at least one field. `A`, `A()` and `A {}` have no fields.
+    pub fn has_fields(&self) -> bool {
+        matches!(&self.fields, Some(fields) if !fields.is_empty())
+    }
+
+    /// Returns the first `#[cfg(...)]` attribute of this variant, or a `#[cfg_attr(..., cfg(...))]` that contains one. The predicate is not evaluated, see [`Attribute::as_cfg`].
+    ///
+    /// Code that names the variant, e.g. a match arm, has to have the same `#[cfg(...)]`. Use [`Attribute::cfg_of`] to get the combined predicate if a variant can have more than one.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = TokenStream::from_str("enum Foo { A, #[cfg(feature = \"b\")] B }").unwrap();
+    /// let (_, _, body) = Parse::new(input)?.into_generator();
+    /// # let body = match body { Body::Enum(body) => body, _ => unreachable!() };
+    /// assert!(body.variants[0].cfg().is_none());
+    /// let cfg = body.variants[1].cfg().unwrap();
+    /// assert_eq!(cfg.as_cfg().unwrap().to_string(), "feature = \"b\"");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`Attribute::as_cfg`]: struct.Attribute.html#method.as_cfg
+    /// [`Attribute::cfg_of`]: struct.Attribute.html#method.cfg_of
+    pub fn cfg(&self) -> Option<&Attribute> {
+        self.attributes
+            .iter()
+            .find(|attribute| !attribute.is_inner && attribute.as_cfg().is_some())
+    }
+}
+
+/// The shape of an [`EnumVariant`], see [`EnumVariant::shape`].
+///
+/// [`EnumVariant::shape`]: struct.EnumVariant.html#method.shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VariantShape {
+    /// A variant without fields or brackets, e.g. `A`
+    Unit,
+    /// A tuple variant with the given amount of fields, e.g. `A(u8, u16)`. This can be `0` for `A()`.
+    Tuple(usize),
+    /// A struct variant with the given amount of fields, e.g. `A { a: u8 }`. This can be `0` for `A {}`.
+    Struct(usize),
 }
 
 #[cfg(test)]
@@ -415,7 +820,7 @@ impl Fields {
     ///     C(u32, u32), // will return `vec[Index { index: 0 }, Index { index: 1 }]`
     ///     D { a: u32, b: u32 }, // will return `vec[Ident { ident: "a" }, Ident { ident: "b" }]`
     /// }
-    pub fn names(&self) -> Vec<IdentOrIndex> {
+    pub fn names(&self) -> Vec<IdentOrIndex<'_>> {
         let result: Vec<IdentOrIndex> = match self {
             Self::Tuple(fields) => fields
                 .iter()
@@ -423,20 +828,55 @@ impl Fields {
                 .map(|(index, field)| IdentOrIndex::Index {
                     index,
                     span: field.span(),
-                    attributes: field.attributes.clone(),
+                    attributes: &field.attributes,
                 })
                 .collect(),
             Self::Struct(fields) => fields
                 .iter()
                 .map(|(ident, field)| IdentOrIndex::Ident {
-                    ident: ident.clone(),
-                    attributes: field.attributes.clone(),
+                    ident,
+                    attributes: &field.attributes,
                 })
                 .collect(),
         };
         result
     }
 
+    /// Returns the names of the local variables that the fields are bound to when they are destructured. This is the field name for named fields, and `prefix` followed by the index for tuple fields. Tuple bindings get the span of the type of their field.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = TokenStream::from_str("struct Foo(u8, u16);").unwrap();
+    /// let (_, _, body) = Parse::new(input)?.into_generator();
+    /// # let fields = match body { Body::Struct(body) => body.fields.unwrap(), _ => unreachable!() };
+    /// let bindings: Vec<String> = fields.bindings("field_")?.iter().map(ToString::to_string).collect();
+    /// assert_eq!(bindings, ["field_0", "field_1"]);
+    /// assert!(fields.bindings("").is_err());
+    /// assert!(fields.bindings("0_").is_err());
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// Returns an error if `prefix` is not the start of an identifier, e.g. if it is empty or starts with a digit.
+    pub fn bindings(&self, prefix: &str) -> Result<Vec<Ident>> {
+        let is_valid_prefix = matches!(prefix.chars().next(), Some(c) if c == '_' || c.is_alphabetic())
+            && prefix.chars().all(|c| c == '_' || c.is_alphanumeric());
+        if !is_valid_prefix {
+            return Err(Error::custom(format!(
+                "Invalid binding prefix `{}`, expected e.g. `field_`",
+                prefix
+            )));
+        }
+        Ok(match self {
+            Self::Tuple(fields) => fields
+                .iter()
+                .enumerate()
+                .map(|(index, field)| Ident::new(&format!("{}{}", prefix, index), field.span()))
+                .collect(),
+            Self::Struct(fields) => fields.iter().map(|(ident, _)| ident.clone()).collect(),
+        })
+    }
+
     /// Return the delimiter of the group for this variant
     ///
     /// ```
@@ -451,54 +891,184 @@ impl Fields {
             Self::Struct(_) => Delimiter::Brace,
         }
     }
-}
 
-#[cfg(test)]
-impl Fields {
-    fn len(&self) -> usize {
+    /// Returns the amount of fields
+    pub fn len(&self) -> usize {
         match self {
             Self::Tuple(fields) => fields.len(),
             Self::Struct(fields) => fields.len(),
         }
     }
 
-    fn get(&self, index: usize) -> Option<(Option<&Ident>, &UnnamedField)> {
+    /// Returns `true` if there are no fields, e.g. `struct Foo {}` or `Foo()`
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the field at the given index, and its name if the fields are named
+    pub fn get(&self, index: usize) -> Option<(Option<&Ident>, &UnnamedField)> {
         match self {
             Self::Tuple(fields) => fields.get(index).map(|f| (None, f)),
             Self::Struct(fields) => fields.get(index).map(|(ident, field)| (Some(ident), field)),
         }
     }
+
+    /// Returns an iterator over the fields, and their names if the fields are named
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = TokenStream::from_str("struct Foo { a: u8, b: u16 }").unwrap();
+    /// let (_, _, body) = Parse::new(input)?.into_generator();
+    /// # let fields = match body { Body::Struct(body) => body.fields.unwrap(), _ => unreachable!() };
+    /// let names: Vec<String> = fields
+    ///     .iter()
+    ///     .map(|(ident, _field)| ident.unwrap().to_string())
+    ///     .collect();
+    /// assert_eq!(names, ["a", "b"]);
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn iter(&self) -> FieldsIter<'_> {
+        FieldsIter(match self {
+            Self::Tuple(fields) => FieldsIterInner::Tuple(fields.iter()),
+            Self::Struct(fields) => FieldsIterInner::Struct(fields.iter()),
+        })
+    }
+}
+
+impl<'a> IntoIterator for &'a Fields {
+    type Item = (Option<&'a Ident>, &'a UnnamedField);
+    type IntoIter = FieldsIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for Fields {
+    type Item = (Option<Ident>, UnnamedField);
+    type IntoIter = FieldsIntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        FieldsIntoIter(match self {
+            Self::Tuple(fields) => FieldsIntoIterInner::Tuple(fields.into_iter()),
+            Self::Struct(fields) => FieldsIntoIterInner::Struct(fields.into_iter()),
+        })
+    }
+}
+
+/// An iterator over the fields of [`Fields`], see [`Fields::iter`]
+#[derive(Debug, Clone)]
+pub struct FieldsIter<'a>(FieldsIterInner<'a>);
+
+#[derive(Debug, Clone)]
+enum FieldsIterInner<'a> {
+    Tuple(std::slice::Iter<'a, UnnamedField>),
+    Struct(std::slice::Iter<'a, (Ident, UnnamedField)>),
+}
+
+impl<'a> Iterator for FieldsIter<'a> {
+    type Item = (Option<&'a Ident>, &'a UnnamedField);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            FieldsIterInner::Tuple(iter) => iter.next().map(|field| (None, field)),
+            FieldsIterInner::Struct(iter) => iter.next().map(|(ident, field)| (Some(ident), field)),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            FieldsIterInner::Tuple(iter) => iter.size_hint(),
+            FieldsIterInner::Struct(iter) => iter.size_hint(),
+        }
+    }
 }
 
+impl<'a> DoubleEndedIterator for FieldsIter<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            FieldsIterInner::Tuple(iter) => iter.next_back().map(|field| (None, field)),
+            FieldsIterInner::Struct(iter) => {
+                iter.next_back().map(|(ident, field)| (Some(ident), field))
+            }
+        }
+    }
+}
+
+impl<'a> ExactSizeIterator for FieldsIter<'a> {}
+
+/// An owning iterator over the fields of [`Fields`]
+#[derive(Debug)]
+pub struct FieldsIntoIter(FieldsIntoIterInner);
+
+#[derive(Debug)]
+enum FieldsIntoIterInner {
+    Tuple(std::vec::IntoIter<UnnamedField>),
+    Struct(std::vec::IntoIter<(Ident, UnnamedField)>),
+}
+
+impl Iterator for FieldsIntoIter {
+    type Item = (Option<Ident>, UnnamedField);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            FieldsIntoIterInner::Tuple(iter) => iter.next().map(|field| (None, field)),
+            FieldsIntoIterInner::Struct(iter) => {
+                iter.next().map(|(ident, field)| (Some(ident), field))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            FieldsIntoIterInner::Tuple(iter) => iter.size_hint(),
+            FieldsIntoIterInner::Struct(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl DoubleEndedIterator for FieldsIntoIter {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            FieldsIntoIterInner::Tuple(iter) => iter.next_back().map(|field| (None, field)),
+            FieldsIntoIterInner::Struct(iter) => {
+                iter.next_back().map(|(ident, field)| (Some(ident), field))
+            }
+        }
+    }
+}
+
+impl ExactSizeIterator for FieldsIntoIter {}
+
 /// An unnamed field
+///
+/// This struct is `#[non_exhaustive]` so that new properties of a field can be added without a breaking change.
 #[derive(Debug)]
+#[non_exhaustive]
 pub struct UnnamedField {
     /// The visibility of the field
     pub vis: Visibility,
     /// The type of the field
     pub r#type: Vec<TokenTree>,
-    /// The attributes of the field
-    pub attributes: Vec<Attribute>,
+    /// The attributes of the field. These are stored as a boxed slice, which does not allocate when there are no attributes.
+    pub attributes: Box<[Attribute]>,
+    /// The default value of the field, e.g. `5` for `a: u8 = 5`.
+    ///
+    /// This is the unstable `default_field_values` syntax, which is only valid for named fields.
+    pub default: Option<Vec<TokenTree>>,
 }
 
 impl UnnamedField {
-    pub(crate) fn parse_with_name(
-        input: &mut Peekable<impl Iterator<Item = TokenTree>>,
-    ) -> Result<Vec<(Ident, Self)>> {
+    pub(crate) fn parse_with_name(tokens: Vec<TokenTree>) -> Result<Vec<(Ident, Self)>> {
         let mut result = Vec::new();
-        loop {
+        for_each_split_on_punct(tokens, ',', |input| {
             let attributes = Attribute::try_take(AttributeLocation::Field, input)?;
             let vis = Visibility::try_take(input)?;
 
             let ident = match input.peek() {
-                Some(TokenTree::Ident(_)) => assume_ident(input.next()),
-                Some(x) => {
-                    return Err(Error::InvalidRustSyntax {
-                        span: x.span(),
-                        expected: format!("ident or end of group, got {:?}", x),
-                    })
-                }
-                None => break,
+                Some(TokenTree::Ident(_)) => assume_ident(input.next())?,
+                token => return Error::wrong_token(token, "ident"),
             };
             match input.peek() {
                 Some(TokenTree::Punct(p)) if p.as_char() == ':' => {
@@ -506,36 +1076,38 @@ impl UnnamedField {
                 }
                 token => return Error::wrong_token(token, ":"),
             }
-            let r#type = read_tokens_until_punct(input, &[','])?;
-            consume_punct_if(input, ',');
+            let r#type = read_tokens_until_punct(input, &['='])?;
+            // `= default`
+            let default = input.next().map(|_| input.collect());
             result.push((
                 ident,
                 Self {
                     vis,
                     r#type,
-                    attributes,
+                    attributes: attributes.into_boxed_slice(),
+                    default,
                 },
             ));
-        }
+            Ok(())
+        })?;
         Ok(result)
     }
 
-    pub(crate) fn parse(
-        input: &mut Peekable<impl Iterator<Item = TokenTree>>,
-    ) -> Result<Vec<Self>> {
+    pub(crate) fn parse(tokens: Vec<TokenTree>) -> Result<Vec<Self>> {
         let mut result = Vec::new();
-        while input.peek().is_some() {
+        for_each_split_on_punct(tokens, ',', |input| {
             let attributes = Attribute::try_take(AttributeLocation::Field, input)?;
             let vis = Visibility::try_take(input)?;
 
-            let r#type = read_tokens_until_punct(input, &[','])?;
-            consume_punct_if(input, ',');
+            let r#type = input.collect();
             result.push(Self {
                 vis,
                 r#type,
-                attributes,
+                attributes: attributes.into_boxed_slice(),
+                default: None,
             });
-        }
+            Ok(())
+        })?;
         Ok(result)
     }
 
@@ -546,6 +1118,20 @@ impl UnnamedField {
         self.r#type.iter().map(|t| t.to_string()).collect()
     }
 
+    /// Returns `true` if the type of this field is `PhantomData<...>`, optionally prefixed with a path like `std::marker::`.
+    pub fn is_phantom_data(&self) -> bool {
+        let mut last_ident = None;
+        for token in &self.r#type {
+            match token {
+                TokenTree::Ident(ident) => last_ident = Some(ident),
+                TokenTree::Punct(p) if p.as_char() == ':' => {}
+                TokenTree::Punct(p) if p.as_char() == '<' => break,
+                _ => return false,
+            }
+        }
+        matches!(last_ident, Some(ident) if ident_eq(ident, "PhantomData"))
+    }
+
     /// Return the span of [`type`].
     ///
     /// **note**: Until <https://github.com/rust-lang/rust/issues/54725> is stable, this will return the first span of the type instead
@@ -580,14 +1166,17 @@ impl UnnamedField {
 ///         a: u32, // will be IdentOrIndex::Ident { ident: "a", .. }
 ///     },
 /// }
-#[derive(Debug, Clone)]
-pub enum IdentOrIndex {
+/// ```
+///
+/// This borrows the name and attributes of the field from the [`Fields`] it was created from.
+#[derive(Debug, Clone, Copy)]
+pub enum IdentOrIndex<'a> {
     /// The variant is a named field
     Ident {
         /// The name of the field
-        ident: Ident,
+        ident: &'a Ident,
         /// The attributes of the field
-        attributes: Vec<Attribute>,
+        attributes: &'a [Attribute],
     },
     /// The variant is an unnamed field
     Index {
@@ -596,28 +1185,58 @@ pub enum IdentOrIndex {
         /// The span of the field type
         span: Span,
         /// The attributes of this field
-        attributes: Vec<Attribute>,
+        attributes: &'a [Attribute],
     },
 }
 
-impl IdentOrIndex {
+impl<'a> IdentOrIndex<'a> {
     /// Get the ident. Will panic if this is an `IdentOrIndex::Index`
     pub fn unwrap_ident(&self) -> Ident {
         match self {
-            Self::Ident { ident, .. } => ident.clone(),
+            Self::Ident { ident, .. } => (*ident).clone(),
             x => panic!("Expected ident, found {:?}", x),
         }
     }
 
     /// Convert this ident into a TokenTree. If this is an `Index`, will return `prefix + index` instead.
+    ///
+    /// With an empty prefix an `Index` is returned as the literal `index`, e.g. for a field access like `self.0`. Use a prefix that starts with `_`, e.g. `_field_`, for bindings that may be unused, or [`to_unused_binding`] to also prefix named fields.
+    ///
+    /// [`to_unused_binding`]: #method.to_unused_binding
     pub fn to_token_tree_with_prefix(&self, prefix: &str) -> TokenTree {
-        TokenTree::Ident(match self {
-            IdentOrIndex::Ident { ident, .. } => (*ident).clone(),
+        match self {
+            IdentOrIndex::Ident { ident, .. } => TokenTree::Ident((*ident).clone()),
+            IdentOrIndex::Index { index, span, .. } if prefix.is_empty() => {
+                let mut literal = Literal::usize_unsuffixed(*index);
+                literal.set_span(*span);
+                TokenTree::Literal(literal)
+            }
             IdentOrIndex::Index { index, span, .. } => {
                 let name = format!("{}{}", prefix, index);
-                Ident::new(&name, *span)
+                TokenTree::Ident(Ident::new(&name, *span))
             }
-        })
+        }
+    }
+
+    /// A binding for this field that does not cause an `unused_variables` warning when it is not used: `__binding_` followed by the name of the field, e.g. `__binding_a` for `a`, `__binding_type` for `r#type` and `__binding_0` for the first unnamed field.
+    ///
+    /// The prefix makes sure that the bindings of different fields do not collide, e.g. for the fields `a` and `_a`.
+    ///
+    /// ```ignore
+    /// // match self { Self { a: __binding_a, _a: __binding__a } => { ... } }
+    /// // match self { Self(__binding_0, __binding_1) => { ... } }
+    /// ```
+    pub fn to_unused_binding(&self) -> Ident {
+        match self {
+            IdentOrIndex::Ident { ident, .. } => {
+                let name = ident.to_string();
+                let name = name.strip_prefix("r#").unwrap_or(&name);
+                Ident::new(&format!("__binding_{}", name), ident.span())
+            }
+            IdentOrIndex::Index { index, span, .. } => {
+                Ident::new(&format!("__binding_{}", index), *span)
+            }
+        }
     }
 
     /// Return either the index or the ident of this field with a fixed prefix. The prefix will always be added.
@@ -631,15 +1250,23 @@ impl IdentOrIndex {
     }
 
     /// Returns the attributes of this field.
-    pub fn attributes(&self) -> &Vec<Attribute> {
-        match self {
+    pub fn attributes(&self) -> &'a [Attribute] {
+        match *self {
             Self::Ident { attributes, .. } => attributes,
             Self::Index { attributes, .. } => attributes,
         }
     }
+
+    /// Returns the name of this field converted to the given case. An `Index` is returned as-is, e.g. `"0"`.
+    pub fn name_with_case(&self, case: Case) -> String {
+        match self {
+            IdentOrIndex::Ident { ident, .. } => case.convert(&ident.to_string()),
+            IdentOrIndex::Index { index, .. } => index.to_string(),
+        }
+    }
 }
 
-impl std::fmt::Display for IdentOrIndex {
+impl std::fmt::Display for IdentOrIndex<'_> {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             IdentOrIndex::Ident { ident, .. } => write!(fmt, "{}", ident),
@@ -648,6 +1275,22 @@ impl std::fmt::Display for IdentOrIndex {
     }
 }
 
+#[test]
+fn test_is_phantom_data() {
+    use crate::token_stream;
+
+    let stream = &mut token_stream(
+        "{ a: PhantomData<T>, b: std::marker::PhantomData<T>, c: ::core::marker::PhantomData<(T, U)>, d: Vec<PhantomData<T>>, e: PhantomDataLike<T>, f: u8 }",
+    );
+    let body = StructBody::take(stream).unwrap();
+    let fields = match body.fields {
+        Some(Fields::Struct(fields)) => fields,
+        fields => panic!("Expected struct fields, got {:?}", fields),
+    };
+    let result: Vec<bool> = fields.iter().map(|(_, f)| f.is_phantom_data()).collect();
+    assert_eq!(result, [true, true, true, false, false, false]);
+}
+
 #[test]
 fn enum_explicit_variants() {
     use crate::token_stream;
@@ -655,3 +1298,175 @@ fn enum_explicit_variants() {
     let body = EnumBody::take(stream).unwrap();
     assert_eq!(body.variants.len(), 2);
 }
+
+#[test]
+fn test_fields_iter() {
+    use crate::token_stream;
+
+    let body = StructBody::take(&mut token_stream("{ a: u8, b: u16 }")).unwrap();
+    let fields = body.fields.unwrap();
+    assert!(!fields.is_empty());
+    let names: Vec<String> = fields
+        .iter()
+        .rev()
+        .map(|(ident, _)| ident.unwrap().to_string())
+        .collect();
+    assert_eq!(names, ["b", "a"]);
+    assert_eq!(fields.iter().len(), 2);
+
+    let body = StructBody::take(&mut token_stream("(u8, u16)")).unwrap();
+    let fields = body.fields.unwrap();
+    let types: Vec<String> = fields
+        .into_iter()
+        .map(|(ident, field)| {
+            assert!(ident.is_none());
+            field.type_string()
+        })
+        .collect();
+    assert_eq!(types, ["u8", "u16"]);
+
+    let body = StructBody::take(&mut token_stream("{}")).unwrap();
+    assert!(body.fields.unwrap().is_empty());
+}
+
+#[test]
+fn test_field_default_values() {
+    use crate::token_stream;
+
+    let stream = &mut token_stream(
+        "{ a: u8 = 5, b: Box<dyn Fn(u8) -> u8> = Box::new(|x| x), c: Vec<T>, d: Foo<Item = u8> = Foo { a: 1, b: 2 } }",
+    );
+    let body = StructBody::take(stream).unwrap();
+    let fields = body.fields.unwrap();
+    let fields: Vec<(String, Option<String>)> = fields
+        .iter()
+        .map(|(_, field)| {
+            let default = field.default.as_ref().map(|default| {
+                default
+                    .iter()
+                    .cloned()
+                    .collect::<proc_macro2::TokenStream>()
+                    .to_string()
+            });
+            (field.type_string(), default)
+        })
+        .collect();
+    assert_eq!(
+        fields,
+        [
+            ("u8".to_string(), Some("5".to_string())),
+            (
+                "Box<dynFn(u8)->u8>".to_string(),
+                Some("Box :: new (| x | x)".to_string())
+            ),
+            ("Vec<T>".to_string(), None),
+            (
+                "Foo<Item=u8>".to_string(),
+                Some("Foo { a : 1 , b : 2 }".to_string())
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_unicode_and_raw_idents() {
+    use crate::token_stream;
+
+    let body = StructBody::take(&mut token_stream(
+        "{ r#type: u8, ñandú: u8, _private: u8, Ünï_cödé: u8 }",
+    ))
+    .unwrap();
+    let names = body.fields.as_ref().unwrap().names();
+    let idents: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+    assert_eq!(idents, ["r#type", "ñandú", "_private", "Ünï_cödé"]);
+    let bindings: Vec<String> = names
+        .iter()
+        .map(|name| name.to_unused_binding().to_string())
+        .collect();
+    assert_eq!(
+        bindings,
+        [
+            "__binding_type",
+            "__binding_ñandú",
+            "__binding__private",
+            "__binding_Ünï_cödé"
+        ]
+    );
+
+    let mut generator = crate::generate::Generator::with_name("Ñandú");
+    generator
+        .r#impl()
+        .generate_fn("ignore")
+        .with_arg("value", "Self")
+        .body(|b| {
+            b.push_parsed("let Self")?;
+            b.group(Delimiter::Brace, |b| {
+                for name in &names {
+                    b.push(name.to_token_tree_with_prefix(""))
+                        .punct(':')
+                        .ident(name.to_unused_binding())
+                        .punct(',');
+                }
+                Ok(())
+            })?;
+            b.push_parsed("= value;")?;
+            Ok(())
+        })
+        .unwrap();
+    generator.assert_eq(
+        "impl Ñandú { fn ignore (value : Self) { let Self { \
+         r#type : __binding_type , ñandú : __binding_ñandú , _private : __binding__private , \
+         Ünï_cödé : __binding_Ünï_cödé , \
+         } = value ; } }",
+    );
+    let snake: Vec<String> = names
+        .iter()
+        .map(|name| name.name_with_case(Case::Snake))
+        .collect();
+    assert_eq!(snake, ["type", "ñandú", "private", "ünï_cödé"]);
+
+    let body = StructBody::take(&mut token_stream("(u8, u16)")).unwrap();
+    let names = body.fields.as_ref().unwrap().names();
+    assert_eq!(names[1].to_token_tree_with_prefix("").to_string(), "1");
+    assert_eq!(
+        names[1].to_token_tree_with_prefix("_field_").to_string(),
+        "_field_1"
+    );
+    assert_eq!(names[1].to_unused_binding().to_string(), "__binding_1");
+
+    // fields that only differ by a leading `_` get different bindings
+    let body = StructBody::take(&mut token_stream(
+        "{ a: u8, _a: u8, r#type: u8, _type: u8 }",
+    ))
+    .unwrap();
+    let bindings: Vec<String> = body
+        .fields
+        .as_ref()
+        .unwrap()
+        .names()
+        .iter()
+        .map(|name| name.to_unused_binding().to_string())
+        .collect();
+    assert_eq!(
+        bindings,
+        [
+            "__binding_a",
+            "__binding__a",
+            "__binding_type",
+            "__binding__type"
+        ]
+    );
+
+    let body = EnumBody::take(&mut token_stream(
+        "{ r#match { r#in: u8 }, Ñandú(u8), _Hidden }",
+    ))
+    .unwrap();
+    let variants: Vec<String> = body.variants.iter().map(|v| v.name.to_string()).collect();
+    assert_eq!(variants, ["r#match", "Ñandú", "_Hidden"]);
+    assert_eq!(
+        body.variants[0].fields.as_ref().unwrap().names()[0]
+            .to_unused_binding()
+            .to_string(),
+        "__binding_in"
+    );
+}
@@ -0,0 +1,155 @@
+use super::utils::{consume_ident, consume_punct_if, read_tokens_until_punct};
+use crate::prelude::{token_stream, Delimiter, Group, Ident, Punct, TokenStream, TokenTree};
+use crate::{Error, Result};
+use std::iter::Peekable;
+
+/// A cursor over a [`TokenStream`], for parsing custom syntax that virtue does not parse itself, e.g. the contents of your own attributes.
+///
+/// The methods of this cursor use the same helpers as the parser of virtue, so e.g. [`read_until_punct`] understands nested `<..>` brackets and `->` arrows.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// # use std::str::FromStr;
+/// use virtue::parse::Cursor;
+///
+/// // The contents of `#[my_derive(with = Vec<(u8, u16)>, skip)]`
+/// let mut cursor = Cursor::new(TokenStream::from_str("with = Vec<(u8, u16)>, skip").unwrap());
+///
+/// let key = cursor.expect_ident()?;
+/// assert_eq!(key.to_string(), "with");
+/// cursor.expect_punct('=')?;
+/// let ty = cursor.read_until_punct(&[','])?;
+/// assert_eq!(ty.len(), 4);
+/// cursor.expect_punct(',')?;
+///
+/// assert_eq!(cursor.consume_ident().unwrap().to_string(), "skip");
+/// assert!(cursor.consume_punct_if(',').is_none());
+/// assert!(cursor.is_empty());
+/// # Ok::<_, virtue::Error>(())
+/// ```
+///
+/// The cursor is an [`Iterator`] over the remaining tokens, and the underlying [`Peekable`] can be used with the functions in [`parse::utils`] through [`as_peekable`].
+///
+/// [`read_until_punct`]: #method.read_until_punct
+/// [`parse::utils`]: utils/index.html
+/// [`as_peekable`]: #method.as_peekable
+pub struct Cursor {
+    input: Peekable<token_stream::IntoIter>,
+}
+
+impl Cursor {
+    /// Create a new cursor at the start of `stream`
+    pub fn new(stream: TokenStream) -> Self {
+        Self {
+            input: stream.into_iter().peekable(),
+        }
+    }
+
+    /// Create a new cursor at the start of the contents of `group`, e.g. the `(...)` of an attribute
+    pub fn from_group(group: &Group) -> Self {
+        Self::new(group.stream())
+    }
+
+    /// Returns the next token without advancing the cursor
+    pub fn peek(&mut self) -> Option<&TokenTree> {
+        self.input.peek()
+    }
+
+    /// Returns `true` if there are no tokens left
+    pub fn is_empty(&mut self) -> bool {
+        self.input.peek().is_none()
+    }
+
+    /// Take the next token if it is an ident. A group without delimiters that only contains an ident, e.g. an ident that is passed on by a `macro_rules!`, is unwrapped. Groups with delimiters like `[c]` are left alone.
+    pub fn consume_ident(&mut self) -> Option<Ident> {
+        consume_ident(&mut self.input)
+    }
+
+    /// Take the next token if it is the punct `punct`
+    pub fn consume_punct_if(&mut self, punct: char) -> Option<Punct> {
+        consume_punct_if(&mut self.input, punct)
+    }
+
+    /// Take the next token, which must be an ident. See [`consume_ident`].
+    ///
+    /// [`consume_ident`]: #method.consume_ident
+    pub fn expect_ident(&mut self) -> Result<Ident> {
+        match self.consume_ident() {
+            Some(ident) => Ok(ident),
+            None => Error::wrong_token(self.input.peek(), "ident"),
+        }
+    }
+
+    /// Take the next token, which must be the punct `punct`
+    pub fn expect_punct(&mut self, punct: char) -> Result<Punct> {
+        match self.consume_punct_if(punct) {
+            Some(punct) => Ok(punct),
+            None => Error::wrong_token(self.input.peek(), &format!("'{}'", punct)),
+        }
+    }
+
+    /// Take the next token, which must be a group with the given `delimiter`
+    pub fn expect_group(&mut self, delimiter: Delimiter) -> Result<Group> {
+        match self.input.peek() {
+            Some(TokenTree::Group(group)) if group.delimiter() == delimiter => {
+                super::utils::assume_group(self.input.next())
+            }
+            token => Error::wrong_token(token, &format!("{:?} group", delimiter)),
+        }
+    }
+
+    /// Take all tokens up to, but not including, the first of `puncts` that is not nested in brackets. Takes all remaining tokens if none of `puncts` is found.
+    ///
+    /// `<`, `(`, `[` and `{` count as brackets, so reading until `,` in `Vec<(u8, u16)>, u32` returns `Vec<(u8, u16)>`. The `>` of an arrow like `->` is not a bracket.
+    pub fn read_until_punct(&mut self, puncts: &[char]) -> Result<Vec<TokenTree>> {
+        read_tokens_until_punct(&mut self.input, puncts)
+    }
+
+    /// The underlying iterator, to use the functions in [`parse::utils`] directly
+    ///
+    /// [`parse::utils`]: utils/index.html
+    pub fn as_peekable(&mut self) -> &mut Peekable<token_stream::IntoIter> {
+        &mut self.input
+    }
+}
+
+impl Iterator for Cursor {
+    type Item = TokenTree;
+
+    fn next(&mut self) -> Option<TokenTree> {
+        self.input.next()
+    }
+}
+
+#[test]
+fn test_cursor() {
+    use std::str::FromStr;
+
+    let stream = TokenStream::from_str("(a: fn(u8) -> u8, b) [c]").unwrap();
+    let mut cursor = Cursor::new(stream);
+    let mut inner = Cursor::from_group(&cursor.expect_group(Delimiter::Parenthesis).unwrap());
+    assert_eq!(inner.expect_ident().unwrap().to_string(), "a");
+    assert!(inner.expect_punct(',').is_err());
+    inner.expect_punct(':').unwrap();
+    let ty: TokenStream = inner
+        .read_until_punct(&[','])
+        .unwrap()
+        .into_iter()
+        .collect();
+    assert_eq!(ty.to_string(), "fn (u8) -> u8");
+    inner.expect_punct(',').unwrap();
+    assert_eq!(inner.count(), 1);
+
+    assert!(cursor.expect_group(Delimiter::Brace).is_err());
+    // a group with delimiters is not an ident, even if it only contains one
+    assert!(cursor.expect_ident().is_err());
+    cursor.expect_group(Delimiter::Bracket).unwrap();
+    assert!(cursor.is_empty());
+    assert!(cursor.expect_ident().is_err());
+
+    // an ident that is passed on by a `macro_rules!` is unwrapped
+    let group = Group::new(Delimiter::None, TokenStream::from_str("c").unwrap());
+    let mut cursor = Cursor::new(TokenTree::Group(group).into());
+    assert_eq!(cursor.expect_ident().unwrap().to_string(), "c");
+    assert!(cursor.is_empty());
+}
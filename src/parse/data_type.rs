@@ -1,5 +1,5 @@
 use crate::prelude::{Ident, TokenTree};
-use crate::{Error, Result};
+use crate::{Error, ItemKind, Result};
 use std::iter::Peekable;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -9,6 +9,10 @@ pub(crate) enum DataType {
 }
 
 impl DataType {
+    pub(crate) fn is_keyword(ident: &Ident) -> bool {
+        matches!(ident.to_string().as_str(), "struct" | "enum")
+    }
+
     pub(crate) fn take(
         input: &mut Peekable<impl Iterator<Item = TokenTree>>,
     ) -> Result<(Self, Ident)> {
@@ -16,7 +20,7 @@ impl DataType {
             let result = match ident.to_string().as_str() {
                 "struct" => DataType::Struct,
                 "enum" => DataType::Enum,
-                _ => return Err(Error::UnknownDataType(ident.span())),
+                _ => return Err(Self::unsupported(ident, input)),
             };
 
             if let Some(ident) = super::utils::consume_ident(input) {
@@ -25,6 +29,36 @@ impl DataType {
         }
         Error::wrong_token(input.peek(), "ident")
     }
+
+    /// Classify an item that is not a struct or enum, skipping modifiers like the `unsafe` in `unsafe trait Foo {}`.
+    pub(crate) fn unsupported(
+        ident: Ident,
+        input: &mut Peekable<impl Iterator<Item = TokenTree>>,
+    ) -> Error {
+        let span = ident.span();
+        let mut keyword = ident.to_string();
+        loop {
+            if let Some(kind) = ItemKind::from_keyword(&keyword) {
+                return Error::UnsupportedItem { kind, span };
+            }
+            if !matches!(
+                keyword.as_str(),
+                "unsafe" | "async" | "const" | "extern" | "auto"
+            ) {
+                return Error::UnknownDataType(span);
+            }
+            if keyword == "extern" {
+                // skip the ABI in `extern "C" fn`
+                if let Some(TokenTree::Literal(_)) = input.peek() {
+                    input.next();
+                }
+            }
+            match super::utils::consume_ident(input) {
+                Some(ident) => keyword = ident.to_string(),
+                None => return Error::UnknownDataType(span),
+            }
+        }
+    }
 }
 
 #[test]
@@ -59,9 +93,31 @@ fn test_datatype_take() {
     validate_output_eq("struct Foo { bar: u32, baz: u32 }", DataType::Struct, "Foo");
     validate_output_eq("struct Foo<'a, T> { bar: &'a T }", DataType::Struct, "Foo");
 
-    assert!(DataType::take(&mut token_stream("fn foo() {}"))
+    assert!(DataType::take(&mut token_stream("mod foo {}"))
         .unwrap_err()
         .is_unknown_data_type());
+    assert!(DataType::take(&mut token_stream("const FOO: u8 = 5;"))
+        .unwrap_err()
+        .is_unknown_data_type());
+
+    for (input, kind) in [
+        ("union Foo { a: u8 }", ItemKind::Union),
+        ("trait Foo {}", ItemKind::Trait),
+        ("unsafe auto trait Foo {}", ItemKind::Trait),
+        ("fn foo() {}", ItemKind::Fn),
+        ("const unsafe extern \"C\" fn foo() {}", ItemKind::Fn),
+        ("type Foo = u8;", ItemKind::Type),
+        ("unsafe impl Send for Foo {}", ItemKind::Impl),
+    ] {
+        let error = DataType::take(&mut token_stream(input)).unwrap_err();
+        assert_eq!(error.unsupported_item_kind(), Some(kind), "{}", input);
+    }
+    assert_eq!(
+        DataType::take(&mut token_stream("union Foo { a: u8 }"))
+            .unwrap_err()
+            .to_string(),
+        "`union` is not supported, only enum and struct are supported (at 1:1)"
+    );
 
     assert!(DataType::take(&mut token_stream("() {}"))
         .unwrap_err()
@@ -0,0 +1,434 @@
+use super::attributes::AttributeLocation;
+use super::{utils, utils::*, Attribute, GenericConstraints, Generics, Visibility};
+use crate::prelude::{Delimiter, Group, Ident, Literal, TokenStream, TokenTree};
+use crate::{Error, Result};
+use std::iter::Peekable;
+
+/// A parsed function item, e.g. the input of an attribute macro on a function.
+///
+/// ```ignore
+/// use virtue::prelude::*;
+/// use virtue::parse::Function;
+///
+/// #[proc_macro_attribute]
+/// pub fn instrument(_attr: TokenStream, input: TokenStream) -> TokenStream {
+///     let function = Function::new(input).unwrap();
+///     // rest
+/// # unimplemented!()
+/// }
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Function {
+    /// The outer attributes of the function
+    pub attributes: Vec<Attribute>,
+    /// The visibility of the function
+    pub visibility: Visibility,
+    /// `true` if this is a `const fn`
+    pub is_const: bool,
+    /// `true` if this is an `async fn`
+    pub is_async: bool,
+    /// `true` if this is an `unsafe fn`
+    pub is_unsafe: bool,
+    /// `true` if this is an `extern fn` or `extern "<abi>" fn`
+    pub is_extern: bool,
+    /// The ABI of the function, e.g. `"C"` in `extern "C" fn`
+    pub abi: Option<Literal>,
+    /// The name of the function
+    pub name: Ident,
+    /// The generics of the function, e.g. `fn foo<T>()` will be `T`
+    pub generics: Option<Generics>,
+    /// The arguments of the function
+    pub args: Vec<FnArg>,
+    /// The return type of the function, e.g. `Result<(), Error>` in `fn foo() -> Result<(), Error>`. `None` if the function has no return type.
+    pub return_type: Option<Vec<TokenTree>>,
+    /// The where clause of the function
+    pub generic_constraints: Option<GenericConstraints>,
+    /// The body of the function, including the braces. `None` for functions without a body, e.g. `fn foo();` in a trait.
+    pub body: Option<Group>,
+}
+
+impl Function {
+    /// Parse the given [`TokenStream`] as a function.
+    pub fn new(input: TokenStream) -> Result<Self> {
+        let source = &mut input.into_iter().peekable();
+        let function = Self::take(source)?;
+        match source.peek() {
+            None => Ok(function),
+            token => Error::wrong_token(token, "end of function"),
+        }
+    }
+
+    pub(crate) fn take(input: &mut Peekable<impl Iterator<Item = TokenTree>>) -> Result<Self> {
+        let attributes = Attribute::try_take(AttributeLocation::Function, input)?;
+        let visibility = Visibility::try_take(input)?;
+
+        let mut is_const = false;
+        let mut is_async = false;
+        let mut is_unsafe = false;
+        let mut is_extern = false;
+        let mut abi = None;
+        loop {
+            match consume_ident(input) {
+                Some(ident) if ident_eq(&ident, "const") => is_const = true,
+                Some(ident) if ident_eq(&ident, "async") => is_async = true,
+                Some(ident) if ident_eq(&ident, "unsafe") => is_unsafe = true,
+                Some(ident) if ident_eq(&ident, "extern") => {
+                    is_extern = true;
+                    if let Some(TokenTree::Literal(_)) = input.peek() {
+                        if let Some(TokenTree::Literal(lit)) = input.next() {
+                            abi = Some(lit);
+                        }
+                    }
+                }
+                Some(ident) if ident_eq(&ident, "fn") => break,
+                Some(ident) => {
+                    return Error::wrong_token(Some(&TokenTree::Ident(ident)), "fn");
+                }
+                None => return Error::wrong_token(input.peek(), "fn"),
+            }
+        }
+
+        let name = match consume_ident(input) {
+            Some(name) => name,
+            None => return Error::wrong_token(input.peek(), "function name"),
+        };
+        let generics = Generics::try_take(input)?;
+
+        let args = match input.peek() {
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => {
+                let group = assume_group(input.next())?;
+                FnArg::parse_all(&mut group.stream().into_iter().peekable())?
+            }
+            token => return Error::wrong_token(token, "function arguments"),
+        };
+
+        let return_type = if is_arrow(input) {
+            input.next();
+            input.next();
+            Some(read_until_body(input, true))
+        } else {
+            None
+        };
+
+        let generic_constraints = match input.peek() {
+            Some(TokenTree::Ident(ident)) if ident_eq(ident, "where") => {
+                input.next();
+                Some(GenericConstraints::from_tokens(read_until_body(
+                    input, false,
+                )))
+            }
+            _ => None,
+        };
+
+        let body = match input.peek() {
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => {
+                Some(assume_group(input.next())?)
+            }
+            Some(TokenTree::Punct(p)) if p.as_char() == ';' => {
+                input.next();
+                None
+            }
+            token => return Error::wrong_token(token, "function body or ;"),
+        };
+
+        Ok(Self {
+            attributes,
+            visibility,
+            is_const,
+            is_async,
+            is_unsafe,
+            is_extern,
+            abi,
+            name,
+            generics,
+            args,
+            return_type,
+            generic_constraints,
+            body,
+        })
+    }
+
+    /// Returns the `self` argument of this function, if it has one.
+    pub fn receiver(&self) -> Option<&FnArg> {
+        self.args.iter().find(|arg| arg.is_receiver())
+    }
+}
+
+/// An argument of a [`Function`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FnArg {
+    /// The `self` argument, e.g. `self`, `mut self`, `&'a mut self` or `self: Box<Self>`
+    Receiver {
+        /// The attributes of this argument
+        attributes: Vec<Attribute>,
+        /// `true` if `self` is taken by reference
+        is_ref: bool,
+        /// The lifetime of the reference, e.g. `a` in `&'a self`
+        lifetime: Option<Ident>,
+        /// `true` if this is `mut self` or `&mut self`
+        is_mut: bool,
+        /// The explicit type of `self`, e.g. `Box<Self>` in `self: Box<Self>`
+        ty: Option<Vec<TokenTree>>,
+        /// The `self` keyword, with its span
+        self_ident: Ident,
+    },
+    /// Any other argument, e.g. `a: u32` or `(a, b): (u8, u8)`
+    Typed {
+        /// The attributes of this argument
+        attributes: Vec<Attribute>,
+        /// The pattern of this argument, e.g. `a`, `mut a` or `(a, b)`
+        pattern: Vec<TokenTree>,
+        /// The type of this argument
+        ty: Vec<TokenTree>,
+    },
+}
+
+impl FnArg {
+    fn parse_all(input: &mut Peekable<impl Iterator<Item = TokenTree>>) -> Result<Vec<Self>> {
+        let mut result = Vec::new();
+        while input.peek().is_some() {
+            let attributes = Attribute::try_take(AttributeLocation::Argument, input)?;
+            let tokens = read_tokens_until_punct(input, &[','])?;
+            consume_punct_if(input, ',');
+            result.push(Self::from_tokens(attributes, tokens)?);
+        }
+        Ok(result)
+    }
+
+    fn from_tokens(attributes: Vec<Attribute>, tokens: Vec<TokenTree>) -> Result<Self> {
+        let colon = (0..tokens.len()).find(|idx| is_single_colon(&tokens, *idx));
+        let (pattern, ty) = match colon {
+            Some(idx) => (&tokens[..idx], Some(&tokens[idx + 1..])),
+            None => (&tokens[..], None),
+        };
+
+        let self_ident = match pattern.last() {
+            Some(TokenTree::Ident(ident)) if ident_eq(ident, "self") => Some(ident.clone()),
+            _ => None,
+        };
+        if let Some(self_ident) = self_ident {
+            let mut is_ref = false;
+            let mut lifetime = None;
+            let mut is_mut = false;
+            let mut iter = pattern[..pattern.len() - 1].iter();
+            while let Some(token) = iter.next() {
+                match token {
+                    TokenTree::Punct(p) if p.as_char() == '&' => is_ref = true,
+                    TokenTree::Punct(p) if p.as_char() == '\'' => {
+                        if let Some(TokenTree::Ident(ident)) = iter.next() {
+                            lifetime = Some(ident.clone());
+                        }
+                    }
+                    TokenTree::Ident(ident) if ident_eq(ident, "mut") => is_mut = true,
+                    token => return Error::wrong_token(Some(token), "&, lifetime or mut"),
+                }
+            }
+            return Ok(Self::Receiver {
+                attributes,
+                is_ref,
+                lifetime,
+                is_mut,
+                ty: ty.map(|ty| ty.to_vec()),
+                self_ident,
+            });
+        }
+
+        match ty {
+            Some(ty) if !pattern.is_empty() && !ty.is_empty() => Ok(Self::Typed {
+                attributes,
+                pattern: pattern.to_vec(),
+                ty: ty.to_vec(),
+            }),
+            _ => Error::wrong_token(tokens.first(), "argument in the form of `pattern: type`"),
+        }
+    }
+
+    /// Returns `true` if this is the `self` argument
+    pub fn is_receiver(&self) -> bool {
+        matches!(self, Self::Receiver { .. })
+    }
+
+    /// The attributes of this argument
+    pub fn attributes(&self) -> &[Attribute] {
+        match self {
+            Self::Receiver { attributes, .. } | Self::Typed { attributes, .. } => attributes,
+        }
+    }
+
+    /// The name of this argument if the pattern is a single ident, e.g. `a` in `a: u32` or `mut a: u32`. For the receiver this is `self`.
+    pub fn ident(&self) -> Option<Ident> {
+        match self {
+            Self::Receiver { self_ident, .. } => Some(self_ident.clone()),
+            Self::Typed { pattern, .. } => match pattern.as_slice() {
+                [TokenTree::Ident(ident)] => Some(ident.clone()),
+                [TokenTree::Ident(m), TokenTree::Ident(ident)] if ident_eq(m, "mut") => {
+                    Some(ident.clone())
+                }
+                _ => None,
+            },
+        }
+    }
+}
+
+fn is_arrow(input: &mut Peekable<impl Iterator<Item = TokenTree>>) -> bool {
+    matches!(input.peek(), Some(TokenTree::Punct(p)) if p.as_char() == '-')
+}
+
+/// Read tokens until the `{ .. }` body or the `;` of a function. If `stop_at_where` is `true`, this also stops at a `where` clause.
+///
+/// A `{ .. }` inside of `<..>` is a const generic argument, e.g. in `fn foo() -> Foo<{ N }> { .. }`, and does not end the tokens.
+fn read_until_body(
+    input: &mut Peekable<impl Iterator<Item = TokenTree>>,
+    stop_at_where: bool,
+) -> Vec<TokenTree> {
+    let mut result = Vec::new();
+    let mut angle_brackets = 0usize;
+    loop {
+        match input.peek() {
+            None => break,
+            Some(TokenTree::Group(group))
+                if angle_brackets == 0 && group.delimiter() == Delimiter::Brace =>
+            {
+                break
+            }
+            Some(TokenTree::Punct(p)) if angle_brackets == 0 && p.as_char() == ';' => break,
+            Some(TokenTree::Ident(ident))
+                if angle_brackets == 0 && stop_at_where && ident_eq(ident, "where") =>
+            {
+                break
+            }
+            Some(TokenTree::Punct(p)) if p.as_char() == '<' => angle_brackets += 1,
+            Some(TokenTree::Punct(p))
+                if p.as_char() == '>' && !utils::is_arrow(result.last(), p) =>
+            {
+                angle_brackets = angle_brackets.saturating_sub(1);
+            }
+            Some(_) => {}
+        }
+        result.extend(input.next());
+    }
+    result
+}
+
+#[test]
+fn test_function_parse() {
+    use std::str::FromStr;
+
+    let to_string = |tokens: &[TokenTree]| {
+        tokens
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let function = Function::new(
+        TokenStream::from_str(
+            r#"
+            #[inline]
+            pub(crate) const unsafe extern "C" fn foo<'a, T: Clone>(
+                &'a mut self,
+                #[attr] a: u32,
+                mut b: Vec<(T, u8)>,
+                (c, d): (u8, u8),
+                e: impl Fn(u8) -> u8,
+            ) -> Result<T, ()> where T: Fn(u8) -> u8 {
+                todo!()
+            }
+            "#,
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    assert_eq!(function.attributes.len(), 1);
+    assert!(matches!(function.visibility, Visibility::Restricted(_)));
+    assert!(function.is_const && function.is_unsafe && function.is_extern);
+    assert!(!function.is_async);
+    assert_eq!(function.abi.as_ref().unwrap().to_string(), "\"C\"");
+    assert_eq!(function.name, "foo");
+    assert_eq!(function.generics.as_ref().unwrap().len(), 2);
+    assert_eq!(function.args.len(), 5);
+    match &function.args[0] {
+        FnArg::Receiver {
+            is_ref,
+            lifetime,
+            is_mut,
+            ty,
+            ..
+        } => {
+            assert!(*is_ref && *is_mut);
+            assert_eq!(lifetime.as_ref().unwrap(), "a");
+            assert!(ty.is_none());
+        }
+        arg => panic!("Expected receiver, got {:?}", arg),
+    }
+    assert_eq!(function.receiver().unwrap().ident().unwrap(), "self");
+    assert_eq!(function.args[1].attributes().len(), 1);
+    assert_eq!(function.args[1].ident().unwrap(), "a");
+    assert_eq!(function.args[2].ident().unwrap(), "b");
+    assert!(function.args[3].ident().is_none());
+    match &function.args[4] {
+        FnArg::Typed { pattern, ty, .. } => {
+            assert_eq!(to_string(pattern), "e");
+            assert_eq!(to_string(ty), "impl Fn (u8) - > u8");
+        }
+        arg => panic!("Expected typed argument, got {:?}", arg),
+    }
+    assert_eq!(
+        to_string(&function.return_type.unwrap()),
+        "Result < T , () >"
+    );
+    assert_eq!(
+        function
+            .generic_constraints
+            .unwrap()
+            .where_clause()
+            .stream
+            .to_string(),
+        "where T : Fn (u8) -> u8"
+    );
+    assert_eq!(function.body.unwrap().stream().to_string(), "todo ! ()");
+
+    let function =
+        Function::new(TokenStream::from_str("async fn bar(self: Box<Self>, x: u8);").unwrap())
+            .unwrap();
+    assert!(function.is_async);
+    assert!(function.body.is_none());
+    assert!(function.return_type.is_none());
+    match &function.args[0] {
+        FnArg::Receiver { is_ref, ty, .. } => {
+            assert!(!is_ref);
+            assert_eq!(to_string(ty.as_ref().unwrap()), "Box < Self >");
+        }
+        arg => panic!("Expected receiver, got {:?}", arg),
+    }
+
+    // a block in the generic arguments of the return type is a const argument, not the body
+    let function = Function::new(
+        TokenStream::from_str(
+            "fn baz<const N: usize>() -> Foo<{ N }, Box<dyn Fn() -> [u8; { N + 1 }]>> where Bar<{ N }>: Sized { Foo }",
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        to_string(&function.return_type.unwrap()),
+        "Foo < { N } , Box < dyn Fn () - > [u8 ; { N + 1 }] > >"
+    );
+    assert_eq!(
+        function
+            .generic_constraints
+            .unwrap()
+            .where_clause()
+            .stream
+            .to_string(),
+        "where Bar < { N } >: Sized"
+    );
+    assert_eq!(function.body.unwrap().stream().to_string(), "Foo");
+
+    assert!(Function::new(TokenStream::from_str("struct Foo;").unwrap()).is_err());
+    assert!(Function::new(TokenStream::from_str("fn foo() {} fn bar() {}").unwrap()).is_err());
+}
@@ -0,0 +1,285 @@
+//! A randomized test that feeds [`Parse::new`] and [`Parse::parse_items`] valid-ish items, e.g. structs and enums with random generics, attributes, nested groups and groups without delimiters, and mutations of them. Parsing may fail, but it should never panic.
+//!
+//! The inputs are generated with a small deterministic random number generator, so a failing seed can be reproduced. Set `VIRTUE_FUZZ_ITERATIONS` to run more inputs than the default.
+
+use super::Parse;
+use crate::prelude::{
+    Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree,
+};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::str::FromStr;
+
+/// xorshift64*
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+
+    fn pick<'a>(&mut self, options: &[&'a str]) -> &'a str {
+        options[self.below(options.len())]
+    }
+}
+
+const IDENTS: &[&str] = &["Foo", "a", "T", "U", "r#type", "_", "Self", "crate", "N"];
+const TYPES: &[&str] = &[
+    "u8",
+    "T",
+    "Vec<T>",
+    "&'a str",
+    "[u8; N]",
+    "(T, U)",
+    "Box<dyn Fn(u8) -> u8 + 'a>",
+    "<T as Iterator>::Item",
+    "Option<Vec<(u8, &'static [T])>>",
+    "fn(T) -> U",
+    "impl Trait",
+    "!",
+];
+const ATTRIBUTES: &[&str] = &[
+    "#[derive(Foo)]",
+    "/// docs",
+    "#[doc = \"docs\"]",
+    "#[cfg(any(unix, feature = \"a\"))]",
+    "#[repr(u8)]",
+    "#![allow(unused)]",
+    "#[foo::bar(a = 1, b(c))]",
+];
+const VISIBILITIES: &[&str] = &["", "pub", "pub(crate)", "pub(in crate::a)", "pub(super)"];
+const DISCRIMINANTS: &[&str] = &["1", "-1", "0x10u8", "BASE + 1", "-(BASE * 2)", "'a' as u8"];
+
+fn attributes(rng: &mut Rng, out: &mut String) {
+    for _ in 0..rng.below(3) {
+        out.push_str(rng.pick(ATTRIBUTES));
+        out.push('\n');
+    }
+}
+
+fn generics(rng: &mut Rng, out: &mut String) {
+    if !rng.chance(50) {
+        return;
+    }
+    out.push('<');
+    for _ in 0..rng.below(4) {
+        match rng.below(4) {
+            0 => out.push_str("'a: 'b, "),
+            1 => out.push_str("T: Clone + Iterator<Item = u8>, "),
+            2 => out.push_str("const N: usize = 5, "),
+            _ => out.push_str("U = Vec<u8>, "),
+        }
+    }
+    out.push('>');
+}
+
+fn where_clause(rng: &mut Rng, out: &mut String) {
+    if rng.chance(30) {
+        out.push_str(" where T: Fn(u8) -> Result<(), E> + 'a, Vec<T>: Clone, ");
+    }
+}
+
+fn fields(rng: &mut Rng, out: &mut String, named: bool) {
+    for _ in 0..rng.below(4) {
+        attributes(rng, out);
+        out.push_str(rng.pick(VISIBILITIES));
+        out.push(' ');
+        if named {
+            out.push_str(rng.pick(IDENTS));
+            out.push_str(": ");
+        }
+        out.push_str(rng.pick(TYPES));
+        out.push_str(", ");
+    }
+}
+
+/// A struct or enum that is mostly valid rust
+fn item(rng: &mut Rng) -> String {
+    let mut out = String::new();
+    attributes(rng, &mut out);
+    out.push_str(rng.pick(VISIBILITIES));
+    if rng.chance(50) {
+        out.push_str(" struct ");
+        out.push_str(rng.pick(IDENTS));
+        generics(rng, &mut out);
+        match rng.below(3) {
+            0 => {
+                where_clause(rng, &mut out);
+                out.push('{');
+                fields(rng, &mut out, true);
+                out.push('}');
+            }
+            1 => {
+                out.push('(');
+                fields(rng, &mut out, false);
+                out.push(')');
+                where_clause(rng, &mut out);
+                out.push(';');
+            }
+            _ => out.push(';'),
+        }
+    } else {
+        out.push_str(" enum ");
+        out.push_str(rng.pick(IDENTS));
+        generics(rng, &mut out);
+        where_clause(rng, &mut out);
+        out.push('{');
+        for _ in 0..rng.below(4) {
+            attributes(rng, &mut out);
+            out.push_str(rng.pick(IDENTS));
+            match rng.below(3) {
+                0 => {
+                    out.push('(');
+                    fields(rng, &mut out, false);
+                    out.push(')');
+                }
+                1 => {
+                    out.push('{');
+                    fields(rng, &mut out, true);
+                    out.push('}');
+                }
+                _ => {}
+            }
+            if rng.chance(30) {
+                out.push_str(" = ");
+                out.push_str(rng.pick(DISCRIMINANTS));
+            }
+            out.push_str(", ");
+        }
+        out.push('}');
+    }
+    out
+}
+
+/// A random token, for mutations
+fn random_token(rng: &mut Rng) -> TokenTree {
+    let span = Span::call_site();
+    match rng.below(6) {
+        0 => Ident::new(
+            rng.pick(&["struct", "enum", "pub", "where", "const", "a"]),
+            span,
+        )
+        .into(),
+        1 => {
+            let c = rng.pick(&["#", "!", "<", ">", ":", ",", ";", "=", "'", "-", "&"]);
+            let spacing = if rng.chance(50) {
+                Spacing::Joint
+            } else {
+                Spacing::Alone
+            };
+            Punct::new(c.chars().next().unwrap(), spacing).into()
+        }
+        2 => Literal::u8_unsuffixed(5).into(),
+        3 => Literal::string("a").into(),
+        _ => {
+            let delimiter = [
+                Delimiter::Brace,
+                Delimiter::Bracket,
+                Delimiter::Parenthesis,
+                Delimiter::None,
+            ][rng.below(4)];
+            let stream = if rng.chance(50) {
+                TokenStream::new()
+            } else {
+                random_token(rng).into()
+            };
+            Group::new(delimiter, stream).into()
+        }
+    }
+}
+
+/// Wrap random ranges of tokens in groups without delimiters, like `macro_rules!` does with fragments, and recurse into groups
+fn wrap_in_none_groups(rng: &mut Rng, stream: TokenStream, depth: usize) -> TokenStream {
+    let mut tokens: Vec<TokenTree> = stream
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Group(group) if depth < 4 && rng.chance(50) => {
+                let stream = wrap_in_none_groups(rng, group.stream(), depth + 1);
+                Group::new(group.delimiter(), stream).into()
+            }
+            token => token,
+        })
+        .collect();
+    for _ in 0..rng.below(3) {
+        if tokens.is_empty() {
+            break;
+        }
+        let start = rng.below(tokens.len());
+        let end = start + 1 + rng.below((tokens.len() - start).min(4));
+        let wrapped: TokenStream = tokens.drain(start..end).collect();
+        tokens.insert(start, Group::new(Delimiter::None, wrapped).into());
+    }
+    tokens.into_iter().collect()
+}
+
+/// Insert, remove or replace random tokens, at the top level and in nested groups
+fn mutate(rng: &mut Rng, stream: TokenStream) -> TokenStream {
+    let mut tokens: Vec<TokenTree> = stream
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Group(group) if rng.chance(20) => {
+                Group::new(group.delimiter(), mutate(rng, group.stream())).into()
+            }
+            token => token,
+        })
+        .collect();
+    for _ in 0..1 + rng.below(3) {
+        let idx = rng.below(tokens.len() + 1);
+        match rng.below(4) {
+            0 => tokens.insert(idx, random_token(rng)),
+            1 if idx < tokens.len() => {
+                tokens.remove(idx);
+            }
+            2 if idx < tokens.len() => tokens[idx] = random_token(rng),
+            _ => tokens.truncate(idx),
+        }
+    }
+    tokens.into_iter().collect()
+}
+
+fn input(rng: &mut Rng) -> TokenStream {
+    let mut code = item(rng);
+    if rng.chance(20) {
+        code.push_str(&item(rng));
+    }
+    let mut stream = TokenStream::from_str(&code).expect("The generated code can be lexed");
+    if rng.chance(50) {
+        stream = wrap_in_none_groups(rng, stream, 0);
+    }
+    if rng.chance(50) {
+        stream = mutate(rng, stream);
+    }
+    stream
+}
+
+#[test]
+fn fuzz_parse() {
+    let iterations = std::env::var("VIRTUE_FUZZ_ITERATIONS")
+        .ok()
+        .and_then(|iterations| iterations.parse().ok())
+        .unwrap_or(2_000u64);
+    for seed in 1..=iterations {
+        let rng = &mut Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        let input = input(rng);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let _ = Parse::new(input.clone());
+            let _ = Parse::parse_items(input.clone());
+        }));
+        assert!(
+            result.is_ok(),
+            "Parsing panicked with seed {} for input `{}`",
+            seed,
+            input
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use super::utils::*;
-use crate::generate::StreamBuilder;
-use crate::prelude::{Ident, TokenTree};
+use super::{Body, Fields};
+use crate::generate::{StreamBuilder, TypeTokens};
+use crate::prelude::{Delimiter, Ident, Punct, Spacing, Span, TokenTree};
 use crate::{Error, Result};
 use std::iter::Peekable;
 use std::ops::{Deref, DerefMut};
@@ -34,7 +35,7 @@ impl Generics {
         let maybe_punct = input.peek();
         if let Some(TokenTree::Punct(punct)) = maybe_punct {
             if punct.as_char() == '<' {
-                let punct = assume_punct(input.next(), '<');
+                let punct = assume_punct(input.next(), '<')?;
                 let mut result = Generics(Vec::new());
                 loop {
                     match input.peek() {
@@ -42,8 +43,16 @@ impl Generics {
                             result.push(Lifetime::take(input)?.into());
                             consume_punct_if(input, ',');
                         }
+                        // `$lt:lifetime` in a `macro_rules!` is wrapped in a group without delimiters
+                        Some(TokenTree::Group(group))
+                            if group.delimiter() == Delimiter::None
+                                && matches!(group.stream().into_iter().next(), Some(TokenTree::Punct(p)) if p.as_char() == '\'') =>
+                        {
+                            result.push(Lifetime::take_grouped(input)?.into());
+                            consume_punct_if(input, ',');
+                        }
                         Some(TokenTree::Punct(punct)) if punct.as_char() == '>' => {
-                            assume_punct(input.next(), '>');
+                            assume_punct(input.next(), '>')?;
                             break;
                         }
                         Some(TokenTree::Ident(ident)) if ident_eq(ident, "const") => {
@@ -68,6 +77,11 @@ impl Generics {
         Ok(None)
     }
 
+    /// The span of the generics in the input, which is the span of the first generic. Returns `None` if the generics are empty.
+    pub fn span(&self) -> Option<Span> {
+        self.first().map(|generic| generic.ident().span())
+    }
+
     /// Returns `true` if any of the generics is a [`Generic::Lifetime`]
     pub fn has_lifetime(&self) -> bool {
         self.iter().any(|lt| lt.is_lifetime())
@@ -97,6 +111,36 @@ impl Generics {
         })
     }
 
+    /// Returns the simple type generics that are only used inside of `PhantomData` in the given `body`.
+    ///
+    /// Derives usually add a `T: Trait` bound for every generic `T`. This is not needed for generics that are only used in `PhantomData<T>`, as `PhantomData` implements most traits for every `T`.
+    ///
+    /// ```ignore
+    /// struct Foo<A, B> {
+    ///     a: Vec<A>,
+    ///     b: PhantomData<B>,
+    /// }
+    /// // phantom_only_generics returns `[B]`
+    /// ```
+    pub fn phantom_only_generics(&self, body: &Body) -> Vec<&SimpleGeneric> {
+        let mut types = Vec::new();
+        match body {
+            Body::Struct(body) => types.extend(body.fields.iter().flat_map(field_types)),
+            Body::Enum(body) => types.extend(
+                body.variants
+                    .iter()
+                    .flat_map(|variant| variant.fields.iter().flat_map(field_types)),
+            ),
+        }
+        self.iter_generics()
+            .filter(|generic| {
+                !types
+                    .iter()
+                    .any(|ty| uses_ident_outside_phantom_data(ty, &generic.ident.to_string()))
+            })
+            .collect()
+    }
+
     pub(crate) fn impl_generics(&self) -> StreamBuilder {
         let mut result = StreamBuilder::new();
         result.punct('<');
@@ -152,6 +196,79 @@ impl Generics {
         result.punct('>');
         result
     }
+
+    /// The same as [`type_generics`], but the `<`, `,` and `>` get the span of the generics in the input, so errors about the generics point at the generics of the target type.
+    ///
+    /// [`type_generics`]: #method.type_generics
+    pub(crate) fn type_generics_at_target(&self) -> StreamBuilder {
+        let mut result = self.type_generics();
+        if let Some(span) = self.span() {
+            result.stream = std::mem::take(&mut result.stream)
+                .into_iter()
+                .map(|mut token| {
+                    if let TokenTree::Punct(_) = token {
+                        token.set_span(span);
+                    }
+                    token
+                })
+                .collect();
+        }
+        result
+    }
+}
+
+fn field_types(fields: &Fields) -> Vec<&[TokenTree]> {
+    fields.iter().map(|(_, f)| f.r#type.as_slice()).collect()
+}
+
+/// Check if `ident` is used in the given type tokens, ignoring everything inside of `PhantomData<...>`.
+pub(crate) fn uses_ident_outside_phantom_data(tokens: &[TokenTree], ident: &str) -> bool {
+    let mut iter = tokens.iter().enumerate();
+    while let Some((idx, token)) = iter.next() {
+        match token {
+            TokenTree::Ident(i) if ident_eq(i, "PhantomData") => {
+                if matches!(tokens.get(idx + 1), Some(TokenTree::Punct(p)) if p.as_char() == '<') {
+                    // skip to the matching `>`
+                    iter.next();
+                    let mut depth = 1;
+                    let mut previous_was_dash = false;
+                    for (_, token) in iter.by_ref() {
+                        if let TokenTree::Punct(p) = token {
+                            match p.as_char() {
+                                '<' => depth += 1,
+                                '>' if !previous_was_dash => depth -= 1,
+                                _ => {}
+                            }
+                            previous_was_dash = p.as_char() == '-';
+                        } else {
+                            previous_was_dash = false;
+                        }
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                }
+            }
+            TokenTree::Ident(i) if ident_eq(i, ident) => {
+                let previous = idx.checked_sub(1).and_then(|idx| tokens.get(idx));
+                let is_lifetime =
+                    matches!(previous, Some(TokenTree::Punct(p)) if p.as_char() == '\'');
+                let is_path_segment =
+                    matches!(previous, Some(TokenTree::Punct(p)) if p.as_char() == ':');
+                if !is_lifetime && !is_path_segment {
+                    return true;
+                }
+            }
+            TokenTree::Group(group) => {
+                let tokens: Vec<TokenTree> = group.stream().into_iter().collect();
+                if uses_ident_outside_phantom_data(&tokens, ident) {
+                    return true;
+                }
+            }
+            _ => {}
+        }
+    }
+    false
 }
 
 impl Deref for Generics {
@@ -202,7 +319,7 @@ pub enum Generic {
 }
 
 impl Generic {
-    fn is_lifetime(&self) -> bool {
+    pub(crate) fn is_lifetime(&self) -> bool {
         matches!(self, Generic::Lifetime(_))
     }
 
@@ -223,15 +340,18 @@ impl Generic {
         }
     }
 
-    fn constraints(&self) -> Vec<TokenTree> {
+    /// The constraints of this generic, e.g. the `Display` in `T: Display`, the `'b` in `'a: 'b` or the `usize` in `const N: usize`.
+    ///
+    /// This borrows the tokens from the parsed generic instead of cloning them.
+    pub fn constraints(&self) -> &[TokenTree] {
         match self {
-            Self::Lifetime(lt) => lt.constraint.clone(),
-            Self::Generic(gen) => gen.constraints.clone(),
-            Self::Const(gen) => gen.constraints.clone(),
+            Self::Lifetime(lt) => &lt.constraint,
+            Self::Generic(gen) => &gen.constraints,
+            Self::Const(gen) => &gen.constraints,
         }
     }
 
-    fn append_to_result_with_constraints(&self, builder: &mut StreamBuilder) {
+    pub(crate) fn append_to_result_with_constraints(&self, builder: &mut StreamBuilder) {
         match self {
             Self::Lifetime(lt) => builder.lifetime(lt.ident.clone()),
             Self::Generic(gen) => builder.ident(gen.ident.clone()),
@@ -242,7 +362,7 @@ impl Generic {
         };
         if self.has_constraints() {
             builder.punct(':');
-            builder.extend(self.constraints());
+            builder.extend(self.constraints().iter().cloned());
         }
     }
 }
@@ -265,6 +385,26 @@ impl From<ConstGeneric> for Generic {
     }
 }
 
+#[test]
+fn test_generic_constraints_ref() {
+    use crate::token_stream;
+
+    let generics = Generics::try_take(&mut token_stream("<'a: 'b, T: Display, U, const N: usize>"))
+        .unwrap()
+        .unwrap();
+    let constraints: Vec<String> = generics
+        .iter()
+        .map(|g| {
+            g.constraints()
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<String>()
+        })
+        .collect();
+    assert_eq!(constraints, ["'b", "Display", "", "usize"]);
+    assert_eq!(generics.iter_generics().next().unwrap().name_ref(), "T");
+}
+
 #[test]
 fn test_generics_try_take() {
     use crate::token_stream;
@@ -359,6 +499,41 @@ fn test_generics_try_take() {
     }
 }
 
+#[test]
+fn test_phantom_only_generics() {
+    use super::{EnumBody, StructBody};
+    use crate::token_stream;
+
+    fn phantom_only(generics: &str, body: Body) -> Vec<String> {
+        let generics = Generics::try_take(&mut token_stream(generics))
+            .unwrap()
+            .unwrap();
+        generics
+            .phantom_only_generics(&body)
+            .into_iter()
+            .map(|g| g.ident.to_string())
+            .collect()
+    }
+
+    let body = StructBody::take(&mut token_stream(
+        "{ a: Vec<A>, b: PhantomData<B>, c: std::marker::PhantomData<fn() -> (C, A)> }",
+    ))
+    .unwrap();
+    assert_eq!(phantom_only("<A, B, C>", Body::Struct(body)), ["B", "C"]);
+
+    let body = StructBody::take(&mut token_stream(
+        "(PhantomData<&'a T>, Box<dyn Fn(PhantomData<U>) -> U>, T::Output)",
+    ))
+    .unwrap();
+    assert!(phantom_only("<'a, T, U>", Body::Struct(body)).is_empty());
+
+    let body = EnumBody::take(&mut token_stream(
+        "{ A(PhantomData<A>), B { b: Option<B> }, C }",
+    ))
+    .unwrap();
+    assert_eq!(phantom_only("<A, B>", Body::Enum(body)), ["A"]);
+}
+
 /// a lifetime generic parameter, e.g. `struct Foo<'a> { ... }`
 #[derive(Debug, Clone)]
 pub struct Lifetime {
@@ -370,9 +545,9 @@ pub struct Lifetime {
 
 impl Lifetime {
     pub(crate) fn take(input: &mut Peekable<impl Iterator<Item = TokenTree>>) -> Result<Self> {
-        let start = assume_punct(input.next(), '\'');
+        let start = assume_punct(input.next(), '\'')?;
         let ident = match input.peek() {
-            Some(TokenTree::Ident(_)) => assume_ident(input.next()),
+            Some(TokenTree::Ident(_)) => assume_ident(input.next())?,
             Some(t) => return Err(Error::ExpectedIdent(t.span())),
             None => return Err(Error::ExpectedIdent(start.span())),
         };
@@ -380,7 +555,7 @@ impl Lifetime {
         let mut constraint = Vec::new();
         if let Some(TokenTree::Punct(p)) = input.peek() {
             if p.as_char() == ':' {
-                assume_punct(input.next(), ':');
+                assume_punct(input.next(), ':')?;
                 constraint = read_tokens_until_punct(input, &[',', '>'])?;
             }
         }
@@ -388,6 +563,19 @@ impl Lifetime {
         Ok(Self { ident, constraint })
     }
 
+    fn take_grouped(input: &mut Peekable<impl Iterator<Item = TokenTree>>) -> Result<Self> {
+        let group = assume_group(input.next())?;
+        let mut lifetime = Self::take(&mut group.stream().into_iter().peekable())?;
+        // The constraint of `$lt: 'b` is outside of the group
+        if let Some(TokenTree::Punct(p)) = input.peek() {
+            if p.as_char() == ':' && lifetime.constraint.is_empty() {
+                assume_punct(input.next(), ':')?;
+                lifetime.constraint = read_tokens_until_punct(input, &[',', '>'])?;
+            }
+        }
+        Ok(lifetime)
+    }
+
     #[cfg(test)]
     fn is_ident(&self, s: &str) -> bool {
         self.ident == s
@@ -396,21 +584,31 @@ impl Lifetime {
 
 #[test]
 fn test_lifetime_take() {
+    use crate::prelude::{Group, Literal};
     use crate::token_stream;
-    use std::panic::catch_unwind;
     assert!(Lifetime::take(&mut token_stream("'a"))
         .unwrap()
         .is_ident("a"));
-    assert!(catch_unwind(|| Lifetime::take(&mut token_stream("'0"))).is_err());
-    assert!(catch_unwind(|| Lifetime::take(&mut token_stream("'("))).is_err());
-    assert!(catch_unwind(|| Lifetime::take(&mut token_stream("')"))).is_err());
-    assert!(catch_unwind(|| Lifetime::take(&mut token_stream("'0'"))).is_err());
+    // a `'` that is not followed by an ident, which can not be lexed from a string
+    let tick = |next: Option<TokenTree>| {
+        let tick = Punct::new('\'', Spacing::Joint).into();
+        std::iter::once(tick).chain(next).peekable()
+    };
+    assert!(Lifetime::take(&mut tick(Some(Literal::u8_unsuffixed(0).into()))).is_err());
+    assert!(Lifetime::take(&mut tick(Some(
+        Group::new(Delimiter::Parenthesis, Default::default()).into()
+    )))
+    .is_err());
+    assert!(Lifetime::take(&mut tick(None)).is_err());
+    // a char literal or an ident instead of a `'`
+    assert!(Lifetime::take(&mut token_stream("'0'")).is_err());
+    assert!(Lifetime::take(&mut token_stream("a")).is_err());
 
     let stream = &mut token_stream("'a: 'b>");
     let lifetime = Lifetime::take(stream).unwrap();
     assert_eq!(lifetime.ident, "a");
     assert_eq!(lifetime.constraint.len(), 2);
-    assume_punct(stream.next(), '>');
+    assume_punct(stream.next(), '>').unwrap();
     assert!(stream.next().is_none());
 }
 
@@ -428,20 +626,8 @@ pub struct SimpleGeneric {
 
 impl SimpleGeneric {
     pub(crate) fn take(input: &mut Peekable<impl Iterator<Item = TokenTree>>) -> Result<Self> {
-        let ident = assume_ident(input.next());
-        let mut constraints = Vec::new();
-        let mut default_value = Vec::new();
-        if let Some(TokenTree::Punct(punct)) = input.peek() {
-            let punct_char = punct.as_char();
-            if punct_char == ':' {
-                assume_punct(input.next(), ':');
-                constraints = read_tokens_until_punct(input, &['>', ','])?;
-            }
-            if punct_char == '=' {
-                assume_punct(input.next(), '=');
-                default_value = read_tokens_until_punct(input, &['>', ','])?;
-            }
-        }
+        let ident = assume_ident(input.next())?;
+        let (constraints, default_value) = take_constraints_and_default(input)?;
         Ok(Self {
             ident,
             constraints,
@@ -450,13 +636,25 @@ impl SimpleGeneric {
     }
 
     /// The name of this generic, e.g. `T`
+    ///
+    /// This clones the ident, use [`name_ref`] to borrow it instead.
+    ///
+    /// [`name_ref`]: #method.name_ref
     pub fn name(&self) -> Ident {
         self.ident.clone()
     }
+
+    /// The name of this generic, e.g. `T`, without cloning it.
+    pub fn name_ref(&self) -> &Ident {
+        &self.ident
+    }
 }
 
 /// a const generic parameter, e.g. `struct Foo<const N: usize> { .. }`
+///
+/// This struct is `#[non_exhaustive]` so that new properties of a const generic can be added without a breaking change.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct ConstGeneric {
     /// The `const` token for this generic
     pub const_token: Ident,
@@ -464,27 +662,128 @@ pub struct ConstGeneric {
     pub ident: Ident,
     /// The "constraints" (type) of this generic, e.g. the `usize` from `const N: usize`
     pub constraints: Vec<TokenTree>,
+    /// The default value of this generic, e.g. the `{ 4 + 4 }` from `const N: usize = { 4 + 4 }`
+    pub default_value: Vec<TokenTree>,
 }
 
 impl ConstGeneric {
     pub(crate) fn take(input: &mut Peekable<impl Iterator<Item = TokenTree>>) -> Result<Self> {
-        let const_token = assume_ident(input.next());
-        let ident = assume_ident(input.next());
-        let mut constraints = Vec::new();
-        if let Some(TokenTree::Punct(punct)) = input.peek() {
-            if punct.as_char() == ':' {
-                assume_punct(input.next(), ':');
-                constraints = read_tokens_until_punct(input, &['>', ','])?;
-            }
-        }
+        let const_token = assume_ident(input.next())?;
+        let ident = match consume_ident(input) {
+            Some(ident) => ident,
+            None => return Error::wrong_token(input.peek(), "ident"),
+        };
+        let (constraints, default_value) = take_constraints_and_default(input)?;
         Ok(Self {
             const_token,
             ident,
             constraints,
+            default_value,
         })
     }
 }
 
+/// Take the optional `: constraints` and `= default` of a generic parameter. A `=` only starts the default if it is not nested in the constraints, e.g. in `T: Iterator<Item = U>`.
+fn take_constraints_and_default(
+    input: &mut Peekable<impl Iterator<Item = TokenTree>>,
+) -> Result<(Vec<TokenTree>, Vec<TokenTree>)> {
+    let mut constraints = Vec::new();
+    let mut default_value = Vec::new();
+    if consume_punct_if(input, ':').is_some() {
+        constraints = read_tokens_until_punct(input, &['>', ',', '='])?;
+    }
+    if consume_punct_if(input, '=').is_some() {
+        default_value = read_tokens_until_punct(input, &['>', ','])?;
+    }
+    Ok((constraints, default_value))
+}
+
+#[test]
+fn test_generic_defaults() {
+    use crate::token_stream;
+
+    let to_string = |tokens: &[TokenTree]| {
+        tokens
+            .iter()
+            .cloned()
+            .collect::<crate::prelude::TokenStream>()
+            .to_string()
+    };
+    // (name, constraints, default value)
+    type Expected<'a> = &'a [(&'a str, &'a str, &'a str)];
+    let cases: &[(&str, Expected)] = &[
+        (
+            "<const N: usize = { 4 + 4 }, T>",
+            &[("N", "usize", "{ 4 + 4 }"), ("T", "", "")],
+        ),
+        (
+            "<const N: usize = 3, const M: bool = { N > 2 }>",
+            &[("N", "usize", "3"), ("M", "bool", "{ N > 2 }")],
+        ),
+        (
+            "<T: Iterator<Item = U>, U>",
+            &[("T", "Iterator < Item = U >", ""), ("U", "", "")],
+        ),
+        (
+            "<T: Clone = Vec<u8>, U = fn(u8) -> u8>",
+            &[("T", "Clone", "Vec < u8 >"), ("U", "", "fn (u8) -> u8")],
+        ),
+        (
+            "<F: Fn(fn(u8) -> u8) -> Option<u8>, G: Iterator<Item = fn() -> ()> = Empty<fn() -> ()>>",
+            &[
+                ("F", "Fn (fn (u8) -> u8) -> Option < u8 >", ""),
+                ("G", "Iterator < Item = fn () -> () >", "Empty < fn () -> () >"),
+            ],
+        ),
+        (
+            "<'a, T: 'a + Default = &'a (), const N: usize = { 1 << 2 }>",
+            &[
+                ("a", "", ""),
+                ("T", "'a + Default", "&'a ()"),
+                ("N", "usize", "{ 1 << 2 }"),
+            ],
+        ),
+    ];
+    for (input, expected) in cases {
+        let stream = &mut token_stream(input);
+        let generics = Generics::try_take(stream).unwrap().unwrap();
+        assert!(stream.next().is_none(), "{} was not fully parsed", input);
+        let parsed: Vec<(String, String, String)> = generics
+            .iter()
+            .map(|generic| match generic {
+                Generic::Lifetime(lt) => (lt.ident.to_string(), String::new(), String::new()),
+                Generic::Generic(gen) => (
+                    gen.ident.to_string(),
+                    to_string(&gen.constraints),
+                    to_string(&gen.default_value),
+                ),
+                Generic::Const(gen) => (
+                    gen.ident.to_string(),
+                    to_string(&gen.constraints),
+                    to_string(&gen.default_value),
+                ),
+            })
+            .collect();
+        let expected: Vec<(String, String, String)> = expected
+            .iter()
+            .map(|(a, b, c)| (a.to_string(), b.to_string(), c.to_string()))
+            .collect();
+        assert_eq!(parsed, expected, "{}", input);
+    }
+
+    // defaults are not allowed in impls
+    let generics = Generics::try_take(&mut token_stream(
+        "<T: Clone = u8, const N: usize = { 4 + 4 }>",
+    ))
+    .unwrap()
+    .unwrap();
+    assert_eq!(
+        generics.impl_generics().stream.to_string(),
+        "< T : Clone , const N : usize >"
+    );
+    assert_eq!(generics.type_generics().stream.to_string(), "< T , N >");
+}
+
 /// Constraints on generic types.
 ///
 /// ```
@@ -504,6 +803,21 @@ pub struct GenericConstraints {
 impl GenericConstraints {
     pub(crate) fn try_take(
         input: &mut Peekable<impl Iterator<Item = TokenTree>>,
+    ) -> Result<Option<Self>> {
+        // The where clause ends at the body (`{` or `(`), or at the `;` of a unit struct
+        Self::try_take_until(input, &['{', '(', ';'])
+    }
+
+    /// Take the where clause of a tuple struct, which is placed after the fields: `struct Foo<T>(T) where T: Clone;`
+    pub(crate) fn try_take_after_fields(
+        input: &mut Peekable<impl Iterator<Item = TokenTree>>,
+    ) -> Result<Option<Self>> {
+        Self::try_take_until(input, &[';'])
+    }
+
+    fn try_take_until(
+        input: &mut Peekable<impl Iterator<Item = TokenTree>>,
+        terminators: &[char],
     ) -> Result<Option<Self>> {
         match input.peek() {
             Some(TokenTree::Ident(ident)) => {
@@ -516,10 +830,14 @@ impl GenericConstraints {
             }
         }
         input.next();
-        let constraints = read_tokens_until_punct(input, &['{', '('])?;
+        let constraints = read_tokens_until_punct(input, terminators)?;
         Ok(Some(Self { constraints }))
     }
 
+    pub(crate) fn from_tokens(constraints: Vec<TokenTree>) -> Self {
+        Self { constraints }
+    }
+
     pub(crate) fn where_clause(&self) -> StreamBuilder {
         let mut result = StreamBuilder::new();
         result.ident_str("where");
@@ -544,17 +862,47 @@ impl GenericConstraints {
         constraint: impl AsRef<str>,
     ) -> Result<()> {
         let mut builder = StreamBuilder::new();
-        let last_constraint_was_comma = self.constraints.last().map_or(
-            false,
-            |l| matches!(l, TokenTree::Punct(c) if c.as_char() == ','),
-        );
-        if !self.constraints.is_empty() && !last_constraint_was_comma {
-            builder.punct(',');
-        }
         builder.ident(generic.ident.clone());
         builder.punct(':');
         builder.push_parsed(constraint)?;
-        self.constraints.extend(builder.stream);
+        self.push_predicates(builder.stream.into_iter().collect());
+
+        Ok(())
+    }
+
+    /// Push a constraint on any type, e.g. an associated type like `T::Output` or a type that contains a generic like `Vec<T>`. The type can be a string or tokens that were parsed already, e.g. the type of a field. See [`TypeTokens`] for more information.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = TokenStream::from_str("struct Foo<T: Iterator> { a: Vec<T>, b: T::Item }").unwrap();
+    /// let (mut generator, _, body) = Parse::new(input)?.into_generator();
+    /// # let fields = match &body { Body::Struct(body) => body.fields.as_ref().unwrap(), _ => unreachable!() };
+    /// generator
+    ///     .impl_for("Encode")
+    ///     .modify_generic_constraints(|_, constraints| {
+    ///         for (_, field) in fields.iter() {
+    ///             constraints.push_type_constraint(field, "Encode")?;
+    ///         }
+    ///         constraints.push_type_constraint("<T as Iterator>::Item", "Clone")?;
+    ///         Ok(())
+    ///     })?;
+    /// # generator.assert_eq("impl < T : Iterator > Encode for Foo < T > \
+    /// #     where Vec < T >: Encode , T :: Item : Encode , < T as Iterator >:: Item : Clone { }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    ///
+    /// [`TypeTokens`]: ../generate/struct.TypeTokens.html
+    pub fn push_type_constraint(
+        &mut self,
+        ty: impl Into<TypeTokens>,
+        constraint: impl AsRef<str>,
+    ) -> Result<()> {
+        let mut builder = StreamBuilder::new();
+        ty.into().append_to(&mut builder)?;
+        builder.punct(':');
+        builder.push_parsed(constraint)?;
+        self.push_predicates(builder.stream.into_iter().collect());
 
         Ok(())
     }
@@ -571,21 +919,222 @@ impl GenericConstraints {
     /// ```
     pub fn push_parsed_constraint(&mut self, constraint: impl AsRef<str>) -> Result<()> {
         let mut builder = StreamBuilder::new();
-        if !self.constraints.is_empty() {
-            builder.punct(',');
-        }
         builder.push_parsed(constraint)?;
-        self.constraints.extend(builder.stream);
+        self.push_predicates(builder.stream.into_iter().collect());
 
         Ok(())
     }
 
+    /// Push the predicates in `tokens`, skipping any predicate that is already present.
+    fn push_predicates(&mut self, tokens: Vec<TokenTree>) {
+        let mut existing: Vec<String> = split_predicates(&self.constraints)
+            .iter()
+            .map(|p| tokens_key(p))
+            .collect();
+        for predicate in split_predicates(&tokens) {
+            let key = tokens_key(predicate);
+            if existing.contains(&key) {
+                continue;
+            }
+            let last_constraint_was_comma = matches!(
+                self.constraints.last(),
+                Some(TokenTree::Punct(c)) if c.as_char() == ','
+            );
+            if !self.constraints.is_empty() && !last_constraint_was_comma {
+                self.constraints
+                    .push(TokenTree::Punct(Punct::new(',', Spacing::Alone)));
+            }
+            self.constraints.extend(predicate.iter().cloned());
+            existing.push(key);
+        }
+    }
+
+    /// Returns `true` if there is a predicate `generic: bound`, e.g. `T: Encode`. The bound may be one of several bounds on the generic, e.g. `T: Clone + Encode`.
+    ///
+    /// ```ignore
+    /// let generic_constraints = GenericConstraints::parse("T: Clone + Encode"); // imaginary function
+    /// let generic = SimpleGeneric::new("T"); // imaginary function
+    ///
+    /// assert!(generic_constraints.contains(&generic, "Encode"));
+    /// assert!(!generic_constraints.contains(&generic, "Decode"));
+    /// ```
+    pub fn contains(&self, generic: &SimpleGeneric, bound: impl AsRef<str>) -> bool {
+        let mut builder = StreamBuilder::new();
+        if builder.push_parsed(bound).is_err() {
+            return false;
+        }
+        let bound: Vec<TokenTree> = builder.stream.into_iter().collect();
+        let bound = tokens_key(&bound);
+        let generic = generic.ident.to_string();
+
+        split_predicates(&self.constraints).iter().any(|predicate| {
+            let colon = match (0..predicate.len()).find(|&idx| is_single_colon(predicate, idx)) {
+                Some(colon) => colon,
+                None => return false,
+            };
+            tokens_key(&predicate[..colon]) == generic
+                && split_at_top_level(&predicate[colon + 1..], '+')
+                    .iter()
+                    .any(|b| tokens_key(b) == bound)
+        })
+    }
+
+    /// Remove any duplicate predicates, keeping the first occurrence.
+    pub fn dedup(&mut self) {
+        let constraints = std::mem::take(&mut self.constraints);
+        self.push_predicates(constraints);
+    }
+
+    /// Sort the predicates, so the generated where clause does not depend on the order the constraints were added in.
+    pub fn sort(&mut self) {
+        let mut predicates: Vec<(String, Vec<TokenTree>)> = split_predicates(&self.constraints)
+            .into_iter()
+            .map(|p| (tokens_key(p), p.to_vec()))
+            .collect();
+        predicates.sort_by(|a, b| a.0.cmp(&b.0));
+        self.constraints.clear();
+        for (idx, (_, predicate)) in predicates.into_iter().enumerate() {
+            if idx > 0 {
+                self.constraints
+                    .push(TokenTree::Punct(Punct::new(',', Spacing::Alone)));
+            }
+            self.constraints.extend(predicate);
+        }
+    }
+
     /// Clear the constraints
     pub fn clear(&mut self) {
         self.constraints.clear();
     }
 }
 
+/// Split the predicates of a where clause on their top-level commas.
+fn split_predicates(tokens: &[TokenTree]) -> Vec<&[TokenTree]> {
+    split_at_top_level(tokens, ',')
+}
+
+/// Split `tokens` on the given punct, ignoring any punct inside of `<...>`. Empty parts are skipped.
+fn split_at_top_level(tokens: &[TokenTree], split: char) -> Vec<&[TokenTree]> {
+    let mut result = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (idx, token) in tokens.iter().enumerate() {
+        if let TokenTree::Punct(p) = token {
+            let previous_is_dash =
+                idx > 0 && matches!(&tokens[idx - 1], TokenTree::Punct(p) if p.as_char() == '-');
+            match p.as_char() {
+                '<' => depth += 1,
+                '>' if !previous_is_dash => depth = depth.saturating_sub(1),
+                c if c == split && depth == 0 => {
+                    if idx > start {
+                        result.push(&tokens[start..idx]);
+                    }
+                    start = idx + 1;
+                }
+                _ => {}
+            }
+        }
+    }
+    if tokens.len() > start {
+        result.push(&tokens[start..]);
+    }
+    result
+}
+
+/// A representation of the given tokens that is independent of spacing, used to compare predicates.
+fn tokens_key(tokens: &[TokenTree]) -> String {
+    let mut result = String::new();
+    for token in tokens {
+        match token {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => ("", ""),
+                };
+                let inner: Vec<TokenTree> = group.stream().into_iter().collect();
+                result.push_str(open);
+                result.push_str(&tokens_key(&inner));
+                result.push_str(close);
+            }
+            TokenTree::Ident(ident) => {
+                // keep idents apart, e.g. `dyn Foo` should not become `dynFoo`
+                if result.ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+                    result.push(' ');
+                }
+                result.push_str(&ident.to_string());
+            }
+            token => result.push_str(&token.to_string()),
+        }
+    }
+    result
+}
+
+#[test]
+fn test_generic_constraints_normalization() {
+    use crate::token_stream;
+
+    let stream = &mut token_stream("where T: Clone, U: Iterator<Item = (T, u8)> + Send, {}");
+    let mut constraints = GenericConstraints::try_take(stream).unwrap().unwrap();
+    let generics = Generics::try_take(&mut token_stream("<T, U, V>"))
+        .unwrap()
+        .unwrap();
+    let generics: Vec<&SimpleGeneric> = generics.iter_generics().collect();
+    let (t, u, v) = (generics[0], generics[1], generics[2]);
+
+    assert!(constraints.contains(t, "Clone"));
+    assert!(constraints.contains(u, "Send"));
+    assert!(constraints.contains(u, "Iterator<Item = (T, u8)>"));
+    assert!(!constraints.contains(u, "Clone"));
+    assert!(!constraints.contains(v, "Clone"));
+
+    constraints.push_constraint(v, "Encode").unwrap();
+    constraints.push_constraint(t, "Clone").unwrap();
+    constraints.push_constraint(v, "Encode").unwrap();
+    constraints
+        .push_parsed_constraint("T: Clone, u32: Encode")
+        .unwrap();
+    assert_eq!(
+        constraints.where_clause().stream.to_string(),
+        "where T : Clone , U : Iterator < Item = (T , u8) > + Send , V : Encode , u32 : Encode"
+    );
+
+    constraints.sort();
+    assert_eq!(
+        constraints.where_clause().stream.to_string(),
+        "where T : Clone , U : Iterator < Item = (T , u8) > + Send , V : Encode , u32 : Encode"
+    );
+
+    let mut constraints = GenericConstraints::default();
+    constraints
+        .push_type_constraint("T::Output", "Encode")
+        .unwrap();
+    let ty: Vec<TokenTree> = token_stream("Vec<T>").collect();
+    constraints.push_type_constraint(ty, "Encode").unwrap();
+    constraints
+        .push_type_constraint("T :: Output", "Encode")
+        .unwrap();
+    assert!(constraints.push_type_constraint("Vec(", "Encode").is_err());
+    assert_eq!(
+        constraints.where_clause().stream.to_string(),
+        "where T :: Output : Encode , Vec < T > : Encode"
+    );
+
+    let stream = &mut token_stream("where V: Encode, T: Clone, V: Encode {}");
+    let mut constraints = GenericConstraints::try_take(stream).unwrap().unwrap();
+    constraints.dedup();
+    assert_eq!(
+        constraints.where_clause().stream.to_string(),
+        "where V : Encode , T : Clone"
+    );
+    constraints.sort();
+    assert_eq!(
+        constraints.where_clause().stream.to_string(),
+        "where T : Clone , V : Encode"
+    );
+}
+
 #[test]
 fn test_generic_constraints_try_take() {
     use super::{DataType, StructBody, Visibility};
@@ -618,7 +1167,7 @@ fn test_generic_constraints_try_take() {
     assert!(GenericConstraints::try_take(stream).unwrap().is_none());
 
     let stream = &mut token_stream("pub(crate) struct Test<T: Encode> {}");
-    assert_eq!(Visibility::Pub, Visibility::try_take(stream).unwrap());
+    assert!(Visibility::try_take(stream).unwrap().is_pub());
     let (data_type, ident) = DataType::take(stream).unwrap();
     assert_eq!(data_type, DataType::Struct);
     assert_eq!(ident, "Test");
@@ -633,6 +1182,87 @@ fn test_generic_constraints_try_take() {
     }
 }
 
+#[test]
+fn test_generic_constraints_terminators() {
+    use super::{DataType, EnumBody, StructBody};
+    use crate::parse::body::Fields;
+    use crate::token_stream;
+
+    // unit struct
+    let stream = &mut token_stream("struct Foo<T> where T: Default;");
+    DataType::take(stream).unwrap();
+    Generics::try_take(stream).unwrap().unwrap();
+    let constraints = GenericConstraints::try_take(stream).unwrap().unwrap();
+    assert_eq!(
+        constraints.where_clause().stream.to_string(),
+        "where T : Default"
+    );
+    let body = StructBody::take(stream).unwrap();
+    assert!(body.fields.is_none());
+    assume_punct(stream.next(), ';').unwrap();
+    assert!(stream.next().is_none());
+
+    // tuple struct with the where clause after the fields
+    let stream = &mut token_stream("struct Foo<T>(T) where T: Iterator<Item = (u8, u16)>;");
+    DataType::take(stream).unwrap();
+    Generics::try_take(stream).unwrap().unwrap();
+    assert!(GenericConstraints::try_take(stream).unwrap().is_none());
+    let body = StructBody::take(stream).unwrap();
+    assert!(matches!(body.fields, Some(Fields::Tuple(ref fields)) if fields.len() == 1));
+    let constraints = GenericConstraints::try_take(stream).unwrap().unwrap();
+    assert_eq!(
+        constraints.where_clause().stream.to_string(),
+        "where T : Iterator < Item = (u8 , u16) >"
+    );
+    assume_punct(stream.next(), ';').unwrap();
+    assert!(stream.next().is_none());
+
+    // `Fn` bounds and parenthesized types are part of the where clause
+    let stream = &mut token_stream(
+        "struct Foo<F, G> where F: Fn(Foo) -> Result<Bar, E> + Send, \
+         G: FnOnce(&'static str) -> (u8, u16), (F, G): Clone, { f: F }",
+    );
+    DataType::take(stream).unwrap();
+    Generics::try_take(stream).unwrap().unwrap();
+    let constraints = GenericConstraints::try_take(stream).unwrap().unwrap();
+    assert_eq!(
+        constraints.where_clause().stream.to_string(),
+        "where F : Fn (Foo) -> Result < Bar , E > + Send , \
+         G : FnOnce (&'static str) -> (u8 , u16) , (F , G) : Clone ,"
+    );
+    let body = StructBody::take(stream).unwrap();
+    assert!(matches!(body.fields, Some(Fields::Struct(ref fields)) if fields.len() == 1));
+    assert!(stream.next().is_none());
+
+    // the where clause of a tuple struct follows the fields
+    let stream = &mut token_stream("struct Foo<T>(T) where T: Into<u8>;");
+    DataType::take(stream).unwrap();
+    Generics::try_take(stream).unwrap().unwrap();
+    assert!(GenericConstraints::try_take(stream).unwrap().is_none());
+    let body = StructBody::take(stream).unwrap();
+    assert!(matches!(body.fields, Some(Fields::Tuple(ref fields)) if fields.len() == 1));
+    let constraints = GenericConstraints::try_take_after_fields(stream)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        constraints.where_clause().stream.to_string(),
+        "where T : Into < u8 >"
+    );
+
+    // enum with discriminants
+    let stream = &mut token_stream("enum Foo<T> where T: Default { A = 1, B = 2 }");
+    DataType::take(stream).unwrap();
+    Generics::try_take(stream).unwrap().unwrap();
+    let constraints = GenericConstraints::try_take(stream).unwrap().unwrap();
+    assert_eq!(
+        constraints.where_clause().stream.to_string(),
+        "where T : Default"
+    );
+    let body = EnumBody::take(stream).unwrap();
+    assert_eq!(body.variants.len(), 2);
+    assert!(stream.next().is_none());
+}
+
 #[test]
 fn test_generic_constraints_trailing_comma() {
     use crate::parse::{
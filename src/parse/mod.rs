@@ -1,22 +1,37 @@
 //! Module for parsing code. The main enum is [`Parse`].
 
 use crate::prelude::*;
+use std::iter::Peekable;
+use std::rc::Rc;
 
 mod attributes;
 mod body;
+mod cursor;
 mod data_type;
+mod function;
+#[cfg(test)]
+mod fuzz;
 mod generics;
-mod utils;
+pub mod utils;
 mod visibility;
 
-pub use self::attributes::{Attribute, AttributeAccess, AttributeLocation, FromAttribute};
-pub use self::body::{EnumBody, EnumVariant, Fields, IdentOrIndex, StructBody, UnnamedField};
+pub use self::attributes::{
+    Attribute, AttributeAccess, AttributeAccessMut, AttributeLocation, FromAttribute,
+};
+pub use self::body::{
+    EnumBody, EnumVariant, Fields, FieldsIntoIter, FieldsIter, IdentOrIndex, ResolvedDiscriminant,
+    StructBody, UnnamedField, VariantShape,
+};
+pub use self::cursor::Cursor;
 pub(crate) use self::data_type::DataType;
+pub use self::function::{FnArg, Function};
+pub(crate) use self::generics::uses_ident_outside_phantom_data;
 pub use self::generics::{
     ConstGeneric, Generic, GenericConstraints, Generics, Lifetime, SimpleGeneric,
 };
 pub use self::visibility::Visibility;
 
+use self::utils::consume_punct_if;
 use crate::generate::Generator;
 
 /// Parser for Enum and Struct derives.
@@ -70,16 +85,97 @@ pub enum Parse {
 impl Parse {
     /// Parse the given [`TokenStream`] and return the result.
     pub fn new(input: TokenStream) -> Result<Self> {
-        let source = &mut input.into_iter().peekable();
+        let source = &mut Attribute::unwrap_none_groups(input).into_iter().peekable();
+        Self::take(source)
+    }
+
+    /// Parse the given [`TokenStream`] once, so it can be shared between multiple derive implementations. See [`ParsedItem`] for more information.
+    ///
+    /// This returns an [`Rc`] and not an `Arc`, because the tokens of a proc macro can not be sent to other threads.
+    pub fn new_shared(input: TokenStream) -> Result<Rc<ParsedItem>> {
+        Self::new(input).map(|parse| Rc::new(parse.into()))
+    }
+
+    /// Parse all items in the given [`TokenStream`], returning one result per item.
+    ///
+    /// This is useful for attribute macros that receive multiple items, e.g. the contents of a `mod`. Any item that is not a `struct` or an `enum` is skipped and returned as an [`Error::UnsupportedItem`] (e.g. `fn` or `impl`) or an [`Error::UnknownDataType`] (e.g. `use` or `extern` blocks). These errors are recoverable: the items after them are still parsed, and a macro can decide to ignore them. If a struct or enum can not be parsed, the whole stream is rejected.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = TokenStream::from_str("struct Foo; fn bar() {} enum Baz { A }").unwrap();
+    /// let items = Parse::parse_items(input)?;
+    /// assert_eq!(items.len(), 3);
+    /// assert!(items[1].is_err());
+    ///
+    /// // Only keep the structs and enums
+    /// let items: Vec<Parse> = items.into_iter().filter_map(Result::ok).collect();
+    /// assert_eq!(items.len(), 2);
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn parse_items(input: TokenStream) -> Result<Vec<Result<Self>>> {
+        let mut tokens = ItemTokens {
+            front: Vec::new(),
+            rest: input.into_iter(),
+        };
+        let mut result = Vec::new();
+        while let Some(token) = tokens.next() {
+            let may_have_groups = match &token {
+                TokenTree::Group(group) => group.delimiter() == Delimiter::None,
+                TokenTree::Punct(punct) => punct.as_char() == '#',
+                _ => false,
+            };
+            tokens.front.push(token);
+            if may_have_groups {
+                // e.g. the items of `$($item)*` in a `macro_rules!`
+                let leading = Attribute::unwrap_leading_none_groups(&mut tokens);
+                tokens.front.extend(leading.into_iter().rev());
+            }
+            let source = &mut tokens.by_ref().peekable();
+            let attributes = Attribute::try_take(AttributeLocation::Container, source)?;
+            let visibility = Visibility::try_take(source)?;
+            match source.peek() {
+                Some(TokenTree::Ident(ident)) if !DataType::is_keyword(ident) => {
+                    let ident = ident.clone();
+                    source.next();
+                    result.push(Err(skip_item(ident, source)));
+                }
+                _ => {
+                    result.push(Ok(Self::take_item(attributes, visibility, source)?));
+                    // `struct Foo;` and `struct Foo(u8);` end with a semicolon that is not part of the body
+                    consume_punct_if(source, ';');
+                }
+            }
+            // `source` may have taken the first token of the next item from `tokens` to peek at it
+            if let Some(token) = source.next() {
+                tokens.front.push(token);
+            }
+        }
+        Ok(result)
+    }
 
+    fn take(source: &mut Peekable<impl Iterator<Item = TokenTree>>) -> Result<Self> {
         let attributes = Attribute::try_take(AttributeLocation::Container, source)?;
         let visibility = Visibility::try_take(source)?;
+        Self::take_item(attributes, visibility, source)
+    }
+
+    fn take_item(
+        attributes: Vec<Attribute>,
+        visibility: Visibility,
+        source: &mut Peekable<impl Iterator<Item = TokenTree>>,
+    ) -> Result<Self> {
         let (datatype, name) = DataType::take(source)?;
         let generics = Generics::try_take(source)?;
         let generic_constraints = GenericConstraints::try_take(source)?;
         match datatype {
             DataType::Struct => {
                 let body = StructBody::take(source)?;
+                // `struct Foo<T>(T) where T: Clone;` has the where clause after the fields
+                let generic_constraints = match generic_constraints {
+                    Some(constraints) => Some(constraints),
+                    None => GenericConstraints::try_take_after_fields(source)?,
+                };
                 Ok(Self::Struct {
                     attributes,
                     visibility,
@@ -103,30 +199,39 @@ impl Parse {
         }
     }
 
+    /// The visibility of the struct or enum, e.g. `pub(crate)` for `pub(crate) struct Foo {}`.
+    pub fn visibility(&self) -> &Visibility {
+        match self {
+            Parse::Struct { visibility, .. } | Parse::Enum { visibility, .. } => visibility,
+        }
+    }
+
     /// Split this struct or enum into a [`Generator`], list of [`Attribute`] and [`Body`].
     pub fn into_generator(self) -> (Generator, Vec<Attribute>, Body) {
         match self {
             Parse::Struct {
                 name,
+                visibility,
                 generics,
                 generic_constraints,
                 body,
                 attributes,
-                ..
             } => (
-                Generator::new(name, generics, generic_constraints),
+                Generator::new(name, generics, generic_constraints)
+                    .with_target_visibility(visibility),
                 attributes,
                 Body::Struct(body),
             ),
             Parse::Enum {
                 name,
+                visibility,
                 generics,
                 generic_constraints,
                 body,
                 attributes,
-                ..
             } => (
-                Generator::new(name, generics, generic_constraints),
+                Generator::new(name, generics, generic_constraints)
+                    .with_target_visibility(visibility),
                 attributes,
                 Body::Enum(body),
             ),
@@ -134,9 +239,444 @@ impl Parse {
     }
 }
 
+/// The tokens of [`Parse::parse_items`]: the tokens in `front`, in reverse order, followed by `rest`. Tokens are put back in `front`, so the leading groups of an item can be unwrapped without collecting the rest of the input again.
+///
+/// [`Parse::parse_items`]: enum.Parse.html#method.parse_items
+struct ItemTokens {
+    front: Vec<TokenTree>,
+    rest: token_stream::IntoIter,
+}
+
+impl Iterator for ItemTokens {
+    type Item = TokenTree;
+
+    fn next(&mut self) -> Option<TokenTree> {
+        self.front.pop().or_else(|| self.rest.next())
+    }
+}
+
+/// Skip the remainder of an item that is not a struct or enum, and return the error that describes the item. The first ident of the item, `keyword`, has already been consumed.
+///
+/// Items end with either a `;` (e.g. `use`, `const`, `mod foo;`) or a `{ ... }` block (e.g. `fn`, `impl`, `extern "C"`).
+fn skip_item(keyword: Ident, source: &mut Peekable<impl Iterator<Item = TokenTree>>) -> Error {
+    // The value of a `const` or `static` can contain blocks, so read until the `;`. A `const fn` ends with a block.
+    let is_fn = match source.peek() {
+        Some(TokenTree::Ident(ident)) => {
+            matches!(
+                ident.to_string().as_str(),
+                "fn" | "unsafe" | "async" | "extern"
+            )
+        }
+        _ => false,
+    };
+    let is_value = matches!(keyword.to_string().as_str(), "const" | "static") && !is_fn;
+    // this consumes modifiers like `unsafe` and `extern "C"`, but never a `;` or a block
+    let error = DataType::unsupported(keyword, source);
+    for token in source.by_ref() {
+        match token {
+            TokenTree::Punct(p) if p.as_char() == ';' => break,
+            TokenTree::Group(g) if g.delimiter() == Delimiter::Brace && !is_value => {
+                consume_punct_if(source, ';');
+                break;
+            }
+            _ => {}
+        }
+    }
+    error
+}
+
+/// A parsed struct or enum that can be shared between multiple derive implementations. This can be created with [`Parse::new_shared`].
+///
+/// [`Parse::into_generator`] consumes the parsed input, so a macro that implements multiple traits would have to parse the input once for every trait. A `ParsedItem` can instead create any amount of [`Generator`]s, while the attributes and body are only borrowed.
+///
+/// ```ignore
+/// use virtue::prelude::*;
+/// use virtue::parse::ParsedItem;
+///
+/// #[proc_macro_derive(Codec)]
+/// pub fn derive_codec(input: TokenStream) -> TokenStream {
+///     derive_codec_inner(input).unwrap_or_else(|e| e.into_token_stream())
+/// }
+///
+/// fn derive_codec_inner(input: TokenStream) -> Result<TokenStream> {
+///     let item = Parse::new_shared(input)?;
+///     let mut stream = derive_encode(&item)?;
+///     stream.extend(derive_decode(&item)?);
+///     Ok(stream)
+/// }
+///
+/// fn derive_encode(item: &ParsedItem) -> Result<TokenStream> {
+///     let mut generator = item.generator();
+///     // use `item.attributes()` and `item.body()` to generate the implementation
+///     generator.finish()
+/// }
+/// # fn derive_decode(item: &ParsedItem) -> Result<TokenStream> { item.generator().finish() }
+/// ```
+#[derive(Debug)]
+pub struct ParsedItem {
+    attributes: Vec<Attribute>,
+    visibility: Visibility,
+    name: Ident,
+    generics: Option<Generics>,
+    generic_constraints: Option<GenericConstraints>,
+    body: Body,
+}
+
+impl ParsedItem {
+    /// Create a new [`Generator`] for this struct or enum. Only the name and the generics are cloned.
+    pub fn generator(&self) -> Generator {
+        Generator::new(
+            self.name.clone(),
+            self.generics.clone(),
+            self.generic_constraints.clone(),
+        )
+        .with_target_visibility(self.visibility.clone())
+    }
+
+    /// The attributes of the struct or enum
+    pub fn attributes(&self) -> &[Attribute] {
+        &self.attributes
+    }
+
+    /// The visibility of the struct or enum
+    pub fn visibility(&self) -> &Visibility {
+        &self.visibility
+    }
+
+    /// The name of the struct or enum
+    pub fn name(&self) -> &Ident {
+        &self.name
+    }
+
+    /// The generics of the struct or enum
+    pub fn generics(&self) -> Option<&Generics> {
+        self.generics.as_ref()
+    }
+
+    /// The generic constraints of the struct or enum
+    pub fn generic_constraints(&self) -> Option<&GenericConstraints> {
+        self.generic_constraints.as_ref()
+    }
+
+    /// The body of the struct or enum
+    pub fn body(&self) -> &Body {
+        &self.body
+    }
+}
+
+impl From<Parse> for ParsedItem {
+    fn from(parse: Parse) -> Self {
+        match parse {
+            Parse::Struct {
+                attributes,
+                visibility,
+                name,
+                generics,
+                generic_constraints,
+                body,
+            } => Self {
+                attributes,
+                visibility,
+                name,
+                generics,
+                generic_constraints,
+                body: Body::Struct(body),
+            },
+            Parse::Enum {
+                attributes,
+                visibility,
+                name,
+                generics,
+                generic_constraints,
+                body,
+            } => Self {
+                attributes,
+                visibility,
+                name,
+                generics,
+                generic_constraints,
+                body: Body::Enum(body),
+            },
+        }
+    }
+}
+
 /// The body of the enum or struct
 #[allow(missing_docs)]
+#[derive(Debug)]
 pub enum Body {
     Struct(StructBody),
     Enum(EnumBody),
 }
+
+impl Body {
+    /// The amount of attributes on the fields and enum variants of this body. The attributes of the struct or enum itself are not included.
+    ///
+    /// Attributes are stored with the field or variant they belong to, so this is the amount of attributes that is kept in memory while generating code. This can be used to check that a derive does not copy more attributes than needed, e.g. in a benchmark.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = TokenStream::from_str(
+    ///     "#[derive(Foo)] enum Foo { #[a] A { #[b] #[c] a: u8 }, B(#[d] u8, u16) }"
+    /// ).unwrap();
+    /// let (_, _, body) = Parse::new(input)?.into_generator();
+    /// assert_eq!(body.attribute_count(), 4);
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn attribute_count(&self) -> usize {
+        let field_attributes = |fields: Option<&Fields>| -> usize {
+            fields
+                .iter()
+                .flat_map(|fields| fields.iter())
+                .map(|(_, field)| field.attributes.len())
+                .sum()
+        };
+        match self {
+            Body::Struct(body) => field_attributes(body.fields.as_ref()),
+            Body::Enum(body) => body
+                .variants
+                .iter()
+                .map(|variant| variant.attributes.len() + field_attributes(variant.fields.as_ref()))
+                .sum(),
+        }
+    }
+}
+
+#[test]
+fn test_parse_items() {
+    use std::str::FromStr;
+
+    let input = TokenStream::from_str(
+        r#"
+        use std::fmt;
+        #[derive(Debug)]
+        pub struct Foo { a: u8 }
+        extern "C" { fn bar(); }
+        const BAZ: Foo = Foo { a: 5 };
+        unsafe impl<T: Iterator<Item = u8>> Send for Foo where T: Clone {}
+        pub(crate) const fn baz() -> u8 { 5 }
+        struct Unit;
+        impl Foo { fn foo(&self) {} }
+        struct Tuple(u8, u16);
+        struct Wrapper<T>(T) where T: Clone;
+        macro_rules! m { () => {} }
+        enum Bar { A, B }
+        "#,
+    )
+    .unwrap();
+    let items = Parse::parse_items(input).unwrap();
+    let names: Vec<String> = items
+        .iter()
+        .map(|item| match item {
+            Ok(Parse::Struct { name, .. } | Parse::Enum { name, .. }) => name.to_string(),
+            Err(Error::UnsupportedItem { kind, .. }) => format!("<{}>", kind),
+            Err(error) => {
+                assert!(error.is_unknown_data_type(), "{:?}", error);
+                "<unknown>".to_string()
+            }
+        })
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "<unknown>",
+            "Foo",
+            "<unknown>",
+            "<unknown>",
+            "<impl>",
+            "<fn>",
+            "Unit",
+            "<impl>",
+            "Tuple",
+            "Wrapper",
+            "<unknown>",
+            "Bar"
+        ]
+    );
+}
+
+#[test]
+fn test_tuple_struct_where_clause() {
+    use std::str::FromStr;
+
+    for input in [
+        "struct Wrapper<T>(pub T) where T: Clone;",
+        "struct Wrapper<T>(pub T) where T: Clone",
+        "struct Wrapper<T>(pub T,) where T: Clone, ;",
+        "struct Wrapper<F>(F) where F: Fn(u8) -> u8;",
+    ] {
+        match Parse::new(TokenStream::from_str(input).unwrap()).unwrap() {
+            Parse::Struct {
+                generic_constraints: Some(constraints),
+                body:
+                    StructBody {
+                        fields: Some(Fields::Tuple(fields)),
+                    },
+                ..
+            } => {
+                assert_eq!(fields.len(), 1);
+                let where_clause = constraints.where_clause().stream.to_string();
+                assert!(
+                    where_clause.starts_with("where T : Clone")
+                        || where_clause.starts_with("where F : Fn (u8) -> u8"),
+                    "{}",
+                    where_clause
+                );
+            }
+            _ => panic!("Expected a tuple struct with constraints for {}", input),
+        }
+    }
+
+    // a where clause in front of the fields is still supported for named structs
+    let input = TokenStream::from_str("struct Foo<T> where T: Clone { t: T }").unwrap();
+    assert!(matches!(
+        Parse::new(input).unwrap(),
+        Parse::Struct {
+            generic_constraints: Some(_),
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_new_shared() {
+    use std::str::FromStr;
+
+    let input = TokenStream::from_str("#[foo] pub struct Foo<T> where T: Clone { t: T }").unwrap();
+    let item = Parse::new_shared(input).unwrap();
+    assert_eq!(item.name().to_string(), "Foo");
+    assert_eq!(item.attributes().len(), 1);
+    assert!(item.generic_constraints().is_some());
+    assert!(matches!(item.body(), Body::Struct(_)));
+
+    for trait_name in ["Encode", "Decode"] {
+        let shared = Rc::clone(&item);
+        let mut generator = shared.generator();
+        let _ = generator.impl_for(trait_name);
+        assert_eq!(
+            generator.finish().unwrap().to_string(),
+            format!(
+                "impl < T > {} for Foo < T > where T : Clone {{ }}",
+                trait_name
+            )
+        );
+    }
+}
+
+#[test]
+fn test_attributes_in_none_groups() {
+    use std::str::FromStr;
+
+    let none_group = |input: &str| -> TokenTree {
+        Group::new(Delimiter::None, TokenStream::from_str(input).unwrap()).into()
+    };
+    let attribute_names = |parse: &Parse| -> Vec<String> {
+        match parse {
+            Parse::Struct { attributes, .. } | Parse::Enum { attributes, .. } => attributes
+                .iter()
+                .map(|attr| attr.tokens.stream().to_string())
+                .collect(),
+        }
+    };
+
+    // `#[derive(Foo)] $item`, with doc comments inside of the `$item:item` group
+    let input: TokenStream = TokenStream::from_str("#[derive(Foo)]")
+        .unwrap()
+        .into_iter()
+        .chain([none_group("/// docs\n #[repr(C)] pub struct Foo { a: u8 }")])
+        .collect();
+    let parse = Parse::new(input).unwrap();
+    assert_eq!(
+        attribute_names(&parse),
+        ["derive (Foo)", "doc = \" docs\"", "repr (C)"]
+    );
+    assert_eq!(parse.visibility(), &Visibility::Pub);
+
+    // a group around the attributes only, nested in another group, followed by a `$vis` group
+    let attributes = Group::new(
+        Delimiter::None,
+        [none_group("#[a]"), none_group("#[b] #[c]")]
+            .into_iter()
+            .collect(),
+    );
+    let input: TokenStream = [TokenTree::from(attributes), none_group("pub(crate)")]
+        .into_iter()
+        .chain(TokenStream::from_str("enum Bar { A }").unwrap())
+        .collect();
+    let parse = Parse::new(input).unwrap();
+    assert_eq!(attribute_names(&parse), ["a", "b", "c"]);
+    assert!(matches!(parse.visibility(), Visibility::Restricted(_)));
+
+    // `$($item)*`
+    let input: TokenStream = [
+        none_group("#[a] struct Foo;"),
+        none_group("fn foo() {}"),
+        none_group("#[b] enum Bar { A }"),
+    ]
+    .into_iter()
+    .collect();
+    let items = Parse::parse_items(input).unwrap();
+    assert_eq!(items.len(), 3);
+    assert_eq!(attribute_names(items[0].as_ref().unwrap()), ["a"]);
+    assert!(matches!(
+        items[1],
+        Err(Error::UnsupportedItem {
+            kind: crate::ItemKind::Fn,
+            ..
+        })
+    ));
+    assert_eq!(attribute_names(items[2].as_ref().unwrap()), ["b"]);
+
+    // groups and plain tokens mixed, the tokens after the groups are not unwrapped
+    let input: TokenStream = [none_group("#[a]"), none_group("struct Foo")]
+        .into_iter()
+        .chain(TokenStream::from_str("{ b: u8 } #[c] struct Bar(u8); enum Baz { A }").unwrap())
+        .collect();
+    let items = Parse::parse_items(input).unwrap();
+    assert_eq!(items.len(), 3);
+    assert_eq!(attribute_names(items[0].as_ref().unwrap()), ["a"]);
+    assert_eq!(attribute_names(items[1].as_ref().unwrap()), ["c"]);
+    assert!(attribute_names(items[2].as_ref().unwrap()).is_empty());
+}
+
+#[test]
+fn test_broken_input() {
+    use crate::prelude::{Punct, Spacing};
+    use std::str::FromStr;
+
+    // a `'` that is not part of a lifetime can not be lexed from a string, but a macro can generate it
+    let with_tick = |before: &str, after: &str| -> TokenStream {
+        let tick = TokenTree::from(Punct::new('\'', Spacing::Joint));
+        TokenStream::from_str(before)
+            .unwrap()
+            .into_iter()
+            .chain([tick])
+            .chain(TokenStream::from_str(after).unwrap())
+            .collect()
+    };
+    let inputs = [
+        TokenStream::from_str("struct Foo<const 5> { }").unwrap(),
+        TokenStream::from_str("struct Foo<const> { }").unwrap(),
+        with_tick("struct Foo<", "5> { }"),
+        with_tick("struct Foo<", ""),
+        with_tick("struct Foo<T> where T:", "> { }"),
+        with_tick("pub", "(crate) struct Foo;"),
+        TokenStream::from_str("pub(crate) struct").unwrap(),
+        TokenStream::from_str("enum Foo { A = }").unwrap(),
+    ];
+    for input in inputs {
+        let error = match Parse::new(input.clone()) {
+            Ok(_) => panic!("`{}` should not parse", input),
+            Err(error) => error,
+        };
+        assert!(
+            error
+                .into_token_stream()
+                .to_string()
+                .starts_with("compile_error !"),
+            "`{}` should be a compile_error",
+            input
+        );
+    }
+}
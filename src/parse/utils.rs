@@ -1,33 +1,66 @@
+//! Low-level helpers for working with token streams.
+
 use crate::error::Error;
-use crate::prelude::{Delimiter, Group, Ident, Punct, TokenTree};
-use std::iter::Peekable;
+use crate::prelude::{Delimiter, Group, Ident, Punct, TokenStream, TokenTree};
+use std::iter::{Peekable, Take};
 
-pub fn assume_group(t: Option<TokenTree>) -> Group {
+/// Unwrap a token that is expected to be a [`Group`], e.g. after peeking at it.
+///
+/// Returns an [`Error::InvalidRustSyntax`] if `t` is not a group, so unexpected input never panics.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// # use std::str::FromStr;
+/// use virtue::parse::utils::{assume_group, assume_ident, assume_punct};
+///
+/// let mut input = TokenStream::from_str("foo = (a, b)").unwrap().into_iter().peekable();
+/// if let Some(TokenTree::Ident(_)) = input.peek() {
+///     let key = assume_ident(input.next())?;
+///     assert_eq!(key.to_string(), "foo");
+/// }
+/// assume_punct(input.next(), '=')?;
+/// assert_eq!(assume_group(input.next())?.delimiter(), Delimiter::Parenthesis);
+/// assert!(assume_group(input.next()).is_err());
+/// # Ok::<_, virtue::Error>(())
+/// ```
+///
+/// [`Error::InvalidRustSyntax`]: ../../enum.Error.html#variant.InvalidRustSyntax
+pub fn assume_group(t: Option<TokenTree>) -> Result<Group, Error> {
     match t {
-        Some(TokenTree::Group(group)) => group,
-        _ => unreachable!(),
+        Some(TokenTree::Group(group)) => Ok(group),
+        t => Error::wrong_token(t.as_ref(), "group"),
     }
 }
-pub fn assume_ident(t: Option<TokenTree>) -> Ident {
+/// Unwrap a token that is expected to be an [`Ident`], e.g. after peeking at it.
+///
+/// Returns an [`Error::InvalidRustSyntax`] if `t` is not an ident, so unexpected input never panics.
+///
+/// [`Error::InvalidRustSyntax`]: ../../enum.Error.html#variant.InvalidRustSyntax
+/// See [`assume_group`] for an example.
+pub fn assume_ident(t: Option<TokenTree>) -> Result<Ident, Error> {
     match t {
-        Some(TokenTree::Ident(ident)) => ident,
-        _ => unreachable!(),
+        Some(TokenTree::Ident(ident)) => Ok(ident),
+        t => Error::wrong_token(t.as_ref(), "ident"),
     }
 }
-pub fn assume_punct(t: Option<TokenTree>, punct: char) -> Punct {
+/// Unwrap a token that is expected to be the [`Punct`] `punct`, e.g. after peeking at it.
+///
+/// Returns an [`Error::InvalidRustSyntax`] if `t` is not the punct `punct`, so unexpected input never panics.
+///
+/// [`Error::InvalidRustSyntax`]: ../../enum.Error.html#variant.InvalidRustSyntax
+/// See [`assume_group`] for an example.
+pub fn assume_punct(t: Option<TokenTree>, punct: char) -> Result<Punct, Error> {
     match t {
-        Some(TokenTree::Punct(p)) => {
-            debug_assert_eq!(punct, p.as_char());
-            p
-        }
-        _ => unreachable!(),
+        Some(TokenTree::Punct(p)) if p.as_char() == punct => Ok(p),
+        t => Error::wrong_token(t.as_ref(), &format!("'{}'", punct)),
     }
 }
 
+/// Take the next token of `input` if it is an [`Ident`]. A group without delimiters that only contains an ident, e.g. an ident that is passed on by a `macro_rules!`, is unwrapped. Groups with delimiters like `[c]` are left alone.
 pub fn consume_ident(input: &mut Peekable<impl Iterator<Item = TokenTree>>) -> Option<Ident> {
     match input.peek() {
-        Some(TokenTree::Ident(_)) => Some(super::utils::assume_ident(input.next())),
-        Some(TokenTree::Group(group)) => {
+        Some(TokenTree::Ident(_)) => super::utils::assume_ident(input.next()).ok(),
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::None => {
             // When calling from a macro_rules!, sometimes an ident is defined as :
             // Group { delimiter: None, stream: TokenStream [Ident] }
             let mut stream = group.stream().into_iter();
@@ -43,40 +76,90 @@ pub fn consume_ident(input: &mut Peekable<impl Iterator<Item = TokenTree>>) -> O
     }
 }
 
+/// Take the next token of `input` if it is the [`Punct`] `punct`.
 pub fn consume_punct_if(
     input: &mut Peekable<impl Iterator<Item = TokenTree>>,
     punct: char,
 ) -> Option<Punct> {
-    if let Some(TokenTree::Punct(p)) = input.peek() {
-        if p.as_char() == punct {
-            match input.next() {
-                Some(TokenTree::Punct(p)) => return Some(p),
-                _ => unreachable!(),
+    match input.peek() {
+        Some(TokenTree::Punct(p)) if p.as_char() == punct => {}
+        _ => return None,
+    }
+    match input.next() {
+        Some(TokenTree::Punct(p)) => Some(p),
+        _ => None,
+    }
+}
+
+/// Replace every group without delimiters in the given stream with its contents, recursively.
+///
+/// When a `macro_rules!` passes e.g. a `$vis:vis`, `$ty:ty` or `$expr:expr` fragment on to a derive macro, the fragment is wrapped in a [`Group`] with [`Delimiter::None`]. [`Parse`] handles these groups, but a derive that inspects the tokens of e.g. a field type itself can call this first so it only has to deal with the plain tokens.
+///
+/// Note that the group of an `$expr` fragment keeps the precedence of the expression, e.g. `$a * 2` where `$a` is `1 + 1`. Unwrapping it makes the expression read as `1 + 1 * 2`.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// use virtue::parse::utils::unwrap_none_groups;
+///
+/// let ty = TokenStream::from(TokenTree::Group(Group::new(
+///     Delimiter::None,
+///     "Vec<u8>".parse().unwrap(),
+/// )));
+/// let unwrapped = unwrap_none_groups(ty);
+/// assert!(matches!(unwrapped.into_iter().next(), Some(TokenTree::Ident(i)) if i.to_string() == "Vec"));
+/// ```
+///
+/// [`Parse`]: ../enum.Parse.html
+pub fn unwrap_none_groups(stream: TokenStream) -> TokenStream {
+    let mut result = Vec::new();
+    for token in stream {
+        match token {
+            TokenTree::Group(group) if group.delimiter() == Delimiter::None => {
+                result.extend(unwrap_none_groups(group.stream()));
+            }
+            TokenTree::Group(group) => {
+                let mut new_group =
+                    Group::new(group.delimiter(), unwrap_none_groups(group.stream()));
+                new_group.set_span(group.span());
+                result.push(TokenTree::Group(new_group));
             }
+            token => result.push(token),
         }
     }
-    None
+    result.into_iter().collect()
 }
 
 #[cfg(any(test, feature = "proc-macro2"))]
-pub fn ident_eq(ident: &Ident, text: &str) -> bool {
+pub(crate) fn ident_eq(ident: &Ident, text: &str) -> bool {
     ident == text
 }
 
 #[cfg(not(any(test, feature = "proc-macro2")))]
-pub fn ident_eq(ident: &Ident, text: &str) -> bool {
+pub(crate) fn ident_eq(ident: &Ident, text: &str) -> bool {
     ident.to_string() == text
 }
 
-fn check_if_arrow(tokens: &[TokenTree], punct: &Punct) -> bool {
-    if punct.as_char() == '>' {
-        if let Some(TokenTree::Punct(previous_punct)) = tokens.last() {
-            if previous_punct.as_char() == '-' {
-                return true;
-            }
-        }
+/// Returns `true` if `tokens[idx]` is a `:` that is not part of a `::`.
+pub(crate) fn is_single_colon(tokens: &[TokenTree], idx: usize) -> bool {
+    let is_colon =
+        |idx: usize| matches!(tokens.get(idx), Some(TokenTree::Punct(p)) if p.as_char() == ':');
+    is_colon(idx) && !is_colon(idx + 1) && (idx == 0 || !is_colon(idx - 1))
+}
+
+/// Returns `true` if `punct` is the `>` of a `->`, where `previous` is the token before it.
+pub(crate) fn is_arrow(previous: Option<&TokenTree>, punct: &Punct) -> bool {
+    punct.as_char() == '>' && matches!(previous, Some(TokenTree::Punct(p)) if p.as_char() == '-')
+}
+
+/// Returns `true` if a `(` after `previous` can start the fields of a tuple struct instead of being part of a where clause.
+///
+/// After an ident the parentheses are the arguments of a `Fn` bound, e.g. `F: Fn(A) -> B`, and after `:`, `+`, `,`, `->` and other puncts they are a type, e.g. `(A, B): Trait` or `F: Fn() -> (A, B)`. Only a predicate that ends with a group or with generic arguments, e.g. `T: Fn(A)` or `T: Into<u8>`, can be followed by the fields. The `>` of a `->` is checked by the caller.
+fn ends_predicate(previous: Option<&TokenTree>) -> bool {
+    match previous {
+        Some(TokenTree::Group(_)) => true,
+        Some(TokenTree::Punct(p)) => p.as_char() == '>',
+        _ => false,
     }
-    false
 }
 
 const OPEN_BRACKETS: &[char] = &['<', '(', '[', '{'];
@@ -88,33 +171,49 @@ const BRACKET_DELIMITER: &[Option<Delimiter>] = &[
     Some(Delimiter::Brace),
 ];
 
-pub fn read_tokens_until_punct(
-    input: &mut Peekable<impl Iterator<Item = TokenTree>>,
-    expected_puncts: &[char],
-) -> Result<Vec<TokenTree>, Error> {
-    let mut result = Vec::new();
-    let mut open_brackets = Vec::<char>::new();
-    'outer: loop {
-        match input.peek() {
-            Some(TokenTree::Punct(punct)) => {
-                if check_if_arrow(&result, punct) {
+/// Keeps track of the `<`, `(`, `[` and `{` puncts that are currently open while scanning for one of the `expected_puncts`.
+struct BracketTracker<'a> {
+    expected_puncts: &'a [char],
+    open_brackets: Vec<char>,
+    // `true` if the previous token was the `>` of a `->`
+    after_arrow: bool,
+}
+
+impl<'a> BracketTracker<'a> {
+    fn new(expected_puncts: &'a [char]) -> Self {
+        Self {
+            expected_puncts,
+            open_brackets: Vec::new(),
+            after_arrow: false,
+        }
+    }
+
+    /// Returns `true` if `token` ends the scan, `false` if it is part of the tokens that are being read.
+    fn is_end(&mut self, previous: Option<&TokenTree>, token: &TokenTree) -> Result<bool, Error> {
+        let after_arrow = std::mem::replace(
+            &mut self.after_arrow,
+            matches!(token, TokenTree::Punct(punct) if is_arrow(previous, punct)),
+        );
+        match token {
+            TokenTree::Punct(punct) => {
+                if is_arrow(previous, punct) {
                     // do nothing
                 } else if OPEN_BRACKETS.contains(&punct.as_char()) {
-                    open_brackets.push(punct.as_char());
+                    self.open_brackets.push(punct.as_char());
                 } else if let Some(index) =
                     CLOSING_BRACKETS.iter().position(|c| c == &punct.as_char())
                 {
-                    let last_bracket = match open_brackets.pop() {
+                    let last_bracket = match self.open_brackets.pop() {
                         Some(bracket) => bracket,
                         None => {
-                            if expected_puncts.contains(&punct.as_char()) {
-                                break;
+                            if self.expected_puncts.contains(&punct.as_char()) {
+                                return Ok(true);
                             }
                             return Err(Error::InvalidRustSyntax {
                                 span: punct.span(),
                                 expected: format!(
                                     "one of {:?}, got '{}'",
-                                    expected_puncts,
+                                    self.expected_puncts,
                                     punct.as_char()
                                 ),
                             });
@@ -128,29 +227,186 @@ pub fn read_tokens_until_punct(
                         punct.as_char(),
                         expected
                     );
-                } else if expected_puncts.contains(&punct.as_char()) && open_brackets.is_empty() {
-                    break;
+                } else if self.expected_puncts.contains(&punct.as_char())
+                    && self.open_brackets.is_empty()
+                {
+                    return Ok(true);
                 }
-                result.push(input.next().unwrap());
+                Ok(false)
             }
-            Some(TokenTree::Group(g)) if open_brackets.is_empty() => {
-                for punct in expected_puncts {
+            TokenTree::Group(g) if self.open_brackets.is_empty() => {
+                if g.delimiter() == Delimiter::Parenthesis
+                    && (after_arrow || !ends_predicate(previous))
+                {
+                    return Ok(false);
+                }
+                for punct in self.expected_puncts {
                     if let Some(idx) = OPEN_BRACKETS.iter().position(|c| c == punct) {
                         if let Some(delim) = BRACKET_DELIMITER[idx] {
                             if delim == g.delimiter() {
                                 // we need to split on this delimiter
-                                break 'outer;
+                                return Ok(true);
                             }
                         }
                     }
                 }
-                result.push(input.next().unwrap());
-            }
-            Some(_) => result.push(input.next().unwrap()),
-            None => {
-                break;
+                Ok(false)
             }
+            _ => Ok(false),
         }
     }
+}
+
+/// Take all tokens of `input` up to, but not including, the first of `expected_puncts` that is not nested in brackets. Takes all remaining tokens if none of `expected_puncts` is found.
+///
+/// `<`, `(`, `[` and `{` count as brackets, so reading until `,` in `Vec<(u8, u16)>, u32` returns `Vec<(u8, u16)>`. The `>` of an arrow like `->` is not a bracket.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// # use std::str::FromStr;
+/// use virtue::parse::utils::read_tokens_until_punct;
+///
+/// let input = &mut TokenStream::from_str("Vec<(u8, u16)>, u32").unwrap().into_iter().peekable();
+/// let ty: TokenStream = read_tokens_until_punct(input, &[','])?.into_iter().collect();
+/// assert_eq!(ty.to_string(), "Vec < (u8 , u16) >");
+/// # Ok::<_, virtue::Error>(())
+/// ```
+///
+/// Returns an error if a closing `>` is found without an opening `<` and it is not one of `expected_puncts`.
+pub fn read_tokens_until_punct(
+    input: &mut Peekable<impl Iterator<Item = TokenTree>>,
+    expected_puncts: &[char],
+) -> Result<Vec<TokenTree>, Error> {
+    let mut result = Vec::new();
+    let mut tracker = BracketTracker::new(expected_puncts);
+    while let Some(token) = input.peek() {
+        if tracker.is_end(result.last(), token)? {
+            break;
+        }
+        result.push(input.next().unwrap());
+    }
     Ok(result)
 }
+
+/// Check that every `<` in `tokens` is closed by a `>`, and that there is no `>` without a `<`. Parenthesized and bracketed groups are checked as well.
+pub(crate) fn check_angle_brackets(tokens: &[TokenTree]) -> Result<(), Error> {
+    let mut open = Vec::new();
+    for (idx, token) in tokens.iter().enumerate() {
+        match token {
+            TokenTree::Punct(p) if p.as_char() == '<' => open.push(p.span()),
+            TokenTree::Punct(p) if p.as_char() == '>' => {
+                let previous = idx.checked_sub(1).and_then(|idx| tokens.get(idx));
+                if !is_arrow(previous, p) && open.pop().is_none() {
+                    return Err(Error::custom_at("Unexpected `>`", p.span()));
+                }
+            }
+            // Braces contain const expressions, which may contain comparisons
+            TokenTree::Group(g) if g.delimiter() != Delimiter::Brace => {
+                let inner: Vec<TokenTree> = g.stream().into_iter().collect();
+                check_angle_brackets(&inner)?;
+            }
+            _ => {}
+        }
+    }
+    match open.pop() {
+        Some(span) => Err(Error::custom_at("Unclosed `<`", span)),
+        None => Ok(()),
+    }
+}
+
+/// Slice-based counterpart of [`read_tokens_until_punct`]. Returns the amount of tokens at the start of `tokens` that would be read, without cloning or allocating any of them.
+pub(crate) fn count_tokens_until_punct(
+    tokens: &[TokenTree],
+    expected_puncts: &[char],
+) -> Result<usize, Error> {
+    let mut tracker = BracketTracker::new(expected_puncts);
+    for (idx, token) in tokens.iter().enumerate() {
+        let previous = if idx == 0 { None } else { tokens.get(idx - 1) };
+        if tracker.is_end(previous, token)? {
+            return Ok(idx);
+        }
+    }
+    Ok(tokens.len())
+}
+
+/// Split `tokens` on every `separator` that is not inside of any brackets and call `f` with each part, e.g. for every field of a struct when splitting on `,`.
+///
+/// The separators are skipped, as is a trailing separator. The bracket matching is done on the slice up front, so every part is handed out by moving the tokens out of `tokens` without cloning them or allocating per part. Any tokens of a part that `f` did not consume are skipped.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// # use std::str::FromStr;
+/// use virtue::parse::utils::for_each_split_on_punct;
+///
+/// let tokens = TokenStream::from_str("a: u8, b: Vec<(u8, u16)>,").unwrap();
+/// let mut fields = Vec::new();
+/// for_each_split_on_punct(tokens.into_iter().collect(), ',', |field| {
+///     fields.push(field.collect::<TokenStream>().to_string());
+///     Ok(())
+/// })?;
+/// assert_eq!(fields, ["a : u8", "b : Vec < (u8 , u16) >"]);
+/// # Ok::<_, virtue::Error>(())
+/// ```
+///
+/// Returns an error if a part is empty, e.g. `a, , b`, or if the brackets in `tokens` do not match.
+pub fn for_each_split_on_punct(
+    tokens: Vec<TokenTree>,
+    separator: char,
+    mut f: impl FnMut(&mut Peekable<Take<&mut std::vec::IntoIter<TokenTree>>>) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut lengths = Vec::new();
+    let mut position = 0;
+    while position < tokens.len() {
+        let remaining = &tokens[position..];
+        let length = count_tokens_until_punct(remaining, &[separator])?;
+        match remaining.get(length) {
+            None => {}
+            Some(TokenTree::Punct(p)) if p.as_char() == separator && length > 0 => {}
+            Some(token) => {
+                return Err(Error::InvalidRustSyntax {
+                    span: token.span(),
+                    expected: format!("tokens before '{}'", separator),
+                })
+            }
+        }
+        lengths.push(length);
+        position += length + 1;
+    }
+
+    let mut tokens = tokens.into_iter();
+    for length in lengths {
+        let mut part = tokens.by_ref().take(length).peekable();
+        f(&mut part)?;
+        part.for_each(drop);
+        // skip the separator
+        let _ = tokens.next();
+    }
+    Ok(())
+}
+
+#[test]
+fn test_for_each_split_on_punct() {
+    let tokens: Vec<TokenTree> =
+        crate::token_stream("a: u8, b: Vec<(u8, u16)>, c: fn(u8) -> Result<(), ()>, d: [u8; 4],")
+            .collect();
+    assert_eq!(count_tokens_until_punct(&tokens, &[',']).unwrap(), 3);
+
+    let mut parts = Vec::<String>::new();
+    for_each_split_on_punct(tokens, ',', |part| {
+        parts.push(part.map(|t| t.to_string()).collect());
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(
+        parts,
+        [
+            "a:u8",
+            "b:Vec<(u8 , u16)>",
+            "c:fn(u8)->Result<(),()>",
+            "d:[u8 ; 4]"
+        ]
+    );
+
+    let tokens: Vec<TokenTree> = crate::token_stream("a: u8,, b: u8").collect();
+    assert!(for_each_split_on_punct(tokens, ',', |_| Ok(())).is_err());
+}
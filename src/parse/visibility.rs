@@ -1,61 +1,127 @@
 use super::utils::*;
-use crate::prelude::{Delimiter, TokenTree};
+use crate::generate::StreamBuilder;
+use crate::prelude::{Delimiter, Group, TokenTree};
 use crate::Result;
 use std::iter::Peekable;
 
 /// The visibility of a struct, enum, field, etc
-#[derive(Debug, PartialEq, Eq, Clone)]
+///
+/// This enum is `#[non_exhaustive]` so that new kinds of visibility can be added without a breaking change.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum Visibility {
     /// Default visibility. Most items are private by default.
     Default,
 
     /// Public visibility
     Pub,
+
+    /// Restricted public visibility, e.g. `pub(crate)`, `pub(super)`, `pub(self)` or `pub(in some::path)`.
+    ///
+    /// The group is the parenthesized restriction, e.g. `(crate)`.
+    Restricted(Group),
+}
+
+impl PartialEq for Visibility {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Visibility::Default, Visibility::Default) | (Visibility::Pub, Visibility::Pub) => true,
+            (Visibility::Restricted(a), Visibility::Restricted(b)) => {
+                a.stream().to_string() == b.stream().to_string()
+            }
+            _ => false,
+        }
+    }
 }
 
+impl Eq for Visibility {}
+
 impl Visibility {
+    /// Returns `true` if this visibility is either `pub` or a restricted `pub(...)`.
+    pub fn is_pub(&self) -> bool {
+        !matches!(self, Visibility::Default)
+    }
+
+    /// Append the tokens of this visibility to the given builder, e.g. `pub(crate)`. Nothing is appended for [`Visibility::Default`].
+    pub(crate) fn append_to(&self, builder: &mut StreamBuilder) {
+        match self {
+            Visibility::Default => {}
+            Visibility::Pub => {
+                builder.ident_str("pub");
+            }
+            Visibility::Restricted(group) => {
+                builder.ident_str("pub");
+                builder.stream.extend([TokenTree::Group(group.clone())]);
+            }
+        }
+    }
+
+    /// Take the `(crate)`, `(self)`, `(super)` or `(in ...)` restriction after a `pub` from the input.
+    ///
+    /// Any other group is left alone, like rustc does, because it can be the type of a tuple field, e.g. `struct A(pub (crate::Foo));`.
+    fn try_take_restriction(
+        input: &mut Peekable<impl Iterator<Item = TokenTree>>,
+    ) -> Result<Option<Group>> {
+        if let Some(TokenTree::Group(g)) = input.peek() {
+            if g.delimiter() == Delimiter::Parenthesis && Self::is_restriction(g) {
+                return Ok(Some(assume_group(input.next())?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns `true` if `group` is exactly `(crate)`, `(self)`, `(super)` or `(in <path>)`
+    fn is_restriction(group: &Group) -> bool {
+        let mut iter = group.stream().into_iter();
+        match (iter.next(), iter.next()) {
+            (Some(TokenTree::Ident(i)), None) => {
+                matches!(i.to_string().as_str(), "crate" | "self" | "super")
+            }
+            (Some(TokenTree::Ident(i)), Some(_)) => ident_eq(&i, "in"),
+            _ => false,
+        }
+    }
+
     pub(crate) fn try_take(input: &mut Peekable<impl Iterator<Item = TokenTree>>) -> Result<Self> {
         match input.peek() {
             Some(TokenTree::Ident(ident)) if ident_eq(ident, "pub") => {
                 // Consume this token
-                assume_ident(input.next());
+                assume_ident(input.next())?;
 
                 // check if the next token is `pub(...)`
-                if let Some(TokenTree::Group(g)) = input.peek() {
-                    if g.delimiter() == Delimiter::Parenthesis {
-                        // check if this is one of:
-                        // - pub ( crate )
-                        // - pub ( self )
-                        // - pub ( super )
-                        // - pub ( in ... )
-                        if let Some(TokenTree::Ident(i)) = g.stream().into_iter().next() {
-                            if matches!(i.to_string().as_str(), "crate" | "self" | "super" | "in") {
-                                // it is, ignore this token
-                                assume_group(input.next());
-                            }
-                        }
-                    }
+                match Self::try_take_restriction(input)? {
+                    Some(group) => Ok(Visibility::Restricted(group)),
+                    None => Ok(Visibility::Pub),
                 }
-
-                Ok(Visibility::Pub)
             }
             Some(TokenTree::Group(group)) => {
                 // sometimes this is a group instead of an ident
                 // e.g. when used in `bitflags! {}`
-                let mut iter = group.stream().into_iter();
-                match (iter.next(), iter.next()) {
-                    (Some(TokenTree::Ident(ident)), None) if ident_eq(&ident, "pub") => {
-                        // Consume this token
-                        assume_group(input.next());
-
-                        // check if the next token is `pub(...)`
-                        if let Some(TokenTree::Group(_)) = input.peek() {
-                            // we just consume the visibility, we're not actually using it for generation
-                            assume_group(input.next());
-                        }
-                        Ok(Visibility::Pub)
-                    }
-                    _ => Ok(Visibility::Default),
+                let mut iter = group.stream().into_iter().peekable();
+                if group.delimiter() == Delimiter::None && iter.peek().is_none() {
+                    // an empty `$vis` in a `macro_rules!`
+                    assume_group(input.next())?;
+                    return Ok(Visibility::Default);
+                }
+                if !matches!(iter.peek(), Some(TokenTree::Ident(ident)) if ident_eq(ident, "pub")) {
+                    return Ok(Visibility::Default);
+                }
+                let _ = iter.next();
+                let restriction = Self::try_take_restriction(&mut iter)?;
+                if iter.next().is_some() {
+                    return Ok(Visibility::Default);
+                }
+                // Consume this token
+                assume_group(input.next())?;
+
+                // check if the next token is `pub(...)`
+                let restriction = match restriction {
+                    Some(group) => Some(group),
+                    None => Self::try_take_restriction(input)?,
+                };
+                match restriction {
+                    Some(group) => Ok(Visibility::Restricted(group)),
+                    None => Ok(Visibility::Pub),
                 }
             }
             _ => Ok(Visibility::Default),
@@ -83,18 +149,84 @@ fn test_visibility_try_take() {
         Visibility::Pub,
         Visibility::try_take(&mut token_stream("\tpub\t")).unwrap()
     );
+    let restricted = |s: &str| {
+        let group = match token_stream(s).next() {
+            Some(TokenTree::Group(group)) => group,
+            _ => unreachable!(),
+        };
+        Visibility::Restricted(group)
+    };
     assert_eq!(
-        Visibility::Pub,
+        restricted("(crate)"),
         Visibility::try_take(&mut token_stream("pub(crate)")).unwrap()
     );
     assert_eq!(
-        Visibility::Pub,
+        restricted("(crate)"),
         Visibility::try_take(&mut token_stream(" pub ( crate ) ")).unwrap()
     );
     assert_eq!(
-        Visibility::Pub,
+        restricted("(crate)"),
         Visibility::try_take(&mut token_stream("\tpub\t(\tcrate\t)\t")).unwrap()
     );
+    assert_eq!(
+        restricted("(in crate::foo)"),
+        Visibility::try_take(&mut token_stream("pub(in crate::foo)")).unwrap()
+    );
+    assert_ne!(
+        restricted("(super)"),
+        Visibility::try_take(&mut token_stream("pub(crate)")).unwrap()
+    );
+    // `pub` followed by a tuple type, e.g. in `struct Foo(pub (u8, u16));`
+    let stream = &mut token_stream("pub (u8, u16)");
+    assert_eq!(Visibility::Pub, Visibility::try_take(stream).unwrap());
+    assert!(stream.next().is_some());
+    // only the exact forms are a restriction, other groups are parenthesized types
+    for input in [
+        "pub (crate::Foo)",
+        "pub (self::Foo)",
+        "pub (super, u8)",
+        "pub (in)",
+    ] {
+        let stream = &mut token_stream(input);
+        assert_eq!(
+            Visibility::Pub,
+            Visibility::try_take(stream).unwrap(),
+            "{}",
+            input
+        );
+        assert!(
+            matches!(stream.next(), Some(TokenTree::Group(_))),
+            "{}",
+            input
+        );
+    }
+
+    // `$vis` in a `macro_rules!` is passed as a group without a delimiter
+    let tokens = [
+        TokenTree::Group(Group::new(
+            Delimiter::None,
+            token_stream("pub(crate)").collect(),
+        )),
+        TokenTree::Ident(crate::prelude::Ident::new(
+            "struct",
+            crate::prelude::Span::call_site(),
+        )),
+    ];
+    let stream = &mut tokens.into_iter().peekable();
+    assert_eq!(restricted("(crate)"), Visibility::try_take(stream).unwrap());
+    assert!(matches!(stream.next(), Some(TokenTree::Ident(_))));
+
+    // an empty `$vis` is an empty group without a delimiter
+    let tokens = [
+        TokenTree::Group(Group::new(Delimiter::None, Default::default())),
+        TokenTree::Ident(crate::prelude::Ident::new(
+            "struct",
+            crate::prelude::Span::call_site(),
+        )),
+    ];
+    let stream = &mut tokens.into_iter().peekable();
+    assert_eq!(Visibility::Default, Visibility::try_take(stream).unwrap());
+    assert!(matches!(stream.next(), Some(TokenTree::Ident(_))));
 
     assert_eq!(
         Visibility::Default,
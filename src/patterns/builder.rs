@@ -0,0 +1,558 @@
+//! Generate a companion builder for a struct with named fields, like `#[derive(Builder)]`.
+//!
+//! For `pub struct Foo<T> { a: u32, b: T }`, [`generate_builder`] generates:
+//!
+//! ```ignore
+//! /// A builder for `Foo`
+//! pub struct FooBuilder<T> {
+//!     a: Option<u32>,
+//!     b: Option<T>,
+//!     _marker: PhantomData<(T,)>,
+//! }
+//! impl<T> FooBuilder<T> {
+//!     /// Creates a builder without any fields set
+//!     pub fn new() -> Self { .. }
+//!     /// Sets `a`
+//!     pub fn a(mut self, a: u32) -> Self { self.a = Some(a); self }
+//!     /// Sets `b`
+//!     pub fn b(mut self, b: T) -> Self { self.b = Some(b); self }
+//!     /// Builds a `Foo`, or returns an error if a required field is not set
+//!     pub fn build(self) -> Result<Foo<T>, &'static str> { .. }
+//! }
+//! impl<T> Default for FooBuilder<T> {
+//!     fn default() -> Self { Self::new() }
+//! }
+//! impl<T> Foo<T> {
+//!     /// Creates a builder for `Foo`
+//!     pub fn builder() -> FooBuilder<T> { FooBuilder::new() }
+//! }
+//! ```
+//!
+//! Which fields have to be set, the error of `build`, and the name and visibility of the builder can be configured with [`BuilderConfig`] and [`generate_builder_with`].
+
+use crate::generate::{unraw, FnSelfArg, GenStruct, Generator, Parent, StreamBuilder};
+use crate::parse::{Attribute, Fields, StructBody, UnnamedField, Visibility};
+use crate::prelude::{Delimiter, Ident, TokenStream};
+use crate::{Error, Result};
+
+/// How the builder sets a field, see [`generate_builder_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuilderField {
+    /// The field has a setter, and `build` returns an error if it is not set
+    Required,
+    /// The field has a setter, and is `Default::default()` if it is not set
+    Optional,
+    /// The field has no setter, and is always `Default::default()`
+    Skip,
+}
+
+/// The error that `build` returns if a required field is not set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BuilderError<'a> {
+    /// A `&'static str` like ``"`a` is not set"``
+    Message,
+    /// A custom error type. `constructor` is called with the name of the missing field as a `&'static str`, e.g. `ty: "MyError"` and `constructor: "MyError::missing_field"` return `MyError::missing_field("a")`.
+    Custom {
+        /// The error type, e.g. `"MyError"`
+        ty: &'a str,
+        /// The function or tuple struct that creates the error, e.g. `"MyError::missing_field"`
+        constructor: &'a str,
+    },
+}
+
+/// The builder that [`generate_builder`] generates.
+#[derive(Debug, Clone)]
+pub struct BuilderConfig<'a> {
+    /// The name of the builder. `None` is the name of the struct followed by `Builder`, e.g. `FooBuilder`.
+    pub name: Option<&'a str>,
+    /// The visibility of the builder and its methods. `None` is the visibility of the struct.
+    pub visibility: Option<Visibility>,
+    /// The error that `build` returns if a required field is not set
+    pub error: BuilderError<'a>,
+    /// Generate a `fn builder() -> FooBuilder` on the struct
+    pub builder_fn: bool,
+}
+
+impl Default for BuilderConfig<'_> {
+    /// A `FooBuilder` with the visibility of the struct, a `&'static str` error and a `Foo::builder()`
+    fn default() -> Self {
+        Self {
+            name: None,
+            visibility: None,
+            error: BuilderError::Message,
+            builder_fn: true,
+        }
+    }
+}
+
+/// The methods of the builder, which can not be the name of a field as well
+const METHODS: &[&str] = &["new", "build"];
+
+/// Generate a builder for `body`, where every field is [`BuilderField::Required`]. See the [module documentation] for the generated code.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// # use std::str::FromStr;
+/// use virtue::patterns::builder::{generate_builder, BuilderConfig};
+///
+/// let input = TokenStream::from_str("pub struct Foo { a: u32 }").unwrap();
+/// let (mut generator, _, body) = Parse::new(input)?.into_generator();
+/// # let body = match body { Body::Struct(body) => body, _ => unreachable!() };
+/// generate_builder(&mut generator, &body, BuilderConfig::default())?;
+///
+/// // pub struct FooBuilder { a: Option<u32>, _marker: PhantomData<()> }
+/// // impl FooBuilder {
+/// //     pub fn new() -> Self { Self { a: None, _marker: PhantomData } }
+/// //     pub fn a(mut self, a: u32) -> Self { self.a = Some(a); self }
+/// //     pub fn build(self) -> Result<Foo, &'static str> {
+/// //         Ok(Foo { a: match self.a { Some(value) => value, None => return Err("`a` is not set") } })
+/// //     }
+/// // }
+/// // impl Default for FooBuilder { .. }
+/// // impl Foo { pub fn builder() -> FooBuilder { FooBuilder::new() } }
+/// # generator.assert_eq("# [doc = \"A builder for `Foo`\"] pub struct FooBuilder { \
+/// #     a : :: core :: option :: Option < u32 > , _marker : :: core :: marker :: PhantomData < () > , } \
+/// #     impl FooBuilder { # [doc = \"Creates a builder without any fields set\"] pub fn new () ->Self { Self { \
+/// #     a : :: core :: option :: Option :: None , _marker : :: core :: marker :: PhantomData } } \
+/// #     # [doc = \"Sets `a`\"] pub fn a (mut self , a : u32) ->Self { self . a = :: core :: option :: Option :: Some (a) ; self } \
+/// #     # [doc = \"Builds a `Foo`, or returns an error if a required field is not set\"] \
+/// #     pub fn build (self) ->:: core :: result :: Result < Foo , &'static str > { :: core :: result :: Result :: Ok (Foo { \
+/// #     a : match self . a { :: core :: option :: Option :: Some (value) => value , \
+/// #     :: core :: option :: Option :: None => return :: core :: result :: Result :: Err (\"`a` is not set\") , } , }) } } \
+/// #     impl :: core :: default :: Default for FooBuilder { fn default () ->Self { Self :: new () } } \
+/// #     impl Foo { # [doc = \"Creates a builder for `Foo`\"] pub fn builder () ->FooBuilder { FooBuilder :: new () } }");
+/// # Ok::<_, virtue::Error>(())
+/// ```
+///
+/// [module documentation]: index.html
+pub fn generate_builder(
+    generator: &mut Generator,
+    body: &StructBody,
+    config: BuilderConfig,
+) -> Result {
+    generate_builder_with(generator, body, config, |_, _| BuilderField::Required)
+}
+
+/// Generate a builder like [`generate_builder`], where `field` decides how every field is set, e.g. based on a `#[builder(default)]` attribute.
+///
+/// The `#[cfg(...)]` attributes of a field are copied to its field in the builder, its setter and `build`.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// # use std::str::FromStr;
+/// use virtue::patterns::builder::{generate_builder_with, BuilderConfig, BuilderError, BuilderField};
+///
+/// let input = TokenStream::from_str(
+///     "struct Foo { a: u32, #[builder(default)] b: Vec<u8>, #[builder(skip)] c: bool }"
+/// ).unwrap();
+/// let (mut generator, _, body) = Parse::new(input)?.into_generator();
+/// # let body = match body { Body::Struct(body) => body, _ => unreachable!() };
+/// let config = BuilderConfig {
+///     error: BuilderError::Custom { ty: "MyError", constructor: "MyError::Missing" },
+///     builder_fn: false,
+///     ..BuilderConfig::default()
+/// };
+/// generate_builder_with(&mut generator, &body, config, |_, field| {
+///     let attribute = |name: &str| {
+///         field.attributes.iter().any(|attr| attr.tokens.stream().to_string() == format!("builder ({})", name))
+///     };
+///     if attribute("default") {
+///         BuilderField::Optional
+///     } else if attribute("skip") {
+///         BuilderField::Skip
+///     } else {
+///         BuilderField::Required
+///     }
+/// })?;
+///
+/// // struct FooBuilder { a: Option<u32>, b: Option<Vec<u8>>, _marker: PhantomData<()> }
+/// // impl FooBuilder {
+/// //     fn build(self) -> Result<Foo, MyError> {
+/// //         Ok(Foo {
+/// //             a: match self.a { Some(value) => value, None => return Err(MyError::Missing("a")) },
+/// //             b: Option::unwrap_or_default(self.b),
+/// //             c: Default::default(),
+/// //         })
+/// //     }
+/// //     ..
+/// // }
+/// # generator.assert_eq("# [doc = \"A builder for `Foo`\"] struct FooBuilder { \
+/// #     a : :: core :: option :: Option < u32 > , b : :: core :: option :: Option < Vec < u8 >> , \
+/// #     _marker : :: core :: marker :: PhantomData < () > , } \
+/// #     impl FooBuilder { # [doc = \"Creates a builder without any fields set\"] fn new () ->Self { Self { \
+/// #     a : :: core :: option :: Option :: None , b : :: core :: option :: Option :: None , \
+/// #     _marker : :: core :: marker :: PhantomData } } \
+/// #     # [doc = \"Sets `a`\"] fn a (mut self , a : u32) ->Self { self . a = :: core :: option :: Option :: Some (a) ; self } \
+/// #     # [doc = \"Sets `b`\"] fn b (mut self , b : Vec < u8 >) ->Self { self . b = :: core :: option :: Option :: Some (b) ; self } \
+/// #     # [doc = \"Builds a `Foo`, or returns an error if a required field is not set\"] \
+/// #     fn build (self) ->:: core :: result :: Result < Foo , MyError > { :: core :: result :: Result :: Ok (Foo { \
+/// #     a : match self . a { :: core :: option :: Option :: Some (value) => value , \
+/// #     :: core :: option :: Option :: None => return :: core :: result :: Result :: Err (MyError :: Missing (\"a\")) , } , \
+/// #     b : :: core :: option :: Option :: unwrap_or_default (self . b) , \
+/// #     c : :: core :: default :: Default :: default () , }) } } \
+/// #     impl :: core :: default :: Default for FooBuilder { fn default () ->Self { Self :: new () } }");
+/// # Ok::<_, virtue::Error>(())
+/// ```
+pub fn generate_builder_with(
+    generator: &mut Generator,
+    body: &StructBody,
+    config: BuilderConfig,
+    field: impl Fn(&Ident, &UnnamedField) -> BuilderField,
+) -> Result {
+    let fields: Vec<BuilderFieldInfo> = match &body.fields {
+        Some(Fields::Struct(fields)) => fields
+            .iter()
+            .map(|(ident, unnamed)| BuilderFieldInfo {
+                ident,
+                field: unnamed,
+                kind: field(ident, unnamed),
+                cfg: Attribute::cfg_of(&unnamed.attributes),
+            })
+            .collect(),
+        Some(Fields::Tuple(_)) => {
+            return Err(Error::custom_at(
+                "A builder can only be generated for structs with named fields",
+                generator.target_name().span(),
+            ))
+        }
+        None => Vec::new(),
+    };
+    for info in fields.iter().filter(|info| info.kind != BuilderField::Skip) {
+        let name = info.ident.to_string();
+        if METHODS.contains(&name.as_str()) || name == GenStruct::<Generator>::PHANTOM_FIELD {
+            return Err(Error::custom_with_help(
+                format!("The field `{}` conflicts with the builder", name),
+                format!(
+                    "the builder has a method or field named `{}`, skip this field or rename it",
+                    name
+                ),
+                info.ident.span(),
+            ));
+        }
+    }
+
+    let target = generator.target_type();
+    let target_name = unraw(&generator.target_name());
+    let name = match config.name {
+        Some(name) => name.to_owned(),
+        None => format!("{}Builder", target_name),
+    };
+    let visibility = config
+        .visibility
+        .clone()
+        .unwrap_or_else(|| generator.target_visibility().clone());
+    let mut builder_type = StreamBuilder::new();
+    builder_type.ident_str(&name);
+    if let Some(generics) = generator.generics() {
+        builder_type.append(generics.type_generics());
+    }
+    let mut result_type = StreamBuilder::new();
+    result_type
+        .push_parsed("::core::result::Result")?
+        .punct('<')
+        .self_type(&target)
+        .punct(',');
+    match config.error {
+        BuilderError::Message => result_type.push_parsed("&'static str")?,
+        BuilderError::Custom { ty, .. } => result_type.push_parsed(ty)?,
+    };
+    result_type.punct('>');
+
+    let mut builder = generator.generate_struct(&name);
+    builder
+        .with_visibility(visibility.clone())
+        .with_attr(format!("doc = \"A builder for `{}`\"", target_name))?
+        .carry_target_generics();
+    for info in fields.iter().filter(|info| info.kind != BuilderField::Skip) {
+        let mut ty = StreamBuilder::new();
+        ty.push_parsed("::core::option::Option")?
+            .punct('<')
+            .extend(info.field.r#type.iter().cloned())
+            .punct('>');
+        builder.add_field(info.ident.to_string(), ty);
+        if let Some(cfg) = &info.cfg {
+            let mut attr = StreamBuilder::new();
+            attr.ident_str("cfg").group(Delimiter::Parenthesis, |b| {
+                b.extend(cfg.clone());
+                Ok(())
+            })?;
+            builder.with_field_attr(attr)?;
+        }
+    }
+
+    let mut r#impl = builder.r#impl();
+    r#impl
+        .generate_fn("new")
+        .with_visibility(visibility.clone())
+        .with_attr("doc = \"Creates a builder without any fields set\"")
+        .with_return_type("Self")
+        .body(|b| {
+            b.ident_str("Self").group(Delimiter::Brace, |b| {
+                for info in fields.iter().filter(|info| info.kind != BuilderField::Skip) {
+                    if let Some(cfg) = &info.cfg {
+                        b.cfg_attribute(cfg.clone());
+                    }
+                    b.ident(info.ident.clone())
+                        .punct(':')
+                        .push_parsed("::core::option::Option::None")?
+                        .punct(',');
+                }
+                b.ident_str(GenStruct::<Generator>::PHANTOM_FIELD)
+                    .punct(':')
+                    .push_parsed("::core::marker::PhantomData")?;
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+    for info in fields.iter().filter(|info| info.kind != BuilderField::Skip) {
+        r#impl
+            .generate_fn(info.ident.to_string())
+            .with_visibility(visibility.clone())
+            .inherit_attrs(&info.field.attributes, |attr| attr.path_is("cfg"))
+            .with_attr(format!("doc = \"Sets `{}`\"", unraw(info.ident)))
+            .with_self_arg(FnSelfArg::MutTakeSelf)
+            .with_arg(info.ident.to_string(), info.field)
+            .with_return_type("Self")
+            .body(|b| {
+                b.ident_str("self")
+                    .punct('.')
+                    .ident(info.ident.clone())
+                    .punct('=')
+                    .push_parsed("::core::option::Option::Some")?
+                    .group(Delimiter::Parenthesis, |b| {
+                        b.ident(info.ident.clone());
+                        Ok(())
+                    })?
+                    .punct(';')
+                    .ident_str("self");
+                Ok(())
+            })?;
+    }
+    r#impl
+        .generate_fn("build")
+        .with_visibility(visibility.clone())
+        .with_attr(format!(
+            "doc = \"Builds a `{}`, or returns an error if a required field is not set\"",
+            target_name
+        ))
+        .with_self_arg(FnSelfArg::TakeSelf)
+        .with_self_arg_trailing_comma(false)
+        .with_return_type(result_type)
+        .body(|b| {
+            b.push_parsed("::core::result::Result::Ok")?
+                .group(Delimiter::Parenthesis, |b| {
+                    b.self_type_turbofish(&target)
+                        .group(Delimiter::Brace, |b| {
+                            for info in &fields {
+                                info.append_build_value(b, config.error)?;
+                            }
+                            Ok(())
+                        })?;
+                    Ok(())
+                })?;
+            Ok(())
+        })?;
+    drop(r#impl);
+    builder
+        .impl_for("::core::default::Default")
+        .generate_fn("default")
+        .with_return_type("Self")
+        .body(|b| {
+            b.push_parsed("Self::new()")?;
+            Ok(())
+        })?;
+    drop(builder);
+
+    if config.builder_fn {
+        generator
+            .r#impl()
+            .generate_fn("builder")
+            .with_visibility(visibility)
+            .with_attr(format!("doc = \"Creates a builder for `{}`\"", target_name))
+            .with_return_type(builder_type)
+            .body(|b| {
+                b.push_parsed(format!("{}::new()", name))?;
+                Ok(())
+            })?;
+    }
+    Ok(())
+}
+
+/// A field of the struct, with how the builder sets it
+struct BuilderFieldInfo<'a> {
+    ident: &'a Ident,
+    field: &'a UnnamedField,
+    kind: BuilderField,
+    cfg: Option<TokenStream>,
+}
+
+impl BuilderFieldInfo<'_> {
+    /// `<field>: <value>,` in the struct expression of `build`
+    fn append_build_value(&self, b: &mut StreamBuilder, error: BuilderError) -> Result {
+        if let Some(cfg) = &self.cfg {
+            b.cfg_attribute(cfg.clone());
+        }
+        b.ident(self.ident.clone()).punct(':');
+        match self.kind {
+            BuilderField::Required => {
+                b.ident_str("match")
+                    .ident_str("self")
+                    .punct('.')
+                    .ident(self.ident.clone())
+                    .group(Delimiter::Brace, |b| {
+                        b.push_parsed(
+                            "::core::option::Option::Some(value) => value, \
+                             ::core::option::Option::None => return ::core::result::Result::Err",
+                        )?;
+                        b.group(Delimiter::Parenthesis, |b| {
+                            let name = unraw(self.ident);
+                            match error {
+                                BuilderError::Message => {
+                                    b.lit_str(format!("`{}` is not set", name));
+                                }
+                                BuilderError::Custom { constructor, .. } => {
+                                    b.push_parsed(constructor)?.group(
+                                        Delimiter::Parenthesis,
+                                        |b| {
+                                            b.lit_str(name);
+                                            Ok(())
+                                        },
+                                    )?;
+                                }
+                            }
+                            Ok(())
+                        })?;
+                        b.punct(',');
+                        Ok(())
+                    })?;
+            }
+            BuilderField::Optional => {
+                b.push_parsed("::core::option::Option::unwrap_or_default")?
+                    .group(Delimiter::Parenthesis, |b| {
+                        b.ident_str("self").punct('.').ident(self.ident.clone());
+                        Ok(())
+                    })?;
+            }
+            BuilderField::Skip => {
+                b.push_parsed("::core::default::Default::default()")?;
+            }
+        }
+        b.punct(',');
+        Ok(())
+    }
+}
+
+#[test]
+fn test_generate_builder() {
+    use crate::parse::{Body, Parse};
+    use crate::token_stream;
+
+    let parse = |input: &str| {
+        let (generator, _, body) =
+            Parse::new(token_stream(input).collect::<proc_macro2::TokenStream>())
+                .unwrap()
+                .into_generator();
+        match body {
+            Body::Struct(body) => (generator, body),
+            Body::Enum(_) => unreachable!(),
+        }
+    };
+
+    let (mut generator, body) = parse(
+        "pub(crate) struct r#Foo<'a, T: Clone> where T: 'a { r#type: &'a T, #[cfg(test)] b: u8 }",
+    );
+    generate_builder(&mut generator, &body, BuilderConfig::default()).unwrap();
+    generator.assert_eq(
+        "# [doc = \"A builder for `Foo`\"] pub (crate) struct FooBuilder < 'a , T : Clone > where T : 'a { \
+         r#type : :: core :: option :: Option < &'a T > , # [cfg (test)] b : :: core :: option :: Option < u8 > , \
+         _marker : :: core :: marker :: PhantomData < (& 'a () , T ,) > , } \
+         impl < 'a , T : Clone > FooBuilder < 'a , T > where T : 'a { \
+         # [doc = \"Creates a builder without any fields set\"] pub (crate) fn new () ->Self { Self { \
+         r#type : :: core :: option :: Option :: None , # [cfg (test)] b : :: core :: option :: Option :: None , \
+         _marker : :: core :: marker :: PhantomData } } \
+         # [doc = \"Sets `type`\"] pub (crate) fn r#type (mut self , r#type : &'a T) ->Self { \
+         self . r#type = :: core :: option :: Option :: Some (r#type) ; self } \
+         # [cfg (test)] # [doc = \"Sets `b`\"] pub (crate) fn b (mut self , b : u8) ->Self { \
+         self . b = :: core :: option :: Option :: Some (b) ; self } \
+         # [doc = \"Builds a `Foo`, or returns an error if a required field is not set\"] \
+         pub (crate) fn build (self) ->:: core :: result :: Result < r#Foo < 'a , T > , &'static str > { \
+         :: core :: result :: Result :: Ok (r#Foo :: < 'a , T > { \
+         r#type : match self . r#type { :: core :: option :: Option :: Some (value) => value , \
+         :: core :: option :: Option :: None => return :: core :: result :: Result :: Err (\"`type` is not set\") , } , \
+         # [cfg (test)] b : match self . b { :: core :: option :: Option :: Some (value) => value , \
+         :: core :: option :: Option :: None => return :: core :: result :: Result :: Err (\"`b` is not set\") , } , }) } } \
+         impl < 'a , T : Clone > :: core :: default :: Default for FooBuilder < 'a , T > where T : 'a { \
+         fn default () ->Self { Self :: new () } } \
+         impl < 'a , T : Clone > r#Foo < 'a , T > where T : 'a { \
+         # [doc = \"Creates a builder for `Foo`\"] pub (crate) fn builder () ->FooBuilder < 'a , T > { FooBuilder :: new () } }",
+    );
+
+    // a custom name and error, without `Foo::builder()`
+    let (mut generator, body) = parse("pub struct Foo { a: u8, b: u8, c: u8 }");
+    let config = BuilderConfig {
+        name: Some("FooOptions"),
+        visibility: Some(Visibility::Default),
+        error: BuilderError::Custom {
+            ty: "Error",
+            constructor: "Error::Missing",
+        },
+        builder_fn: false,
+    };
+    generate_builder_with(&mut generator, &body, config, |ident, _| {
+        match ident.to_string().as_str() {
+            "a" => BuilderField::Required,
+            "b" => BuilderField::Optional,
+            _ => BuilderField::Skip,
+        }
+    })
+    .unwrap();
+    generator.assert_eq(
+        "# [doc = \"A builder for `Foo`\"] struct FooOptions { a : :: core :: option :: Option < u8 > , \
+         b : :: core :: option :: Option < u8 > , _marker : :: core :: marker :: PhantomData < () > , } \
+         impl FooOptions { # [doc = \"Creates a builder without any fields set\"] fn new () ->Self { Self { \
+         a : :: core :: option :: Option :: None , b : :: core :: option :: Option :: None , \
+         _marker : :: core :: marker :: PhantomData } } \
+         # [doc = \"Sets `a`\"] fn a (mut self , a : u8) ->Self { self . a = :: core :: option :: Option :: Some (a) ; self } \
+         # [doc = \"Sets `b`\"] fn b (mut self , b : u8) ->Self { self . b = :: core :: option :: Option :: Some (b) ; self } \
+         # [doc = \"Builds a `Foo`, or returns an error if a required field is not set\"] \
+         fn build (self) ->:: core :: result :: Result < Foo , Error > { :: core :: result :: Result :: Ok (Foo { \
+         a : match self . a { :: core :: option :: Option :: Some (value) => value , \
+         :: core :: option :: Option :: None => return :: core :: result :: Result :: Err (Error :: Missing (\"a\")) , } , \
+         b : :: core :: option :: Option :: unwrap_or_default (self . b) , \
+         c : :: core :: default :: Default :: default () , }) } } \
+         impl :: core :: default :: Default for FooOptions { fn default () ->Self { Self :: new () } }",
+    );
+
+    // only structs with named fields, and no fields that conflict with the builder
+    for (input, error) in [
+        (
+            "struct Foo(u8);",
+            "A builder can only be generated for structs with named fields (at 1:8)",
+        ),
+        (
+            "struct Foo { build: u8 }",
+            "The field `build` conflicts with the builder (at 1:14)\n\
+             help: the builder has a method or field named `build`, skip this field or rename it",
+        ),
+    ] {
+        let (mut generator, body) = parse(input);
+        let result = generate_builder(&mut generator, &body, BuilderConfig::default());
+        assert_eq!(result.unwrap_err().to_string(), error);
+        generator.assert_eq("");
+    }
+    let (mut generator, body) = parse("struct Foo { build: u8, a: u8 }");
+    generate_builder_with(
+        &mut generator,
+        &body,
+        BuilderConfig::default(),
+        |ident, _| {
+            if crate::parse::utils::ident_eq(ident, "build") {
+                BuilderField::Skip
+            } else {
+                BuilderField::Required
+            }
+        },
+    )
+    .unwrap();
+}
@@ -0,0 +1,7 @@
+//! High-level generators for common derive patterns, built on the [`generate`] module. These are only available with the `patterns` feature.
+//!
+//! - [`builder`]: a companion `FooBuilder` struct with a setter for every field, like `#[derive(Builder)]`
+//!
+//! [`generate`]: ../generate/index.html
+
+pub mod builder;
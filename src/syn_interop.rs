@@ -0,0 +1,107 @@
+//! Conversions between the types of [`syn`] and virtue, for projects that use both. This module is only available with the `syn-interop` feature.
+//!
+//! - [`Parse`] can be created from a [`syn::DeriveInput`] with `Parse::try_from(input)`, so a macro that already parsed its input with `syn` can generate code with virtue.
+//! - A [`syn::Type`] can be passed to every builder method that takes a [`TypeTokens`], e.g. [`FnBuilder::with_return_type`].
+//! - Code that was generated with `quote` can be added to a [`Generator`] with [`Generator::append_syn`].
+//!
+//! `syn` and `quote` work with the tokens of `proc_macro2`, which are converted to the tokens of the [`prelude`]. Enabling this feature does not change the prelude, so in a proc macro it still uses `proc_macro`.
+//!
+//! Note that `syn` 2 needs a newer version of rust than virtue itself.
+//!
+//! [`syn`]: https://docs.rs/syn
+//! [`prelude`]: ../prelude/index.html
+//! [`Parse`]: ../parse/enum.Parse.html
+//! [`TypeTokens`]: ../generate/struct.TypeTokens.html
+//! [`FnBuilder::with_return_type`]: ../generate/struct.FnBuilder.html#method.with_return_type
+//! [`Generator`]: ../generate/struct.Generator.html
+//! [`Generator::append_syn`]: ../generate/struct.Generator.html#method.append_syn
+
+use crate::generate::{Generator, Position, TypeTokens};
+use crate::parse::Parse;
+use crate::prelude::{TokenStream, TokenTree};
+use crate::{Error, Result};
+use quote::ToTokens;
+use std::convert::TryFrom;
+
+impl TryFrom<syn::DeriveInput> for Parse {
+    type Error = Error;
+
+    /// Parse the tokens of `input`. This fails for inputs that virtue does not support, e.g. unions.
+    fn try_from(input: syn::DeriveInput) -> Result<Self> {
+        Parse::new(into_prelude(input))
+    }
+}
+
+impl<'a> From<&'a syn::Type> for TypeTokens {
+    fn from(ty: &'a syn::Type) -> Self {
+        into_prelude(ty).into()
+    }
+}
+
+/// The tokens of a [`syn::Type`], e.g. to compare them with the type of an [`UnnamedField`].
+///
+/// [`UnnamedField`]: ../parse/struct.UnnamedField.html
+pub fn type_tokens(ty: &syn::Type) -> Vec<TokenTree> {
+    into_prelude(ty).into_iter().collect()
+}
+
+/// Convert the `proc_macro2` tokens of `syn` and `quote` to the tokens of the [`prelude`], which are `proc_macro` tokens in a proc macro.
+///
+/// `proc_macro2` is not named here, because depending on it would enable the `proc-macro2` feature, which changes the prelude.
+///
+/// [`prelude`]: ../prelude/index.html
+fn into_prelude(tokens: impl ToTokens) -> TokenStream {
+    // an identity conversion when the prelude uses `proc_macro2`
+    #[allow(clippy::useless_conversion)]
+    tokens.into_token_stream().into()
+}
+
+impl Generator {
+    /// Add code that was generated with e.g. `quote` to the output. The code is added as a single item, after all items that were generated so far.
+    ///
+    /// ```
+    /// # use virtue::prelude::*;
+    /// # let mut generator = Generator::with_name("Foo");
+    /// generator.append_syn(quote::quote!(impl Foo { fn foo() {} }))?;
+    /// # generator.assert_eq("impl Foo { fn foo () { } }");
+    /// # Ok::<_, virtue::Error>(())
+    /// ```
+    pub fn append_syn(&mut self, tokens: impl ToTokens) -> Result<&mut Self> {
+        self.insert_at(Position::End, into_prelude(tokens))
+    }
+}
+
+#[test]
+fn test_syn_interop() {
+    use crate::parse::Body;
+
+    let input: syn::DeriveInput = syn::parse_str("struct Foo<T> { a: Vec<T> }").unwrap();
+    let (mut generator, _, body) = Parse::try_from(input).unwrap().into_generator();
+    let fields = match body {
+        Body::Struct(body) => body.fields.unwrap(),
+        _ => unreachable!(),
+    };
+    let ty: syn::Type = syn::parse_str("Vec<T>").unwrap();
+    let (_, field) = fields.get(0).unwrap();
+    assert_eq!(
+        field.type_string(),
+        TypeTokens::from(&ty).to_string().replace(' ', "")
+    );
+    assert_eq!(type_tokens(&ty).len(), 4);
+
+    generator
+        .r#impl()
+        .generate_fn("a")
+        .with_return_type(&ty)
+        .body(|_| Ok(()))
+        .unwrap();
+    generator
+        .append_syn(quote::quote!(
+            const _: () = ();
+        ))
+        .unwrap();
+    generator.assert_eq("impl < T > Foo < T > { fn a () ->Vec < T > { } } const _ : () = () ;");
+
+    let input: syn::DeriveInput = syn::parse_str("union Foo { a: u8 }").unwrap();
+    assert!(Parse::try_from(input).is_err());
+}
@@ -1,13 +1,23 @@
 //! Utility functions
-use crate::{prelude::*, Error};
+use crate::{
+    parse::{utils::ident_eq, Attribute},
+    prelude::*,
+    Error,
+};
+
+pub mod case;
+mod diff;
+
+pub use self::diff::{token_diff, DiffReport};
 
 /// Parse a tagged attribute. This is very helpful for implementing [`FromAttribute`].
 ///
 /// A tagged attribute is an attribute in the form of `#[prefix(result)]`. This function will return `Some(result)` if the `prefix` matches.
 ///
-/// The contents of the result can be either:
+/// The contents of the result can be one of:
 /// - `ParsedAttribute::Tagged(Ident)`, e.g. `#[serde(skip)]` will be `Tagged("skip")`
 /// - `ParsedAttribute::Property(Ident, lit)`, e.g. `#[bincode(crate = "foo")]` will be `Property("crate", "foo")`
+/// - `ParsedAttribute::PropertyExpr(Ident, Vec<TokenTree>)` for any other value, e.g. `#[my(default = Vec::new())]` will be `PropertyExpr("default", [Vec, ::, new, ()])`
 ///
 /// # Examples
 /// ```
@@ -67,22 +77,35 @@ pub fn parse_tagged_attribute(group: &Group, prefix: &str) -> Result<Option<Vec<
                         (TokenTree::Ident(key), Some(TokenTree::Punct(p)))
                             if p.as_char() == '=' =>
                         {
-                            stream.next();
-                            if let Some(TokenTree::Literal(lit)) = stream.next() {
-                                result.push(ParsedAttribute::Property(key, lit));
-
-                                match stream.next() {
-                                    Some(TokenTree::Punct(p)) if p.as_char() == ',' => {}
-                                    None => {}
-                                    x => {
-                                        return Err(Error::custom_at_opt_token("Expected `,`", x));
-                                    }
+                            let eq = stream.next();
+                            let value = read_property_value(&mut stream);
+                            let property = match value.as_slice() {
+                                [] => {
+                                    return Err(Error::custom_at_opt_token(
+                                        "Expected a value after `=`",
+                                        eq,
+                                    )
+                                    .with_help(format!(
+                                        "use e.g. `#[{}({} = \"val\")]`",
+                                        prefix, key
+                                    )));
+                                }
+                                [TokenTree::Literal(lit)] => {
+                                    ParsedAttribute::Property(key, lit.clone())
                                 }
-                            }
+                                _ => ParsedAttribute::PropertyExpr(key, value),
+                            };
+                            result.push(property);
+                            // the `,` after the value, if any
+                            stream.next();
                         }
                         (x, _) => {
-                            return Err(Error::custom_at(
+                            return Err(Error::custom_with_help(
                                 "Expected `key` or `key = \"val\"`",
+                                format!(
+                                    "use e.g. `#[{0}(key)]` or `#[{0}(key = \"val\")]`",
+                                    prefix
+                                ),
                                 x.span(),
                             ));
                         }
@@ -96,6 +119,49 @@ pub fn parse_tagged_attribute(group: &Group, prefix: &str) -> Result<Option<Vec<
     Ok(None)
 }
 
+/// Read the value of a `key = value` property, up to the next `,` that is not nested in a group or in `<...>`.
+///
+/// A `<` after a path segment, like in `Foo<A, B>` or `HashMap::<K, V>`, is read as the start of generic arguments. If one of these is never closed, it was a comparison or a shift like `x < 5` or `x << 2`, and the value is read again with only the `<` of a turbofish tracked.
+fn read_property_value<I>(stream: &mut std::iter::Peekable<I>) -> Vec<TokenTree>
+where
+    I: Iterator<Item = TokenTree> + Clone,
+{
+    let tokens: Vec<TokenTree> = stream.clone().collect();
+    let len = property_value_len(&tokens, true)
+        .or_else(|| property_value_len(&tokens, false))
+        .unwrap_or(tokens.len());
+    stream.take(len).collect()
+}
+
+/// The number of tokens before the first `,` that is not nested in `<...>`, or `None` if a `<` is not closed. A `<` opens generic arguments after `::`, and after any ident if `after_idents` is set.
+fn property_value_len(tokens: &[TokenTree], after_idents: bool) -> Option<usize> {
+    let mut depth = 0usize;
+    for (idx, token) in tokens.iter().enumerate() {
+        let previous = idx.checked_sub(1).map(|i| &tokens[i]);
+        let is_path_sep =
+            is_punct(previous, ':') && is_punct(idx.checked_sub(2).map(|i| &tokens[i]), ':');
+        if let TokenTree::Punct(punct) = token {
+            match punct.as_char() {
+                ',' if depth == 0 => return Some(idx),
+                '<' if depth > 0
+                    || is_path_sep
+                    || (after_idents && matches!(previous, Some(TokenTree::Ident(_)))) =>
+                {
+                    depth += 1
+                }
+                // the `>` of a `->` does not close anything
+                '>' if depth > 0 && !is_punct(previous, '-') => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+    if depth == 0 {
+        Some(tokens.len())
+    } else {
+        None
+    }
+}
+
 #[derive(Clone, Debug)]
 #[non_exhaustive]
 /// A parsed attribute. See [`parse_tagged_attribute`] for more information.
@@ -104,6 +170,738 @@ pub enum ParsedAttribute {
     Tag(Ident),
     /// A property, created by parsing `#[prefix(foo = "bar")]`
     Property(Ident, Literal),
+    /// A property with a value that is not a single literal, created by parsing e.g. `#[prefix(foo = Vec::new())]`, `#[prefix(foo = ["a", "b"])]` or `#[prefix(foo = SomeType)]`. The value is the tokens up to the next `,` that is not nested in brackets, including the `<...>` of generic arguments like `Foo<A, B>` or `HashMap::<K, V>::new()`.
+    PropertyExpr(Ident, Vec<TokenTree>),
+}
+
+/// Create an error for a `key` in an attribute that is not one of the `expected` keys.
+///
+/// If one of the `expected` keys looks similar to `key`, the error will suggest it. Otherwise the error lists all `expected` keys.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// use virtue::utils::unknown_attribute_key;
+///
+/// let key = Ident::new("skp", Span::call_site());
+/// let error = unknown_attribute_key(&key, &["skip", "rename"]);
+/// assert_eq!(error.to_string(), "unknown key `skp`\nhelp: did you mean `skip`?");
+///
+/// let key = Ident::new("serde", Span::call_site());
+/// let error = unknown_attribute_key(&key, &["skip", "rename"]);
+/// assert_eq!(error.to_string(), "unknown key `serde`\nhelp: expected one of `skip`, `rename`");
+/// ```
+pub fn unknown_attribute_key(key: &Ident, expected: &[&str]) -> Error {
+    let name = key.to_string();
+    let closest = expected
+        .iter()
+        .map(|candidate| (edit_distance(&name, candidate), candidate))
+        .filter(|(distance, candidate)| *distance <= candidate.len().max(name.len()) / 3)
+        .min_by_key(|(distance, _)| *distance);
+    let help = match closest {
+        Some((_, candidate)) => format!("did you mean `{}`?", candidate),
+        None => format!(
+            "expected one of {}",
+            expected
+                .iter()
+                .map(|candidate| format!("`{}`", candidate))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    Error::custom_with_help(format!("unknown key `{}`", name), help, key.span())
+}
+
+/// Find a `#[prefix(bound = "...")]` in the given attributes, and return the contents of the string. Other keys in `#[prefix(...)]` and inner attributes like `#![prefix(bound = "...")]` are ignored.
+///
+/// This is the conventional way to override the bounds of a derive, see [`Generator::infer_bounds_with`] and [`InferredBounds::with_override`].
+///
+/// ```
+/// # use virtue::prelude::*;
+/// # use std::str::FromStr;
+/// use virtue::utils::parse_bound_attribute;
+///
+/// let input = TokenStream::from_str(
+///     "#[encode(skip_default, bound = \"T: Encode + 'static\")] struct Foo<T>(T);"
+/// ).unwrap();
+/// let attributes = match Parse::new(input)? {
+///     Parse::Struct { attributes, .. } => attributes,
+///     _ => unreachable!(),
+/// };
+/// assert_eq!(
+///     parse_bound_attribute(&attributes, "encode")?,
+///     Some(String::from("T: Encode + 'static"))
+/// );
+/// assert_eq!(parse_bound_attribute(&attributes, "decode")?, None);
+/// # Ok::<_, virtue::Error>(())
+/// ```
+///
+/// [`Generator::infer_bounds_with`]: ../generate/struct.Generator.html#method.infer_bounds_with
+/// [`InferredBounds::with_override`]: ../generate/struct.InferredBounds.html#method.with_override
+pub fn parse_bound_attribute(attributes: &[Attribute], prefix: &str) -> Result<Option<String>> {
+    for attribute in attributes.iter().filter(|attr| !attr.is_inner) {
+        for parsed in parse_tagged_attribute(&attribute.tokens, prefix)?.unwrap_or_default() {
+            if let ParsedAttribute::Property(key, value) = parsed {
+                if ident_eq(&key, "bound") {
+                    return string_literal_value(&value).map(Some);
+                }
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// The path to a crate in the generated code, e.g. `::my_crate::bincode` for a user that only depends on a crate that re-exports `bincode`. See [`Generator::set_crate_path_tokens`].
+///
+/// This is parsed from a `#[prefix(crate = "...")]` attribute with [`parse_crate_attribute`], and keeps the spans of the attribute. A path in a string can be passed to [`Generator::set_crate_path`] instead.
+///
+/// [`Generator::set_crate_path_tokens`]: ../generate/struct.Generator.html#method.set_crate_path_tokens
+/// [`Generator::set_crate_path`]: ../generate/struct.Generator.html#method.set_crate_path
+#[derive(Clone, Debug)]
+pub struct CratePath(TokenStream);
+
+impl CratePath {
+    /// Parse a path like `::my_crate::bincode` from a string
+    pub(crate) fn parse(crate_name: &str, code: &str) -> Result<Self> {
+        let code = code.trim();
+        let tokens = code.parse().ok().filter(is_crate_path);
+        match tokens {
+            Some(tokens) => Ok(Self(tokens)),
+            None => Err(Error::custom(crate_path_error(crate_name, code))),
+        }
+    }
+
+    /// The tokens of the path
+    pub(crate) fn into_tokens(self) -> TokenStream {
+        self.0
+    }
+}
+
+impl std::fmt::Display for CratePath {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.0.fmt(fmt)
+    }
+}
+
+/// Find a `#[prefix(crate = ...)]` in the given attributes and return the path. Other keys in `#[prefix(...)]` are ignored.
+///
+/// The path can be a string, like `crate = "::my_crate::bincode"`, or the path itself, like `crate = ::my_crate::bincode`. Both are checked to be a path of idents separated by `::`, optionally starting with `::`, and the tokens of a string get the span of the string. A `crate` key that is given twice is an error.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// # use std::str::FromStr;
+/// use virtue::utils::parse_crate_attribute;
+///
+/// let input = TokenStream::from_str(
+///     "#[bincode(crate = \"::my_crate::bincode\", skip_default)] struct Foo;"
+/// ).unwrap();
+/// let (mut generator, attributes, _) = Parse::new(input)?.into_generator();
+/// if let Some(path) = parse_crate_attribute(&attributes, "bincode")? {
+///     generator.set_crate_path_tokens("bincode", path);
+/// }
+/// let _ = generator.impl_for("::bincode::Encode");
+/// assert_eq!(
+///     generator.finish()?.to_string(),
+///     "impl :: my_crate :: bincode :: Encode for Foo { }"
+/// );
+/// # Ok::<_, virtue::Error>(())
+/// ```
+pub fn parse_crate_attribute(attributes: &[Attribute], prefix: &str) -> Result<Option<CratePath>> {
+    let mut result = None;
+    for attribute in attributes.iter().filter(|attr| !attr.is_inner) {
+        let mut tokens = attribute.tokens.stream().into_iter();
+        let group = match (tokens.next(), tokens.next()) {
+            (Some(TokenTree::Ident(ident)), Some(TokenTree::Group(group)))
+                if ident_eq(&ident, prefix) && group.delimiter() == Delimiter::Parenthesis =>
+            {
+                group
+            }
+            _ => continue,
+        };
+        let mut parts = vec![Vec::new()];
+        for token in group.stream() {
+            match token {
+                TokenTree::Punct(p) if p.as_char() == ',' => parts.push(Vec::new()),
+                token => parts.last_mut().unwrap().push(token),
+            }
+        }
+        for part in parts {
+            let key = match part.get(..2) {
+                Some([TokenTree::Ident(key), TokenTree::Punct(eq)])
+                    if ident_eq(key, "crate") && eq.as_char() == '=' =>
+                {
+                    key.clone()
+                }
+                _ => continue,
+            };
+            if result.is_some() {
+                return Err(Error::custom_at("Duplicate `crate` key", key.span()));
+            }
+            result = Some(parse_crate_path_value(prefix, key.span(), &part[2..])?);
+        }
+    }
+    Ok(result)
+}
+
+/// The path of `crate = <value>`, where the value is either a string or a path
+fn parse_crate_path_value(
+    crate_name: &str,
+    key_span: Span,
+    value: &[TokenTree],
+) -> Result<CratePath> {
+    let (tokens, span, code) = match value {
+        [TokenTree::Literal(literal)] => {
+            let code = string_literal_value(literal)?;
+            let tokens: Option<TokenStream> = code.trim().parse().ok();
+            let tokens = tokens.map(|tokens| {
+                tokens
+                    .into_iter()
+                    .map(|mut token| {
+                        token.set_span(literal.span());
+                        token
+                    })
+                    .collect()
+            });
+            (tokens, literal.span(), code)
+        }
+        [first, ..] => {
+            let tokens: TokenStream = value.iter().cloned().collect();
+            let code = tokens.to_string();
+            (Some(tokens), first.span(), code)
+        }
+        [] => (None, key_span, String::new()),
+    };
+    match tokens.filter(is_crate_path) {
+        Some(tokens) => Ok(CratePath(tokens)),
+        None => Err(Error::custom_with_help(
+            crate_path_error(crate_name, &code),
+            format!("use e.g. `#[{0}(crate = \"::my_crate::{0}\")]`", crate_name),
+            span,
+        )),
+    }
+}
+
+fn crate_path_error(crate_name: &str, path: &str) -> String {
+    format!(
+        "Expected a path like `::my_crate::{}`, found `{}`",
+        crate_name, path
+    )
+}
+
+/// Returns `true` if `tokens` is a path of idents separated by `::`, optionally starting with `::`, e.g. `crate::module` or `::my_crate`
+fn is_crate_path(tokens: &TokenStream) -> bool {
+    let tokens: Vec<TokenTree> = tokens.clone().into_iter().collect();
+    let mut idx = 0;
+    let is_colons = |idx: usize| {
+        matches!(tokens.get(idx), Some(TokenTree::Punct(p)) if p.as_char() == ':' && p.spacing() == Spacing::Joint)
+            && is_punct(tokens.get(idx + 1), ':')
+    };
+    if is_colons(0) {
+        idx = 2;
+    }
+    loop {
+        if !matches!(tokens.get(idx), Some(TokenTree::Ident(_))) {
+            return false;
+        }
+        idx += 1;
+        if idx == tokens.len() {
+            return true;
+        }
+        if !is_colons(idx) {
+            return false;
+        }
+        idx += 2;
+    }
+}
+
+/// The contents of a string literal like `"foo"` or `r#"foo"#`.
+///
+/// The escapes of a (non-raw) string literal are decoded. An escape that is not valid in a string literal is an error, so this never returns only part of the value.
+fn string_literal_value(literal: &Literal) -> Result<String> {
+    let repr = literal.to_string();
+    if let Some(raw) = repr.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        if let Some(value) = raw
+            .get(hashes + 1..raw.len().saturating_sub(hashes + 1))
+            .filter(|_| raw.len() >= 2 * hashes + 2)
+        {
+            return Ok(value.to_owned());
+        }
+    } else if repr.len() >= 2 && repr.starts_with('"') && repr.ends_with('"') {
+        return unescape(&repr[1..repr.len() - 1]).ok_or_else(|| {
+            Error::custom_at(
+                "Unsupported escape sequence in string literal",
+                literal.span(),
+            )
+        });
+    }
+    Err(Error::custom_at(
+        "Expected a string literal",
+        literal.span(),
+    ))
+}
+
+/// Decode the escape sequences in the contents of a string literal, or `None` if it contains an invalid escape sequence
+fn unescape(value: &str) -> Option<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => result.push('\n'),
+            'r' => result.push('\r'),
+            't' => result.push('\t'),
+            '0' => result.push('\0'),
+            c @ ('\\' | '"' | '\'') => result.push(c),
+            // `\x41`, limited to ascii
+            'x' => {
+                let digits: String = chars.by_ref().take(2).collect();
+                if digits.len() != 2 || !is_hex(&digits) {
+                    return None;
+                }
+                let value = u8::from_str_radix(&digits, 16).ok()?;
+                if value > 0x7F {
+                    return None;
+                }
+                result.push(char::from(value));
+            }
+            // `\u{1F600}`
+            'u' => {
+                if chars.next()? != '{' {
+                    return None;
+                }
+                let mut digits = String::new();
+                loop {
+                    match chars.next()? {
+                        '}' => break,
+                        '_' => {}
+                        c => digits.push(c),
+                    }
+                }
+                if digits.is_empty() || digits.len() > 6 || !is_hex(&digits) {
+                    return None;
+                }
+                result.push(char::from_u32(u32::from_str_radix(&digits, 16).ok()?)?);
+            }
+            // a line continuation, which skips the newline and the whitespace at the start of the next line
+            '\n' => {
+                while matches!(chars.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+                    chars.next();
+                }
+            }
+            _ => return None,
+        }
+    }
+    Some(result)
+}
+
+/// Whether `digits` is a non-signed hexadecimal number
+fn is_hex(digits: &str) -> bool {
+    digits.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Parse a single type, e.g. one that is given in an attribute like `#[prefix(with = "MyWrapper<T>")]`.
+///
+/// Besides checking that `ty` is valid rust syntax, this also checks that:
+/// - every `<` is closed by a `>`
+/// - `ty` starts like a type, e.g. not with a literal
+/// - `ty` contains only a single type, e.g. not `u8, u16` or `u8 = 5`
+///
+/// ```
+/// use virtue::utils::parse_type;
+///
+/// let ty = parse_type("Vec<(u8, Box<dyn Fn(u8) -> u8>)>")?;
+/// # let ty: virtue::prelude::TokenStream = ty.into_iter().collect();
+/// # assert_eq!(ty.to_string(), "Vec < (u8 , Box < dyn Fn (u8) -> u8 >) >");
+///
+/// assert!(parse_type("Vec<u8").unwrap_err().to_string().starts_with("Unclosed `<`"));
+/// assert!(parse_type("u8, u16").is_err());
+/// assert!(parse_type("5").is_err());
+/// # Ok::<_, virtue::Error>(())
+/// ```
+pub fn parse_type(ty: &str) -> Result<Vec<TokenTree>> {
+    use std::str::FromStr;
+
+    let stream = TokenStream::from_str(ty).map_err(|error| crate::generate::PushParseError {
+        error,
+        code: ty.to_string(),
+    })?;
+    let tokens: Vec<TokenTree> = stream.into_iter().collect();
+
+    match tokens.first() {
+        None => return Err(Error::custom("Expected a type, found an empty string")),
+        Some(TokenTree::Ident(_)) => {}
+        Some(TokenTree::Punct(p)) if matches!(p.as_char(), '&' | '*' | '<' | '!' | ':') => {}
+        Some(TokenTree::Group(g)) if g.delimiter() != Delimiter::Brace => {}
+        Some(token) => {
+            return Err(Error::custom_at(
+                format!("Expected a type, found `{}`", token),
+                token.span(),
+            ));
+        }
+    }
+    if let Some(TokenTree::Punct(p)) = tokens.last() {
+        if matches!(p.as_char(), ':' | '&' | '*' | '-') {
+            return Err(Error::custom_at(
+                format!("Incomplete type `{}`", ty),
+                p.span(),
+            ));
+        }
+    }
+    crate::parse::utils::check_angle_brackets(&tokens)?;
+
+    let mut input = tokens.iter().cloned().peekable();
+    crate::parse::utils::read_tokens_until_punct(&mut input, &[',', ';', '='])?;
+    if let Some(token) = input.next() {
+        return Err(Error::custom_with_help(
+            format!("Expected a single type, found `{}`", token),
+            "use a tuple like `(A, B)` for multiple types",
+            token.span(),
+        ));
+    }
+    Ok(tokens)
+}
+
+/// The layout of a struct or enum, as parsed from its `#[repr(...)]` attributes by [`parse_repr`].
+///
+/// A `#[repr(packed)]` without a value is `packed: Some(1)`.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Repr {
+    /// The integer type of `#[repr(u8)]`, `#[repr(i32)]`, etc.
+    pub int_type: Option<Ident>,
+    /// `true` for `#[repr(C)]`
+    pub c: bool,
+    /// `true` for `#[repr(transparent)]`
+    pub transparent: bool,
+    /// The alignment of `#[repr(align(N))]`. With multiple alignments this is the largest one, as in rustc.
+    pub align: Option<u32>,
+    /// The maximum alignment of `#[repr(packed)]` or `#[repr(packed(N))]`
+    pub packed: Option<u32>,
+}
+
+const REPR_INT_TYPES: &[&str] = &[
+    "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+];
+
+/// Parse all `#[repr(...)]` attributes in `attributes` into a single [`Repr`]. Returns the default `Repr` if there are no `#[repr(...)]` attributes.
+///
+/// Malformed and conflicting representations, like `#[repr(align(3))]` or `#[repr(u8, u16)]`, return an error at the offending tokens.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// # use std::str::FromStr;
+/// use virtue::utils::parse_repr;
+///
+/// let input = TokenStream::from_str("#[repr(C, align(16))] #[repr(u8)] enum Foo { A }").unwrap();
+/// let (_, attributes, _) = Parse::new(input)?.into_generator();
+/// let repr = parse_repr(&attributes)?;
+/// assert!(repr.c);
+/// assert_eq!(repr.align, Some(16));
+/// assert_eq!(repr.int_type.unwrap().to_string(), "u8");
+/// # Ok::<_, virtue::Error>(())
+/// ```
+pub fn parse_repr(attributes: &[Attribute]) -> Result<Repr> {
+    let mut repr = Repr::default();
+    for attribute in attributes.iter().filter(|attr| attr.path_is("repr")) {
+        let mut tokens = attribute.tokens.stream().into_iter();
+        let path = tokens.next();
+        let group = match tokens.next() {
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => group,
+            _ => {
+                return Err(Error::custom_with_help(
+                    "Expected `#[repr(...)]`",
+                    "use e.g. `#[repr(C)]` or `#[repr(u8)]`",
+                    path.map_or_else(|| attribute.tokens.span(), |path| path.span()),
+                ))
+            }
+        };
+        let mut parts = vec![Vec::new()];
+        for token in group.stream() {
+            match token {
+                TokenTree::Punct(p) if p.as_char() == ',' => parts.push(Vec::new()),
+                token => parts.last_mut().unwrap().push(token),
+            }
+        }
+        for part in parts.into_iter().filter(|part| !part.is_empty()) {
+            parse_repr_hint(&mut repr, part)?;
+        }
+    }
+    Ok(repr)
+}
+
+fn parse_repr_hint(repr: &mut Repr, part: Vec<TokenTree>) -> Result {
+    let mut tokens = part.into_iter();
+    let ident = match tokens.next() {
+        Some(TokenTree::Ident(ident)) => ident,
+        token => {
+            return Err(Error::custom_at_opt_token(
+                "Expected a representation hint",
+                token,
+            ))
+        }
+    };
+    let value = match tokens.next() {
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => {
+            Some(parse_repr_value(&ident, &group)?)
+        }
+        None => None,
+        Some(token) => {
+            return Err(Error::custom_at(
+                format!("Unexpected `{}` after `{}`", token, ident),
+                token.span(),
+            ))
+        }
+    };
+    if let Some(token) = tokens.next() {
+        return Err(Error::custom_at(
+            format!("Unexpected `{}` after `{}(...)`", token, ident),
+            token.span(),
+        ));
+    }
+    let name = ident.to_string();
+    match (name.as_str(), value) {
+        ("align", Some(align)) => repr.align = Some(repr.align.unwrap_or(0).max(align)),
+        ("align", None) => {
+            return Err(Error::custom_with_help(
+                "`align` needs a value",
+                "use e.g. `align(8)`",
+                ident.span(),
+            ))
+        }
+        ("packed", value) => repr.packed = Some(value.unwrap_or(1)),
+        (_, Some(_)) => {
+            return Err(Error::custom_at(
+                format!("`{}` does not take a value", name),
+                ident.span(),
+            ))
+        }
+        ("C", None) => repr.c = true,
+        ("transparent", None) => repr.transparent = true,
+        ("Rust", None) => {}
+        (int_type, None) if REPR_INT_TYPES.contains(&int_type) => {
+            if let Some(existing) = &repr.int_type {
+                if !ident_eq(existing, int_type) {
+                    return Err(Error::custom_at(
+                        format!(
+                            "Conflicting representation hints `{}` and `{}`",
+                            existing, int_type
+                        ),
+                        ident.span(),
+                    ));
+                }
+            }
+            repr.int_type = Some(ident.clone());
+        }
+        _ => {
+            let mut expected = vec!["C", "transparent", "Rust", "align", "packed"];
+            expected.extend(REPR_INT_TYPES);
+            return Err(unknown_attribute_key(&ident, &expected));
+        }
+    }
+    if repr.packed.is_some() && repr.align.is_some() {
+        return Err(Error::custom_at(
+            "Conflicting representation hints `packed` and `align`",
+            ident.span(),
+        ));
+    }
+    if repr.transparent
+        && (repr.c || repr.int_type.is_some() || repr.packed.is_some() || repr.align.is_some())
+    {
+        return Err(Error::custom_at(
+            "`transparent` can not be combined with other representation hints",
+            ident.span(),
+        ));
+    }
+    Ok(())
+}
+
+/// The `N` of `align(N)` or `packed(N)`, which has to be a power of two
+fn parse_repr_value(ident: &Ident, group: &Group) -> Result<u32> {
+    let mut tokens = group.stream().into_iter();
+    let literal = match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Literal(literal)), None) => literal,
+        _ => {
+            return Err(Error::custom_with_help(
+                format!("Expected an integer in `{}(...)`", ident),
+                format!("use e.g. `{}(8)`", ident),
+                group.span(),
+            ))
+        }
+    };
+    match literal.to_string().parse::<u32>() {
+        Ok(value) if value.is_power_of_two() && value <= 1 << 29 => Ok(value),
+        _ => Err(Error::custom_at(
+            format!(
+                "Expected a power of two of at most 2^29 in `{}(...)`, found `{}`",
+                ident, literal
+            ),
+            literal.span(),
+        )),
+    }
+}
+
+/// The Levenshtein distance between `a` and `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Replace the generic parameters in the given type `tokens` with concrete types.
+///
+/// Each entry in `substitutions` is a generic ident (e.g. `T`) and the tokens it should be replaced with (e.g. `u8`). Nested groups are rewritten as well, so `Vec<(T, [T; 4])>` with `T -> u8` becomes `Vec<(u8, [u8; 4])>`.
+///
+/// Idents that only look like the generic are left alone:
+/// - path segments after `::`, e.g. the `T` in `module::T`
+/// - lifetimes, e.g. `'T`
+/// - associated type bindings and bounds, e.g. the `T` in `Iterator<T = u8>`
+///
+/// When a generic is used as the start of a path (`T::Output`), the replacement is wrapped in angle brackets (`<u8>::Output`, `<Vec<u8>>::Output`), because a type like `Vec<u8>::Output` or `&u8::Output` does not parse.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// # use std::str::FromStr;
+/// use virtue::utils::substitute_generics;
+///
+/// let ty: Vec<TokenTree> = TokenStream::from_str("Vec<T>").unwrap().into_iter().collect();
+/// let replacement: Vec<TokenTree> = TokenStream::from_str("u8").unwrap().into_iter().collect();
+/// let result = substitute_generics(ty, &[(Ident::new("T", Span::call_site()), replacement)]);
+///
+/// let result: TokenStream = result.into_iter().collect();
+/// assert_eq!(result.to_string(), "Vec < u8 >");
+/// ```
+pub fn substitute_generics(
+    tokens: impl IntoIterator<Item = TokenTree>,
+    substitutions: &[(Ident, Vec<TokenTree>)],
+) -> Vec<TokenTree> {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    let mut result = Vec::with_capacity(tokens.len());
+    for (idx, token) in tokens.iter().enumerate() {
+        match token {
+            TokenTree::Group(group) => {
+                let stream = substitute_generics(group.stream(), substitutions)
+                    .into_iter()
+                    .collect();
+                let mut new_group = Group::new(group.delimiter(), stream);
+                new_group.set_span(group.span());
+                result.push(TokenTree::Group(new_group));
+            }
+            TokenTree::Ident(ident) => {
+                let replacement = substitutions
+                    .iter()
+                    .find(|(generic, _)| ident_eq(ident, &generic.to_string()))
+                    .map(|(_, replacement)| replacement);
+                match replacement {
+                    Some(replacement) if is_generic_position(&tokens, idx) => {
+                        let starts_path = is_punct(tokens.get(idx + 1), ':')
+                            && is_punct(tokens.get(idx + 2), ':');
+                        if starts_path {
+                            result.push(TokenTree::Punct(Punct::new('<', Spacing::Alone)));
+                            result.extend(replacement.iter().cloned());
+                            result.push(TokenTree::Punct(Punct::new('>', Spacing::Alone)));
+                        } else {
+                            result.extend(replacement.iter().cloned());
+                        }
+                    }
+                    _ => result.push(token.clone()),
+                }
+            }
+            _ => result.push(token.clone()),
+        }
+    }
+    result
+}
+
+/// Check if the ident at `tokens[idx]` is used as a type, and not as a path segment, lifetime or associated type name.
+fn is_generic_position(tokens: &[TokenTree], idx: usize) -> bool {
+    let previous = idx.checked_sub(1).and_then(|i| tokens.get(i));
+    let before_previous = idx.checked_sub(2).and_then(|i| tokens.get(i));
+    // `'T`
+    if is_punct(previous, '\'') {
+        return false;
+    }
+    // `module::T`
+    if is_punct(previous, ':') && is_punct(before_previous, ':') {
+        return false;
+    }
+    match tokens.get(idx + 1) {
+        // `Iterator<T = u8>`, but not `T == u8`
+        Some(TokenTree::Punct(p)) if p.as_char() == '=' => {
+            is_punct(tokens.get(idx + 2), '=') && p.spacing() == Spacing::Joint
+        }
+        // `Iterator<T: Clone>`, but not `T::Output`
+        Some(TokenTree::Punct(p)) if p.as_char() == ':' => is_punct(tokens.get(idx + 2), ':'),
+        _ => true,
+    }
+}
+
+fn is_punct(token: Option<&TokenTree>, c: char) -> bool {
+    matches!(token, Some(TokenTree::Punct(p)) if p.as_char() == c)
+}
+
+#[test]
+fn test_substitute_generics() {
+    fn substitute(input: &str, substitutions: &[(&str, &str)]) -> String {
+        let substitutions: Vec<(Ident, Vec<TokenTree>)> = substitutions
+            .iter()
+            .map(|(generic, replacement)| {
+                (
+                    Ident::new(generic, Span::call_site()),
+                    crate::token_stream(replacement).collect(),
+                )
+            })
+            .collect();
+        let result = substitute_generics(crate::token_stream(input), &substitutions);
+        result.into_iter().collect::<TokenStream>().to_string()
+    }
+
+    assert_eq!(substitute("Vec<T>", &[("T", "u8")]), "Vec < u8 >");
+    assert_eq!(
+        substitute("Vec<(T, [T; 4])>", &[("T", "u8")]),
+        "Vec < (u8 , [u8 ; 4]) >"
+    );
+    assert_eq!(
+        substitute("HashMap<K, Vec<V>>", &[("K", "String"), ("V", "u32")]),
+        "HashMap < String , Vec < u32 >>"
+    );
+    assert_eq!(substitute("&'T T", &[("T", "u8")]), "&'T u8");
+    assert_eq!(
+        substitute("module::T<T>", &[("T", "u8")]),
+        "module :: T < u8 >"
+    );
+    assert_eq!(
+        substitute("Box<dyn Iterator<Item = Item>>", &[("Item", "u8")]),
+        "Box < dyn Iterator < Item = u8 >>"
+    );
+    assert_eq!(substitute("T::Output", &[("T", "u8")]), "< u8 > :: Output");
+    assert_eq!(
+        substitute("T::Output", &[("T", "(u8, u16)")]),
+        "< (u8 , u16) > :: Output"
+    );
+    assert_eq!(
+        substitute("Option<T::Item>", &[("T", "Vec<u8>")]),
+        "Option < < Vec < u8 > > :: Item >"
+    );
+    assert_eq!(
+        substitute("Box<T::Output>", &[("T", "&'a u8")]),
+        "Box < < &'a u8 > :: Output >"
+    );
+    assert_eq!(
+        substitute("<T as Add>::Output", &[("T", "Vec<u8>")]),
+        "< Vec < u8 > as Add >:: Output"
+    );
+    assert_eq!(substitute("Vec<U>", &[("T", "u8")]), "Vec < U >");
 }
 
 #[test]
@@ -137,3 +935,299 @@ fn test_parse_tagged_attribute() {
         x => panic!("Unexpected attribute: {:?}", x),
     }
 }
+
+#[test]
+fn test_parse_tagged_attribute_expr() {
+    let parse = |input: &str| {
+        let group: Group = match crate::token_stream(input).next() {
+            Some(TokenTree::Group(group)) => group,
+            x => panic!("Unexpected token {:?}", x),
+        };
+        parse_tagged_attribute(&group, "prefix").map(Option::unwrap)
+    };
+    let to_string = |attribute: &ParsedAttribute| match attribute {
+        ParsedAttribute::Tag(key) => key.to_string(),
+        ParsedAttribute::Property(key, lit) => format!("{}: {}", key, lit),
+        ParsedAttribute::PropertyExpr(key, value) => format!(
+            "{}: expr {}",
+            key,
+            value.iter().cloned().collect::<TokenStream>()
+        ),
+    };
+
+    let attributes = parse(
+        "[prefix(default = Vec::new(), tags = [\"a\", \"b\"], bound = SomeType, \
+         map = HashMap::<K, V>::new(), offset = -1, skip, name = \"foo\")]",
+    )
+    .unwrap();
+    assert_eq!(
+        attributes.iter().map(to_string).collect::<Vec<_>>(),
+        [
+            "default: expr Vec :: new ()",
+            "tags: expr [\"a\" , \"b\"]",
+            "bound: expr SomeType",
+            "map: expr HashMap ::< K , V >:: new ()",
+            "offset: expr - 1",
+            "skip",
+            "name: \"foo\"",
+        ]
+    );
+
+    // `<` and `>` outside of a turbofish are operators
+    let attributes = parse(
+        "[prefix(skip_if = x < 5, other, f = 1 << 2, last = a > b, \
+         call = f::<fn(u8) -> Vec<u8>, u8>(), x)]",
+    )
+    .unwrap();
+    assert_eq!(
+        attributes.iter().map(to_string).collect::<Vec<_>>(),
+        [
+            "skip_if: expr x < 5",
+            "other",
+            "f: expr 1 << 2",
+            "last: expr a > b",
+            "call: expr f ::< fn (u8) -> Vec < u8 >, u8 > ()",
+            "x",
+        ]
+    );
+    // generic arguments after a path segment are kept together
+    let attributes =
+        parse("[prefix(bound = Foo<A, B>, x, map = HashMap<K, Vec<V>>, skip_if = x < 5)]").unwrap();
+    assert_eq!(
+        attributes.iter().map(to_string).collect::<Vec<_>>(),
+        [
+            "bound: expr Foo < A , B >",
+            "x",
+            "map: expr HashMap < K , Vec < V >>",
+            "skip_if: expr x < 5",
+        ]
+    );
+    let attributes = parse("[prefix(skip_if = a > b)]").unwrap();
+    assert_eq!(
+        attributes.iter().map(to_string).collect::<Vec<_>>(),
+        ["skip_if: expr a > b"]
+    );
+
+    for input in ["[prefix(a = , b)]", "[prefix(a =)]"] {
+        let error = parse(input).unwrap_err().to_string();
+        assert!(
+            error.starts_with("Expected a value after `=`"),
+            "{}: {}",
+            input,
+            error
+        );
+    }
+}
+
+#[test]
+fn test_unknown_attribute_key() {
+    assert_eq!(edit_distance("skip", "skip"), 0);
+    assert_eq!(edit_distance("skpi", "skip"), 2);
+    assert_eq!(edit_distance("", "abc"), 3);
+    assert_eq!(edit_distance("rename", "renamed"), 1);
+
+    let error = unknown_attribute_key(&Ident::new("renam", Span::call_site()), &["skip", "rename"]);
+    assert_eq!(
+        error.to_string(),
+        "unknown key `renam`\nhelp: did you mean `rename`?"
+    );
+
+    let group: Group = match crate::token_stream("[prefix(\"foo\")]").next() {
+        Some(TokenTree::Group(group)) => group,
+        x => panic!("Unexpected token {:?}", x),
+    };
+    let error = parse_tagged_attribute(&group, "prefix").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Expected `key` or `key = \"val\"` (at 1:9)\nhelp: use e.g. `#[prefix(key)]` or `#[prefix(key = \"val\")]`"
+    );
+}
+
+#[test]
+fn test_string_literal_value() {
+    let value = |code: &str| match crate::token_stream(code).next() {
+        Some(TokenTree::Literal(literal)) => string_literal_value(&literal),
+        _ => unreachable!(),
+    };
+    assert_eq!(value(r#""T: Clone""#).unwrap(), "T: Clone");
+    assert_eq!(value(r##"r#"a "b""#"##).unwrap(), r#"a "b""#);
+    assert_eq!(value(r#""a\tb\"\0""#).unwrap(), "a\tb\"\0");
+    assert_eq!(value(r#""T: \x41""#).unwrap(), "T: A");
+    assert_eq!(value(r#""\u{1F600} \u{4_1}""#).unwrap(), "\u{1F600} A");
+    assert_eq!(
+        value("\"T: Clone +\\\n    Send\"").unwrap(),
+        "T: Clone +Send"
+    );
+
+    assert!(value("5").is_err());
+
+    // the tokenizer already rejects these
+    assert_eq!(unescape(r#"\x80"#), None);
+    assert_eq!(unescape(r#"\x4"#), None);
+    assert_eq!(unescape(r#"\x+4"#), None);
+    assert_eq!(unescape(r#"\u{110000}"#), None);
+    assert_eq!(unescape(r#"\u{}"#), None);
+    assert_eq!(unescape(r#"\u41"#), None);
+    assert_eq!(unescape(r#"\q"#), None);
+    assert_eq!(unescape("a\\"), None);
+}
+
+#[test]
+fn test_parse_type() {
+    for ty in [
+        "u8",
+        "&'a mut [T; 4]",
+        "<T as Iterator>::Item",
+        "::core::option::Option<fn(u8) -> u16>",
+        "impl Iterator<Item = (u8, u16)>",
+        "!",
+        "[u8; { N < 5 } as usize]",
+    ] {
+        assert!(parse_type(ty).is_ok(), "{} should be a valid type", ty);
+    }
+    for (ty, error) in [
+        ("", "Expected a type, found an empty string"),
+        ("Vec<u8>>", "Unexpected `>` (at 1:8)"),
+        ("(Vec<u8, u16)", "Unclosed `<` (at 1:5)"),
+        ("'a", "Expected a type, found `'` (at 1:1)"),
+        ("std::", "Incomplete type `std::` (at 1:5)"),
+        (
+            "u8; 5",
+            "Expected a single type, found `;` (at 1:3)\nhelp: use a tuple like `(A, B)` for multiple types",
+        ),
+    ] {
+        assert_eq!(parse_type(ty).unwrap_err().to_string(), error);
+    }
+}
+
+#[test]
+fn test_parse_repr() {
+    use crate::parse::Parse;
+
+    let parse = |input: &str| {
+        let (_, attributes, _) = Parse::new(crate::token_stream(input).collect())
+            .unwrap()
+            .into_generator();
+        parse_repr(&attributes)
+    };
+
+    let repr = parse("struct Foo;").unwrap();
+    assert!(!repr.c && !repr.transparent && repr.int_type.is_none());
+    assert_eq!((repr.align, repr.packed), (None, None));
+
+    let repr = parse("#[repr(packed)] #[derive(Debug)] struct Foo;").unwrap();
+    assert_eq!(repr.packed, Some(1));
+    let repr = parse("#[repr(C, packed(4))] struct Foo;").unwrap();
+    assert_eq!((repr.c, repr.packed), (true, Some(4)));
+    let repr =
+        parse("#[repr(align(8))] #[repr(align(32), C)] #[repr(align(4))] struct Foo;").unwrap();
+    assert_eq!(repr.align, Some(32));
+    let repr = parse("#[repr(transparent)] struct Foo(u8);").unwrap();
+    assert!(repr.transparent);
+    let repr = parse("#[repr(i64)] #[repr(i64, Rust)] enum Foo { A }").unwrap();
+    assert_eq!(repr.int_type.unwrap().to_string(), "i64");
+
+    let error = |input: &str| parse(input).unwrap_err().to_string();
+    assert_eq!(
+        error("#[repr(align(3))] struct Foo;"),
+        "Expected a power of two of at most 2^29 in `align(...)`, found `3` (at 1:14)"
+    );
+    assert_eq!(
+        error("#[repr(u8, u16)] enum Foo { A }"),
+        "Conflicting representation hints `u8` and `u16` (at 1:12)"
+    );
+    assert_eq!(
+        error("#[repr(packed(2), align(4))] struct Foo;"),
+        "Conflicting representation hints `packed` and `align` (at 1:19)"
+    );
+    assert_eq!(
+        error("#[repr(transparent, C)] struct Foo(u8);"),
+        "`transparent` can not be combined with other representation hints (at 1:21)"
+    );
+    assert_eq!(
+        error("#[repr(transparnt)] struct Foo(u8);"),
+        "unknown key `transparnt` (at 1:8)\nhelp: did you mean `transparent`?"
+    );
+    assert!(parse("#[repr(align)] struct Foo;").is_err());
+    assert!(parse("#[repr(align(\"8\"))] struct Foo;").is_err());
+    assert!(parse("#[repr(align(8, 16))] struct Foo;").is_err());
+    assert!(parse("#[repr(C(1))] struct Foo;").is_err());
+    assert!(parse("#[repr] struct Foo;").is_err());
+    assert!(parse("#[repr = \"C\"] struct Foo;").is_err());
+}
+
+#[test]
+fn test_parse_bound_attribute() {
+    use crate::parse::{Attribute, AttributeLocation};
+
+    let parse = |input: &str| {
+        let stream = &mut crate::token_stream(input);
+        let attributes = Attribute::try_take(AttributeLocation::Container, stream).unwrap();
+        parse_bound_attribute(&attributes, "encode")
+    };
+    assert_eq!(
+        parse("#[encode(bound = \"T: Encode\")] struct Foo;").unwrap(),
+        Some(String::from("T: Encode"))
+    );
+    assert_eq!(
+        parse("#![encode(bound = \"T: Encode\")] struct Foo;").unwrap(),
+        None
+    );
+    assert_eq!(
+        parse("#![encode(bound = \"T: Clone\")] #[encode(bound = \"T: Encode\")] struct Foo;")
+            .unwrap(),
+        Some(String::from("T: Encode"))
+    );
+}
+
+#[test]
+fn test_parse_crate_attribute() {
+    use crate::parse::Parse;
+
+    let parse = |input: &str| {
+        let (_, attributes, _) = Parse::new(crate::token_stream(input).collect())
+            .unwrap()
+            .into_generator();
+        parse_crate_attribute(&attributes, "bincode")
+            .map(|path| path.map(|path| path.into_tokens().to_string()))
+    };
+
+    assert_eq!(parse("#[serde(crate = \"a\")] struct Foo;").unwrap(), None);
+    assert_eq!(
+        parse("#[bincode(crate = \"::my_crate::bincode\")] struct Foo;").unwrap(),
+        Some(String::from(":: my_crate :: bincode"))
+    );
+    assert_eq!(
+        parse("#[bincode(skip)] #[bincode(crate = r\"crate::bincode\", bound = \"T: X\")] struct Foo;")
+            .unwrap(),
+        Some(String::from("crate :: bincode"))
+    );
+    assert_eq!(
+        parse("#[bincode(crate = ::my_crate::bincode, skip)] struct Foo;").unwrap(),
+        Some(String::from(":: my_crate :: bincode"))
+    );
+    assert_eq!(
+        parse("#[bincode(crate = bincode)] struct Foo;").unwrap(),
+        Some(String::from("bincode"))
+    );
+
+    let error = |input: &str| parse(input).unwrap_err().to_string();
+    assert_eq!(
+        error("#[bincode(crate = \"my_crate::\")] struct Foo;"),
+        "Expected a path like `::my_crate::bincode`, found `my_crate::` (at 1:19)\n\
+         help: use e.g. `#[bincode(crate = \"::my_crate::bincode\")]`"
+    );
+    assert_eq!(
+        error("#[bincode(crate = a::<T>)] struct Foo;"),
+        "Expected a path like `::my_crate::bincode`, found `a ::< T >` (at 1:19)\n\
+         help: use e.g. `#[bincode(crate = \"::my_crate::bincode\")]`"
+    );
+    assert!(parse("#[bincode(crate = )] struct Foo;").is_err());
+    assert!(parse("#[bincode(crate = \"\")] struct Foo;").is_err());
+    assert!(parse("#[bincode(crate = \"a b\")] struct Foo;").is_err());
+    assert!(parse("#[bincode(crate = 5)] struct Foo;").is_err());
+    assert_eq!(
+        error("#[bincode(crate = \"a\")] #[bincode(crate = \"b\")] struct Foo;"),
+        "Duplicate `crate` key (at 1:35)"
+    );
+}
@@ -0,0 +1,202 @@
+//! Case conversions for identifiers, e.g. for implementing `#[serde(rename_all = "...")]`-style attributes.
+//!
+//! Words are split on `_`, `-` and whitespace, on a lowercase letter or digit followed by an uppercase letter (`fooBar`, `V2Beta`), and at the end of an acronym (`HTTPServer` is `HTTP` and `Server`). Digits stay with the word they follow.
+//!
+//! ```
+//! use virtue::utils::case::Case;
+//!
+//! assert_eq!(Case::Snake.convert("HTTPServer2Go"), "http_server2_go");
+//! assert_eq!(Case::ScreamingSnake.convert("fooBar"), "FOO_BAR");
+//! assert_eq!(Case::Camel.convert("foo_bar"), "fooBar");
+//! assert_eq!(Case::Pascal.convert("http_server"), "HttpServer");
+//! assert_eq!(Case::Kebab.convert("FooBar"), "foo-bar");
+//! ```
+
+/// A casing style. See the [module documentation] for how words are split.
+///
+/// [module documentation]: index.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Case {
+    /// `snake_case`
+    Snake,
+    /// `SCREAMING_SNAKE_CASE`
+    ScreamingSnake,
+    /// `camelCase`
+    Camel,
+    /// `PascalCase`
+    Pascal,
+    /// `kebab-case`
+    Kebab,
+}
+
+impl Case {
+    /// Parse the name of a casing style as it is commonly written in `rename_all` attributes, e.g. `"snake_case"` or `"camelCase"`. Returns `None` if the name is unknown.
+    ///
+    /// ```
+    /// # use virtue::utils::case::Case;
+    /// assert_eq!(Case::from_name("SCREAMING_SNAKE_CASE"), Some(Case::ScreamingSnake));
+    /// assert_eq!(Case::from_name("lowercase"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "snake_case" => Some(Case::Snake),
+            "SCREAMING_SNAKE_CASE" => Some(Case::ScreamingSnake),
+            "camelCase" => Some(Case::Camel),
+            "PascalCase" => Some(Case::Pascal),
+            "kebab-case" => Some(Case::Kebab),
+            _ => None,
+        }
+    }
+
+    /// Convert the given input to this case. A leading `r#` of a raw identifier is removed.
+    pub fn convert(self, input: &str) -> String {
+        let input = input.strip_prefix("r#").unwrap_or(input);
+        let words = split_words(input);
+        let mut result = String::with_capacity(input.len() + words.len());
+        for (idx, word) in words.iter().enumerate() {
+            match self {
+                Case::Snake | Case::ScreamingSnake if idx > 0 => result.push('_'),
+                Case::Kebab if idx > 0 => result.push('-'),
+                _ => {}
+            }
+            match self {
+                Case::Snake | Case::Kebab => result.push_str(&word.to_lowercase()),
+                Case::ScreamingSnake => result.push_str(&word.to_uppercase()),
+                Case::Camel if idx == 0 => result.push_str(&word.to_lowercase()),
+                Case::Camel | Case::Pascal => {
+                    let mut chars = word.chars();
+                    if let Some(first) = chars.next() {
+                        result.extend(first.to_uppercase());
+                        result.push_str(&chars.as_str().to_lowercase());
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Convert the input to `snake_case`. See [`Case::Snake`].
+pub fn to_snake_case(input: &str) -> String {
+    Case::Snake.convert(input)
+}
+
+/// Convert the input to `SCREAMING_SNAKE_CASE`. See [`Case::ScreamingSnake`].
+pub fn to_screaming_snake_case(input: &str) -> String {
+    Case::ScreamingSnake.convert(input)
+}
+
+/// Convert the input to `camelCase`. See [`Case::Camel`].
+pub fn to_camel_case(input: &str) -> String {
+    Case::Camel.convert(input)
+}
+
+/// Convert the input to `PascalCase`. See [`Case::Pascal`].
+pub fn to_pascal_case(input: &str) -> String {
+    Case::Pascal.convert(input)
+}
+
+/// Convert the input to `kebab-case`. See [`Case::Kebab`].
+pub fn to_kebab_case(input: &str) -> String {
+    Case::Kebab.convert(input)
+}
+
+fn split_words(input: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    for part in input.split(|c: char| c == '_' || c == '-' || c.is_whitespace()) {
+        let chars: Vec<(usize, char)> = part.char_indices().collect();
+        let mut start = 0;
+        for (i, &(idx, c)) in chars.iter().enumerate().skip(1) {
+            let previous = chars[i - 1].1;
+            let next = chars.get(i + 1).map(|&(_, c)| c);
+            let is_boundary = c.is_uppercase()
+                && (previous.is_lowercase()
+                    || previous.is_numeric()
+                    || (previous.is_uppercase() && matches!(next, Some(n) if n.is_lowercase())));
+            if is_boundary {
+                words.push(&part[start..idx]);
+                start = idx;
+            }
+        }
+        if start < part.len() {
+            words.push(&part[start..]);
+        }
+    }
+    words
+}
+
+#[test]
+fn test_case() {
+    let cases = [
+        (
+            "foo_bar",
+            ["foo_bar", "FOO_BAR", "fooBar", "FooBar", "foo-bar"],
+        ),
+        (
+            "FooBar",
+            ["foo_bar", "FOO_BAR", "fooBar", "FooBar", "foo-bar"],
+        ),
+        (
+            "fooBar",
+            ["foo_bar", "FOO_BAR", "fooBar", "FooBar", "foo-bar"],
+        ),
+        (
+            "FOO_BAR",
+            ["foo_bar", "FOO_BAR", "fooBar", "FooBar", "foo-bar"],
+        ),
+        (
+            "foo-bar",
+            ["foo_bar", "FOO_BAR", "fooBar", "FooBar", "foo-bar"],
+        ),
+        (
+            "HTTPServer",
+            [
+                "http_server",
+                "HTTP_SERVER",
+                "httpServer",
+                "HttpServer",
+                "http-server",
+            ],
+        ),
+        (
+            "IOError",
+            ["io_error", "IO_ERROR", "ioError", "IoError", "io-error"],
+        ),
+        (
+            "GetHTTP",
+            ["get_http", "GET_HTTP", "getHttp", "GetHttp", "get-http"],
+        ),
+        ("A", ["a", "A", "a", "A", "a"]),
+        (
+            "Version2",
+            ["version2", "VERSION2", "version2", "Version2", "version2"],
+        ),
+        (
+            "V2Beta",
+            ["v2_beta", "V2_BETA", "v2Beta", "V2Beta", "v2-beta"],
+        ),
+        (
+            "utf8_str",
+            ["utf8_str", "UTF8_STR", "utf8Str", "Utf8Str", "utf8-str"],
+        ),
+        ("r#type", ["type", "TYPE", "type", "Type", "type"]),
+        (
+            "_private",
+            ["private", "PRIVATE", "private", "Private", "private"],
+        ),
+        ("", ["", "", "", "", ""]),
+    ];
+    let all = [
+        Case::Snake,
+        Case::ScreamingSnake,
+        Case::Camel,
+        Case::Pascal,
+        Case::Kebab,
+    ];
+    for (input, expected) in cases {
+        for (case, expected) in all.iter().zip(expected) {
+            assert_eq!(case.convert(input), expected, "{:?} of {:?}", case, input);
+        }
+    }
+}
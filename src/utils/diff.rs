@@ -0,0 +1,166 @@
+//! Compare generated code token by token, see [`token_diff`].
+
+use crate::prelude::{Delimiter, TokenStream, TokenTree};
+use std::fmt;
+use std::str::FromStr;
+
+/// The amount of tokens that are shown before and after the first difference
+const CONTEXT: usize = 8;
+
+/// Compare the `expected` code with the `actual` generated code, token by token. Whitespace and the spacing of punctuation are ignored, so `a->b` and `a -> b` are equal.
+///
+/// Returns `None` if both are equal, otherwise a [`DiffReport`] of the first token that differs. This is much easier to read than comparing the output of `to_string()` of a large derive.
+///
+/// ```
+/// # use virtue::prelude::*;
+/// # use std::str::FromStr;
+/// use virtue::utils::token_diff;
+///
+/// let actual = TokenStream::from_str("impl Foo for Bar { fn foo(&self) -> u32 { 5 } }").unwrap();
+/// assert!(token_diff("impl Foo for Bar { fn foo (& self) ->u32 { 5 } }", &actual).is_none());
+///
+/// let report = token_diff("impl Foo for Bar { fn foo(&self) -> u64 { 5 } }", &actual).unwrap();
+/// assert_eq!(report.index, 13);
+/// assert_eq!(report.expected.as_deref(), Some("u64"));
+/// assert_eq!(report.actual.as_deref(), Some("u32"));
+/// ```
+pub fn token_diff(expected: &str, actual: &TokenStream) -> Option<DiffReport> {
+    let expected = match TokenStream::from_str(expected) {
+        Ok(expected) => flatten(expected),
+        Err(error) => {
+            return Some(DiffReport {
+                index: 0,
+                expected: Some(format!("<invalid code: {:?}>", error)),
+                actual: None,
+                expected_context: String::new(),
+                actual_context: String::new(),
+            })
+        }
+    };
+    let actual = flatten(actual.clone());
+
+    let index = expected
+        .iter()
+        .zip(actual.iter())
+        .position(|(expected, actual)| expected != actual)
+        .unwrap_or_else(|| expected.len().min(actual.len()));
+    if index == expected.len() && index == actual.len() {
+        return None;
+    }
+    Some(DiffReport {
+        index,
+        expected: expected.get(index).cloned(),
+        actual: actual.get(index).cloned(),
+        expected_context: context(&expected, index),
+        actual_context: context(&actual, index),
+    })
+}
+
+/// The first difference between two token streams, see [`token_diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DiffReport {
+    /// The index of the first token that differs. Opening and closing brackets count as a token.
+    pub index: usize,
+    /// The expected token, or `None` if the expected code ended before this token
+    pub expected: Option<String>,
+    /// The actual token, or `None` if the actual code ended before this token
+    pub actual: Option<String>,
+    /// The expected tokens around `index`, with the differing token marked with `>>> <<<`
+    pub expected_context: String,
+    /// The actual tokens around `index`, with the differing token marked with `>>> <<<`
+    pub actual_context: String,
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let describe = |token: &Option<String>| match token {
+            Some(token) => format!("`{}`", token),
+            None => String::from("end of code"),
+        };
+        writeln!(
+            fmt,
+            "Generated code differs at token {}: expected {}, found {}",
+            self.index,
+            describe(&self.expected),
+            describe(&self.actual)
+        )?;
+        writeln!(fmt, "expected: {}", self.expected_context)?;
+        write!(fmt, "actual:   {}", self.actual_context)
+    }
+}
+
+/// Flatten a stream into a list of tokens, with the brackets of groups as separate tokens
+fn flatten(stream: TokenStream) -> Vec<String> {
+    let mut result = Vec::new();
+    flatten_into(stream, &mut result);
+    result
+}
+
+fn flatten_into(stream: TokenStream, result: &mut Vec<String>) {
+    for token in stream {
+        match token {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => ("", ""),
+                };
+                if !open.is_empty() {
+                    result.push(open.to_string());
+                }
+                flatten_into(group.stream(), result);
+                if !close.is_empty() {
+                    result.push(close.to_string());
+                }
+            }
+            TokenTree::Punct(punct) => result.push(punct.as_char().to_string()),
+            token => result.push(token.to_string()),
+        }
+    }
+}
+
+/// The tokens around `index`, with the token at `index` marked
+fn context(tokens: &[String], index: usize) -> String {
+    let start = index.saturating_sub(CONTEXT);
+    let end = (index + CONTEXT + 1).min(tokens.len());
+    let mut result = Vec::new();
+    if start > 0 {
+        result.push(String::from("..."));
+    }
+    for (idx, token) in tokens.iter().enumerate().take(end).skip(start) {
+        if idx == index {
+            result.push(format!(">>>{}<<<", token));
+        } else {
+            result.push(token.clone());
+        }
+    }
+    if index >= tokens.len() {
+        result.push(String::from(">>><<<"));
+    }
+    if end < tokens.len() {
+        result.push(String::from("..."));
+    }
+    result.join(" ")
+}
+
+#[test]
+fn test_token_diff() {
+    let actual = TokenStream::from_str("fn foo() { a; b; c; d; e; f; g; h; i; j; k; }").unwrap();
+    let report = token_diff("fn foo() { a; b; c; d; e; f; X; h; i; j; k; }", &actual).unwrap();
+    assert_eq!(
+        report.to_string(),
+        "Generated code differs at token 17: expected `X`, found `g`\n\
+         expected: ... c ; d ; e ; f ; >>>X<<< ; h ; i ; j ; k ...\n\
+         actual:   ... c ; d ; e ; f ; >>>g<<< ; h ; i ; j ; k ..."
+    );
+
+    let report = token_diff("fn foo() {}", &TokenStream::from_str("fn foo()").unwrap()).unwrap();
+    assert_eq!(report.index, 4);
+    assert_eq!(report.expected.as_deref(), Some("{"));
+    assert_eq!(report.actual, None);
+    assert_eq!(report.actual_context, "fn foo ( ) >>><<<");
+
+    assert!(token_diff("\"unterminated", &actual).is_some());
+}
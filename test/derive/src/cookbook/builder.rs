@@ -0,0 +1,123 @@
+//! `#[derive(Builder)]` on `struct Foo { a: A, b: B }` generates:
+//!
+//! ```ignore
+//! #[derive(Default)]
+//! pub struct FooBuilder {
+//!     a: Option<A>,
+//!     b: Option<B>,
+//! }
+//! impl FooBuilder {
+//!     pub fn a(mut self, a: A) -> Self { self.a = Some(a); self }
+//!     pub fn b(mut self, b: B) -> Self { self.b = Some(b); self }
+//!     pub fn build(self) -> Result<Foo, &'static str> { .. }
+//! }
+//! impl Foo {
+//!     pub fn builder() -> FooBuilder { .. }
+//! }
+//! ```
+//!
+//! `build` fails if a field was not set, unless it has a `#[builder(default)]` attribute.
+//!
+//! With the `patterns` feature, `virtue::patterns::builder` generates a builder like this for structs with generics as well, see the `PatternBuilder` derive.
+
+use virtue::parse::StructBody;
+use virtue::prelude::*;
+
+pub fn derive(input: TokenStream) -> Result<TokenStream> {
+    let parse = Parse::new(input)?;
+    if let Parse::Struct {
+        name,
+        generics: Some(_),
+        ..
+    } = &parse
+    {
+        return Err(Error::custom_at(
+            "Builder can not be derived for structs with generics",
+            name.span(),
+        ));
+    }
+    let (mut generator, _, body) = parse.into_generator();
+    let fields = match body {
+        Body::Struct(StructBody {
+            fields: Some(Fields::Struct(fields)),
+        }) => fields,
+        _ => {
+            return Err(Error::custom_at(
+                "Builder can only be derived for structs with named fields",
+                generator.target_name().span(),
+            ))
+        }
+    };
+    let target_name = generator.target_name().to_string();
+    let builder_name = format!("{}Builder", target_name);
+    let visibility = generator.target_visibility().clone();
+
+    let mut builder_struct = generator.generate_struct(&builder_name);
+    builder_struct
+        .with_visibility(visibility.clone())
+        .with_derive(["Default"])?;
+    for (ident, field) in &fields {
+        builder_struct.add_field(
+            ident.to_string(),
+            format!("Option<{}>", field.type_string()),
+        );
+    }
+
+    let mut builder_impl = builder_struct.r#impl();
+    for (ident, field) in &fields {
+        builder_impl
+            .generate_fn(ident.to_string())
+            .with_visibility(visibility.clone())
+            .with_self_arg(FnSelfArg::MutTakeSelf)
+            .with_self_arg_trailing_comma(false)
+            .with_arg(ident.to_string(), field)
+            .with_return_type("Self")
+            .body(|b| {
+                b.push_parsed(format!("self.{0} = Some({0}); self", ident))?;
+                Ok(())
+            })?;
+    }
+    builder_impl
+        .generate_fn("build")
+        .with_visibility(visibility.clone())
+        .with_self_arg(FnSelfArg::TakeSelf)
+        .with_self_arg_trailing_comma(false)
+        .with_return_type(format!("Result<{}, &'static str>", target_name))
+        .body(|b| {
+            b.ident_str("Ok").group(Delimiter::Parenthesis, |b| {
+                b.ident_str(&target_name).group(Delimiter::Brace, |b| {
+                    for (ident, field) in &fields {
+                        let has_default = field
+                            .attributes
+                            .iter()
+                            .any(|attr| attr.tokens.stream().to_string() == "builder(default)");
+                        let value = if has_default {
+                            format!("self.{}.unwrap_or_default()", ident)
+                        } else {
+                            format!("self.{0}.ok_or(\"`{0}` is not set\")?", ident)
+                        };
+                        b.ident(ident.clone())
+                            .punct(':')
+                            .push_parsed(value)?
+                            .punct(',');
+                    }
+                    Ok(())
+                })?;
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+    drop(builder_impl);
+    drop(builder_struct);
+
+    generator
+        .r#impl()
+        .generate_fn("builder")
+        .with_visibility(visibility)
+        .with_return_type(&builder_name)
+        .body(|b| {
+            b.push_parsed(format!("{}::default()", builder_name))?;
+            Ok(())
+        })?;
+    generator.finish()
+}
@@ -0,0 +1,44 @@
+//! `#[derive(Display)]` implements `core::fmt::Display`. The format is set with an attribute on the struct or enum:
+//!
+//! - `#[display(name)]` writes the name of the struct or variant. This is the default.
+//! - `#[display(debug)]` forwards to the `Debug` implementation.
+//! - `#[display(transparent)]` forwards to the `Display` implementation of the only field.
+
+use virtue::generate::DisplayStrategy;
+use virtue::parse::{AttributeAccess, Cursor, FromAttribute};
+use virtue::prelude::*;
+
+struct Strategy(DisplayStrategy);
+
+impl FromAttribute for Strategy {
+    fn parse(group: &Group) -> Result<Option<Self>> {
+        let mut cursor = Cursor::from_group(group);
+        match cursor.consume_ident() {
+            Some(ident) if ident.to_string() == "display" => {}
+            _ => return Ok(None),
+        }
+        let group = cursor.expect_group(Delimiter::Parenthesis)?;
+        let strategy = Cursor::from_group(&group).expect_ident()?;
+        let strategy = match strategy.to_string().as_str() {
+            "name" => DisplayStrategy::Name,
+            "debug" => DisplayStrategy::Debug,
+            "transparent" => DisplayStrategy::Transparent,
+            _ => {
+                return Err(Error::custom_at(
+                    "Expected `name`, `debug` or `transparent`",
+                    strategy.span(),
+                ))
+            }
+        };
+        Ok(Some(Self(strategy)))
+    }
+}
+
+pub fn derive(input: TokenStream) -> Result<TokenStream> {
+    let (mut generator, attributes, body) = Parse::new(input)?.into_generator();
+    let strategy = attributes
+        .get_attribute::<Strategy>()?
+        .map_or(DisplayStrategy::Name, |strategy| strategy.0);
+    generator.generate_display_impl(&body, strategy)?;
+    generator.finish()
+}
@@ -0,0 +1,77 @@
+//! `#[derive(Encode)]` implements `trait Encode { fn encode(&self, out: &mut Vec<u8>); }`.
+//!
+//! Every field is encoded in order. Enums encode the index of the variant as a `u32` first.
+
+use virtue::generate::{FieldContext, PatternBuilder};
+use virtue::prelude::*;
+
+pub fn derive(input: TokenStream) -> Result<TokenStream> {
+    let (mut generator, _, body) = Parse::new(input)?.into_generator();
+    let bounds = generator.infer_bounds("Encode", &body, |_| false);
+    generator
+        .impl_for("Encode")
+        .modify_generic_constraints(|_, constraints| bounds.apply(constraints))?
+        .generate_fn("encode")
+        .with_self_arg(FnSelfArg::RefSelf)
+        .with_arg("out", "&mut Vec<u8>")
+        .body(|b| match &body {
+            Body::Struct(body) => match &body.fields {
+                Some(fields) => {
+                    b.ident_str("let")
+                        .pattern(&PatternBuilder::destructure(
+                            "Self",
+                            fields,
+                            FieldContext::BINDING_PREFIX,
+                        )?)
+                        .push_parsed("= self;")?;
+                    encode_fields(b, fields)
+                }
+                None => Ok(()),
+            },
+            Body::Enum(body) if body.is_uninhabited() => {
+                b.match_uninhabited(&FnSelfArg::RefSelf)?;
+                Ok(())
+            }
+            Body::Enum(body) => {
+                b.push_parsed("match self")?.group(Delimiter::Brace, |b| {
+                    for (index, variant) in body.variants.iter().enumerate() {
+                        let path = format!("Self::{}", variant.name);
+                        match &variant.fields {
+                            Some(fields) => b.pattern(&PatternBuilder::destructure(
+                                &path,
+                                fields,
+                                FieldContext::BINDING_PREFIX,
+                            )?),
+                            None => b.push_parsed(&path)?,
+                        };
+                        b.puncts("=>").group(Delimiter::Brace, |b| {
+                            b.push_parsed(format!("Encode::encode(&{}u32, out);", index))?;
+                            match &variant.fields {
+                                Some(fields) => encode_fields(b, fields),
+                                None => Ok(()),
+                            }
+                        })?;
+                    }
+                    Ok(())
+                })?;
+                Ok(())
+            }
+        })?;
+    generator.finish()
+}
+
+/// `Encode::encode(<binding>, out);` for every field. The fields must have been destructured with [`FieldContext::BINDING_PREFIX`].
+fn encode_fields(builder: &mut StreamBuilder, fields: &Fields) -> Result {
+    builder.for_each_field(fields, |mut field| {
+        let binding = field.binding().clone();
+        field
+            .builder()
+            .push_parsed("Encode::encode")?
+            .group(Delimiter::Parenthesis, |b| {
+                b.ident(binding).push_parsed(", out")?;
+                Ok(())
+            })?
+            .punct(';');
+        Ok(())
+    })
+}
@@ -0,0 +1,9 @@
+//! Realistic derives that are built with the public API of virtue only. They are used by `test/tests/cookbook.rs`, and can be used as examples for writing your own derives.
+//!
+//! - [`encode`]: an `Encode` derive like the one of bincode, for structs and enums.
+//! - [`builder`]: a `Builder` derive that generates a `FooBuilder` struct with a setter for every field.
+//! - [`display`]: a `Display` derive that is configured with a `#[display(...)]` attribute.
+
+pub mod builder;
+pub mod display;
+pub mod encode;
@@ -1,5 +1,8 @@
+use virtue::parse::utils::unwrap_none_groups;
 use virtue::prelude::*;
 
+mod cookbook;
+
 #[proc_macro_derive(RetHi)]
 pub fn derive_ret_hi(input: TokenStream) -> TokenStream {
     derive_ret_hi_inner(input).unwrap_or_else(|error| error.into_token_stream())
@@ -20,3 +23,345 @@ fn derive_ret_hi_inner(input: TokenStream) -> Result<TokenStream> {
         })?;
     generator.finish()
 }
+
+#[proc_macro_derive(VirtueDebug, attributes(debug))]
+pub fn derive_virtue_debug(input: TokenStream) -> TokenStream {
+    derive_virtue_debug_inner(input).unwrap_or_else(|error| error.into_token_stream())
+}
+
+fn derive_virtue_debug_inner(input: TokenStream) -> Result<TokenStream> {
+    let parse = Parse::new(input)?;
+    let (mut generator, _, body) = parse.into_generator();
+    generator.generate_debug_impl_with(&body, |field| {
+        field
+            .attributes()
+            .iter()
+            .any(|attr| attr.tokens.stream().to_string() == "debug(skip)")
+    })?;
+    generator.finish()
+}
+
+/// See [`cookbook::encode`]
+#[proc_macro_derive(Encode)]
+pub fn derive_encode(input: TokenStream) -> TokenStream {
+    cookbook::encode::derive(input).unwrap_or_else(|error| error.into_token_stream())
+}
+
+/// See [`cookbook::builder`]
+#[proc_macro_derive(Builder, attributes(builder))]
+pub fn derive_builder(input: TokenStream) -> TokenStream {
+    cookbook::builder::derive(input).unwrap_or_else(|error| error.into_token_stream())
+}
+
+/// See [`cookbook::display`]
+#[proc_macro_derive(Display, attributes(display))]
+pub fn derive_display(input: TokenStream) -> TokenStream {
+    cookbook::display::derive(input).unwrap_or_else(|error| error.into_token_stream())
+}
+
+/// Adds a `fn boxed(self) -> Box<Self>`. With `#[boxed(no_std)]` the `Box` of `::alloc` is used.
+#[proc_macro_derive(Boxed, attributes(boxed))]
+pub fn derive_boxed(input: TokenStream) -> TokenStream {
+    derive_boxed_inner(input).unwrap_or_else(|error| error.into_token_stream())
+}
+
+fn derive_boxed_inner(input: TokenStream) -> Result<TokenStream> {
+    let parse = Parse::new(input)?;
+    let (mut generator, attributes, _) = parse.into_generator();
+    let no_std = attributes
+        .iter()
+        .any(|attr| attr.tokens.stream().to_string() == "boxed(no_std)");
+    generator.no_std(no_std);
+    let alloc = generator.alloc_path();
+    generator
+        .generate_impl()
+        .generate_fn("boxed")
+        .make_pub()
+        .with_self_arg(FnSelfArg::TakeSelf)
+        .with_return_type(format!("{}::boxed::Box<Self>", alloc))
+        .body(|body| {
+            body.push_parsed(format!("{}::boxed::Box::new(self)", alloc))?;
+            Ok(())
+        })?;
+    generator.finish()
+}
+
+/// Generates a `<Name>Tag` enum with `from_tag(u8)`, and `fn tag(&self)`. With `#[tagged(ordinal)]` the tags are numbered by position instead of by discriminant.
+#[proc_macro_derive(Tagged, attributes(tagged))]
+pub fn derive_tagged(input: TokenStream) -> TokenStream {
+    derive_tagged_inner(input).unwrap_or_else(|error| error.into_token_stream())
+}
+
+fn derive_tagged_inner(input: TokenStream) -> Result<TokenStream> {
+    use virtue::generate::{TagConfig, TagValue};
+
+    let parse = Parse::new(input)?;
+    let (mut generator, attributes, body) = parse.into_generator();
+    let body = match body {
+        Body::Enum(body) => body,
+        Body::Struct(_) => {
+            return Err(Error::custom_at(
+                "Tagged can only be derived for enums",
+                generator.target_name().span(),
+            ))
+        }
+    };
+    let ordinal = attributes
+        .iter()
+        .any(|attr| attr.tokens.stream().to_string() == "tagged(ordinal)");
+    let name = format!("{}Tag", generator.target_name());
+    generator.generate_tag_enum(
+        &body,
+        TagConfig {
+            name: &name,
+            repr: "u8",
+            value: if ordinal {
+                TagValue::Ordinal
+            } else {
+                TagValue::Discriminant
+            },
+        },
+    )?;
+    generator.finish()
+}
+
+/// Generates a `<Name>Visitor` with the generics of the target, which counts the values it visits.
+#[proc_macro_derive(Visitor)]
+pub fn derive_visitor(input: TokenStream) -> TokenStream {
+    derive_visitor_inner(input).unwrap_or_else(|error| error.into_token_stream())
+}
+
+fn derive_visitor_inner(input: TokenStream) -> Result<TokenStream> {
+    let parse = Parse::new(input)?;
+    let (mut generator, _, _) = parse.into_generator();
+    let name = format!("{}Visitor", generator.target_name());
+    let visibility = generator.target_visibility().clone();
+    let target = format!("&{}", generator.self_type());
+    let mut visitor = generator.generate_struct(name);
+    visitor
+        .with_visibility(visibility)
+        .carry_target_generics()
+        .add_field("count", "usize");
+    let mut r#impl = visitor.r#impl();
+    r#impl
+        .generate_fn("new")
+        .make_pub()
+        .with_return_type("Self")
+        .body(|b| {
+            b.push_parsed("Self { count: 0, _marker: ::core::marker::PhantomData }")?;
+            Ok(())
+        })?;
+    r#impl
+        .generate_fn("visit")
+        .make_pub()
+        .with_self_arg(FnSelfArg::MutSelf)
+        .with_arg("_value", target)
+        .body(|b| {
+            b.push_parsed("let _ = _value; self.count += 1;")?;
+            Ok(())
+        })?;
+    drop(r#impl);
+    drop(visitor);
+    generator.finish()
+}
+
+/// Generates a `<Name>Builder` with `virtue::patterns::builder`. Fields with `#[builder(default)]` are optional, and fields with `#[builder(skip)]` have no setter.
+#[proc_macro_derive(PatternBuilder, attributes(builder))]
+pub fn derive_pattern_builder(input: TokenStream) -> TokenStream {
+    derive_pattern_builder_inner(input).unwrap_or_else(|error| error.into_token_stream())
+}
+
+fn derive_pattern_builder_inner(input: TokenStream) -> Result<TokenStream> {
+    use virtue::patterns::builder::{generate_builder_with, BuilderConfig, BuilderField};
+    use virtue::utils::{parse_tagged_attribute, ParsedAttribute};
+
+    let parse = Parse::new(input)?;
+    let (mut generator, _, body) = parse.into_generator();
+    let body = match body {
+        Body::Struct(body) => body,
+        Body::Enum(_) => {
+            return Err(Error::custom_at(
+                "PatternBuilder can only be derived for structs",
+                generator.target_name().span(),
+            ))
+        }
+    };
+    let mut kinds = Vec::new();
+    for (ident, field) in body.fields.iter().flat_map(Fields::iter) {
+        let mut kind = BuilderField::Required;
+        for attribute in &field.attributes {
+            for parsed in parse_tagged_attribute(&attribute.tokens, "builder")?.unwrap_or_default()
+            {
+                match parsed {
+                    ParsedAttribute::Tag(tag) if tag.to_string() == "default" => {
+                        kind = BuilderField::Optional
+                    }
+                    ParsedAttribute::Tag(tag) if tag.to_string() == "skip" => {
+                        kind = BuilderField::Skip
+                    }
+                    _ => return Err(Error::custom("Expected `default` or `skip`")),
+                }
+            }
+        }
+        if let Some(ident) = ident {
+            kinds.push((ident.to_string(), kind));
+        }
+    }
+    generate_builder_with(
+        &mut generator,
+        &body,
+        BuilderConfig::default(),
+        |ident, _| {
+            let ident = ident.to_string();
+            kinds
+                .iter()
+                .find(|(name, _)| *name == ident)
+                .map_or(BuilderField::Required, |(_, kind)| *kind)
+        },
+    )?;
+    generator.finish()
+}
+
+/// Describes how virtue parsed the input, as `fn describe() -> &'static str`.
+///
+/// The input is also parsed after calling `unwrap_none_groups`, and the derive fails if this is parsed differently.
+#[proc_macro_derive(Describe)]
+pub fn derive_describe(input: TokenStream) -> TokenStream {
+    derive_describe_inner(input).unwrap_or_else(|error| error.into_token_stream())
+}
+
+fn derive_describe_inner(input: TokenStream) -> Result<TokenStream> {
+    let unwrapped = describe(&Parse::new(unwrap_none_groups(input.clone()))?);
+    let parse = Parse::new(input)?;
+    let description = describe(&parse);
+    if description != unwrapped {
+        return Err(Error::custom(format!(
+            "Input was parsed as {:?}, but as {:?} without none groups",
+            description, unwrapped
+        )));
+    }
+
+    let (mut generator, _, _) = parse.into_generator();
+    generator
+        .generate_impl()
+        .generate_fn("describe")
+        .make_pub()
+        .with_return_type("&'static str")
+        .body(|body| {
+            body.lit_str(description);
+            Ok(())
+        })?;
+    generator.finish()
+}
+
+fn describe(parse: &Parse) -> String {
+    let (keyword, attributes, visibility, name, generics, generic_constraints, fields) = match parse
+    {
+        Parse::Struct {
+            attributes,
+            visibility,
+            name,
+            generics,
+            generic_constraints,
+            body,
+        } => (
+            "struct",
+            attributes,
+            visibility,
+            name,
+            generics,
+            generic_constraints,
+            describe_fields(body.fields.as_ref()),
+        ),
+        Parse::Enum {
+            attributes,
+            visibility,
+            name,
+            generics,
+            generic_constraints,
+            body,
+        } => {
+            let variants = body
+                .variants
+                .iter()
+                .map(|variant| {
+                    let mut result = variant.name.to_string();
+                    result += &describe_fields(variant.fields.as_ref());
+                    if let Some(value) = &variant.value {
+                        result += &format!(" = {}", value);
+                    }
+                    if let Some(value_expr) = &variant.value_expr {
+                        let value_expr: TokenStream = value_expr.iter().cloned().collect();
+                        result += &format!(" = {}", value_expr.to_string().replace(' ', ""));
+                    }
+                    result
+                })
+                .collect::<Vec<_>>();
+            (
+                "enum",
+                attributes,
+                visibility,
+                name,
+                generics,
+                generic_constraints,
+                format!(" {{ {} }}", variants.join(", ")),
+            )
+        }
+        _ => unreachable!(),
+    };
+    let mut result = format!("{}{} {}", describe_visibility(visibility), keyword, name);
+    if let Some(generics) = generics {
+        let names = generics
+            .iter()
+            .map(|generic| match generic {
+                virtue::parse::Generic::Lifetime(lt) => format!("'{}", lt.ident),
+                generic => generic.ident().to_string(),
+            })
+            .collect::<Vec<_>>();
+        result += &format!("<{}>", names.join(", "));
+    }
+    if generic_constraints.is_some() {
+        result += " where ..";
+    }
+    result += &fields;
+    if !attributes.is_empty() {
+        result += &format!(" #{}", attributes.len());
+    }
+    result
+}
+
+fn describe_fields(fields: Option<&Fields>) -> String {
+    let fields = match fields {
+        Some(fields) => fields,
+        None => return String::new(),
+    };
+    let result: Vec<String> = fields
+        .iter()
+        .map(|(ident, field)| {
+            let name = ident
+                .map(|ident| format!("{}: ", ident))
+                .unwrap_or_default();
+            describe_visibility(&field.vis) + &name + &normalize(field.type_string())
+        })
+        .collect();
+    match fields {
+        Fields::Tuple(_) => format!("({})", result.join(", ")),
+        Fields::Struct(_) => format!(" {{ {} }}", result.join(", ")),
+    }
+}
+
+fn describe_visibility(visibility: &virtue::parse::Visibility) -> String {
+    match visibility {
+        virtue::parse::Visibility::Default => String::new(),
+        virtue::parse::Visibility::Pub => String::from("pub "),
+        virtue::parse::Visibility::Restricted(group) => {
+            format!("pub({}) ", normalize(group.stream().to_string()))
+        }
+        visibility => panic!("Unknown visibility {:?}", visibility),
+    }
+}
+
+/// Remove all whitespace, as the string representation of a group without delimiters is different from the string representation of its tokens
+fn normalize(s: String) -> String {
+    s.chars().filter(|c| !c.is_whitespace()).collect()
+}
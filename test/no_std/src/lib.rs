@@ -0,0 +1,21 @@
+//! A `#![no_std]` crate, to check that code generated with `Generator::no_std` compiles without `std`.
+
+#![no_std]
+
+extern crate alloc;
+
+use virtue_test_derive::{Boxed, VirtueDebug};
+
+#[derive(Boxed, VirtueDebug)]
+#[boxed(no_std)]
+pub struct Foo<T> {
+    pub a: T,
+    pub b: u8,
+}
+
+#[derive(Boxed, VirtueDebug)]
+#[boxed(no_std)]
+pub enum Bar {
+    A,
+    B(u16),
+}
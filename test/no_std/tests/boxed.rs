@@ -0,0 +1,11 @@
+use virtue_test_no_std::{Bar, Foo};
+
+#[test]
+fn boxed() {
+    let foo: Box<Foo<&str>> = Foo { a: "a", b: 5 }.boxed();
+    assert_eq!(format!("{:?}", foo), "Foo { a: \"a\", b: 5 }");
+
+    let bar: Box<Bar> = Bar::B(5).boxed();
+    assert_eq!(format!("{:?}", bar), "B(5)");
+    assert_eq!(format!("{:?}", Bar::A), "A");
+}
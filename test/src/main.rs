@@ -19,8 +19,54 @@ where
     pub b: Vec<T>,
 }
 
+#[derive(virtue_test_derive::RetHi)]
+pub struct Wrapper<T>(pub T)
+where
+    T: Clone;
+
+#[derive(virtue_test_derive::VirtueDebug)]
+pub struct DebugStruct<T> {
+    pub a: T,
+    #[debug(skip)]
+    pub secret: u32,
+    pub r#type: &'static str,
+    #[cfg(any())]
+    pub missing: NotDebug,
+}
+
+#[derive(virtue_test_derive::VirtueDebug)]
+pub enum DebugEnum {
+    Unit,
+    Tuple(u8, #[debug(skip)] u16),
+    Struct {
+        f: bool,
+    },
+    #[cfg(any())]
+    Disabled(NotDebug),
+}
+
+#[cfg(any())]
+pub struct NotDebug;
+
 fn main() {
     assert_eq!("hi", Foo::A.hi());
     assert_eq!("hi", Foo::B.hi());
     assert_eq!("hi", MyStruct::<i32>::default().hi());
+    assert_eq!("hi", Wrapper(5).hi());
+
+    let debug = DebugStruct {
+        a: 5,
+        secret: 0,
+        r#type: "foo",
+    };
+    assert_eq!(
+        "DebugStruct { a: 5, type: \"foo\", .. }",
+        format!("{:?}", debug)
+    );
+    assert_eq!("Unit", format!("{:?}", DebugEnum::Unit));
+    assert_eq!("Tuple(1)", format!("{:?}", DebugEnum::Tuple(1, 2)));
+    assert_eq!(
+        "Struct { f: true }",
+        format!("{:?}", DebugEnum::Struct { f: true })
+    );
 }
@@ -0,0 +1,52 @@
+//! Tests for `virtue::patterns::builder`, through the `PatternBuilder` derive.
+
+#![allow(dead_code)]
+
+use virtue_test_derive::PatternBuilder;
+
+#[derive(PatternBuilder, Debug, PartialEq)]
+pub struct Request<'a, T: Clone>
+where
+    T: Default,
+{
+    url: &'a str,
+    body: T,
+    #[builder(default)]
+    headers: Vec<(String, String)>,
+    #[builder(skip)]
+    retries: u8,
+    #[cfg(any())]
+    disabled: NotAType,
+}
+
+#[derive(PatternBuilder, Debug, PartialEq)]
+struct r#Empty {}
+
+#[test]
+fn builder_pattern() {
+    let request = Request::builder()
+        .url("https://example.com")
+        .body(vec![1u8, 2])
+        .build()
+        .unwrap();
+    assert_eq!(
+        request,
+        Request {
+            url: "https://example.com",
+            body: vec![1, 2],
+            headers: Vec::new(),
+            retries: 0,
+        }
+    );
+
+    let builder: RequestBuilder<u8> = RequestBuilder::default()
+        .headers(vec![(String::from("a"), String::from("b"))])
+        .url("");
+    assert_eq!(builder.build(), Err("`body` is not set"));
+    assert_eq!(
+        Request::<u8>::builder().body(5).build(),
+        Err("`url` is not set")
+    );
+
+    assert_eq!(Empty::builder().build(), Ok(Empty {}));
+}
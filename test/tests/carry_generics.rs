@@ -0,0 +1,50 @@
+//! Tests for `GenStruct::carry_target_generics`, through the `Visitor` derive.
+
+#![allow(dead_code)]
+
+use std::fmt::Debug;
+use virtue_test_derive::Visitor;
+
+#[derive(Visitor)]
+pub struct Borrowed<'a, T: Debug, const N: usize>
+where
+    T: Clone,
+{
+    values: &'a [T; N],
+}
+
+#[derive(Visitor)]
+pub struct Tuple<T>(T)
+where
+    T: Default;
+
+#[derive(Visitor)]
+enum Unit {
+    A,
+    B,
+}
+
+#[derive(Visitor)]
+struct OnlyConst<const N: usize>([u8; N]);
+
+#[test]
+fn carry_generics() {
+    let values = [1u8, 2, 3];
+    let mut visitor = BorrowedVisitor::<'_, u8, 3>::new();
+    visitor.visit(&Borrowed { values: &values });
+    visitor.visit(&Borrowed { values: &values });
+    assert_eq!(visitor.count, 2);
+
+    let mut visitor = TupleVisitor::new();
+    visitor.visit(&Tuple(String::new()));
+    assert_eq!(visitor.count, 1);
+
+    let mut visitor = UnitVisitor::new();
+    visitor.visit(&Unit::A);
+    visitor.visit(&Unit::B);
+    assert_eq!(visitor.count, 2);
+
+    let mut visitor = OnlyConstVisitor::<2>::new();
+    visitor.visit(&OnlyConst([0; 2]));
+    assert_eq!(visitor.count, 1);
+}
@@ -0,0 +1,149 @@
+//! Tests for the derives in `test/derive/src/cookbook`, which only use the public API of virtue.
+
+#![allow(dead_code)]
+
+use virtue_test_derive::{Builder, Display, Encode};
+
+trait Encode {
+    fn encode(&self, out: &mut Vec<u8>);
+}
+
+impl Encode for u8 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self);
+    }
+}
+
+impl Encode for u32 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode(out);
+        for item in self {
+            item.encode(out);
+        }
+    }
+}
+
+fn encode(value: &impl Encode) -> Vec<u8> {
+    let mut out = Vec::new();
+    value.encode(&mut out);
+    out
+}
+
+#[derive(Encode)]
+struct Unit;
+
+#[derive(Encode)]
+struct Named<T> {
+    a: u8,
+    b: Vec<T>,
+}
+
+#[derive(Encode)]
+struct Tuple<'a, T>(&'a T, u8)
+where
+    T: Copy;
+
+impl<T: Encode> Encode for &T {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (**self).encode(out);
+    }
+}
+
+#[derive(Encode)]
+enum Message<T> {
+    Ping,
+    Data(T, u8),
+    Named { r#type: u8 },
+}
+
+#[derive(Encode)]
+enum Never {}
+
+#[test]
+fn encode_derive() {
+    assert!(encode(&Unit).is_empty());
+    assert_eq!(
+        encode(&Named {
+            a: 1,
+            b: vec![2u8, 3]
+        }),
+        [1, 2, 0, 0, 0, 2, 3]
+    );
+    assert_eq!(encode(&Tuple(&5u8, 6)), [5, 6]);
+    assert_eq!(encode(&Message::<u8>::Ping), [0, 0, 0, 0]);
+    assert_eq!(encode(&Message::Data(7u8, 8)), [1, 0, 0, 0, 7, 8]);
+    assert_eq!(encode(&Message::<u8>::Named { r#type: 9 }), [2, 0, 0, 0, 9]);
+}
+
+#[derive(Builder, Debug, PartialEq)]
+pub struct Config {
+    name: String,
+    retries: u32,
+    #[builder(default)]
+    verbose: bool,
+}
+
+#[test]
+fn builder_derive() {
+    let config = Config::builder()
+        .name(String::from("cookbook"))
+        .retries(3)
+        .build()
+        .unwrap();
+    assert_eq!(
+        config,
+        Config {
+            name: String::from("cookbook"),
+            retries: 3,
+            verbose: false,
+        }
+    );
+    assert_eq!(
+        Config::builder().name(String::new()).build(),
+        Err("`retries` is not set")
+    );
+    let builder: ConfigBuilder = ConfigBuilder::default().verbose(true);
+    assert!(
+        builder
+            .retries(0)
+            .name(String::new())
+            .build()
+            .unwrap()
+            .verbose
+    );
+}
+
+#[derive(Display)]
+enum Color {
+    Red,
+    Green,
+}
+
+#[derive(Display, Debug)]
+#[display(debug)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Display)]
+#[display(transparent)]
+enum Either<L, R> {
+    Left(L),
+    Right(R),
+}
+
+#[test]
+fn display_derive() {
+    assert_eq!(Color::Red.to_string(), "Red");
+    assert_eq!(Color::Green.to_string(), "Green");
+    assert_eq!(Point { x: 1, y: 2 }.to_string(), "Point { x: 1, y: 2 }");
+    assert_eq!(Either::<u8, &str>::Left(5).to_string(), "5");
+    assert_eq!(Either::<u8, &str>::Right("right").to_string(), "right");
+}
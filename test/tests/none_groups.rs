@@ -0,0 +1,188 @@
+//! `macro_rules!` pass fragments like `$vis:vis` and `$ty:ty` on to derive macros wrapped in groups without delimiters.
+//!
+//! Every item in here derives `Describe`, which fails to compile if virtue parses the item differently than it does after `unwrap_none_groups`.
+
+#![allow(dead_code)]
+
+use virtue_test_derive::Describe;
+
+macro_rules! named_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident<$($lt:lifetime,)* $($generic:ident),*>
+        where $($bound_ty:ident: $bound:path),*
+        {
+            $($field_vis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name<$($lt,)* $($generic),*>
+        where $($bound_ty: $bound),*
+        {
+            $($field_vis $field: $ty),*
+        }
+    };
+}
+
+macro_rules! tuple_struct {
+    ($(#[$meta:meta])* $vis:vis struct $name:ident($($field_vis:vis $ty:ty),* $(,)?);) => {
+        $(#[$meta])*
+        $vis struct $name($($field_vis $ty),*);
+    };
+}
+
+macro_rules! enum_with_discriminants {
+    ($(#[$meta:meta])* $vis:vis enum $name:ident { $($variant:ident = $value:literal),* $(,)? }) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $($variant = $value),*
+        }
+    };
+}
+
+macro_rules! enum_with_expr_discriminants {
+    ($(#[$meta:meta])* $vis:vis enum $name:ident { $($variant:ident = $value:expr),* $(,)? }) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $($variant = $value),*
+        }
+    };
+}
+
+macro_rules! enum_with_fields {
+    ($(#[$meta:meta])* $vis:vis enum $name:ident<$generic:ident> { $($variant:ident($($ty:ty),*)),* $(,)? }) => {
+        $(#[$meta])*
+        $vis enum $name<$generic> {
+            $($variant($($ty),*)),*
+        }
+    };
+}
+
+/// Puts a derive in front of an `$item:item` group
+macro_rules! with_describe {
+    ($item:item) => {
+        #[derive(Describe)]
+        $item
+    };
+}
+
+/// Passes an `$item` group on to another macro, with a doc attribute in front of it
+macro_rules! documented {
+    ($doc:literal, $item:item) => {
+        with_describe! {
+            #[doc = $doc]
+            $item
+        }
+    };
+}
+
+mod inner {
+    use super::Describe;
+
+    named_struct! {
+        #[derive(Describe)]
+        #[doc(hidden)]
+        pub(crate) struct Named<'a, T> where T: Clone {
+            pub a: &'a [T],
+            pub(super) b: Option<Vec<(u8, T)>>,
+            c: fn(u8) -> u16,
+        }
+    }
+
+    tuple_struct! {
+        #[derive(Describe)]
+        pub(in crate::inner) struct Tuple(pub u8, Box<dyn Fn(u8) -> u8>);
+    }
+
+    tuple_struct! {
+        /// An empty `$vis` is a group without any tokens
+        #[derive(Describe)]
+        struct Private(u8);
+    }
+
+    enum_with_discriminants! {
+        #[derive(Describe)]
+        #[repr(u8)]
+        pub enum Discriminants {
+            A = 1,
+            B = 0x10,
+        }
+    }
+
+    enum_with_discriminants! {
+        #[derive(Describe)]
+        #[repr(i8)]
+        pub enum NegativeDiscriminants {
+            A = -1,
+            B = 2,
+        }
+    }
+
+    pub const BASE: i16 = 10;
+
+    enum_with_expr_discriminants! {
+        #[derive(Describe)]
+        #[repr(i16)]
+        pub enum ExprDiscriminants {
+            A = BASE,
+            B = -1,
+            C = BASE + 1,
+            D = self::BASE * 2,
+            E = -(BASE * 3),
+        }
+    }
+
+    enum_with_fields! {
+        #[derive(Describe)]
+        pub enum WithFields<T> {
+            Unit(),
+            One(Vec<T>),
+            Two(T, std::collections::HashMap<u8, T>),
+        }
+    }
+
+    with_describe! {
+        /// Docs inside of the `$item` group
+        #[doc(hidden)]
+        pub struct InItem<T>(T) where T: Clone;
+    }
+
+    documented! {
+        "Docs from a macro",
+        /// Docs inside of the nested `$item` group
+        #[derive(Clone)]
+        pub(crate) enum Nested { A, B(u8) }
+    }
+
+    pub fn descriptions() -> Vec<&'static str> {
+        vec![
+            Named::<u8>::describe(),
+            Tuple::describe(),
+            Private::describe(),
+            Discriminants::describe(),
+            NegativeDiscriminants::describe(),
+            ExprDiscriminants::describe(),
+            WithFields::<u8>::describe(),
+            InItem::<u8>::describe(),
+            Nested::describe(),
+        ]
+    }
+}
+
+#[test]
+fn none_groups() {
+    assert_eq!(
+        inner::descriptions(),
+        [
+            "pub(crate) struct Named<'a, T> where .. { pub a: &'a[T], pub(super) b: Option<Vec<(u8,T)>>, c: fn(u8)->u16 } #1",
+            "pub(incrate::inner) struct Tuple(pub u8, Box<dynFn(u8)->u8>)",
+            "struct Private(u8) #1",
+            "pub enum Discriminants { A = 1, B = 0x10 } #1",
+            "pub enum NegativeDiscriminants { A = -1, B = 2 } #1",
+            "pub enum ExprDiscriminants { A = BASE, B = -1, C = BASE+1, D = self::BASE*2, E = -(BASE*3) } #1",
+            "pub enum WithFields<T> { Unit(), One(Vec<T>), Two(T, std::collections::HashMap<u8,T>) }",
+            "pub struct InItem<T> where ..(T) #2",
+            "pub(crate) enum Nested { A, B(u8) } #3",
+        ]
+    );
+}
@@ -0,0 +1,42 @@
+//! Tests for `Generator::generate_tag_enum`, through the `Tagged` derive.
+
+#![allow(dead_code)]
+
+use virtue_test_derive::Tagged;
+
+const BASE: u8 = 10;
+
+#[derive(Tagged)]
+#[repr(u8)]
+pub enum Message {
+    Ping = BASE,
+    Data(Vec<u8>),
+    #[cfg(any())]
+    Disabled,
+    Close {
+        code: u16,
+    } = 20,
+}
+
+#[derive(Tagged)]
+#[tagged(ordinal)]
+#[repr(u8)]
+enum Ordinal {
+    A = 5,
+    B(u8),
+}
+
+#[test]
+fn tagged_enum() {
+    assert_eq!(Message::Ping.tag(), MessageTag::Ping);
+    assert_eq!(Message::Data(vec![1]).tag() as u8, 11);
+    assert_eq!(Message::Close { code: 1 }.tag() as u8, 20);
+    assert_eq!(MessageTag::from_tag(11), Some(MessageTag::Data));
+    assert_eq!(MessageTag::from_tag(20), Some(MessageTag::Close));
+    assert_eq!(MessageTag::from_tag(0), None);
+
+    assert_eq!(Ordinal::B(1).tag() as u8, 1);
+    assert_eq!(Ordinal::A.tag(), OrdinalTag::A);
+    assert_eq!(OrdinalTag::from_tag(0), Some(OrdinalTag::A));
+    assert_eq!(OrdinalTag::from_tag(5), None);
+}